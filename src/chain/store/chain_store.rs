@@ -187,6 +187,11 @@ where
         &self.publisher
     }
 
+    /// Returns the settings store, e.g. to back it up.
+    pub fn settings(&self) -> &(dyn SettingsStore + Sync + Send) {
+        self.settings.as_ref()
+    }
+
     /// Returns key-value store instance.
     pub fn blockstore(&self) -> &DB {
         &self.db
@@ -47,6 +47,8 @@ use crate::chain_sync::{
 const DEFAULT_REQUEST_WINDOW: usize = 8;
 const DEFAULT_TIPSET_SAMPLE_SIZE: usize = 5;
 const DEFAULT_RECENT_STATE_ROOTS: i64 = 2000;
+// One day of epochs
+const DEFAULT_MAX_RECOMPUTED_EPOCHS: i64 = 2880;
 
 pub(in crate::chain_sync) type WorkerState = Arc<RwLock<SyncState>>;
 
@@ -86,6 +88,15 @@ pub struct SyncConfig {
     /// Number of recent state roots to keep in the database after `sync`
     /// and to include in the exported snapshot.
     pub recent_state_roots: i64,
+    /// Retain the state of every epoch instead of pruning state older than
+    /// `recent_state_roots`. Missing historical state is recomputed on demand.
+    #[serde(default)]
+    pub archival: bool,
+    /// Maximum number of epochs an archival node walks back and recomputes to
+    /// serve the state of a historical tipset. Older state has to come from
+    /// archive snapshots.
+    #[serde(default = "default_max_recomputed_epochs")]
+    pub max_recomputed_epochs: i64,
     /// Sample size of tipsets to acquire before determining what the network
     /// head is
     #[cfg_attr(test, arbitrary(gen(|g| u32::arbitrary(g) as _)))]
@@ -97,11 +108,17 @@ impl Default for SyncConfig {
         Self {
             request_window: DEFAULT_REQUEST_WINDOW,
             recent_state_roots: DEFAULT_RECENT_STATE_ROOTS,
+            archival: false,
+            max_recomputed_epochs: DEFAULT_MAX_RECOMPUTED_EPOCHS,
             tipset_sample_size: DEFAULT_TIPSET_SAMPLE_SIZE,
         }
     }
 }
 
+fn default_max_recomputed_epochs() -> i64 {
+    DEFAULT_MAX_RECOMPUTED_EPOCHS
+}
+
 /// Represents the result of evaluating the network head tipset against the
 /// local head tipset
 enum NetworkHeadEvaluation {
@@ -1,19 +1,105 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::path::{Path, PathBuf};
+
+use crate::chain_sync::SyncConfig;
+use crate::rpc_api::db_api::DbBackupParams;
+use crate::rpc_client::ApiInfo;
 use clap::Subcommand;
+use human_repr::HumanCount as _;
 
 #[derive(Debug, Subcommand)]
 pub enum DBCommands {
     // This is a noop as the manual GC is no longer available.
     #[command(hide = true)]
     GC,
+    /// Print the phase, progress and statistics of the garbage collector
+    GcStatus,
+    /// Suspend the garbage collector until it's resumed
+    GcPause,
+    /// Resume a paused garbage collector
+    GcResume,
+    /// Cancel the ongoing garbage collection run, the next one starts after
+    /// the configured interval
+    GcAbort,
+    /// Write a consistent point-in-time backup of the running node's database
+    /// into `<path>`, a directory on the node's file system. Restore it with
+    /// `forest-tool db restore`.
+    Backup {
+        /// Directory on the node's file system, created if missing. A relative
+        /// path is resolved against the current directory.
+        path: PathBuf,
+        /// How many state-roots to include. Lower limit is 900 for `calibnet` and `mainnet`.
+        #[arg(short, long)]
+        depth: Option<crate::chain::ChainEpochDelta>,
+    },
 }
 
 impl DBCommands {
-    pub async fn run(self) -> anyhow::Result<()> {
+    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
         match self {
             Self::GC => anyhow::bail!("manual garbage collection has been deprecated"),
+            Self::GcStatus => {
+                let status = api.db_gc_status().await?;
+                let paused = if status.paused { " (paused)" } else { "" };
+                println!("Phase:           {:?}{paused}", status.phase);
+                println!("Progress:        {} records", status.progress);
+                println!("Marked:          {} records", status.marked);
+                println!("Last run swept:  {} records", status.swept);
+                println!(
+                    "Last run freed:  {}",
+                    status.reclaimed_bytes.human_count_bytes()
+                );
+                match status.last_run {
+                    Some(last_run) => println!("Last run:        {last_run}"),
+                    None => println!("Last run:        never"),
+                }
+                Ok(())
+            }
+            Self::GcPause => Ok(api.db_gc_pause().await?),
+            Self::GcResume => Ok(api.db_gc_resume().await?),
+            Self::GcAbort => Ok(api.db_gc_abort().await?),
+            Self::Backup { path, depth } => {
+                // The node writes the backup, make the path independent of our working directory.
+                let output_dir = canonicalize(&path)?;
+                api.db_backup(DbBackupParams {
+                    output_dir: output_dir.clone(),
+                    recent_roots: depth.unwrap_or(SyncConfig::default().recent_state_roots),
+                })
+                .await?;
+                println!("Backup written to {}", output_dir.display());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Canonicalizes `path`, which may not exist yet. Its missing components are
+/// appended to its canonicalized existing ancestor.
+fn canonicalize(path: &Path) -> anyhow::Result<PathBuf> {
+    match (path.canonicalize(), path.parent(), path.file_name()) {
+        (Ok(path), _, _) => Ok(path),
+        (Err(_), Some(parent), Some(name)) if !parent.as_os_str().is_empty() => {
+            Ok(canonicalize(parent)?.join(name))
         }
+        (Err(_), _, _) => Ok(std::env::current_dir()?.join(path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn canonicalize_missing_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let canonical_dir = dir.path().canonicalize().unwrap();
+        let path = dir.path().join("backups").join("forest");
+        assert_eq!(
+            canonicalize(&path).unwrap(),
+            canonical_dir.join("backups").join("forest")
+        );
+        assert!(canonicalize(Path::new("forest")).unwrap().is_absolute());
     }
 }
@@ -0,0 +1,36 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc_client::ApiInfo;
+use clap::Subcommand;
+
+#[derive(Debug, Subcommand)]
+pub enum LogCommands {
+    /// List the log filter directives currently in effect
+    List,
+    /// Set the log level of a subsystem without restarting the node
+    SetLevel {
+        /// Tracing target, e.g. `forest_filecoin::chain_sync`, or `*` for the
+        /// default level
+        subsystem: String,
+        /// One of `off`, `error`, `warn`, `info`, `debug` or `trace`
+        level: String,
+    },
+}
+
+impl LogCommands {
+    pub async fn run(self, api: ApiInfo) -> anyhow::Result<()> {
+        match self {
+            Self::List => {
+                for directive in api.log_list().await? {
+                    println!("{directive}");
+                }
+                Ok(())
+            }
+            Self::SetLevel { subsystem, level } => {
+                api.log_set_level(subsystem, level).await?;
+                Ok(())
+            }
+        }
+    }
+}
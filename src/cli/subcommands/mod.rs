@@ -12,6 +12,7 @@ mod chain_cmd;
 mod config_cmd;
 mod db_cmd;
 mod info_cmd;
+mod log_cmd;
 mod mpool_cmd;
 mod net_cmd;
 pub(crate) mod send_cmd;
@@ -32,9 +33,9 @@ use tracing::error;
 
 pub(super) use self::{
     attach_cmd::AttachCommand, auth_cmd::AuthCommands, chain_cmd::ChainCommands,
-    config_cmd::ConfigCommands, db_cmd::DBCommands, mpool_cmd::MpoolCommands, net_cmd::NetCommands,
-    send_cmd::SendCommand, shutdown_cmd::ShutdownCommand, snapshot_cmd::SnapshotCommands,
-    state_cmd::StateCommands, sync_cmd::SyncCommands,
+    config_cmd::ConfigCommands, db_cmd::DBCommands, log_cmd::LogCommands, mpool_cmd::MpoolCommands,
+    net_cmd::NetCommands, send_cmd::SendCommand, shutdown_cmd::ShutdownCommand,
+    snapshot_cmd::SnapshotCommands, state_cmd::StateCommands, sync_cmd::SyncCommands,
 };
 use crate::cli::subcommands::info_cmd::InfoCommand;
 
@@ -96,6 +97,10 @@ pub enum Subcommand {
     #[command(subcommand)]
     DB(DBCommands),
 
+    /// Inspect and change the log levels of a running node
+    #[command(subcommand)]
+    Log(LogCommands),
+
     /// Attach to daemon via a JavaScript console
     Attach(AttachCommand),
 
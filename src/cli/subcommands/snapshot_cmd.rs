@@ -11,9 +11,8 @@ use anyhow::Context as _;
 use chrono::NaiveDateTime;
 use clap::Subcommand;
 use human_repr::HumanCount;
-use std::path::{Path, PathBuf};
+use std::path::PathBuf;
 use tempfile::NamedTempFile;
-use tokio::io::AsyncWriteExt;
 
 #[derive(Debug, Subcommand)]
 pub enum SnapshotCommands {
@@ -35,6 +34,9 @@ pub enum SnapshotCommands {
         #[arg(short, long)]
         depth: Option<crate::chain::ChainEpochDelta>,
     },
+    /// Print the status of the exports scheduled in the `[snapshot_export]`
+    /// section of the node's configuration
+    ExportStatus,
 }
 
 impl SnapshotCommands {
@@ -119,34 +121,44 @@ impl SnapshotCommands {
                 let _ = handle.await;
 
                 if let Some(hash) = hash_result {
-                    save_checksum(&output_path, hash).await?;
+                    snapshot::save_checksum(&output_path, hash).await?;
                 }
                 temp_path.persist(output_path)?;
 
                 println!("Export completed.");
                 Ok(())
             }
+            Self::ExportStatus => {
+                let status = api.chain_export_status().await?;
+                if !status.enabled {
+                    println!("Scheduled exports are disabled");
+                    return Ok(());
+                }
+                if let Some(output_dir) = &status.output_dir {
+                    println!("Output directory: {}", output_dir.display());
+                }
+                match &status.last_export {
+                    Some(export) => {
+                        println!("Last export:      {} ({})", export.file, export.completed);
+                        println!("Epoch:            {}", export.epoch);
+                        if let Some(diff_base) = export.diff_base {
+                            println!("Diff of epoch:    {diff_base}");
+                        }
+                        println!("Size:             {}", export.size.human_count_bytes());
+                        println!("SHA-256:          {}", export.checksum);
+                    }
+                    None => println!("Last export:      never"),
+                }
+                if let Some(error) = &status.last_error {
+                    println!("Last error:       {error}");
+                }
+                if status.running {
+                    println!("Next export:      in progress");
+                } else if let Some(next_export) = status.next_export {
+                    println!("Next export:      {next_export}");
+                }
+                Ok(())
+            }
         }
     }
 }
-
-/// Prints hex-encoded representation of SHA-256 checksum and saves it to a file
-/// with the same name but with a `.sha256sum` extension.
-async fn save_checksum(source: &Path, encoded_hash: String) -> anyhow::Result<()> {
-    let checksum_file_content = format!(
-        "{encoded_hash} {}\n",
-        source
-            .file_name()
-            .and_then(std::ffi::OsStr::to_str)
-            .context("Failed to retrieve file name while saving checksum")?
-    );
-
-    let checksum_path = PathBuf::from(source).with_extension("sha256sum");
-
-    let mut checksum_file = tokio::fs::File::create(&checksum_path).await?;
-    checksum_file
-        .write_all(checksum_file_content.as_bytes())
-        .await?;
-    checksum_file.flush().await?;
-    Ok(())
-}
@@ -3,9 +3,12 @@
 
 use std::path::PathBuf;
 
+use crate::lotus_json::LotusJson;
 use crate::rpc_client::ApiInfo;
+use crate::shim::address::Address;
 use crate::shim::clock::ChainEpoch;
 use crate::shim::econ::TokenAmount;
+use crate::state_manager::proof::parse_path;
 use cid::Cid;
 use clap::Subcommand;
 use serde_tuple::{self, Deserialize_tuple, Serialize_tuple};
@@ -29,6 +32,19 @@ pub enum StateCommands {
         #[arg(short, long)]
         save_to_file: Option<PathBuf>,
     },
+    /// Prove the state of an actor at the chain head, and optionally of a
+    /// value in its state. See `forest-tool archive verify-proof`.
+    ActorProof {
+        /// Address of the actor
+        address: Address,
+        /// Path to a value in the state of the actor, as `/`-separated map
+        /// keys, list indices and `hamt:`, `amt:` or `kamt:` keys
+        #[arg(long)]
+        path: Option<String>,
+        /// The `.car` file path to save the proof
+        #[arg(short, long, default_value = "proof.car")]
+        output: PathBuf,
+    },
 }
 
 impl StateCommands {
@@ -37,6 +53,23 @@ impl StateCommands {
             Self::Fetch { root, save_to_file } => {
                 println!("{}", api.state_fetch_root(root, save_to_file).await?);
             }
+            Self::ActorProof {
+                address,
+                path,
+                output,
+            } => {
+                let path = parse_path(&path.unwrap_or_default());
+                let head = api.chain_head().await?;
+                let proof = api
+                    .state_actor_proof(address, path, head.key().clone())
+                    .await?;
+                std::fs::write(output, &proof.proof)?;
+                println!("State root: {}", proof.state_root);
+                println!("{}", serde_json::to_string_pretty(&LotusJson(proof.actor))?);
+                if let Some(value) = proof.value {
+                    println!("{}", serde_json::to_string_pretty(&LotusJson(value))?);
+                }
+            }
         }
         Ok(())
     }
@@ -11,9 +11,9 @@ use crate::rpc_client::DEFAULT_PORT;
 use chrono::Duration;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
-use serde_with::{serde_as, DurationSeconds};
+use serde_with::{serde_as, DurationMilliSeconds, DurationSeconds};
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(transparent)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 pub struct ChunkSize(pub u32);
@@ -23,7 +23,7 @@ impl Default for ChunkSize {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(transparent)]
 #[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
 pub struct BufferSize(pub u32);
@@ -51,6 +51,33 @@ pub struct Client {
     pub snapshot_height: Option<i64>,
     pub snapshot_head: Option<i64>,
     pub snapshot_path: Option<PathBuf>,
+    /// Diff snapshots imported, in order, on top of the snapshot at
+    /// `snapshot_path` or of the current head.
+    pub snapshot_diff_paths: Vec<PathBuf>,
+    /// Addresses of the keys trusted to sign snapshot manifests. If set,
+    /// imported snapshots must come with a `<snapshot>.manifest.json` signed
+    /// by one of them.
+    pub snapshot_trusted_signers: Vec<String>,
+    /// CIDs of block headers that must be part of the chain of imported
+    /// snapshots.
+    pub snapshot_checkpoints: Vec<String>,
+    /// Import snapshots given as a URL while downloading them, without a
+    /// temporary copy: blocks are transcoded into a `.forest.car.zst` file of
+    /// the `car_db` directory, which is discarded unless the snapshot is
+    /// valid. Blocks are processed in chunks of `chunk_size`, with up to
+    /// `buffer_size` chunks read ahead. Local snapshots can't be streamed.
+    pub stream_snapshot: bool,
+    /// Archive snapshots (`.car`, `.car.zst` or `.forest.car.zst`) mounted
+    /// read-only in archival mode to serve historical state.
+    pub archive_snapshot_paths: Vec<PathBuf>,
+    /// Move the reachable blocks older than the garbage collector depth into
+    /// immutable `.forest.car.zst` segments in the `car_db` directory,
+    /// removing them from the database.
+    pub gc_cold_storage: bool,
+    /// Build a bloom filter for each `.forest.car.zst` file in use, so that
+    /// lookups of missing blocks skip the files that can't contain them. This
+    /// takes about 10 bits of memory per block.
+    pub car_bloom_filters: bool,
     /// Skips loading import CAR file and assumes it's already been loaded.
     /// Will use the CIDs in the header of the file to index the chain.
     pub skip_load: bool,
@@ -65,6 +92,30 @@ pub struct Client {
     pub metrics_address: SocketAddr,
     /// RPC bind, e.g. 127.0.0.1:1234
     pub rpc_address: SocketAddr,
+    /// PEM-encoded certificate chain. Together with `rpc_tls_key_path`, this
+    /// enables TLS on the RPC endpoint.
+    pub rpc_tls_cert_path: Option<PathBuf>,
+    /// PEM-encoded private key for `rpc_tls_cert_path`.
+    pub rpc_tls_key_path: Option<PathBuf>,
+    /// Additionally serve RPC on this Unix domain socket. Access is controlled
+    /// by the socket file permissions instead of a JWT, e.g. for local tools.
+    pub rpc_socket_path: Option<PathBuf>,
+    /// File permissions of the RPC Unix domain socket. Defaults to owner-only.
+    pub rpc_socket_mode: u32,
+    /// RPC calls taking longer than this (in milliseconds) are logged with
+    /// their parameters. Zero disables the logging.
+    #[serde_as(as = "DurationMilliSeconds<u64>")]
+    #[cfg_attr(test, arbitrary(gen(
+        |g| std::time::Duration::from_millis(u32::arbitrary(g) as u64)
+    )))]
+    pub rpc_slow_call_threshold: std::time::Duration,
+    /// Append a JSON line for every RPC call (method, token ID, caller, status
+    /// and duration) to this file.
+    pub rpc_audit_log: Option<PathBuf>,
+    /// Append every RPC request together with its response to this file. The
+    /// capture can be replayed with `forest-tool api replay`. Wallet methods
+    /// and methods requiring admin or sign permissions are not captured.
+    pub rpc_capture_file: Option<PathBuf>,
     // Period of validity for JWT in seconds. Defaults to 60 days.
     #[serde_as(as = "DurationSeconds<i64>")]
     #[cfg_attr(test, arbitrary(gen(
@@ -85,6 +136,13 @@ impl Default for Client {
             enable_metrics_endpoint: true,
             rpc_token: None,
             snapshot_path: None,
+            snapshot_diff_paths: vec![],
+            snapshot_trusted_signers: vec![],
+            snapshot_checkpoints: vec![],
+            stream_snapshot: false,
+            archive_snapshot_paths: vec![],
+            gc_cold_storage: false,
+            car_bloom_filters: false,
             snapshot: false,
             consume_snapshot: false,
             snapshot_height: None,
@@ -95,6 +153,13 @@ impl Default for Client {
             encrypt_keystore: true,
             metrics_address: FromStr::from_str("0.0.0.0:6116").unwrap(),
             rpc_address: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), DEFAULT_PORT),
+            rpc_tls_cert_path: None,
+            rpc_tls_key_path: None,
+            rpc_socket_path: None,
+            rpc_socket_mode: 0o600,
+            rpc_slow_call_threshold: std::time::Duration::from_secs(5),
+            rpc_audit_log: None,
+            rpc_capture_file: None,
             token_exp: Duration::seconds(5184000), // 60 Days = 5184000 Seconds
             load_actors: true,
         }
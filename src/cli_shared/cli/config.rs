@@ -1,7 +1,9 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::db::db_engine::DbConfig;
+use crate::daemon::snapshot_export::SnapshotExportConfig;
+use crate::db::db_engine::{BlockstoreConfig, DbBackend, DbConfig};
+use crate::db::{parity_db_config::ParityDbConfig, redb_db::RedbConfig, GcConfig};
 use crate::libp2p::Libp2pConfig;
 use crate::{chain_sync::SyncConfig, networks::NetworkChain};
 use serde::{Deserialize, Serialize};
@@ -42,15 +44,23 @@ impl Default for DaemonConfig {
 pub struct Config {
     pub chain: NetworkChain,
     pub client: Client,
-    pub parity_db: crate::db::parity_db_config::ParityDbConfig,
+    pub db: BlockstoreConfig,
+    pub parity_db: ParityDbConfig,
+    pub redb: RedbConfig,
     pub network: Libp2pConfig,
     pub sync: SyncConfig,
+    pub gc: GcConfig,
+    pub snapshot_export: SnapshotExportConfig,
     pub daemon: DaemonConfig,
 }
 
 impl Config {
-    pub fn db_config(&self) -> &DbConfig {
-        &self.parity_db
+    /// Configuration of the database backend selected in the `[db]` section.
+    pub fn db_config(&self) -> DbConfig {
+        match self.db.backend {
+            DbBackend::ParityDb => DbConfig::ParityDb(self.parity_db.clone()),
+            DbBackend::Redb => DbConfig::Redb(self.redb.clone()),
+        }
     }
 }
 
@@ -78,9 +78,19 @@ pub struct CliOpts {
     /// Import a snapshot from a local CAR file or URL
     #[arg(long)]
     pub import_snapshot: Option<String>,
+    /// Import a snapshot given as a URL while downloading it, without a
+    /// temporary copy. Can't be combined with local snapshots or
+    /// `--consume-snapshot`
+    #[arg(long)]
+    pub stream_snapshot: bool,
     /// Import a snapshot from a local CAR file and delete it, or from a URL
     #[arg(long)]
     pub consume_snapshot: Option<String>,
+    /// Import a diff snapshot, created with `forest-tool archive export
+    /// --diff`, on top of the imported snapshot or of the current head. May be
+    /// repeated, diffs are imported in the given order.
+    #[arg(long)]
+    pub import_diff: Vec<String>,
     /// Halt with exit code 0 after successfully importing a snapshot
     #[arg(long)]
     pub halt_after_import: bool,
@@ -141,6 +151,10 @@ pub struct CliOpts {
     /// Disable the automatic database garbage collection.
     #[arg(long)]
     pub no_gc: bool,
+    /// Run an archival node, retaining the state of every epoch. Historical
+    /// state can be backfilled with `archive_snapshot_paths`.
+    #[arg(long)]
+    pub archival: bool,
     /// Check your command-line options and configuration file if one is used
     #[arg(long)]
     pub dry_run: bool,
@@ -184,12 +198,17 @@ impl CliOpts {
             anyhow::bail!("Can't set import_snapshot and consume_snapshot at the same time!")
         } else if self.consume_snapshot.is_some() && self.import_chain.is_some() {
             anyhow::bail!("Can't set consume_snapshot and import_chain at the same time!")
+        } else if self.stream_snapshot && self.consume_snapshot.is_some() {
+            anyhow::bail!("Can't set stream_snapshot and consume_snapshot at the same time!")
         }
 
         if let Some(snapshot_path) = &self.import_snapshot {
             cfg.client.snapshot_path = Some(snapshot_path.into());
             cfg.client.snapshot = true;
         }
+        if self.stream_snapshot {
+            cfg.client.stream_snapshot = true;
+        }
         if let Some(snapshot_path) = &self.consume_snapshot {
             cfg.client.snapshot_path = Some(snapshot_path.into());
             cfg.client.snapshot = true;
@@ -199,6 +218,13 @@ impl CliOpts {
             cfg.client.snapshot_path = Some(snapshot_path.into());
             cfg.client.snapshot = false;
         }
+        if !self.import_diff.is_empty() {
+            if self.import_chain.is_some() {
+                anyhow::bail!("Can't set import_diff and import_chain at the same time!")
+            }
+            cfg.client.snapshot_diff_paths = self.import_diff.iter().map(Into::into).collect();
+            cfg.client.snapshot = true;
+        }
         cfg.client.snapshot_height = self.height;
         cfg.client.snapshot_head = self.head.map(|head| head as i64);
         if let Some(skip_load) = self.skip_load {
@@ -224,6 +250,9 @@ impl CliOpts {
         }
 
         cfg.client.load_actors = !self.skip_load_actors;
+        if self.archival {
+            cfg.sync.archival = true;
+        }
 
         Ok((cfg, path))
     }
@@ -415,4 +444,29 @@ mod tests {
         };
         assert!(options.to_config().is_ok());
     }
+
+    #[test]
+    fn import_diff() {
+        let options = CliOpts {
+            import_snapshot: Some("base.forest.car.zst".into()),
+            import_diff: vec!["diff1.forest.car.zst".into(), "diff2.forest.car.zst".into()],
+            ..Default::default()
+        };
+        let (cfg, _) = options.to_config().unwrap();
+        assert_eq!(
+            cfg.client.snapshot_diff_paths,
+            vec![
+                PathBuf::from("diff1.forest.car.zst"),
+                PathBuf::from("diff2.forest.car.zst")
+            ]
+        );
+
+        // Diffs only extend snapshots
+        let options = CliOpts {
+            import_chain: Some("snapshot.car".into()),
+            import_diff: vec!["diff.forest.car.zst".into()],
+            ..Default::default()
+        };
+        assert!(options.to_config().is_err());
+    }
 }
@@ -1,13 +1,33 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use anyhow::Context as _;
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
 use tracing_chrome::{ChromeLayerBuilder, FlushGuard};
-use tracing_subscriber::{filter::LevelFilter, prelude::*, EnvFilter};
+use tracing_subscriber::{
+    filter::{Directive, LevelFilter},
+    prelude::*,
+    reload, EnvFilter,
+};
 
 use crate::cli_shared::cli::CliOpts;
 use crate::utils::misc::LoggingColor;
 
+type FilterReloader = Box<dyn Fn(EnvFilter) -> Result<(), reload::Error> + Send + Sync>;
+
+/// Filter directives of the stdout and log file layers, which can be changed
+/// at runtime, e.g. through the `Filecoin.LogSetLevel` RPC method.
+struct ReloadableFilter {
+    directives: Mutex<Vec<String>>,
+    reloaders: Vec<FilterReloader>,
+}
+
+static RELOADABLE_FILTER: OnceCell<ReloadableFilter> = OnceCell::new();
+
 pub fn setup_logger(opts: &CliOpts) -> (Option<tracing_loki::BackgroundTask>, Option<FlushGuard>) {
+    let directives = get_env_directives(&DEFAULT_ENV_DIRECTIVES);
+    let mut reloaders: Vec<FilterReloader> = vec![];
     let mut loki_task = None;
     let tracing_tokio_console = if opts.tokio_console {
         Some(
@@ -43,11 +63,13 @@ pub fn setup_logger(opts: &CliOpts) -> (Option<tracing_loki::BackgroundTask>, Op
     };
     let tracing_rolling_file = if let Some(log_dir) = &opts.log_dir {
         let file_appender = tracing_appender::rolling::hourly(log_dir, "forest.log");
+        let (filter, handle) = reload::Layer::new(node_filter(&directives));
+        reloaders.push(Box::new(move |filter| handle.reload(filter)));
         Some(
             tracing_subscriber::fmt::Layer::new()
                 .with_ansi(false)
                 .with_writer(file_appender)
-                .with_filter(get_env_filter(default_env_filter())),
+                .with_filter(filter),
         )
     } else {
         None
@@ -64,6 +86,9 @@ pub fn setup_logger(opts: &CliOpts) -> (Option<tracing_loki::BackgroundTask>, Op
             None => (None, None),
         };
 
+    let (stdout_filter, stdout_handle) = reload::Layer::new(node_filter(&directives));
+    reloaders.push(Box::new(move |filter| stdout_handle.reload(filter)));
+
     tracing_subscriber::registry()
         .with(tracing_tokio_console)
         .with(tracing_loki)
@@ -72,12 +97,91 @@ pub fn setup_logger(opts: &CliOpts) -> (Option<tracing_loki::BackgroundTask>, Op
         .with(
             tracing_subscriber::fmt::Layer::new()
                 .with_ansi(opts.color.coloring_enabled())
-                .with_filter(get_env_filter(default_env_filter())),
+                .with_filter(stdout_filter),
         )
         .init();
+    let _ = RELOADABLE_FILTER.set(ReloadableFilter {
+        directives: Mutex::new(directives),
+        reloaders,
+    });
     (loki_task, flush_guard)
 }
 
+/// Returns the filter directives currently applied to the node logs.
+pub fn log_directives() -> anyhow::Result<Vec<String>> {
+    let filter = RELOADABLE_FILTER
+        .get()
+        .context("log filter is not reloadable")?;
+    let directives = filter.directives.lock().clone();
+    Ok(directives)
+}
+
+/// Sets the log level of `subsystem`, which is a tracing target such as
+/// `forest_filecoin::chain_sync`. The subsystem `*` sets the default level.
+pub fn set_log_level(subsystem: &str, level: &str) -> anyhow::Result<()> {
+    let filter = RELOADABLE_FILTER
+        .get()
+        .context("log filter is not reloadable")?;
+    let mut directives = filter.directives.lock();
+    let updated = with_log_level(&directives, subsystem, level)?;
+    // Build every filter before swapping any, so that an invalid level leaves
+    // all layers untouched.
+    let filters = filter
+        .reloaders
+        .iter()
+        .map(|_| env_filter(&updated))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    for (i, (reload, new_filter)) in filter.reloaders.iter().zip(filters).enumerate() {
+        if let Err(e) = reload(new_filter) {
+            // Put back the layers which were already swapped.
+            for reload in &filter.reloaders[..i] {
+                let _ = reload(node_filter(&directives));
+            }
+            return Err(e.into());
+        }
+    }
+    *directives = updated;
+    Ok(())
+}
+
+/// Replaces the directive for `subsystem` in `directives`, keeping the
+/// directives of all other targets.
+fn with_log_level(
+    directives: &[String],
+    subsystem: &str,
+    level: &str,
+) -> anyhow::Result<Vec<String>> {
+    let level = level
+        .parse::<LevelFilter>()
+        .map_err(|_| anyhow::anyhow!("invalid log level: {level}"))?;
+    let target = match subsystem.trim() {
+        "" => anyhow::bail!("subsystem must not be empty"),
+        "*" => None,
+        target if target.contains(['=', ',', '[', '{']) => {
+            anyhow::bail!("invalid subsystem: {target}")
+        }
+        target => Some(target),
+    };
+    let directive_target = |directive: &str| match directive.rsplit_once('=') {
+        Some((target, _)) => Some(target.to_owned()),
+        // a bare level is the default directive, anything else is a target
+        None if directive.parse::<LevelFilter>().is_ok() => None,
+        None => Some(directive.to_owned()),
+    };
+    let mut updated = directives
+        .iter()
+        .filter(|directive| directive_target(directive).as_deref() != target)
+        .cloned()
+        .collect::<Vec<_>>();
+    let level = level.to_string().to_lowercase();
+    match target {
+        Some(target) => updated.push(format!("{target}={level}")),
+        None => updated.insert(0, level),
+    }
+    env_filter(&updated)?;
+    Ok(updated)
+}
+
 // Log warnings to stderr
 pub fn setup_minimal_logger() {
     tracing_subscriber::registry()
@@ -85,57 +189,147 @@ pub fn setup_minimal_logger() {
             tracing_subscriber::fmt::Layer::new()
                 .with_ansi(LoggingColor::Auto.coloring_enabled())
                 .with_writer(std::io::stderr)
-                .with_filter(get_env_filter(default_tool_filter())),
+                .with_filter(node_filter(&get_env_directives(&DEFAULT_TOOL_DIRECTIVES))),
         )
         .init();
 }
 
-/// Returns an [`EnvFilter`] according to the `RUST_LOG` environment variable, or a default
-/// - see [`default_env_filter`] and [`default_tool_filter`]
+/// Builds the [`EnvFilter`] for `directives`. Every filter, at start-up or
+/// when changing a log level at runtime, is built here.
 ///
 /// Note that [`tracing_subscriber::filter::Builder`] only allows a single default directive,
 /// whereas we want to provide multiple.
 /// See also <https://github.com/tokio-rs/tracing/blob/27f688efb72316a26f3ec1f952c82626692c08ff/tracing-subscriber/src/filter/env/builder.rs#L189-L194>
-fn get_env_filter(def: EnvFilter) -> EnvFilter {
+///
+/// The directives are added one by one rather than joined, as [`EnvFilter`]
+/// would split span filters such as `target[span{a=1,b=2}]=debug` on their
+/// commas.
+fn env_filter(directives: &[impl AsRef<str>]) -> anyhow::Result<EnvFilter> {
+    directives
+        .iter()
+        .try_fold(EnvFilter::try_new("")?, |filter, directive| {
+            Ok(filter.add_directive(directive.as_ref().parse::<Directive>()?))
+        })
+}
+
+/// Like [`env_filter`], for directives returned by [`get_env_directives`],
+/// which are known to be valid.
+fn node_filter(directives: &[String]) -> EnvFilter {
+    env_filter(directives).expect("directives are validated")
+}
+
+/// Returns the filter directives of the `RUST_LOG` environment variable, or the
+/// defaults - see [`DEFAULT_ENV_DIRECTIVES`] and [`DEFAULT_TOOL_DIRECTIVES`].
+/// Invalid directives are ignored.
+fn get_env_directives(def: &[&str]) -> Vec<String> {
     use std::env::{
         self,
         VarError::{NotPresent, NotUnicode},
     };
     match env::var(tracing_subscriber::EnvFilter::DEFAULT_ENV) {
-        Ok(s) => EnvFilter::new(s),
-        Err(NotPresent) => def,
-        Err(NotUnicode(_)) => EnvFilter::default(),
+        Ok(s) => parse_directives(&s),
+        Err(NotPresent) => def.iter().map(|directive| directive.to_string()).collect(),
+        Err(NotUnicode(_)) => vec![],
     }
 }
 
-fn default_env_filter() -> EnvFilter {
-    let default_directives = [
-        "info",
-        "bellperson::groth16::aggregate::verify=warn",
-        "axum=warn",
-        "filecoin_proofs=warn",
-        "libp2p_bitswap=off",
-        "libp2p_gossipsub=error",
-        "libp2p_kad=error",
-        "rpc=error",
-        "storage_proofs_core=warn",
-        "tracing_loki=off",
-    ];
-    EnvFilter::try_new(default_directives.join(",")).unwrap()
+/// Splits `s` into its valid directives. Only the commas outside of span
+/// filters separate directives.
+fn parse_directives(s: &str) -> Vec<String> {
+    let mut directives = vec![];
+    let (mut depth, mut start) = (0usize, 0);
+    for (i, c) in s.char_indices() {
+        match c {
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                directives.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    directives.push(&s[start..]);
+    directives
+        .into_iter()
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .filter(|directive| directive.parse::<Directive>().is_ok())
+        .map(str::to_owned)
+        .collect()
 }
 
-fn default_tool_filter() -> EnvFilter {
-    let default_directives = [
-        "warn",
-        "forest::snapshot=info",
-        "forest::progress=info",
-        "libp2p_bitswap=off",
-        "tracing_loki=off",
-    ];
-    EnvFilter::try_new(default_directives.join(",")).unwrap()
-}
+const DEFAULT_ENV_DIRECTIVES: [&str; 10] = [
+    "info",
+    "bellperson::groth16::aggregate::verify=warn",
+    "axum=warn",
+    "filecoin_proofs=warn",
+    "libp2p_bitswap=off",
+    "libp2p_gossipsub=error",
+    "libp2p_kad=error",
+    "rpc=error",
+    "storage_proofs_core=warn",
+    "tracing_loki=off",
+];
+
+const DEFAULT_TOOL_DIRECTIVES: [&str; 5] = [
+    "warn",
+    "forest::snapshot=info",
+    "forest::progress=info",
+    "libp2p_bitswap=off",
+    "tracing_loki=off",
+];
 
 #[test]
 fn test_default_env_filter() {
-    let _did_not_panic = default_env_filter();
+    assert!(env_filter(&DEFAULT_ENV_DIRECTIVES).is_ok());
+    assert!(env_filter(&DEFAULT_TOOL_DIRECTIVES).is_ok());
+}
+
+#[test]
+fn test_with_log_level() {
+    let directives = ["info", "axum=warn", "rpc=error"].map(String::from);
+
+    let updated = with_log_level(&directives, "axum", "DEBUG").unwrap();
+    assert_eq!(updated, ["info", "rpc=error", "axum=debug"]);
+
+    let updated = with_log_level(&directives, "*", "warn").unwrap();
+    assert_eq!(updated, ["warn", "axum=warn", "rpc=error"]);
+
+    let updated = with_log_level(&directives, "forest_filecoin::chain_sync", "trace").unwrap();
+    assert_eq!(
+        updated,
+        [
+            "info",
+            "axum=warn",
+            "rpc=error",
+            "forest_filecoin::chain_sync=trace"
+        ]
+    );
+
+    assert!(with_log_level(&directives, "axum", "loud").is_err());
+    assert!(with_log_level(&directives, "axum=info", "info").is_err());
+    assert!(with_log_level(&directives, "", "info").is_err());
+}
+
+#[test]
+fn test_parse_directives() {
+    let directives = parse_directives("info, forest[sync{a=1,b=2}]=debug,,axum=loud,rpc=error");
+    assert_eq!(
+        directives,
+        ["info", "forest[sync{a=1,b=2}]=debug", "rpc=error"]
+    );
+    assert!(env_filter(&directives).is_ok());
+
+    // A span filter isn't mistaken for the target of a directive.
+    let updated = with_log_level(&directives, "forest", "trace").unwrap();
+    assert_eq!(
+        updated,
+        [
+            "info",
+            "forest[sync{a=1,b=2}]=debug",
+            "rpc=error",
+            "forest=trace"
+        ]
+    );
 }
@@ -9,15 +9,22 @@ use std::{
 
 use crate::{
     networks::NetworkChain,
-    utils::{retry, RetryArgs},
+    utils::{
+        net::{download_file, Download, DownloadOptions},
+        retry, RetryArgs,
+    },
 };
 use anyhow::{bail, Context as _};
 use chrono::NaiveDate;
-use tracing::event;
+use itertools::Itertools as _;
+use tokio::io::AsyncWriteExt as _;
+use tracing::{debug, event, warn};
 use url::Url;
 
 use crate::cli_shared::snapshot::parse::ParsedFilename;
 
+pub mod manifest;
+
 /// Who hosts the snapshot on the web?
 /// See [`stable_url`].
 #[derive(
@@ -61,37 +68,204 @@ pub fn filename(
     .to_string()
 }
 
-/// Returns the path to the downloaded file.
+/// Saves the hex-encoded SHA-256 checksum of `source` to a file with the same
+/// name but with a `.sha256sum` extension, in the `sha256sum` format.
+pub async fn save_checksum(source: &Path, encoded_hash: String) -> anyhow::Result<()> {
+    let checksum_file_content = format!(
+        "{encoded_hash} {}\n",
+        source
+            .file_name()
+            .and_then(std::ffi::OsStr::to_str)
+            .context("Failed to retrieve file name while saving checksum")?
+    );
+
+    let checksum_path = PathBuf::from(source).with_extension("sha256sum");
+
+    let mut checksum_file = tokio::fs::File::create(&checksum_path).await?;
+    checksum_file
+        .write_all(checksum_file_content.as_bytes())
+        .await?;
+    checksum_file.flush().await?;
+    Ok(())
+}
+
+/// Returns the path to the downloaded file. If the snapshot of `vendor`
+/// can't be downloaded, the snapshots of the other trusted vendors are tried.
+/// Vendors publishing the same snapshot are used as mirrors of each other.
 pub async fn fetch(
     directory: &Path,
     chain: &NetworkChain,
     vendor: TrustedVendor,
 ) -> anyhow::Result<PathBuf> {
-    let (_len, path) = peek(vendor, chain).await?;
-    let (date, height, forest_format) = ParsedFilename::parse_str(&path)
-        .context("unexpected path format")?
-        .date_and_height_and_forest();
-    let url = stable_url(vendor, chain)?;
-    let filename = filename(vendor, chain, date, height, forest_format);
-
-    download_file_with_retry(&url, directory, &filename).await
+    let vendors = std::iter::once(vendor).chain(
+        [TrustedVendor::Forest, TrustedVendor::Filops]
+            .into_iter()
+            .filter(|it| *it != vendor),
+    );
+    let mut error = None;
+    let mut published = vec![];
+    for vendor in vendors {
+        match Published::peek(vendor, chain).await {
+            Ok(snapshot) => published.push(snapshot),
+            Err(e) => {
+                warn!("couldn't find the snapshot of {vendor}: {e:#}");
+                error = Some(e);
+            }
+        }
+    }
+    for (i, snapshot) in published.iter().enumerate() {
+        let mirrors = mirrors(&published[i..])
+            .map(|vendor| stable_url(vendor, chain))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        match fetch_from(directory, chain, snapshot, &mirrors).await {
+            Ok(path) => return Ok(path),
+            Err(e) => {
+                warn!(
+                    "couldn't fetch the snapshot from {}: {e:#}",
+                    snapshot.vendor
+                );
+                error = Some(e);
+            }
+        }
+    }
+    Err(error.unwrap_or_else(|| anyhow::anyhow!("no trusted vendor")))
+}
+
+/// A snapshot published by a vendor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Published {
+    vendor: TrustedVendor,
+    size: u64,
+    date: NaiveDate,
+    height: i64,
+    forest_format: bool,
+}
+
+impl Published {
+    async fn peek(vendor: TrustedVendor, chain: &NetworkChain) -> anyhow::Result<Self> {
+        let (size, path) = peek(vendor, chain).await?;
+        let (date, height, forest_format) = ParsedFilename::parse_str(&path)
+            .context("unexpected path format")?
+            .date_and_height_and_forest();
+        Ok(Self {
+            vendor,
+            size,
+            date,
+            height,
+            forest_format,
+        })
+    }
+}
+
+/// Returns the vendors serving the first snapshot of `published`, starting
+/// with its own vendor.
+fn mirrors(published: &[Published]) -> impl Iterator<Item = TrustedVendor> + '_ {
+    let primary = &published[0];
+    published
+        .iter()
+        .filter(|it| {
+            (it.size, it.date, it.height, it.forest_format)
+                == (
+                    primary.size,
+                    primary.date,
+                    primary.height,
+                    primary.forest_format,
+                )
+        })
+        .map(|it| it.vendor)
+}
+
+async fn fetch_from(
+    directory: &Path,
+    chain: &NetworkChain,
+    snapshot: &Published,
+    mirrors: &[Url],
+) -> anyhow::Result<PathBuf> {
+    let filename = filename(
+        snapshot.vendor,
+        chain,
+        snapshot.date,
+        snapshot.height,
+        snapshot.forest_format,
+    );
+
+    let download = download_file_with_retry(mirrors, directory, &filename).await?;
+    // Only a mismatch discards the download, failing to fetch the checksum doesn't.
+    let published = retry(
+        RetryArgs {
+            timeout: None,
+            max_retries: Some(DOWNLOAD_ATTEMPTS),
+            ..Default::default()
+        },
+        || fetch_published_checksum(&download.url),
+    )
+    .await;
+    match published {
+        Ok(Some(published)) if !published.eq_ignore_ascii_case(&download.sha256) => {
+            tokio::fs::remove_file(&download.path).await?;
+            bail!(
+                "snapshot checksum {} doesn't match the published checksum {published}",
+                download.sha256
+            );
+        }
+        Ok(_) => {}
+        Err(e) => warn!(
+            "couldn't fetch the published checksum of {}, keeping it unverified: {e}",
+            download.path.display()
+        ),
+    }
+    Ok(download.path)
 }
 
+/// Number of attempts at downloading a file from a set of mirrors, before
+/// giving up on them.
+const DOWNLOAD_ATTEMPTS: usize = 3;
+
+/// Downloads the file served by `mirrors` to `directory/filename`. See
+/// [`download_file`] for how mirrors are used and interrupted downloads resumed.
 pub async fn download_file_with_retry(
-    url: &Url,
+    mirrors: &[Url],
     directory: &Path,
     filename: &str,
-) -> anyhow::Result<PathBuf> {
+) -> anyhow::Result<Download> {
+    let destination = directory.join(filename);
+    let options = DownloadOptions::default();
+    event!(target: "forest::snapshot", tracing::Level::INFO, url = %mirrors.iter().format(", "), "downloading snapshot");
     Ok(retry(
         RetryArgs {
             timeout: None,
+            max_retries: Some(DOWNLOAD_ATTEMPTS),
             ..Default::default()
         },
-        || download_http(url, directory, filename),
+        || download_file(mirrors, &destination, &options),
     )
     .await?)
 }
 
+/// Fetches the `.sha256sum` file published next to `url`, if there is one.
+async fn fetch_published_checksum(url: &Url) -> anyhow::Result<Option<String>> {
+    let mut url = url.clone();
+    url.set_path(&format!("{}.sha256sum", url.path()));
+    let response = reqwest::get(url.clone())
+        .await
+        .with_context(|| format!("couldn't fetch the published checksum {url}"))?;
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        debug!(%url, "no published checksum");
+        return Ok(None);
+    }
+    let published = response
+        .error_for_status()
+        .with_context(|| format!("couldn't fetch the published checksum {url}"))?
+        .text()
+        .await
+        .with_context(|| format!("couldn't read the published checksum {url}"))?;
+    let published = published
+        .split_whitespace()
+        .next()
+        .context("empty checksum file")?;
+    Ok(Some(published.to_owned()))
+}
+
 /// Returns
 /// - The size of the snapshot from this vendor on this chain
 /// - The filename of the snapshot
@@ -128,23 +302,6 @@ fn parse_content_disposition(value: &reqwest::header::HeaderValue) -> Option<Str
     Some(cap.get(1)?.as_str().to_owned())
 }
 
-/// Download the file at `url` with a private HTTP client, returning the path to the downloaded file
-async fn download_http(url: &Url, directory: &Path, filename: &str) -> anyhow::Result<PathBuf> {
-    let dst_path = directory.join(filename);
-
-    event!(target: "forest::snapshot", tracing::Level::INFO, %url, "downloading snapshot");
-    let mut reader = crate::utils::net::reader(url.as_str()).await?;
-
-    let mut dst = tokio::fs::File::create(&dst_path)
-        .await
-        .context("couldn't create destination file")?;
-
-    tokio::io::copy(&mut reader, &mut dst)
-        .await
-        .map(|_| dst_path)
-        .context("couldn't download file")
-}
-
 /// Also defines an `ALL_URLS` constant for test purposes
 macro_rules! define_urls {
     ($($vis:vis const $name:ident: &str = $value:literal;)* $(,)?) => {
@@ -180,6 +337,35 @@ pub fn stable_url(vendor: TrustedVendor, chain: &NetworkChain) -> anyhow::Result
     Ok(Url::from_str(s).unwrap())
 }
 
+#[test]
+fn vendors_publishing_the_same_snapshot_are_mirrors() {
+    let date = NaiveDate::from_ymd_opt(2023, 9, 14).unwrap();
+    let snapshot = |vendor, height| Published {
+        vendor,
+        size: 100,
+        date,
+        height,
+        forest_format: false,
+    };
+    let same = [
+        snapshot(TrustedVendor::Filops, 10),
+        snapshot(TrustedVendor::Forest, 10),
+    ];
+    assert_eq!(
+        mirrors(&same).collect_vec(),
+        [TrustedVendor::Filops, TrustedVendor::Forest]
+    );
+    let different = [
+        snapshot(TrustedVendor::Filops, 10),
+        snapshot(TrustedVendor::Forest, 20),
+    ];
+    assert_eq!(mirrors(&different).collect_vec(), [TrustedVendor::Filops]);
+    assert_eq!(
+        mirrors(&different[1..]).collect_vec(),
+        [TrustedVendor::Forest]
+    );
+}
+
 #[test]
 fn parse_stable_urls() {
     for url in ALL_URLS {
@@ -0,0 +1,339 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Signed snapshot manifests.
+//!
+//! A manifest describes a snapshot: its network, head tipset, state root, size
+//! and SHA-256 checksum. It is signed with a Filecoin wallet key and published
+//! next to the snapshot, as `<snapshot>.manifest.json`. A node configured with
+//! trusted signers only imports snapshots whose manifest is signed by one of
+//! them and matches the snapshot.
+
+use crate::blocks::{BlockHeader, Tipset, TipsetKeys};
+use crate::chain::index::{ChainIndex, ResolveNullTipset};
+use crate::db::car::AnyCar;
+use crate::key_management::{Key, KeyInfo};
+use crate::lotus_json::LotusJson;
+use crate::networks::NetworkChain;
+use crate::shim::address::Address;
+use crate::shim::clock::ChainEpoch;
+use crate::shim::crypto::{Signature, SignatureType};
+use crate::utils::io::{AsyncWriterWithChecksum, Checksum as _, VoidAsyncWriter};
+use anyhow::{bail, ensure, Context as _};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+use std::str::FromStr as _;
+use std::sync::Arc;
+use tokio::io::BufWriter;
+use url::Url;
+
+pub const MANIFEST_EXTENSION: &str = ".manifest.json";
+
+/// Fields not covered by the signature are rejected rather than ignored, see
+/// [`SnapshotManifest::signing_bytes`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+pub struct SnapshotManifest {
+    pub network: String,
+    pub epoch: ChainEpoch,
+    #[serde(with = "crate::lotus_json")]
+    pub head: TipsetKeys,
+    #[serde(with = "crate::lotus_json")]
+    pub state_root: Cid,
+    pub size: u64,
+    /// Hex-encoded SHA-256 checksum of the snapshot file.
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SignedSnapshotManifest {
+    pub manifest: SnapshotManifest,
+    #[serde(with = "crate::lotus_json")]
+    pub signer: Address,
+    #[serde(with = "crate::lotus_json")]
+    pub signature: Signature,
+}
+
+/// Location of the manifest of the snapshot at `snapshot`, a local path or a URL.
+pub fn manifest_path(snapshot: &Path) -> PathBuf {
+    let mut path = snapshot.as_os_str().to_owned();
+    path.push(MANIFEST_EXTENSION);
+    path.into()
+}
+
+impl SnapshotManifest {
+    /// Describes the snapshot file at `path`.
+    pub async fn from_snapshot(path: &Path) -> anyhow::Result<Self> {
+        let car = AnyCar::try_from(path)?;
+        let head = car.heaviest_tipset()?;
+        let genesis = head.genesis(&car)?;
+        Ok(Self {
+            network: NetworkChain::from_genesis_or_devnet_placeholder(genesis.cid()).to_string(),
+            epoch: head.epoch(),
+            head: head.key().clone(),
+            state_root: *head.parent_state(),
+            size: std::fs::metadata(path)?.len(),
+            sha256: sha256_file(path).await?,
+        })
+    }
+
+    /// The signed representation of the manifest: its fields, in order, as a
+    /// DAG-CBOR list. Unlike JSON, this encoding is canonical, so it doesn't
+    /// depend on formatting or on the order of the fields in the manifest file.
+    pub fn signing_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(fvm_ipld_encoding::to_vec(&(
+            &self.network,
+            self.epoch,
+            &self.head,
+            self.state_root,
+            self.size,
+            &self.sha256,
+        ))?)
+    }
+
+    pub fn sign(self, key: &Key) -> anyhow::Result<SignedSnapshotManifest> {
+        let signature = crate::key_management::sign(
+            *key.key_info.key_type(),
+            key.key_info.private_key(),
+            &self.signing_bytes()?,
+        )?;
+        Ok(SignedSnapshotManifest {
+            manifest: self,
+            signer: key.address,
+            signature,
+        })
+    }
+
+    /// Checks the size and the checksum of the snapshot file at `path`.
+    pub async fn check_file(&self, path: &Path) -> anyhow::Result<()> {
+        let size = std::fs::metadata(path)?.len();
+        self.check_size(size)?;
+        self.check_digest(size, &sha256_file(path).await?)
+    }
+
+    /// Checks the size and the hex-encoded SHA-256 checksum of a snapshot,
+    /// e.g. as computed while downloading it.
+    pub fn check_digest(&self, size: u64, sha256: &str) -> anyhow::Result<()> {
+        self.check_size(size)?;
+        ensure!(
+            sha256 == self.sha256,
+            "snapshot checksum {sha256} doesn't match the manifest checksum {}",
+            self.sha256
+        );
+        Ok(())
+    }
+
+    fn check_size(&self, size: u64) -> anyhow::Result<()> {
+        ensure!(
+            size == self.size,
+            "snapshot size {size} doesn't match the manifest size {}",
+            self.size
+        );
+        Ok(())
+    }
+
+    /// Checks that `head`, the heaviest tipset of a snapshot, is the one of the manifest.
+    pub fn check_head(&self, head: &Tipset) -> anyhow::Result<()> {
+        ensure!(
+            head.key() == &self.head && head.epoch() == self.epoch,
+            "snapshot head at epoch {} doesn't match the manifest head at epoch {}",
+            head.epoch(),
+            self.epoch
+        );
+        ensure!(
+            head.parent_state() == &self.state_root,
+            "snapshot state root {} doesn't match the manifest state root {}",
+            head.parent_state(),
+            self.state_root
+        );
+        Ok(())
+    }
+}
+
+impl SignedSnapshotManifest {
+    /// Loads the manifest at `path`, a local path or a URL.
+    pub async fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes = match Url::parse(&path.display().to_string()) {
+            Ok(url) => reqwest::get(url)
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?
+                .to_vec(),
+            Err(_) => tokio::fs::read(path).await?,
+        };
+        serde_json::from_slice(&bytes)
+            .with_context(|| format!("invalid snapshot manifest {}", path.display()))
+    }
+
+    pub async fn save(&self, path: &Path) -> anyhow::Result<()> {
+        tokio::fs::write(path, serde_json::to_vec_pretty(self)?).await?;
+        Ok(())
+    }
+
+    /// Checks the signature of the manifest, and that its signer is one of
+    /// `trusted_signers`.
+    pub fn verify(&self, trusted_signers: &[Address]) -> anyhow::Result<&SnapshotManifest> {
+        ensure!(
+            trusted_signers.contains(&self.signer),
+            "snapshot manifest signer {} isn't trusted",
+            self.signer
+        );
+        // Delegated signatures aren't checked by `Signature::verify`.
+        if !matches!(
+            self.signature.signature_type(),
+            SignatureType::Secp256k1 | SignatureType::Bls
+        ) {
+            bail!("unsupported snapshot manifest signature type");
+        }
+        self.signature
+            .verify(&self.manifest.signing_bytes()?, &self.signer)
+            .map_err(|e| anyhow::anyhow!("invalid snapshot manifest signature: {e}"))?;
+        Ok(&self.manifest)
+    }
+}
+
+pub fn parse_signers(signers: &[String]) -> anyhow::Result<Vec<Address>> {
+    signers
+        .iter()
+        .map(|signer| {
+            Address::from_str(signer).with_context(|| format!("invalid signer address {signer}"))
+        })
+        .collect()
+}
+
+pub fn parse_checkpoints(checkpoints: &[String]) -> anyhow::Result<Vec<Cid>> {
+    checkpoints
+        .iter()
+        .map(|checkpoint| {
+            Cid::from_str(checkpoint).with_context(|| format!("invalid checkpoint {checkpoint}"))
+        })
+        .collect()
+}
+
+/// Parses a key exported with `forest-wallet export`.
+pub fn parse_exported_key(exported: &str) -> anyhow::Result<Key> {
+    let decoded = hex::decode(exported.trim()).context("Key must be hex encoded")?;
+    let LotusJson(key_info) =
+        serde_json::from_slice::<LotusJson<KeyInfo>>(&decoded).context("invalid key format")?;
+    Ok(Key::try_from(key_info)?)
+}
+
+/// Checks that each of the `checkpoints` block headers is part of the chain
+/// ending at `head`.
+pub fn ensure_checkpoints(
+    store: impl Blockstore,
+    head: &Tipset,
+    checkpoints: &[Cid],
+) -> anyhow::Result<()> {
+    let store = Arc::new(store);
+    let index = ChainIndex::new(store.clone());
+    for checkpoint in checkpoints {
+        let header = BlockHeader::load(&store, *checkpoint)?
+            .with_context(|| format!("checkpoint {checkpoint} is missing from the snapshot"))?;
+        let tipset = index
+            .tipset_by_height(
+                header.epoch(),
+                Arc::new(head.clone()),
+                ResolveNullTipset::TakeOlder,
+            )
+            .with_context(|| {
+                format!(
+                    "couldn't load the snapshot tipset at epoch {}",
+                    header.epoch()
+                )
+            })?;
+        ensure!(
+            tipset.key().cids.contains(*checkpoint),
+            "checkpoint {checkpoint} at epoch {} isn't part of the snapshot chain",
+            header.epoch()
+        );
+    }
+    Ok(())
+}
+
+async fn sha256_file(path: &Path) -> anyhow::Result<String> {
+    let mut file = tokio::fs::File::open(path).await?;
+    let mut hasher =
+        AsyncWriterWithChecksum::<Sha256, _>::new(BufWriter::new(VoidAsyncWriter), true);
+    tokio::io::copy(&mut file, &mut hasher).await?;
+    Ok(hex::encode(hasher.finalize()?.context("missing checksum")?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::key_management::generate_key;
+    use crate::networks::calibnet;
+    use std::io::Write as _;
+
+    async fn calibnet_genesis_manifest() -> (tempfile::NamedTempFile, SnapshotManifest) {
+        let mut file = tempfile::Builder::new().tempfile().unwrap();
+        file.write_all(calibnet::DEFAULT_GENESIS).unwrap();
+        let manifest = SnapshotManifest::from_snapshot(file.path()).await.unwrap();
+        (file, manifest)
+    }
+
+    #[tokio::test]
+    async fn sign_and_verify() {
+        let (file, manifest) = calibnet_genesis_manifest().await;
+        assert_eq!(manifest.network, "calibnet");
+        assert_eq!(manifest.epoch, 0);
+        manifest.check_file(file.path()).await.unwrap();
+
+        for sig_type in [SignatureType::Secp256k1, SignatureType::Bls] {
+            let key = generate_key(sig_type).unwrap();
+            let signed = manifest.clone().sign(&key).unwrap();
+            assert_eq!(signed.verify(&[key.address]).unwrap(), &manifest);
+            // Untrusted signer
+            let other = generate_key(sig_type).unwrap();
+            signed.verify(&[other.address]).unwrap_err();
+            // Tampered manifest
+            let mut tampered = signed.clone();
+            tampered.manifest.epoch += 1;
+            tampered.verify(&[key.address]).unwrap_err();
+        }
+    }
+
+    #[tokio::test]
+    async fn signature_is_independent_of_the_json_layout() {
+        let (_file, manifest) = calibnet_genesis_manifest().await;
+        let key = generate_key(SignatureType::Secp256k1).unwrap();
+        let signed = manifest.sign(&key).unwrap();
+
+        // Reformatted fields, sorted alphabetically by `serde_json::Value`, still verify.
+        let mut json = serde_json::to_value(&signed).unwrap();
+        let reordered: SignedSnapshotManifest =
+            serde_json::from_slice(&serde_json::to_vec_pretty(&json).unwrap()).unwrap();
+        reordered.verify(&[key.address]).unwrap();
+
+        // Unsigned fields are rejected.
+        json["Manifest"]["Mirror"] = "https://example.com".into();
+        serde_json::from_value::<SignedSnapshotManifest>(json).unwrap_err();
+    }
+
+    #[tokio::test]
+    async fn check_file_and_head() {
+        let (file, mut manifest) = calibnet_genesis_manifest().await;
+        let car = AnyCar::try_from(file.path()).unwrap();
+        let head = car.heaviest_tipset().unwrap();
+        manifest.check_head(&head).unwrap();
+        ensure_checkpoints(&car, &head, &[*head.min_ticket_block().cid()]).unwrap();
+        ensure_checkpoints(
+            MemoryDB::default(),
+            &head,
+            &[*head.min_ticket_block().cid()],
+        )
+        .unwrap_err();
+
+        manifest.size += 1;
+        manifest.check_file(file.path()).await.unwrap_err();
+        manifest.epoch += 1;
+        manifest.check_head(&head).unwrap_err();
+    }
+}
@@ -1,22 +1,32 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::blocks::Tipset;
-use crate::cli_shared::snapshot;
-use crate::db::car::forest::FOREST_CAR_FILE_EXTENSION;
+use crate::blocks::{BlockHeader, Tipset, TipsetKeys};
+use crate::cli_shared::cli::{BufferSize, ChunkSize};
+use crate::cli_shared::snapshot::{
+    self,
+    manifest::{self, SnapshotManifest},
+};
+use crate::db::car::forest::{Encoder, FOREST_CAR_FILE_EXTENSION};
 use crate::db::car::{ForestCar, ManyCar};
-use crate::utils::db::car_stream::CarStream;
-use crate::utils::io::EitherMmapOrRandomAccessFile;
+use crate::shim::state_tree::StateTree;
+use crate::utils::db::car_stream::{CarBlock, CarStream};
+use crate::utils::io::{EitherMmapOrRandomAccessFile, WithProgress};
+use crate::utils::reqwest_resume;
 use anyhow::Context as _;
-use futures::TryStreamExt;
-use std::ffi::OsStr;
+use cid::Cid;
+use futures::{StreamExt as _, TryStreamExt};
+use fvm_ipld_blockstore::Blockstore;
+use sha2::{Digest as _, Sha256};
 use std::fs;
 use std::io;
+use std::sync::Arc;
 use std::{
     path::{Path, PathBuf},
     time,
 };
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncBufRead, AsyncWriteExt, BufReader};
+use tokio_util::io::StreamReader;
 use tracing::{debug, info};
 use url::Url;
 use walkdir::WalkDir;
@@ -50,22 +60,40 @@ pub fn load_all_forest_cars<T>(store: &ManyCar<T>, forest_car_db_dir: &Path) ->
 
 /// This function validates and stores the CAR binary from `from_path`(either local path or URL) into the `{DB_ROOT}/car_db/`
 /// (automatically trans-code into `.forest.car.zst` format when needed), and returns its final file path and the heaviest tipset.
+/// The snapshot is rejected if it doesn't match `manifest`.
 pub async fn import_chain_as_forest_car(
     from_path: &Path,
     forest_car_db_dir: &Path,
     consume_snapshot_file: bool,
+    manifest: Option<&SnapshotManifest>,
 ) -> anyhow::Result<(PathBuf, Tipset)> {
     info!("Importing chain from snapshot at: {}", from_path.display());
 
     let stopwatch = time::Instant::now();
 
-    let downloaded_car_temp_path =
-        tempfile::NamedTempFile::new_in(forest_car_db_dir)?.into_temp_path();
-    if let Ok(url) = Url::parse(&from_path.display().to_string()) {
-        download_to(&url, &downloaded_car_temp_path).await?;
+    let downloaded_car_temp_path = if let Ok(url) = Url::parse(&from_path.display().to_string()) {
+        // Downloads have a stable name in the database directory, so that an
+        // interrupted download is resumed on the next start.
+        let download = snapshot::download_file_with_retry(
+            &[url.clone()],
+            forest_car_db_dir,
+            &download_filename(&url),
+        )
+        .await?;
+        let path = tempfile::TempPath::from_path(&download.path);
+        if let Some(manifest) = manifest {
+            // The checksum was computed while downloading.
+            manifest.check_digest(download.size, &download.sha256)?;
+        }
+        path
     } else {
-        move_or_copy_file(from_path, &downloaded_car_temp_path, consume_snapshot_file)?;
-    }
+        let path = tempfile::NamedTempFile::new_in(forest_car_db_dir)?.into_temp_path();
+        move_or_copy_file(from_path, &path, consume_snapshot_file)?;
+        if let Some(manifest) = manifest {
+            manifest.check_file(&path).await?;
+        }
+        path
+    };
 
     let forest_car_db_path = forest_car_db_dir.join(format!(
         "{}{FOREST_CAR_FILE_EXTENSION}",
@@ -85,6 +113,10 @@ pub async fn import_chain_as_forest_car(
     }
 
     let ts = ForestCar::try_from(forest_car_db_path.as_path())?.heaviest_tipset()?;
+    if let Some(Err(e)) = manifest.map(|manifest| manifest.check_head(&ts)) {
+        fs::remove_file(&forest_car_db_path)?;
+        return Err(e);
+    }
     info!(
         "Imported snapshot in: {}s, heaviest tipset epoch: {}",
         stopwatch.elapsed().as_secs(),
@@ -94,25 +126,192 @@ pub async fn import_chain_as_forest_car(
     Ok((forest_car_db_path, ts))
 }
 
-async fn download_to(url: &Url, destination: &Path) -> anyhow::Result<()> {
-    snapshot::download_file_with_retry(
-        url,
-        destination.parent().with_context(|| {
-            format!(
-                "Error getting the parent directory of {}",
-                destination.display()
-            )
-        })?,
-        destination
-            .file_name()
-            .and_then(OsStr::to_str)
-            .with_context(|| format!("Error getting the file name of {}", destination.display()))?,
+/// Imports the snapshot at `url` while downloading it, without a temporary
+/// copy of the snapshot: blocks are transcoded on the fly into a
+/// `.forest.car.zst` file in `forest_car_db_dir`, in chunks of `chunk_size`,
+/// with at most `buffer_size` chunks read ahead. The file only becomes a
+/// database file once the snapshot has been validated, and is discarded
+/// otherwise. The snapshot is rejected if it doesn't match `manifest`, if the
+/// state of its heaviest tipset is incomplete or if its chain doesn't lead to
+/// `genesis` and `checkpoints`. Returns the path of the file and the heaviest
+/// tipset of the snapshot.
+pub async fn stream_import_snapshot(
+    url: &Url,
+    forest_car_db_dir: &Path,
+    chunk_size: ChunkSize,
+    buffer_size: BufferSize,
+    manifest: Option<&SnapshotManifest>,
+    genesis: &BlockHeader,
+    checkpoints: &[Cid],
+) -> anyhow::Result<(PathBuf, Tipset)> {
+    info!("Streaming snapshot import from: {url}");
+    let stopwatch = time::Instant::now();
+
+    let response = reqwest_resume::get(url.clone()).await?;
+    response.response().error_for_status_ref()?;
+    let content_length = response.response().content_length().unwrap_or_default();
+    let (mut hasher, mut size) = (Sha256::new(), 0);
+    let body = response.bytes_stream().inspect_ok(|bytes| {
+        hasher.update(bytes);
+        size += bytes.len() as u64;
+    });
+    let reader = BufReader::new(WithProgress::wrap_async_read(
+        "Importing",
+        StreamReader::new(body.map_err(io::Error::other)),
+        content_length,
+    ));
+    let temp_path = tempfile::NamedTempFile::new_in(forest_car_db_dir)?.into_temp_path();
+    import_car_stream(
+        reader,
+        &temp_path,
+        chunk_size,
+        buffer_size,
+        manifest.map(|manifest| &manifest.head),
     )
     .await?;
+    if let Some(manifest) = manifest {
+        manifest.check_digest(size, &hex::encode(hasher.finalize()))?;
+    }
+
+    let car = ForestCar::try_from(temp_path.as_ref())?;
+    let ts = car.heaviest_tipset()?;
+    if let Some(manifest) = manifest {
+        manifest.check_head(&ts)?;
+    }
+    let car = Arc::new(car);
+    ensure_head(&car, &ts, genesis)?;
+    manifest::ensure_checkpoints(&car, &ts, checkpoints)?;
+    drop(car);
+
+    let forest_car_db_path = forest_car_db_dir.join(format!(
+        "{}{FOREST_CAR_FILE_EXTENSION}",
+        chrono::Utc::now().timestamp_millis()
+    ));
+    temp_path.persist(&forest_car_db_path)?;
+    info!(
+        "Imported snapshot in: {}s, heaviest tipset epoch: {}",
+        stopwatch.elapsed().as_secs(),
+        ts.epoch()
+    );
+    Ok((forest_car_db_path, ts))
+}
+
+/// Transcodes the CAR archive read from `reader` into a `.forest.car.zst`
+/// file at `path`, see [`stream_import_snapshot`]. The roots are checked
+/// against `expected_head` before any block is read, and each block against
+/// its CID.
+async fn import_car_stream(
+    reader: impl AsyncBufRead + Unpin,
+    path: &Path,
+    chunk_size: ChunkSize,
+    buffer_size: BufferSize,
+    expected_head: Option<&TipsetKeys>,
+) -> anyhow::Result<()> {
+    let mut stream = CarStream::new(reader).await?;
+    anyhow::ensure!(
+        stream.header.version == 1,
+        "unsupported CAR version {}",
+        stream.header.version
+    );
+    let roots = stream.header.roots.clone();
+    let head = TipsetKeys::from_iter(roots.iter().copied());
+    anyhow::ensure!(!head.cids.is_empty(), "snapshot has no roots");
+    if let Some(expected_head) = expected_head {
+        anyhow::ensure!(
+            &head == expected_head,
+            "snapshot roots don't match the manifest head"
+        );
+    }
 
+    // Blocks are checked and compressed by another task, while the next
+    // chunks are downloaded.
+    let (tx, rx) = flume::bounded::<Vec<CarBlock>>(buffer_size.0.max(1) as usize);
+    let mut file = tokio::io::BufWriter::new(tokio::fs::File::create(path).await?);
+    let writer = tokio::spawn(async move {
+        let blocks = rx
+            .into_stream()
+            .flat_map(futures::stream::iter)
+            .map(|block| {
+                anyhow::ensure!(
+                    block.valid(),
+                    "snapshot block {} doesn't match its CID",
+                    block.cid
+                );
+                Ok(block)
+            });
+        let frames = Encoder::compress_stream_default(blocks);
+        Encoder::write(&mut file, roots, frames).await?;
+        file.shutdown().await?;
+        anyhow::Ok(())
+    });
+    let chunk_size = chunk_size.0.max(1) as usize;
+    let mut chunk = vec![];
+    while let Some(block) = stream.try_next().await? {
+        chunk.push(block);
+        if chunk.len() >= chunk_size && tx.send_async(std::mem::take(&mut chunk)).await.is_err() {
+            // The writer failed, its error is returned below.
+            break;
+        }
+    }
+    if !chunk.is_empty() {
+        let _ = tx.send_async(chunk).await;
+    }
+    drop(tx);
+    writer.await?
+}
+
+/// Checks that `head`, the heaviest tipset of a snapshot, comes with its full
+/// state tree and descends from `genesis`.
+fn ensure_head<DB: Blockstore>(
+    store: &Arc<DB>,
+    head: &Tipset,
+    genesis: &BlockHeader,
+) -> anyhow::Result<()> {
+    StateTree::new_from_root(store.clone(), head.parent_state()).with_context(|| {
+        format!(
+            "snapshot state root {} is missing or invalid",
+            head.parent_state()
+        )
+    })?;
+    manifest::ensure_checkpoints(store, head, &[*genesis.cid()])
+        .context("snapshot isn't part of the chain of this network")
+}
+
+/// Checks that the diff snapshot `diff`, whose heaviest tipset is `diff_head`,
+/// extends the chain ending at `head`: the oldest tipset of the diff must be a
+/// child of `head`, as for diffs exported with
+/// `forest-tool archive export --diff <head epoch>`. Diffs that also contain
+/// `head` are accepted.
+pub fn ensure_diff_extends(
+    head: &Tipset,
+    diff: &impl Blockstore,
+    diff_head: &Tipset,
+) -> anyhow::Result<()> {
+    let mut oldest = diff_head.clone();
+    while oldest.key() != head.key() && oldest.epoch() > head.epoch() {
+        match Tipset::load(diff, oldest.parents())? {
+            Some(parent) => oldest = parent,
+            None if oldest.parents() == head.key() => return Ok(()),
+            None => break,
+        }
+    }
+    anyhow::ensure!(
+        oldest.key() == head.key(),
+        "diff snapshot at epochs {}..={} doesn't extend the chain at epoch {}",
+        oldest.epoch(),
+        diff_head.epoch(),
+        head.epoch()
+    );
     Ok(())
 }
 
+/// Name of the file a snapshot is downloaded to, unique to its URL.
+fn download_filename(url: &Url) -> String {
+    use sha2::Digest as _;
+    let digest = sha2::Sha256::digest(url.as_str().as_bytes());
+    format!("download-{}.car", hex::encode(&digest[..8]))
+}
+
 fn move_or_copy_file(from: &Path, to: &Path, consume: bool) -> io::Result<()> {
     if consume && fs::rename(from, to).is_ok() {
         Ok(())
@@ -145,6 +344,10 @@ async fn transcode_into_forest_car(from: &Path, to: &Path) -> anyhow::Result<()>
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::blocks::BlockHeader;
+    use crate::db::MemoryDB;
+    use crate::networks::calibnet;
+    use crate::utils::db::CborStoreExt;
 
     #[tokio::test]
     async fn import_snapshot_from_file_valid() {
@@ -180,9 +383,94 @@ mod test {
     async fn import_snapshot_from_file(file_path: &str) -> anyhow::Result<()> {
         let temp = tempfile::Builder::new().tempdir()?;
         let (path, ts) =
-            import_chain_as_forest_car(Path::new(file_path), temp.path(), false).await?;
+            import_chain_as_forest_car(Path::new(file_path), temp.path(), false, None).await?;
         assert!(path.is_file());
         assert!(ts.epoch() > 0);
         Ok(())
     }
+
+    #[tokio::test]
+    async fn stream_import_snapshot_blocks() {
+        let file = tempfile::Builder::new().tempfile().unwrap();
+        import_car_stream(
+            calibnet::DEFAULT_GENESIS,
+            file.path(),
+            ChunkSize(100),
+            BufferSize(1),
+            None,
+        )
+        .await
+        .unwrap();
+        let car = Arc::new(ForestCar::try_from(file.path()).unwrap());
+        let ts = car.heaviest_tipset().unwrap();
+        assert_eq!(ts.epoch(), 0);
+        ensure_head(&car, &ts, ts.min_ticket_block()).unwrap();
+        // The chain of another network is rejected.
+        ensure_head(&car, &ts, &BlockHeader::default()).unwrap_err();
+
+        // The roots are checked before importing.
+        let file = tempfile::Builder::new().tempfile().unwrap();
+        let other_head = TipsetKeys::from_iter([*ts.parent_state()]);
+        import_car_stream(
+            calibnet::DEFAULT_GENESIS,
+            file.path(),
+            ChunkSize(100),
+            BufferSize(1),
+            Some(&other_head),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(file.as_file().metadata().unwrap().len(), 0);
+
+        // Snapshots without state are rejected.
+        let reader = BufReader::new(
+            tokio::fs::File::open("test-snapshots/chain4.car.zst")
+                .await
+                .unwrap(),
+        );
+        import_car_stream(reader, file.path(), ChunkSize(100), BufferSize(1), None)
+            .await
+            .unwrap();
+        let car = Arc::new(ForestCar::try_from(file.path()).unwrap());
+        let ts = car.heaviest_tipset().unwrap();
+        ensure_head(&car, &ts, &ts.genesis(&car).unwrap()).unwrap_err();
+    }
+
+    /// Returns a chain of single-block tipsets at epochs `0..len`.
+    fn chain(len: i64) -> Vec<Tipset> {
+        let mut chain = vec![Tipset::from(BlockHeader::default())];
+        for epoch in 1..len {
+            let parent = chain.last().unwrap();
+            chain.push(Tipset::from(
+                BlockHeader::builder()
+                    .parents(parent.key().clone())
+                    .epoch(epoch)
+                    .build()
+                    .unwrap(),
+            ));
+        }
+        chain
+    }
+
+    fn persist(tipsets: &[Tipset]) -> MemoryDB {
+        let db = MemoryDB::default();
+        for block in tipsets.iter().flat_map(Tipset::blocks) {
+            db.put_cbor_default(block).unwrap();
+        }
+        db
+    }
+
+    #[test]
+    fn diff_extends_chain() {
+        let chain = chain(10);
+        // Diff of epochs 5..=9 over a base ending at epoch 4
+        let diff = persist(&chain[5..]);
+        ensure_diff_extends(&chain[4], &diff, &chain[9]).unwrap();
+        // Overlapping diffs are fine too
+        ensure_diff_extends(&chain[6], &diff, &chain[9]).unwrap();
+        // Gap between the base and the diff
+        ensure_diff_extends(&chain[3], &diff, &chain[9]).unwrap_err();
+        // Diff behind the base
+        ensure_diff_extends(&chain[9], &persist(&chain[2..5]), &chain[4]).unwrap_err();
+    }
 }
@@ -2,23 +2,30 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 pub mod bundle;
-mod db_util;
+pub(crate) mod db_util;
 pub mod main;
+pub mod snapshot_export;
 
 use crate::auth::{create_token, generate_priv_key, ADMIN, JWT_IDENTIFIER};
 use crate::blocks::Tipset;
 use crate::chain::ChainStore;
 use crate::chain_sync::ChainMuxer;
-use crate::cli_shared::snapshot;
+use crate::cli_shared::snapshot::{
+    self,
+    manifest::{self, SignedSnapshotManifest, SnapshotManifest},
+};
 use crate::cli_shared::{
     chain_path,
     cli::{CliOpts, Config},
 };
 
-use crate::daemon::db_util::{import_chain_as_forest_car, load_all_forest_cars};
-use crate::db::car::ManyCar;
+use crate::daemon::db_util::{
+    ensure_diff_extends, import_chain_as_forest_car, load_all_forest_cars, stream_import_snapshot,
+};
+use crate::daemon::snapshot_export::{SnapshotExportStatus, SnapshotExporter};
+use crate::db::car::{ForestCar, ManyCar};
 use crate::db::db_engine::{db_root, open_db};
-use crate::db::MarkAndSweep;
+use crate::db::{BlockCache, ColdStorage, GcControl, MarkAndSweep};
 use crate::genesis::{get_network_name_from_genesis, read_genesis_header};
 use crate::key_management::{
     KeyStore, KeyStoreConfig, ENCRYPTED_KEYSTORE_NAME, FOREST_KEYSTORE_PHRASE_ENV,
@@ -26,9 +33,13 @@ use crate::key_management::{
 use crate::libp2p::{Libp2pConfig, Libp2pService, PeerManager};
 use crate::message_pool::{MessagePool, MpoolConfig, MpoolRpcProvider};
 use crate::networks::ChainConfig;
-use crate::rpc::start_rpc;
+use crate::rpc::{
+    audit::RpcAudit,
+    rpc_listener::{bind_unix_socket, load_tls_config},
+    start_rpc, RpcListeners,
+};
 use crate::rpc_api::data_types::RPCState;
-use crate::shim::address::{CurrentNetwork, Network};
+use crate::shim::address::{Address, CurrentNetwork, Network};
 use crate::shim::clock::ChainEpoch;
 use crate::shim::version::NetworkVersion;
 use crate::state_manager::StateManager;
@@ -57,6 +68,7 @@ use tokio::{
     task::JoinSet,
 };
 use tracing::{debug, info, warn};
+use url::Url;
 
 static IPC_PATH: Lazy<TempPath> = Lazy::new(|| {
     Builder::new()
@@ -132,9 +144,6 @@ pub async fn start_interruptable(opts: CliOpts, config: Config) -> anyhow::Resul
     result
 }
 
-// Garbage collection interval, currently set at 10 hours.
-const GC_INTERVAL: Duration = Duration::from_secs(60 * 60 * 10);
-
 /// Starts daemon process
 pub(super) async fn start(
     opts: CliOpts,
@@ -172,14 +181,31 @@ pub(super) async fn start(
     // to avoid breaking the node.
     let db_migration = crate::db::migration::DbMigration::new(chain_data_path.clone());
     if let Err(e) = db_migration.migrate() {
-        warn!("Failed to migrate database: {e}");
+        warn!("Failed to migrate database: {e}. The previous database is kept, resume the migration with `forest-tool db migrate` or abandon it with `forest-tool db migrate --rollback`");
     }
 
     let db_root_dir = db_root(&chain_data_path)?;
-    let db_writer = Arc::new(open_db(db_root_dir.clone(), config.db_config().clone())?);
-    let db = Arc::new(ManyCar::new(db_writer.clone()));
+    let db_writer = Arc::new(open_db(db_root_dir.clone(), config.db_config())?);
+    let db = Arc::new(
+        ManyCar::new(BlockCache::new(
+            db_writer.clone(),
+            config.db.block_cache_size as usize,
+        ))
+        .with_bloom_filters(config.client.car_bloom_filters),
+    );
     let forest_car_db_dir = db_root_dir.join("car_db");
     load_all_forest_cars(&db, &forest_car_db_dir)?;
+    if config.sync.archival {
+        // Historical state is served straight from the archive snapshots
+        // rather than being imported into the database.
+        for path in &config.client.archive_snapshot_paths {
+            db.read_only_files(std::iter::once(path.clone()))
+                .with_context(|| format!("couldn't load archive snapshot {}", path.display()))?;
+            info!("Loaded archive snapshot {}", path.display());
+        }
+    } else if !config.client.archive_snapshot_paths.is_empty() {
+        warn!("Archive snapshots are only used in archival mode, ignoring them");
+    }
 
     if config.client.load_actors {
         load_actor_bundles(&db, &config.chain).await?;
@@ -205,7 +231,7 @@ pub(super) async fn start(
             config.client.metrics_address
         );
         let db_directory = crate::db::db_engine::db_root(&chain_path(&config))?;
-        let db = db.writer().clone();
+        let db = db_writer.clone();
         services.spawn(async {
             crate::metrics::init_prometheus(prometheus_listener, db_directory, db)
                 .await
@@ -226,11 +252,12 @@ pub(super) async fn start(
     // Initialize ChainStore
     let chain_store = Arc::new(ChainStore::new(
         Arc::clone(&db),
-        db.writer().clone(),
+        db_writer.clone(),
         chain_config.clone(),
         genesis_header.clone(),
     )?);
 
+    let gc_control = Arc::new(GcControl::default());
     if !opts.no_gc {
         let mut db_garbage_collector = {
             let chain_store = chain_store.clone();
@@ -240,15 +267,50 @@ pub(super) async fn start(
             );
 
             let get_heaviest_tipset = Box::new(move || chain_store.heaviest_tipset());
+            let cold_storage = config.client.gc_cold_storage.then(|| {
+                let db = db.clone();
+                ColdStorage::new(
+                    forest_car_db_dir.clone(),
+                    Box::new(move |path| Ok(db.read_only_files(std::iter::once(path.to_owned()))?)),
+                )
+            });
 
+            // Removals go through the block cache, so that it doesn't serve collected blocks.
             MarkAndSweep::new(
-                db_writer,
+                db.clone(),
                 get_heaviest_tipset,
                 depth,
                 Duration::from_secs(chain_config.block_delay_secs as u64),
             )
+            .with_archival(config.sync.archival)
+            .with_cold_storage(cold_storage)
+            .with_config(config.gc.clone())
+            .with_control(gc_control.clone())
         };
-        services.spawn(async move { db_garbage_collector.gc_loop(GC_INTERVAL).await });
+        services.spawn(async move { db_garbage_collector.gc_loop().await });
+    }
+
+    let snapshot_export_status =
+        Arc::new(parking_lot::RwLock::new(SnapshotExportStatus::default()));
+    if config.snapshot_export.enabled {
+        anyhow::ensure!(
+            config.snapshot_export.depth >= chain_config.policy.chain_finality,
+            "snapshot_export.depth must be at least {}",
+            chain_config.policy.chain_finality
+        );
+        let output_dir = config
+            .snapshot_export
+            .output_dir
+            .clone()
+            .unwrap_or_else(|| config.client.data_dir.join("snapshots"));
+        let exporter = SnapshotExporter::new(
+            chain_store.clone(),
+            config.chain.clone(),
+            config.snapshot_export.clone(),
+            output_dir,
+            snapshot_export_status.clone(),
+        );
+        services.spawn(exporter.export_loop());
     }
 
     let publisher = chain_store.publisher();
@@ -312,7 +374,7 @@ pub(super) async fn start(
         provider,
         network_name.clone(),
         network_send.clone(),
-        MpoolConfig::load_config(db.writer().as_ref())?,
+        MpoolConfig::load_config(db_writer.as_ref())?,
         state_manager.chain_config().clone(),
         &mut services,
     )?;
@@ -343,6 +405,28 @@ pub(super) async fn start(
                 config.client.rpc_address
             ))?;
 
+        let tls_config = match (
+            &config.client.rpc_tls_cert_path,
+            &config.client.rpc_tls_key_path,
+        ) {
+            (Some(cert_path), Some(key_path)) => Some(load_tls_config(cert_path, key_path)?),
+            (None, None) => None,
+            _ => bail!("both rpc_tls_cert_path and rpc_tls_key_path must be set to enable TLS"),
+        };
+        let rpc_socket = match &config.client.rpc_socket_path {
+            Some(path) => {
+                let socket = bind_unix_socket(path, config.client.rpc_socket_mode)?;
+                info!("JSON-RPC endpoint started at {}", path.display());
+                Some(socket)
+            }
+            None => None,
+        };
+
+        let rpc_audit = RpcAudit::new(
+            config.client.rpc_audit_log.as_deref(),
+            config.client.rpc_capture_file.as_deref(),
+        )?;
+
         let rpc_state_manager = Arc::clone(&state_manager);
         let rpc_chain_store = Arc::clone(&chain_store);
 
@@ -365,8 +449,16 @@ pub(super) async fn start(
                     start_time,
                     beacon,
                     chain_store: rpc_chain_store,
+                    gc: gc_control,
+                    snapshot_export: snapshot_export_status,
                 }),
-                rpc_listen,
+                RpcListeners {
+                    tcp: rpc_listen,
+                    tls_config,
+                    unix_socket: rpc_socket,
+                },
+                config.client.rpc_slow_call_threshold,
+                rpc_audit,
                 FOREST_VERSION_STRING.as_str(),
                 shutdown_send,
             )
@@ -402,19 +494,79 @@ pub(super) async fn start(
 
     // Import chain if needed
     if !opts.skip_load.unwrap_or_default() {
+        let trusted_signers = manifest::parse_signers(&config.client.snapshot_trusted_signers)?;
+        let checkpoints = manifest::parse_checkpoints(&config.client.snapshot_checkpoints)?;
+        let stream_url = match &config.client.snapshot_path {
+            Some(path) if config.client.stream_snapshot => {
+                anyhow::ensure!(
+                    !config.client.consume_snapshot,
+                    "streamed snapshots can't be consumed"
+                );
+                Some(Url::parse(&path.display().to_string()).with_context(|| {
+                    format!(
+                        "only snapshots given as a URL can be streamed, not {}",
+                        path.display()
+                    )
+                })?)
+            }
+            _ => None,
+        };
         if let Some(path) = &config.client.snapshot_path {
-            let (car_db_path, ts) = import_chain_as_forest_car(
-                path,
-                &forest_car_db_dir,
-                config.client.consume_snapshot,
-            )
-            .await?;
+            let manifest = trusted_manifest(&config, path, &trusted_signers).await?;
+            let (car_db_path, ts) = if let Some(url) = stream_url {
+                stream_import_snapshot(
+                    &url,
+                    &forest_car_db_dir,
+                    config.client.chunk_size,
+                    config.client.buffer_size,
+                    manifest.as_ref(),
+                    state_manager.chain_store().genesis(),
+                    &checkpoints,
+                )
+                .await
+                .with_context(|| format!("couldn't import snapshot {}", path.display()))?
+            } else {
+                let (car_db_path, ts) = import_chain_as_forest_car(
+                    path,
+                    &forest_car_db_dir,
+                    config.client.consume_snapshot,
+                    manifest.as_ref(),
+                )
+                .await?;
+                if let Err(e) = manifest::ensure_checkpoints(
+                    ForestCar::try_from(car_db_path.as_path())?,
+                    &ts,
+                    &checkpoints,
+                ) {
+                    std::fs::remove_file(&car_db_path)?;
+                    return Err(e.context(format!("untrusted snapshot {}", path.display())));
+                }
+                (car_db_path, ts)
+            };
             db.read_only_files(std::iter::once(car_db_path.clone()))?;
             debug!("Loaded car DB at {}", car_db_path.display());
             state_manager
                 .chain_store()
                 .set_heaviest_tipset(Arc::new(ts))?;
         }
+        for path in &config.client.snapshot_diff_paths {
+            let head = state_manager.chain_store().heaviest_tipset();
+            let manifest = trusted_manifest(&config, path, &trusted_signers).await?;
+            let (car_db_path, ts) =
+                import_chain_as_forest_car(path, &forest_car_db_dir, false, manifest.as_ref())
+                    .await?;
+            let diff = ForestCar::try_from(car_db_path.as_path())?;
+            if let Err(e) = ensure_diff_extends(&head, &diff, &ts) {
+                drop(diff);
+                std::fs::remove_file(&car_db_path)?;
+                return Err(e.context(format!("invalid diff snapshot {}", path.display())));
+            }
+            db.read_only(diff.into());
+            debug!("Loaded diff car DB at {}", car_db_path.display());
+            state_manager
+                .chain_store()
+                .set_heaviest_tipset(Arc::new(ts))?;
+        }
     }
 
     if let (true, Some(validate_from)) = (config.client.snapshot, config.client.snapshot_height) {
@@ -452,6 +604,36 @@ pub(super) async fn start(
         .map(|_| {})
 }
 
+/// Loads and verifies the manifest of the snapshot at `path` (local path or
+/// URL) if snapshots have to be signed by one of `trusted_signers`.
+async fn trusted_manifest(
+    config: &Config,
+    path: &Path,
+    trusted_signers: &[Address],
+) -> anyhow::Result<Option<SnapshotManifest>> {
+    if trusted_signers.is_empty() {
+        return Ok(None);
+    }
+    let manifest_path = manifest::manifest_path(path);
+    let signed = SignedSnapshotManifest::load(&manifest_path)
+        .await
+        .with_context(|| {
+            format!(
+                "snapshots must be signed by a trusted signer, but the manifest {} couldn't be loaded",
+                manifest_path.display()
+            )
+        })?;
+    let manifest = signed.verify(trusted_signers)?.clone();
+    anyhow::ensure!(
+        manifest.network == config.chain.to_string(),
+        "snapshot manifest is for {}, not {}",
+        manifest.network,
+        config.chain
+    );
+    info!("Snapshot manifest signed by {}", signed.signer);
+    Ok(Some(manifest))
+}
+
 /// If our current chain is below a supported height, we need a snapshot to bring it up
 /// to a supported height. If we've not been given a snapshot by the user, get one.
 ///
@@ -0,0 +1,81 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use once_cell::sync::Lazy;
+use prometheus::core::{AtomicI64, AtomicU64, GenericCounter, GenericGauge};
+
+pub static SNAPSHOT_EXPORT_LAST_SUCCESS: Lazy<Box<GenericGauge<AtomicI64>>> = Lazy::new(|| {
+    let snapshot_export_last_success = Box::new(
+        GenericGauge::<AtomicI64>::new(
+            "snapshot_export_last_success",
+            "Unix timestamp of the last successful scheduled snapshot export",
+        )
+        .expect("Defining the snapshot_export_last_success metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(snapshot_export_last_success.clone())
+        .expect(
+            "Registering the snapshot_export_last_success metric with the metrics registry must succeed",
+        );
+    snapshot_export_last_success
+});
+pub static SNAPSHOT_EXPORT_LAST_EPOCH: Lazy<Box<GenericGauge<AtomicI64>>> = Lazy::new(|| {
+    let snapshot_export_last_epoch = Box::new(
+        GenericGauge::<AtomicI64>::new(
+            "snapshot_export_last_epoch",
+            "Epoch of the last scheduled snapshot export",
+        )
+        .expect("Defining the snapshot_export_last_epoch metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(snapshot_export_last_epoch.clone())
+        .expect(
+            "Registering the snapshot_export_last_epoch metric with the metrics registry must succeed",
+        );
+    snapshot_export_last_epoch
+});
+pub static SNAPSHOT_EXPORT_LAST_SIZE: Lazy<Box<GenericGauge<AtomicI64>>> = Lazy::new(|| {
+    let snapshot_export_last_size = Box::new(
+        GenericGauge::<AtomicI64>::new(
+            "snapshot_export_last_size_bytes",
+            "Size of the last scheduled snapshot export",
+        )
+        .expect("Defining the snapshot_export_last_size_bytes metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(snapshot_export_last_size.clone())
+        .expect(
+            "Registering the snapshot_export_last_size_bytes metric with the metrics registry must succeed",
+        );
+    snapshot_export_last_size
+});
+pub static SNAPSHOT_EXPORT_LAST_DURATION: Lazy<Box<GenericGauge<AtomicI64>>> = Lazy::new(|| {
+    let snapshot_export_last_duration = Box::new(
+        GenericGauge::<AtomicI64>::new(
+            "snapshot_export_last_duration_seconds",
+            "Duration of the last scheduled snapshot export",
+        )
+        .expect("Defining the snapshot_export_last_duration_seconds metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(snapshot_export_last_duration.clone())
+        .expect(
+            "Registering the snapshot_export_last_duration_seconds metric with the metrics registry must succeed",
+        );
+    snapshot_export_last_duration
+});
+pub static SNAPSHOT_EXPORT_FAILURES: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
+    let snapshot_export_failures = Box::new(
+        GenericCounter::<AtomicU64>::new(
+            "snapshot_export_failures",
+            "Total failed scheduled snapshot exports",
+        )
+        .expect("Defining the snapshot_export_failures metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(snapshot_export_failures.clone())
+        .expect(
+            "Registering the snapshot_export_failures metric with the metrics registry must succeed",
+        );
+    snapshot_export_failures
+});
@@ -0,0 +1,401 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Scheduled snapshot exports, configured in the `[snapshot_export]` section.
+//!
+//! Every `interval`, the heaviest tipset is exported to `output_dir` as a
+//! `.forest.car.zst` snapshot along with its `.sha256sum` checksum file.
+//!
+//! With `diff` enabled, an export only holds the data that isn't reachable from
+//! the previous export, like `forest-tool archive export --diff`, and can be
+//! imported on top of it with `forest --import-diff`. A full snapshot followed
+//! by its diffs forms a group of at most `retention` exports, after which a new
+//! full snapshot is written.
+//!
+//! The exports are recorded in an `exports.json` journal in the output
+//! directory. Once there are more than `retention` of them, the oldest groups
+//! are removed, so that the remaining diffs can always be applied.
+
+mod metrics;
+
+use crate::blocks::Tipset;
+use crate::chain::index::ResolveNullTipset;
+use crate::chain::{ChainEpochDelta, ChainStore};
+use crate::cid_collections::CidHashSet;
+use crate::cli_shared::snapshot::{self, TrustedVendor};
+use crate::ipld::unordered_stream_graph;
+use crate::networks::NetworkChain;
+use crate::shim::clock::ChainEpoch;
+use anyhow::Context as _;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use futures::TryStreamExt as _;
+use fvm_ipld_blockstore::Blockstore;
+use hex::ToHex as _;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DurationSeconds};
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+const EXPORT_JOURNAL: &str = "exports.json";
+
+/// Scheduling of the snapshot exports.
+#[serde_as]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct SnapshotExportConfig {
+    pub enabled: bool,
+    /// Time to wait between two exports.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    #[cfg_attr(test, arbitrary(gen(
+        |g| Duration::from_secs(u32::arbitrary(g) as u64)
+    )))]
+    pub interval: Duration,
+    /// How many state-roots to include, at least the chain finality.
+    pub depth: ChainEpochDelta,
+    /// Directory to write the snapshots to, `snapshots` in the data directory by default.
+    pub output_dir: Option<PathBuf>,
+    /// Number of exports to keep. `0` keeps them all.
+    pub retention: u32,
+    /// Export the difference with the previous export rather than full snapshots.
+    pub diff: bool,
+}
+
+impl Default for SnapshotExportConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval: Duration::from_secs(60 * 60 * 24),
+            depth: 2000,
+            output_dir: None,
+            retention: 7,
+            diff: false,
+        }
+    }
+}
+
+/// A completed export.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ExportRecord {
+    /// File name in the output directory.
+    pub file: String,
+    pub epoch: ChainEpoch,
+    /// Epoch of the previous export for diff snapshots.
+    pub diff_base: Option<ChainEpoch>,
+    pub size: u64,
+    /// Hex-encoded SHA-256 checksum of the file.
+    pub checksum: String,
+    pub completed: DateTime<Utc>,
+}
+
+/// State of the snapshot export scheduler.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct SnapshotExportStatus {
+    pub enabled: bool,
+    pub output_dir: Option<PathBuf>,
+    /// Whether an export is in progress.
+    pub running: bool,
+    pub last_export: Option<ExportRecord>,
+    /// Error of the last attempt, if it failed.
+    pub last_error: Option<String>,
+    pub next_export: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ExportJournal {
+    exports: Vec<ExportRecord>,
+}
+
+impl ExportJournal {
+    fn load(output_dir: &Path) -> anyhow::Result<Self> {
+        match std::fs::read(output_dir.join(EXPORT_JOURNAL)) {
+            Ok(bytes) => Ok(serde_json::from_slice(&bytes)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, output_dir: &Path) -> anyhow::Result<()> {
+        // Write then rename, so that a crash never leaves a truncated journal behind.
+        let tmp = output_dir.join(format!("{EXPORT_JOURNAL}.tmp"));
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(tmp, output_dir.join(EXPORT_JOURNAL))?;
+        Ok(())
+    }
+
+    /// Epoch of the export the next one can be a diff of, or `None` if the
+    /// next one has to be a full snapshot to keep groups within `retention`.
+    fn diff_base(&self, retention: u32) -> Option<ChainEpoch> {
+        let last = self.exports.last()?;
+        let group = 1 + self
+            .exports
+            .iter()
+            .rev()
+            .take_while(|export| export.diff_base.is_some())
+            .count();
+        (retention == 0 || group < retention as usize).then_some(last.epoch)
+    }
+
+    /// Drops the oldest groups of exports while at least `retention` remain,
+    /// and returns them.
+    fn rotate(&mut self, retention: u32) -> Vec<ExportRecord> {
+        let mut removed = vec![];
+        if retention == 0 {
+            return removed;
+        }
+        loop {
+            let group = 1 + self
+                .exports
+                .iter()
+                .skip(1)
+                .take_while(|export| export.diff_base.is_some())
+                .count();
+            if self.exports.len() < group + retention as usize {
+                return removed;
+            }
+            removed.extend(self.exports.drain(..group));
+        }
+    }
+}
+
+pub struct SnapshotExporter<DB> {
+    chain_store: Arc<ChainStore<DB>>,
+    chain: NetworkChain,
+    config: SnapshotExportConfig,
+    output_dir: PathBuf,
+    status: Arc<RwLock<SnapshotExportStatus>>,
+}
+
+impl<DB> SnapshotExporter<DB>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    pub fn new(
+        chain_store: Arc<ChainStore<DB>>,
+        chain: NetworkChain,
+        config: SnapshotExportConfig,
+        output_dir: PathBuf,
+        status: Arc<RwLock<SnapshotExportStatus>>,
+    ) -> Self {
+        Self {
+            chain_store,
+            chain,
+            config,
+            output_dir,
+            status,
+        }
+    }
+
+    pub async fn export_loop(self) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.output_dir)?;
+        let last_export = ExportJournal::load(&self.output_dir)?.exports.pop();
+        // Carry on with the schedule of the previous runs of the node.
+        let mut last_attempt = last_export
+            .as_ref()
+            .map(|export| export.completed)
+            .unwrap_or_else(Utc::now);
+        {
+            let mut status = self.status.write();
+            status.enabled = true;
+            status.output_dir = Some(self.output_dir.clone());
+            status.last_export = last_export;
+        }
+
+        loop {
+            let next_export = last_attempt + chrono::Duration::from_std(self.config.interval)?;
+            self.status.write().next_export = Some(next_export);
+            tokio::time::sleep((next_export - Utc::now()).to_std().unwrap_or_default()).await;
+
+            last_attempt = Utc::now();
+            self.status.write().running = true;
+            let stopwatch = Instant::now();
+            let result = self.export().await;
+            let mut status = self.status.write();
+            status.running = false;
+            match result {
+                Ok(export) => {
+                    info!(
+                        "Exported snapshot at epoch {} to {} in {}s",
+                        export.epoch,
+                        export.file,
+                        stopwatch.elapsed().as_secs()
+                    );
+                    metrics::SNAPSHOT_EXPORT_LAST_SUCCESS.set(export.completed.timestamp());
+                    metrics::SNAPSHOT_EXPORT_LAST_EPOCH.set(export.epoch);
+                    metrics::SNAPSHOT_EXPORT_LAST_SIZE.set(export.size as i64);
+                    metrics::SNAPSHOT_EXPORT_LAST_DURATION
+                        .set(stopwatch.elapsed().as_secs() as i64);
+                    status.last_export = Some(export);
+                    status.last_error = None;
+                }
+                Err(e) => {
+                    warn!("Snapshot export failed: {e:#}");
+                    metrics::SNAPSHOT_EXPORT_FAILURES.inc();
+                    status.last_error = Some(format!("{e:#}"));
+                }
+            }
+        }
+    }
+
+    async fn export(&self) -> anyhow::Result<ExportRecord> {
+        let mut journal = ExportJournal::load(&self.output_dir)?;
+        let head = self.chain_store.heaviest_tipset();
+        if let Some(last) = journal.exports.last() {
+            anyhow::ensure!(
+                head.epoch() > last.epoch,
+                "the chain head hasn't progressed since the export at epoch {}",
+                last.epoch
+            );
+        }
+
+        let (diff_base, seen) = match journal
+            .diff_base(self.config.retention)
+            .filter(|_| self.config.diff)
+        {
+            Some(epoch) => match self.reachable_from(epoch, &head).await {
+                Ok(seen) => (Some(epoch), seen),
+                Err(e) => {
+                    warn!("Couldn't diff with the export at epoch {epoch}, exporting a full snapshot: {e:#}");
+                    (None, CidHashSet::default())
+                }
+            },
+            None => (None, CidHashSet::default()),
+        };
+
+        let file = self.file_name(&head, diff_base);
+        let path = self.output_dir.join(&file);
+        let temp_path = tempfile::NamedTempFile::new_in(&self.output_dir)?.into_temp_path();
+        let writer = tokio::fs::File::create(&temp_path).await?;
+        let checksum = crate::chain::export::<Sha256>(
+            Arc::clone(&self.chain_store.db),
+            &head,
+            self.config.depth,
+            writer,
+            seen,
+            false,
+        )
+        .await?
+        .context("missing snapshot checksum")?
+        .encode_hex::<String>();
+        temp_path.persist(&path)?;
+        snapshot::save_checksum(&path, checksum.clone()).await?;
+
+        let export = ExportRecord {
+            file,
+            epoch: head.epoch(),
+            diff_base,
+            size: std::fs::metadata(&path)?.len(),
+            checksum,
+            completed: Utc::now(),
+        };
+        journal.exports.push(export.clone());
+        let removed = journal.rotate(self.config.retention);
+        journal.save(&self.output_dir)?;
+        for old in removed {
+            let path = self.output_dir.join(&old.file);
+            for path in [path.with_extension("sha256sum"), path] {
+                if let Err(e) = std::fs::remove_file(&path) {
+                    warn!("Couldn't remove old snapshot file {}: {e}", path.display());
+                }
+            }
+            info!("Removed old snapshot {}", old.file);
+        }
+        Ok(export)
+    }
+
+    /// Collects the data included in a previous export at `epoch`.
+    async fn reachable_from(
+        &self,
+        epoch: ChainEpoch,
+        head: &Arc<Tipset>,
+    ) -> anyhow::Result<CidHashSet> {
+        let base = self.chain_store.chain_index.tipset_by_height(
+            epoch,
+            head.clone(),
+            ResolveNullTipset::TakeOlder,
+        )?;
+        let db = Arc::clone(&self.chain_store.db);
+        let mut stream = unordered_stream_graph(
+            db.clone(),
+            Tipset::clone(&base).chain(db),
+            base.epoch() - self.config.depth,
+        );
+        while stream.try_next().await?.is_some() {}
+        Ok(stream.into_seen())
+    }
+
+    fn file_name(&self, head: &Tipset, diff_base: Option<ChainEpoch>) -> String {
+        let date = NaiveDateTime::from_timestamp_opt(head.min_ticket_block().timestamp() as i64, 0)
+            .unwrap_or_default()
+            .date();
+        match diff_base {
+            None => {
+                snapshot::filename(TrustedVendor::Forest, &self.chain, date, head.epoch(), true)
+            }
+            Some(base) => format!(
+                "forest_diff_{}_{}_height_{base}-{}.forest.car.zst",
+                self.chain,
+                date.format("%Y-%m-%d"),
+                head.epoch()
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal(diff_bases: &[Option<ChainEpoch>]) -> ExportJournal {
+        ExportJournal {
+            exports: diff_bases
+                .iter()
+                .enumerate()
+                .map(|(i, &diff_base)| ExportRecord {
+                    file: format!("{i}.forest.car.zst"),
+                    epoch: i as ChainEpoch,
+                    diff_base,
+                    size: 0,
+                    checksum: String::new(),
+                    completed: Utc::now(),
+                })
+                .collect(),
+        }
+    }
+
+    fn epochs(exports: &[ExportRecord]) -> Vec<ChainEpoch> {
+        exports.iter().map(|export| export.epoch).collect()
+    }
+
+    #[test]
+    fn diff_base() {
+        assert_eq!(journal(&[]).diff_base(3), None);
+        assert_eq!(journal(&[None]).diff_base(3), Some(0));
+        assert_eq!(journal(&[None, Some(0)]).diff_base(3), Some(1));
+        // The group is full
+        assert_eq!(journal(&[None, Some(0), Some(1)]).diff_base(3), None);
+        assert_eq!(journal(&[None, Some(0), Some(1)]).diff_base(0), Some(2));
+        assert_eq!(journal(&[None]).diff_base(1), None);
+    }
+
+    #[test]
+    fn rotate() {
+        let mut full = journal(&[None, None, None, None]);
+        assert_eq!(epochs(&full.rotate(2)), [0, 1]);
+        assert_eq!(epochs(&full.exports), [2, 3]);
+        assert!(full.rotate(0).is_empty());
+
+        // Diffs are only removed along with their base
+        let mut diffs = journal(&[None, Some(0), Some(1), None, Some(3)]);
+        assert!(diffs.rotate(3).is_empty());
+        diffs.exports.push(journal(&[None]).exports.remove(0));
+        assert_eq!(epochs(&diffs.rotate(3)), [0, 1, 2]);
+        assert_eq!(diffs.exports.len(), 3);
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Point-in-time backups of a node database. A backup is a directory holding a
+//! `.forest.car.zst` export of the reachable chain and a JSON dump of the
+//! settings store, so that the node resumes from the same head, with the same
+//! message pool configuration, once restored.
+
+use crate::blocks::TipsetKeys;
+use crate::db::{setting_keys::HEAD_KEY, SettingsStore};
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Name of the chain export inside a backup directory.
+pub const BACKUP_CHAIN_FILE: &str = "chain.forest.car.zst";
+/// Name of the settings dump inside a backup directory.
+pub const BACKUP_SETTINGS_FILE: &str = "settings.json";
+
+/// Contents of the settings store. Values are opaque bytes, stored hex-encoded.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SettingsDump(#[serde(with = "hex_values")] pub BTreeMap<String, Vec<u8>>);
+
+impl SettingsDump {
+    /// Reads every key of `store`. The head is overridden with `head`, the
+    /// tipset the chain was exported from, which keeps the two consistent
+    /// even if the node moved on in the meantime.
+    pub fn read(store: &(impl SettingsStore + ?Sized), head: &TipsetKeys) -> anyhow::Result<Self> {
+        let mut settings = BTreeMap::new();
+        for key in store.setting_keys()? {
+            if let Some(value) = store.read_bin(&key)? {
+                settings.insert(key, value);
+            }
+        }
+        settings.insert(HEAD_KEY.to_owned(), serde_json::to_vec(head)?);
+        Ok(Self(settings))
+    }
+
+    pub fn write_to(&self, store: &(impl SettingsStore + ?Sized)) -> anyhow::Result<()> {
+        for (key, value) in &self.0 {
+            store.write_bin(key, value)?;
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("couldn't write {}", path.display()))
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let bytes =
+            std::fs::read(path).with_context(|| format!("couldn't read {}", path.display()))?;
+        Ok(serde_json::from_slice(&bytes)?)
+    }
+
+    /// The head recorded in the backup.
+    pub fn head(&self) -> anyhow::Result<TipsetKeys> {
+        let head = self.0.get(HEAD_KEY).context("backup has no head")?;
+        Ok(serde_json::from_slice(head)?)
+    }
+}
+
+mod hex_values {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    pub fn serialize<S: Serializer>(
+        map: &BTreeMap<String, Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        map.iter()
+            .map(|(key, value)| (key, hex::encode(value)))
+            .collect::<BTreeMap<_, _>>()
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<BTreeMap<String, Vec<u8>>, D::Error> {
+        BTreeMap::<String, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, value)| Ok((key, hex::decode(value).map_err(serde::de::Error::custom)?)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::{setting_keys::MPOOL_CONFIG_KEY, MemoryDB, SettingsStoreExt as _};
+
+    #[test]
+    fn settings_round_trip() {
+        let source = MemoryDB::default();
+        source.write_obj(HEAD_KEY, &TipsetKeys::default()).unwrap();
+        source.write_bin(MPOOL_CONFIG_KEY, b"{\"x\":1}").unwrap();
+
+        let head = TipsetKeys::from_iter([cid::Cid::default()]);
+        let dump = SettingsDump::read(&source, &head).unwrap();
+        assert_eq!(dump.head().unwrap(), head);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(BACKUP_SETTINGS_FILE);
+        dump.save(&path).unwrap();
+        let loaded = SettingsDump::load(&path).unwrap();
+        assert_eq!(loaded, dump);
+
+        let target = MemoryDB::default();
+        loaded.write_to(&target).unwrap();
+        assert_eq!(
+            target.read_bin(MPOOL_CONFIG_KEY).unwrap().unwrap(),
+            b"{\"x\":1}"
+        );
+        assert_eq!(target.require_obj::<TipsetKeys>(HEAD_KEY).unwrap(), head);
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! A size-bounded LRU cache of blocks in front of any [`Blockstore`]. Blocks are cached both when
+//! they are read and when they are written, as freshly computed state is typically read back soon
+//! after.
+//!
+//! Blocks removed through [`GarbageCollectable`] are evicted from the cache. The garbage collector
+//! therefore has to remove them through the cache rather than through the wrapped store.
+
+use crate::db::{truncated_hash, DBStatistics, GarbageCollectable, RemovedRecords, SettingsStore};
+use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
+use crate::metrics;
+use ahash::HashSet;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use lru::LruCache;
+use parking_lot::Mutex;
+
+pub struct BlockCache<DB> {
+    inner: DB,
+    cache: Mutex<SizedLru>,
+}
+
+// Tracks the total size of the cached blocks, evicting the least recently used ones above
+// `max_size`. The generation is bumped on every removal, so that blocks read before a removal
+// aren't cached after it.
+struct SizedLru {
+    max_size: usize,
+    current_size: usize,
+    generation: u64,
+    lru: LruCache<Cid, Vec<u8>>,
+}
+
+impl SizedLru {
+    fn put(&mut self, cid: Cid, block: Vec<u8>) {
+        if block.len() > self.max_size {
+            return;
+        }
+        self.current_size += block.len();
+        if let Some(prev) = self.lru.put(cid, block) {
+            self.current_size -= prev.len();
+        }
+        while self.current_size > self.max_size {
+            match self.lru.pop_lru() {
+                Some((_, evicted)) => self.current_size -= evicted.len(),
+                None => break,
+            }
+        }
+    }
+
+    fn evict(&mut self, keys: &HashSet<u32>) {
+        let evicted = self
+            .lru
+            .iter()
+            .map(|(cid, _)| *cid)
+            .filter(|cid| keys.contains(&truncated_hash(cid.hash())))
+            .collect::<Vec<_>>();
+        for cid in evicted {
+            if let Some(block) = self.lru.pop(&cid) {
+                self.current_size -= block.len();
+            }
+        }
+    }
+}
+
+impl<DB> BlockCache<DB> {
+    /// Default maximum size of the cached blocks, in bytes (256 MiB).
+    pub const DEFAULT_SIZE: usize = 256 * 1024 * 1024;
+
+    /// Wraps `inner`, caching at most `max_size` bytes of blocks.
+    pub fn new(inner: DB, max_size: usize) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(SizedLru {
+                max_size,
+                current_size: 0,
+                generation: 0,
+                lru: LruCache::unbounded(),
+            }),
+        }
+    }
+}
+
+impl<DB: Blockstore> Blockstore for BlockCache<DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        let mut cache = self.cache.lock();
+        if cache.max_size == 0 {
+            drop(cache);
+            return self.inner.get(k);
+        }
+        if let Some(block) = cache.lru.get(k) {
+            metrics::LRU_CACHE_HIT
+                .with_label_values(&[metrics::values::BLOCK])
+                .inc();
+            return Ok(Some(block.clone()));
+        }
+        let generation = cache.generation;
+        drop(cache);
+        metrics::LRU_CACHE_MISS
+            .with_label_values(&[metrics::values::BLOCK])
+            .inc();
+        let block = self.inner.get(k)?;
+        if let Some(block) = &block {
+            let mut cache = self.cache.lock();
+            if cache.generation == generation {
+                cache.put(*k, block.clone());
+            }
+        }
+        Ok(block)
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        if self.cache.lock().lru.contains(k) {
+            return Ok(true);
+        }
+        self.inner.has(k)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.inner.put_keyed(k, block)?;
+        self.cache.lock().put(*k, block.to_vec());
+        Ok(())
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> anyhow::Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        let blocks = blocks.into_iter().collect::<Vec<_>>();
+        self.inner
+            .put_many_keyed(blocks.iter().map(|(k, block)| (*k, block.as_ref())))?;
+        let mut cache = self.cache.lock();
+        for (k, block) in blocks {
+            cache.put(k, block.as_ref().to_vec());
+        }
+        Ok(())
+    }
+}
+
+impl<DB: GarbageCollectable> GarbageCollectable for BlockCache<DB> {
+    fn get_keys(&self) -> anyhow::Result<HashSet<u32>> {
+        self.inner.get_keys()
+    }
+
+    fn remove_keys_with(
+        &self,
+        keys: HashSet<u32>,
+        on_removed: &mut dyn FnMut(u64),
+    ) -> anyhow::Result<RemovedRecords> {
+        let removed = self.inner.remove_keys_with(keys.clone(), on_removed)?;
+        let mut cache = self.cache.lock();
+        cache.generation += 1;
+        cache.evict(&keys);
+        Ok(removed)
+    }
+}
+
+impl<DB: SettingsStore> SettingsStore for BlockCache<DB> {
+    fn read_bin(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        self.inner.read_bin(key)
+    }
+
+    fn write_bin(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        self.inner.write_bin(key, value)
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        self.inner.exists(key)
+    }
+
+    fn setting_keys(&self) -> anyhow::Result<Vec<String>> {
+        self.inner.setting_keys()
+    }
+}
+
+impl<DB: Blockstore> BitswapStoreRead for BlockCache<DB> {
+    fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
+        Blockstore::has(self, cid)
+    }
+
+    fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        Blockstore::get(self, cid)
+    }
+}
+
+impl<DB: Blockstore + Send + Sync + 'static> BitswapStoreReadWrite for BlockCache<DB> {
+    type Params = libipld::DefaultParams;
+
+    fn insert(&self, block: &libipld::Block<Self::Params>) -> anyhow::Result<()> {
+        Blockstore::put_keyed(self, block.cid(), block.data())
+    }
+}
+
+impl<DB: DBStatistics> DBStatistics for BlockCache<DB> {
+    fn get_statistics(&self) -> Option<String> {
+        self.inner.get_statistics()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::utils::db::CborStoreExt as _;
+
+    #[test]
+    fn block_cache_is_size_bounded() {
+        let db = BlockCache::new(MemoryDB::default(), 100);
+        let small = db.put_cbor_default(&vec![0_u8; 40]).unwrap();
+        let other = db.put_cbor_default(&vec![1_u8; 40]).unwrap();
+        let large = db.put_cbor_default(&vec![2_u8; 200]).unwrap();
+        // The large block doesn't fit in the cache at all.
+        assert!(db.cache.lock().current_size <= 100);
+        assert!(db.cache.lock().lru.contains(&small));
+        assert!(db.cache.lock().lru.contains(&other));
+        assert!(!db.cache.lock().lru.contains(&large));
+        assert!(db.has(&large).unwrap());
+
+        let third = db.put_cbor_default(&vec![3_u8; 40]).unwrap();
+        // The least recently used block has been evicted, reading it from the
+        // store caches it again.
+        assert!(!db.cache.lock().lru.contains(&small));
+        assert!(Blockstore::get(&db, &small).unwrap().is_some());
+        assert!(db.cache.lock().lru.contains(&small));
+        assert!(db.cache.lock().lru.contains(&third));
+        assert!(db.cache.lock().current_size <= 100);
+    }
+}
@@ -1,15 +1,14 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-//! There are three different CAR formats: `.car`, `.car.zst` and
-//! `.forest.car.zst`. [`AnyCar`] identifies the format by inspecting the CAR
-//! header and the first key-value block, and picks the appropriate block store
-//! (either [`super::ForestCar`] or [`super::PlainCar`]).
-//!
-//! CARv2 is not supported yet.
+//! There are four different CAR formats: `.car` (CARv1 or CARv2), `.car.zst`
+//! and `.forest.car.zst`. [`AnyCar`] identifies the format by inspecting the
+//! CAR header and the first key-value block, and picks the appropriate block
+//! store (either [`super::ForestCar`], [`super::CarV2`] or [`super::PlainCar`]).
 
 use super::{CacheKey, ZstdFrameCache};
 use crate::blocks::Tipset;
+use crate::utils::db::car_index::BloomFilter;
 use crate::utils::io::EitherMmapOrRandomAccessFile;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
@@ -22,12 +21,13 @@ use std::sync::Arc;
 pub enum AnyCar<ReaderT> {
     Plain(super::PlainCar<ReaderT>),
     Forest(super::ForestCar<ReaderT>),
+    V2(super::CarV2<ReaderT>),
     Memory(super::PlainCar<Vec<u8>>),
 }
 
 impl<ReaderT: super::RandomAccessFileReader> AnyCar<ReaderT> {
-    /// Open an archive. May be formatted as `.car`, `.car.zst` or
-    /// `.forest.car.zst`. This call may block for an indeterminate amount of
+    /// Open an archive. May be formatted as `.car` (CARv1 or CARv2), `.car.zst`
+    /// or `.forest.car.zst`. This call may block for an indeterminate amount of
     /// time while data is decoded and indexed.
     pub fn new(reader: ReaderT) -> Result<Self> {
         if super::ForestCar::is_valid(&reader) {
@@ -35,6 +35,10 @@ impl<ReaderT: super::RandomAccessFileReader> AnyCar<ReaderT> {
             return Ok(AnyCar::Forest(forest_car));
         }
 
+        if super::CarV2::is_valid(&reader) {
+            return Ok(AnyCar::V2(super::CarV2::new(reader)?));
+        }
+
         // Maybe use a tempfile for this in the future.
         if let Ok(decompressed) = zstd::stream::decode_all(positioned_io::Cursor::new(&reader)) {
             if let Ok(mem_car) = super::PlainCar::new(decompressed) {
@@ -47,7 +51,7 @@ impl<ReaderT: super::RandomAccessFileReader> AnyCar<ReaderT> {
         }
         Err(Error::new(
             ErrorKind::InvalidData,
-            "input not recognized as any kind of CAR data (.car, .car.zst, .forest.car)",
+            "input not recognized as any kind of CAR data (.car, .car.zst, .forest.car, CARv2)",
         ))
     }
 
@@ -56,17 +60,40 @@ impl<ReaderT: super::RandomAccessFileReader> AnyCar<ReaderT> {
     pub fn heaviest_tipset(&self) -> anyhow::Result<Tipset> {
         match self {
             AnyCar::Forest(forest) => forest.heaviest_tipset(),
+            AnyCar::V2(v2) => v2.heaviest_tipset(),
             AnyCar::Plain(plain) => plain.heaviest_tipset(),
             AnyCar::Memory(mem) => mem.heaviest_tipset(),
         }
     }
 
-    /// Return the identified CAR format variant. There are three variants:
-    /// `CARv1`, `CARv1.zst` and `ForestCARv1.zst`.
+    /// Root CIDs of the archive. Segments written by the garbage collector have
+    /// no roots.
+    pub fn roots(&self) -> Vec<Cid> {
+        match self {
+            AnyCar::Forest(forest) => forest.roots(),
+            AnyCar::V2(v2) => v2.roots(),
+            AnyCar::Plain(plain) => plain.roots(),
+            AnyCar::Memory(mem) => mem.roots(),
+        }
+    }
+
+    /// Bloom filter of the blocks in the archive. Only `.forest.car.zst`
+    /// archives have one, the other variants either keep their whole index in
+    /// memory or search it with few reads.
+    pub fn bloom_filter(&self) -> Result<Option<BloomFilter>> {
+        match self {
+            AnyCar::Forest(forest) => forest.bloom_filter().map(Some),
+            AnyCar::Plain(_) | AnyCar::V2(_) | AnyCar::Memory(_) => Ok(None),
+        }
+    }
+
+    /// Return the identified CAR format variant. There are four variants:
+    /// `CARv1`, `CARv2`, `CARv1.zst` and `ForestCARv1.zst`.
     pub fn variant(&self) -> &'static str {
         match self {
             AnyCar::Forest(_) => "ForestCARv1.zst",
             AnyCar::Plain(_) => "CARv1",
+            AnyCar::V2(_) => "CARv2",
             AnyCar::Memory(_) => "CARv1.zst",
         }
     }
@@ -76,6 +103,7 @@ impl<ReaderT: super::RandomAccessFileReader> AnyCar<ReaderT> {
         match self {
             AnyCar::Forest(f) => AnyCar::Forest(f.into_dyn()),
             AnyCar::Plain(p) => AnyCar::Plain(p.into_dyn()),
+            AnyCar::V2(v2) => AnyCar::V2(v2.into_dyn()),
             AnyCar::Memory(m) => AnyCar::Memory(m),
         }
     }
@@ -85,6 +113,7 @@ impl<ReaderT: super::RandomAccessFileReader> AnyCar<ReaderT> {
         match self {
             AnyCar::Forest(f) => AnyCar::Forest(f.with_cache(cache, key)),
             AnyCar::Plain(p) => AnyCar::Plain(p),
+            AnyCar::V2(v2) => AnyCar::V2(v2),
             AnyCar::Memory(m) => AnyCar::Memory(m),
         }
     }
@@ -112,6 +141,7 @@ where
         match self {
             AnyCar::Forest(forest) => forest.get(k),
             AnyCar::Plain(plain) => plain.get(k),
+            AnyCar::V2(v2) => v2.get(k),
             AnyCar::Memory(mem) => mem.get(k),
         }
     }
@@ -120,6 +150,7 @@ where
         match self {
             AnyCar::Forest(forest) => forest.put_keyed(k, block),
             AnyCar::Plain(plain) => plain.put_keyed(k, block),
+            AnyCar::V2(v2) => v2.put_keyed(k, block),
             AnyCar::Memory(mem) => mem.put_keyed(k, block),
         }
     }
@@ -131,6 +162,12 @@ impl<ReaderT> From<super::ForestCar<ReaderT>> for AnyCar<ReaderT> {
     }
 }
 
+impl<ReaderT> From<super::CarV2<ReaderT>> for AnyCar<ReaderT> {
+    fn from(car: super::CarV2<ReaderT>) -> Self {
+        Self::V2(car)
+    }
+}
+
 impl<ReaderT> From<super::PlainCar<ReaderT>> for AnyCar<ReaderT> {
     fn from(car: super::PlainCar<ReaderT>) -> Self {
         Self::Plain(car)
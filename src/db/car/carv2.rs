@@ -0,0 +1,682 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! # CARv2 format
+//!
+//! A CARv2 file wraps a CARv1 _data payload_ (see [`crate::db::car::plain`])
+//! between a fixed-size header and an optional index:
+//!
+//! ```text
+//! ┌──────┬──────────┬───────────────────┬───────┐
+//! │pragma│header    │CARv1 data payload │index  │
+//! │11 B  │40 B      │                   │       │
+//! └──────┴──────────┴───────────────────┴───────┘
+//! ```
+//!
+//! The header holds the offset and size of the data payload and the offset of
+//! the index (`0` if there is none). The index maps multihash digests to the
+//! offset of their block frame, relative to the start of the data payload.
+//! Digests are sorted, so [`CarV2`] looks blocks up with a binary search
+//! directly in the file, without loading the index in memory. Both the
+//! `IndexSorted` and the `MultihashIndexSorted` index formats are supported.
+//! Files without an index are scanned on opening, like [`super::PlainCar`].
+//!
+//! [`Encoder`] writes CARv2 files with a `MultihashIndexSorted` index. The
+//! index is sorted in bounded runs which are spilled to temporary files, so
+//! large archives can be converted without holding their index in memory.
+//!
+//! CARv2 specification: <https://ipld.io/specs/transport/car/carv2/>
+
+use super::plain::{
+    get_roots_from_v1_header, read_block_data_location_and_skip, UncompressedBlockDataLocation,
+};
+use crate::blocks::{Tipset, TipsetKeys};
+use crate::cid_collections::CidHashMap;
+use crate::utils::db::car_stream::{CarBlock, CarHeader};
+use crate::utils::io::EitherMmapOrRandomAccessFile;
+use cid::Cid;
+use futures::{Stream, TryStreamExt as _};
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::to_vec;
+use integer_encoding::{VarInt as _, VarIntReader as _};
+use parking_lot::RwLock;
+use positioned_io::{Cursor, ReadAt};
+use std::collections::BTreeMap;
+use std::io::{self, BufReader, Read, Seek as _, SeekFrom};
+use std::path::Path;
+use tokio::io::{AsyncSeek, AsyncSeekExt as _, AsyncWrite, AsyncWriteExt as _};
+use tracing::debug;
+
+/// First bytes of every CARv2 file: a CARv1 header frame containing `{version: 2}`.
+const PRAGMA: [u8; 11] = [
+    0x0a, 0xa1, 0x67, 0x76, 0x65, 0x72, 0x73, 0x69, 0x6f, 0x6e, 0x02,
+];
+/// Multicodec of the `IndexSorted` index format.
+const INDEX_SORTED: u64 = 0x0400;
+/// Multicodec of the `MultihashIndexSorted` index format.
+const MULTIHASH_INDEX_SORTED: u64 = 0x0401;
+/// The most significant bit of the characteristics: every block, including
+/// the ones with an identity CID, is in the index.
+const FULLY_INDEXED: u8 = 0x80;
+
+/// Fixed-size header following the pragma.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CarV2Header {
+    pub characteristics: [u8; 16],
+    pub data_offset: u64,
+    pub data_size: u64,
+    /// `0` if the file has no index.
+    pub index_offset: u64,
+}
+
+impl CarV2Header {
+    const SIZE: usize = 40;
+
+    /// Reads the pragma and the header from the start of a CARv2 file.
+    pub fn read(mut reader: impl Read) -> io::Result<Self> {
+        let mut pragma = [0; PRAGMA.len()];
+        reader.read_exact(&mut pragma)?;
+        if pragma != PRAGMA {
+            return Err(invalid_data("not a CARv2 file"));
+        }
+        let mut buffer = [0; Self::SIZE];
+        reader.read_exact(&mut buffer)?;
+        let u64_at =
+            |at: usize| u64::from_le_bytes(buffer[at..at + 8].try_into().expect("8 bytes"));
+        Ok(Self {
+            characteristics: buffer[..16].try_into().expect("16 bytes"),
+            data_offset: u64_at(16),
+            data_size: u64_at(24),
+            index_offset: u64_at(32),
+        })
+    }
+
+    fn to_le_bytes(self) -> [u8; Self::SIZE] {
+        let mut buffer = [0; Self::SIZE];
+        buffer[..16].copy_from_slice(&self.characteristics);
+        buffer[16..24].copy_from_slice(&self.data_offset.to_le_bytes());
+        buffer[24..32].copy_from_slice(&self.data_size.to_le_bytes());
+        buffer[32..].copy_from_slice(&self.index_offset.to_le_bytes());
+        buffer
+    }
+}
+
+/// **Note that all operations on this store are blocking**.
+///
+/// [`Blockstore`] over a CARv2 file. See [module documentation](mod@self).
+///
+/// Writes for new blocks are cached in-memory.
+pub struct CarV2<ReaderT> {
+    reader: ReaderT,
+    data_offset: u64,
+    /// End of the data payload, which bounds every block frame.
+    data_end: u64,
+    index: CarV2Index,
+    write_cache: RwLock<CidHashMap<Vec<u8>>>,
+    roots: Vec<Cid>,
+}
+
+enum CarV2Index {
+    /// Index embedded in the file, searched in place.
+    Embedded(Vec<IndexBucket>),
+    /// The file has no index, the data payload has been scanned on opening.
+    Scanned(CidHashMap<UncompressedBlockDataLocation>),
+}
+
+/// Sorted entries of the index sharing the same multihash code and digest
+/// length. Each entry is the digest followed by the little-endian offset of
+/// the block frame in the data payload.
+#[derive(Debug)]
+struct IndexBucket {
+    /// `None` for `IndexSorted` indexes, which don't record the multihash code.
+    code: Option<u64>,
+    width: u64,
+    /// Position of the first entry in the file.
+    offset: u64,
+    count: u64,
+}
+
+impl<ReaderT: super::RandomAccessFileReader> CarV2<ReaderT> {
+    /// To be correct, `reader` must read immutable data, see [`super::PlainCar::new`].
+    #[tracing::instrument(level = "debug", skip_all)]
+    pub fn new(reader: ReaderT) -> io::Result<Self> {
+        let header = CarV2Header::read(Cursor::new(&reader))?;
+        let data_end = header
+            .data_offset
+            .checked_add(header.data_size)
+            .ok_or_else(|| invalid_data("invalid CARv2 data payload size"))?;
+        // Lengths read from the file are checked against the payload, which
+        // has to fit in the file.
+        let file_size = reader.size()?.unwrap_or(u64::MAX);
+        if data_end > file_size || header.index_offset > file_size {
+            return Err(invalid_data("CARv2 header points past the end of the file"));
+        }
+
+        let mut cursor = BufReader::new(Cursor::new_pos(&reader, header.data_offset));
+        let roots = get_roots_from_v1_header(&mut cursor)?;
+
+        let index = if header.index_offset == 0 {
+            let mut index = CidHashMap::new();
+            while cursor.stream_position()? < data_end {
+                match read_block_data_location_and_skip(&mut cursor)? {
+                    Some((_, location))
+                        if location.offset + u64::from(location.length) > data_end =>
+                    {
+                        return Err(invalid_data("CARv2 block frame exceeds the data payload"));
+                    }
+                    Some((cid, location)) => index.insert(cid, location),
+                    None => break,
+                };
+            }
+            debug!(num_blocks = index.len(), "scanned CARv2 without index");
+            CarV2Index::Scanned(index)
+        } else {
+            CarV2Index::Embedded(read_index(
+                Cursor::new_pos(&reader, header.index_offset),
+                file_size,
+            )?)
+        };
+
+        Ok(Self {
+            reader,
+            data_offset: header.data_offset,
+            data_end,
+            index,
+            write_cache: RwLock::new(CidHashMap::new()),
+            roots,
+        })
+    }
+
+    pub fn is_valid(reader: &ReaderT) -> bool {
+        let mut pragma = [0; PRAGMA.len()];
+        reader.read_exact_at(0, &mut pragma).is_ok() && pragma == PRAGMA
+    }
+
+    pub fn roots(&self) -> Vec<Cid> {
+        self.roots.clone()
+    }
+
+    pub fn heaviest_tipset(&self) -> anyhow::Result<Tipset> {
+        Tipset::load_required(self, &TipsetKeys::from_iter(self.roots()))
+    }
+
+    pub fn into_dyn(self) -> CarV2<Box<dyn super::RandomAccessFileReader>> {
+        CarV2 {
+            reader: Box::new(self.reader),
+            data_offset: self.data_offset,
+            data_end: self.data_end,
+            index: self.index,
+            write_cache: self.write_cache,
+            roots: self.roots,
+        }
+    }
+}
+
+impl TryFrom<&Path> for CarV2<EitherMmapOrRandomAccessFile> {
+    type Error = io::Error;
+    fn try_from(path: &Path) -> io::Result<Self> {
+        CarV2::new(EitherMmapOrRandomAccessFile::open(path)?)
+    }
+}
+
+impl<ReaderT: ReadAt> CarV2<ReaderT> {
+    fn read_from_index(&self, buckets: &[IndexBucket], k: &Cid) -> io::Result<Option<Vec<u8>>> {
+        let digest = k.hash().digest();
+        let width = digest.len() as u64 + 8;
+        let mut entry = vec![0; width as usize];
+        for bucket in buckets
+            .iter()
+            .filter(|b| b.width == width && b.code.map_or(true, |code| code == k.hash().code()))
+        {
+            let read_entry = |n: u64, entry: &mut [u8]| {
+                self.reader
+                    .read_exact_at(bucket.offset + n * bucket.width, entry)
+            };
+            // Find the first entry with this digest.
+            let (mut lo, mut hi) = (0, bucket.count);
+            while lo < hi {
+                let mid = lo + (hi - lo) / 2;
+                read_entry(mid, &mut entry)?;
+                if &entry[..digest.len()] < digest {
+                    lo = mid + 1;
+                } else {
+                    hi = mid;
+                }
+            }
+            // Several CIDs, e.g. with different codecs, may share a digest.
+            for n in lo..bucket.count {
+                read_entry(n, &mut entry)?;
+                if &entry[..digest.len()] != digest {
+                    break;
+                }
+                let offset = u64::from_le_bytes(entry[digest.len()..].try_into().expect("8 bytes"));
+                if let Some(data) = self.read_block(self.data_offset + offset, k)? {
+                    return Ok(Some(data));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Reads the block frame at `position`, if it holds `k`.
+    fn read_block(&self, position: u64, k: &Cid) -> io::Result<Option<Vec<u8>>> {
+        let mut cursor = BufReader::with_capacity(256, Cursor::new_pos(&self.reader, position));
+        let body_length: u64 = cursor.read_varint()?;
+        let cid = Cid::read_bytes(&mut cursor).map_err(invalid_data)?;
+        if cid != *k {
+            return Ok(None);
+        }
+        let cid_length = cid.encoded_len() as u64;
+        let body_offset = position + body_length.required_space() as u64;
+        if body_length < cid_length
+            || body_offset
+                .checked_add(body_length)
+                .map_or(true, |end| end > self.data_end)
+        {
+            return Err(invalid_data("CARv2 block frame exceeds the data payload"));
+        }
+        let mut data = vec![0; usize::try_from(body_length - cid_length).map_err(invalid_data)?];
+        self.reader
+            .read_exact_at(body_offset + cid_length, &mut data)?;
+        Ok(Some(data))
+    }
+}
+
+impl<ReaderT> Blockstore for CarV2<ReaderT>
+where
+    ReaderT: ReadAt,
+{
+    #[tracing::instrument(level = "trace", skip(self))]
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        if let Some(value) = self.write_cache.read().get(k) {
+            return Ok(Some(value.clone()));
+        }
+        match &self.index {
+            CarV2Index::Embedded(buckets) => Ok(self.read_from_index(buckets, k)?),
+            CarV2Index::Scanned(index) => match index.get(k) {
+                Some(UncompressedBlockDataLocation { offset, length }) => {
+                    let mut data = vec![0; usize::try_from(*length)?];
+                    self.reader.read_exact_at(*offset, &mut data)?;
+                    Ok(Some(data))
+                }
+                None => Ok(None),
+            },
+        }
+    }
+
+    #[tracing::instrument(level = "trace", skip(self, block))]
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.write_cache.write().insert(*k, Vec::from(block));
+        Ok(())
+    }
+}
+
+fn read_index(mut reader: impl Read + io::Seek, file_size: u64) -> io::Result<Vec<IndexBucket>> {
+    let codec: u64 = reader.read_varint()?;
+    match codec {
+        INDEX_SORTED => read_buckets(&mut reader, None, file_size),
+        MULTIHASH_INDEX_SORTED => {
+            let mut buckets = vec![];
+            for _ in 0..read_u32(&mut reader)? {
+                let code = read_u64(&mut reader)?;
+                buckets.extend(read_buckets(&mut reader, Some(code), file_size)?);
+            }
+            Ok(buckets)
+        }
+        other => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("unsupported CARv2 index format {other:#x}"),
+        )),
+    }
+}
+
+fn read_buckets(
+    mut reader: impl Read + io::Seek,
+    code: Option<u64>,
+    file_size: u64,
+) -> io::Result<Vec<IndexBucket>> {
+    let mut buckets = vec![];
+    for _ in 0..read_u32(&mut reader)? {
+        let width = u64::from(read_u32(&mut reader)?);
+        let size = read_u64(&mut reader)?;
+        let offset = reader.stream_position()?;
+        if width <= 8
+            || size % width != 0
+            || offset.checked_add(size).map_or(true, |end| end > file_size)
+        {
+            return Err(invalid_data("malformed CARv2 index"));
+        }
+        buckets.push(IndexBucket {
+            code,
+            width,
+            offset,
+            count: size / width,
+        });
+        reader.seek(SeekFrom::Current(
+            i64::try_from(size).map_err(invalid_data)?,
+        ))?;
+    }
+    Ok(buckets)
+}
+
+fn read_u32(mut reader: impl Read) -> io::Result<u32> {
+    let mut buffer = [0; 4];
+    reader.read_exact(&mut buffer)?;
+    Ok(u32::from_le_bytes(buffer))
+}
+
+fn read_u64(mut reader: impl Read) -> io::Result<u64> {
+    let mut buffer = [0; 8];
+    reader.read_exact(&mut buffer)?;
+    Ok(u64::from_le_bytes(buffer))
+}
+
+fn invalid_data(inner: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, inner)
+}
+
+/// Number of index entries sorted in memory before they are spilled to a
+/// temporary file. Each entry takes about 100 bytes.
+const INDEX_RUN_LENGTH: usize = 1 << 20;
+
+/// An index entry, ordered like the `MultihashIndexSorted` index: by multihash
+/// code, digest length and digest.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct IndexEntry {
+    code: u64,
+    digest_length: usize,
+    digest: Vec<u8>,
+    offset: u64,
+}
+
+impl IndexEntry {
+    fn write(&self, mut writer: impl io::Write) -> io::Result<()> {
+        writer.write_all(&self.code.to_le_bytes())?;
+        writer.write_all(&[self.digest_length as u8])?;
+        writer.write_all(&self.digest)?;
+        writer.write_all(&self.offset.to_le_bytes())
+    }
+
+    /// Reads an entry written by [`IndexEntry::write`], or returns `None` at
+    /// the end of `reader`.
+    fn read(mut reader: impl Read) -> io::Result<Option<Self>> {
+        let mut code = [0; 8];
+        match reader.read_exact(&mut code) {
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            result => result?,
+        }
+        let mut digest_length = [0; 1];
+        reader.read_exact(&mut digest_length)?;
+        let mut digest = vec![0; usize::from(digest_length[0])];
+        reader.read_exact(&mut digest)?;
+        Ok(Some(Self {
+            code: u64::from_le_bytes(code),
+            digest_length: digest.len(),
+            digest,
+            offset: read_u64(reader)?,
+        }))
+    }
+}
+
+/// Builds the index in sorted runs of bounded length. Full runs are spilled
+/// to temporary files and merged when the index is written, so that the
+/// memory used doesn't grow with the number of blocks.
+struct IndexBuilder {
+    run_length: usize,
+    run: Vec<IndexEntry>,
+    spilled: Vec<std::fs::File>,
+    /// Number of entries per multihash code and digest length.
+    buckets: BTreeMap<(u64, usize), u64>,
+}
+
+impl IndexBuilder {
+    fn new(run_length: usize) -> Self {
+        Self {
+            run_length,
+            run: vec![],
+            spilled: vec![],
+            buckets: BTreeMap::new(),
+        }
+    }
+
+    fn push(&mut self, cid: &Cid, offset: u64) -> io::Result<()> {
+        let hash = cid.hash();
+        let digest = hash.digest().to_vec();
+        *self.buckets.entry((hash.code(), digest.len())).or_default() += 1;
+        self.run.push(IndexEntry {
+            code: hash.code(),
+            digest_length: digest.len(),
+            digest,
+            offset,
+        });
+        if self.run.len() >= self.run_length {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        self.run.sort_unstable();
+        let mut writer = io::BufWriter::new(tempfile::tempfile()?);
+        for entry in self.run.drain(..) {
+            entry.write(&mut writer)?;
+        }
+        let mut file = writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)?;
+        file.rewind()?;
+        self.spilled.push(file);
+        Ok(())
+    }
+
+    /// All entries in index order, merged from the spilled runs and the
+    /// current run.
+    fn into_sorted(mut self) -> impl Iterator<Item = io::Result<IndexEntry>> {
+        self.run.sort_unstable();
+        let mut runs: Vec<Box<dyn Iterator<Item = io::Result<IndexEntry>>>> =
+            vec![Box::new(self.run.into_iter().map(Ok))];
+        for file in self.spilled {
+            let mut reader = BufReader::new(file);
+            runs.push(Box::new(std::iter::from_fn(move || {
+                IndexEntry::read(&mut reader).transpose()
+            })));
+        }
+
+        let mut heads = std::collections::BinaryHeap::new();
+        let mut error = None;
+        for (n, run) in runs.iter_mut().enumerate() {
+            match run.next() {
+                Some(Ok(entry)) => heads.push(std::cmp::Reverse((entry, n))),
+                Some(Err(e)) => error = Some(e),
+                None => {}
+            }
+        }
+        std::iter::from_fn(move || {
+            if let Some(e) = error.take() {
+                return Some(Err(e));
+            }
+            let std::cmp::Reverse((entry, n)) = heads.pop()?;
+            match runs[n].next() {
+                Some(Ok(next)) => heads.push(std::cmp::Reverse((next, n))),
+                Some(Err(e)) => error = Some(e),
+                None => {}
+            }
+            Some(Ok(entry))
+        })
+    }
+}
+
+pub struct Encoder {}
+
+impl Encoder {
+    /// Writes a CARv2 file with a `MultihashIndexSorted` index of all the
+    /// blocks. The header is written last, hence the need for seeking.
+    pub async fn write(
+        sink: &mut (impl AsyncWrite + AsyncSeek + Unpin),
+        roots: Vec<Cid>,
+        blocks: impl Stream<Item = io::Result<CarBlock>> + Unpin,
+    ) -> io::Result<()> {
+        Self::write_with_run_length(sink, roots, blocks, INDEX_RUN_LENGTH).await
+    }
+
+    async fn write_with_run_length(
+        sink: &mut (impl AsyncWrite + AsyncSeek + Unpin),
+        roots: Vec<Cid>,
+        mut blocks: impl Stream<Item = io::Result<CarBlock>> + Unpin,
+        run_length: usize,
+    ) -> io::Result<()> {
+        let data_offset = (PRAGMA.len() + CarV2Header::SIZE) as u64;
+        sink.write_all(&PRAGMA).await?;
+        sink.write_all(&[0; CarV2Header::SIZE]).await?;
+
+        // CARv1 data payload
+        let header = to_vec(&CarHeader { roots, version: 1 }).map_err(invalid_data)?;
+        let mut frame = header.len().encode_var_vec();
+        frame.extend(header);
+        sink.write_all(&frame).await?;
+        let mut data_size = frame.len() as u64;
+
+        let mut index = IndexBuilder::new(run_length);
+        while let Some(block) = blocks.try_next().await? {
+            index.push(&block.cid, data_size)?;
+            let mut frame = vec![];
+            block.write(&mut frame)?;
+            sink.write_all(&frame).await?;
+            data_size += frame.len() as u64;
+        }
+
+        // Index, with a bucket per multihash code and digest length.
+        let mut codes = BTreeMap::<u64, u32>::new();
+        for (code, _) in index.buckets.keys() {
+            *codes.entry(*code).or_default() += 1;
+        }
+        let buckets = std::mem::take(&mut index.buckets);
+        let mut buffer = MULTIHASH_INDEX_SORTED.encode_var_vec();
+        buffer.extend((codes.len() as u32).to_le_bytes());
+        let mut bucket = None;
+        for entry in index.into_sorted() {
+            let entry = entry?;
+            if bucket != Some((entry.code, entry.digest_length)) {
+                if bucket.map(|(code, _)| code) != Some(entry.code) {
+                    buffer.extend(entry.code.to_le_bytes());
+                    buffer.extend(codes[&entry.code].to_le_bytes());
+                }
+                let count = buckets[&(entry.code, entry.digest_length)];
+                let width = entry.digest_length as u64 + 8;
+                buffer.extend((width as u32).to_le_bytes());
+                buffer.extend((width * count).to_le_bytes());
+                bucket = Some((entry.code, entry.digest_length));
+            }
+            buffer.extend(&entry.digest);
+            buffer.extend(entry.offset.to_le_bytes());
+            if buffer.len() >= 1 << 16 {
+                sink.write_all(&buffer).await?;
+                buffer.clear();
+            }
+        }
+        sink.write_all(&buffer).await?;
+
+        let mut characteristics = [0; 16];
+        characteristics[0] = FULLY_INDEXED;
+        let header = CarV2Header {
+            characteristics,
+            data_offset,
+            data_size,
+            index_offset: data_offset + data_size,
+        };
+        sink.seek(SeekFrom::Start(PRAGMA.len() as u64)).await?;
+        sink.write_all(&header.to_le_bytes()).await?;
+        sink.flush().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::car::PlainCar;
+    use crate::utils::db::car_stream::CarStream;
+
+    fn chain4_car() -> &'static [u8] {
+        include_bytes!("../../../test-snapshots/chain4.car")
+    }
+
+    async fn chain4_carv2() -> Vec<u8> {
+        let stream = CarStream::new(chain4_car()).await.unwrap();
+        let roots = stream.header.roots.clone();
+        let mut carv2 = std::io::Cursor::new(vec![]);
+        Encoder::write(&mut carv2, roots, stream).await.unwrap();
+        carv2.into_inner()
+    }
+
+    fn set_data_size(bytes: &mut [u8], data_size: u64) {
+        bytes[PRAGMA.len() + 24..PRAGMA.len() + 32].copy_from_slice(&data_size.to_le_bytes());
+    }
+
+    #[tokio::test]
+    async fn spilled_index_is_identical() {
+        let stream = CarStream::new(chain4_car()).await.unwrap();
+        let roots = stream.header.roots.clone();
+        let mut spilled = std::io::Cursor::new(vec![]);
+        Encoder::write_with_run_length(&mut spilled, roots, stream, 3)
+            .await
+            .unwrap();
+        assert_eq!(spilled.into_inner(), chain4_carv2().await);
+    }
+
+    #[tokio::test]
+    async fn carv2_rejects_frames_past_the_payload() {
+        let bytes = chain4_carv2().await;
+        let header = CarV2Header::read(bytes.as_slice()).unwrap();
+
+        let mut oversized = bytes.clone();
+        set_data_size(&mut oversized, u64::MAX / 2);
+        assert!(CarV2::new(oversized).is_err());
+
+        // The last block frame now ends past the payload.
+        let mut truncated = bytes.clone();
+        set_data_size(&mut truncated, header.data_size - 1);
+        let carv2 = CarV2::new(truncated).unwrap();
+        let reference = PlainCar::new(chain4_car()).unwrap();
+        assert!(reference.cids().iter().any(|cid| carv2.get(cid).is_err()));
+
+        // Without an index, the frames are checked while scanning.
+        let mut scanned = bytes;
+        scanned.truncate((header.data_offset + header.data_size) as usize);
+        scanned[PRAGMA.len() + 32..PRAGMA.len() + 40].fill(0);
+        set_data_size(&mut scanned, header.data_size - 1);
+        assert!(CarV2::new(scanned).is_err());
+    }
+
+    #[tokio::test]
+    async fn carv2_round_trip() {
+        let reference = PlainCar::new(chain4_car()).unwrap();
+        let carv2 = CarV2::new(chain4_carv2().await).unwrap();
+        assert!(matches!(carv2.index, CarV2Index::Embedded(_)));
+        assert_eq!(carv2.roots(), reference.roots());
+        for cid in reference.cids() {
+            assert_eq!(carv2.get(&cid).unwrap(), reference.get(&cid).unwrap());
+        }
+        let missing = Cid::new_v1(
+            fvm_ipld_encoding::DAG_CBOR,
+            cid::multihash::MultihashDigest::digest(&cid::multihash::Code::Blake2b256, b"missing"),
+        );
+        assert_eq!(carv2.get(&missing).unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn carv2_without_index() {
+        let mut bytes = chain4_carv2().await;
+        let header = CarV2Header::read(bytes.as_slice()).unwrap();
+        // Drop the index.
+        bytes.truncate((header.data_offset + header.data_size) as usize);
+        bytes[PRAGMA.len() + 32..PRAGMA.len() + 40].fill(0);
+
+        let reference = PlainCar::new(chain4_car()).unwrap();
+        let carv2 = CarV2::new(bytes).unwrap();
+        assert!(matches!(carv2.index, CarV2Index::Scanned(_)));
+        for cid in reference.cids() {
+            assert_eq!(carv2.get(&cid).unwrap(), reference.get(&cid).unwrap());
+        }
+    }
+}
@@ -49,7 +49,7 @@
 use super::{CacheKey, ZstdFrameCache};
 use crate::blocks::{Tipset, TipsetKeys};
 use crate::db::car::plain::write_skip_frame_header_async;
-use crate::utils::db::car_index::{CarIndex, CarIndexBuilder, FrameOffset, Hash};
+use crate::utils::db::car_index::{BloomFilter, CarIndex, CarIndexBuilder, FrameOffset, Hash};
 use crate::utils::db::car_stream::{CarBlock, CarHeader};
 use crate::utils::encoding::from_slice_with_fallback;
 use crate::utils::io::EitherMmapOrRandomAccessFile;
@@ -142,6 +142,11 @@ impl<ReaderT: super::RandomAccessFileReader> ForestCar<ReaderT> {
         Tipset::load_required(self, &TipsetKeys::from_iter(self.roots()))
     }
 
+    /// Builds a bloom filter of the blocks in this archive, see [`CarIndex::bloom_filter`].
+    pub fn bloom_filter(&self) -> io::Result<BloomFilter> {
+        self.indexed.bloom_filter()
+    }
+
     pub fn into_dyn(self) -> ForestCar<Box<dyn super::RandomAccessFileReader>> {
         fn any_reader<ReaderT: super::RandomAccessFileReader>(
             reader: ReaderT,
@@ -452,6 +457,16 @@ mod tests {
         }
     }
 
+    #[quickcheck]
+    fn forest_car_bloom_filter(head: CarBlock, mut tail: Vec<CarBlock>) {
+        tail.push(head);
+        let forest_car = ForestCar::new(mk_encoded_car(1024 * 4, 3, vec![], tail.clone())).unwrap();
+        let filter = forest_car.bloom_filter().unwrap();
+        for block in tail {
+            assert!(filter.contains(Hash::from(block.cid)));
+        }
+    }
+
     #[quickcheck]
     fn forest_car_create_options(
         head: CarBlock,
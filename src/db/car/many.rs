@@ -6,23 +6,50 @@
 //! store (including the writable store) and the first hit is returned. Write
 //! requests are only forwarded to the writable store.
 //!
-//! A single z-frame cache is shared between all read-only stores.
+//! A single z-frame cache is shared between all read-only stores. Optionally,
+//! each `.forest.car.zst` store is paired with a bloom filter built from its
+//! index, so that lookups of missing keys skip the stores that can't contain
+//! them.
+//!
+//! Shards of a historical archive are mounted through their
+//! [`ShardIndex`], either all at once or only those needed for an epoch.
 
-use super::{AnyCar, ZstdFrameCache};
-use crate::db::{MemoryDB, SettingsStore};
+use super::shards::is_shard_index;
+use super::{AnyCar, ShardIndex, ZstdFrameCache};
+use crate::db::{GarbageCollectable, MemoryDB, RemovedRecords, SettingsStore};
 use crate::libp2p_bitswap::BitswapStoreReadWrite;
+use crate::shim::clock::ChainEpoch;
+use crate::utils::db::car_index::{BloomFilter, Hash};
 use crate::utils::io::EitherMmapOrRandomAccessFile;
 use crate::{blocks::Tipset, libp2p_bitswap::BitswapStoreRead};
+use ahash::HashSet;
 use anyhow::Context as _;
 use cid::Cid;
 use fvm_ipld_blockstore::Blockstore;
 use parking_lot::{Mutex, RwLock};
 use std::{io, path::PathBuf, sync::Arc};
+use tracing::{debug, warn};
 
 pub struct ManyCar<WriterT = MemoryDB> {
     shared_cache: Arc<Mutex<ZstdFrameCache>>,
-    read_only: RwLock<Vec<AnyCar<Box<dyn super::RandomAccessFileReader>>>>,
+    read_only: RwLock<Vec<ReadOnlyCar>>,
     writer: WriterT,
+    bloom_filters: bool,
+    /// Shards mounted from a [`ShardIndex`], so that none is mounted twice.
+    mounted_shards: Mutex<HashSet<PathBuf>>,
+}
+
+struct ReadOnlyCar {
+    car: AnyCar<Box<dyn super::RandomAccessFileReader>>,
+    bloom_filter: Option<BloomFilter>,
+}
+
+impl ReadOnlyCar {
+    fn may_contain(&self, k: &Cid) -> bool {
+        self.bloom_filter
+            .as_ref()
+            .map_or(true, |filter| filter.contains(Hash::from(*k)))
+    }
 }
 
 impl<WriterT> ManyCar<WriterT> {
@@ -31,9 +58,19 @@ impl<WriterT> ManyCar<WriterT> {
             shared_cache: Arc::new(Mutex::new(ZstdFrameCache::default())),
             read_only: RwLock::new(Vec::new()),
             writer,
+            bloom_filters: false,
+            mounted_shards: Mutex::new(HashSet::default()),
         }
     }
 
+    /// Builds a bloom filter for each read-only store added from now on. This
+    /// costs a scan of the store index when it is added, and about 10 bits of
+    /// memory per block.
+    pub fn with_bloom_filters(mut self, bloom_filters: bool) -> Self {
+        self.bloom_filters = bloom_filters;
+        self
+    }
+
     pub fn writer(&self) -> &WriterT {
         &self.writer
     }
@@ -55,13 +92,30 @@ impl<WriterT> ManyCar<WriterT> {
     }
 
     pub fn read_only<ReaderT: super::RandomAccessFileReader>(&self, any_car: AnyCar<ReaderT>) {
+        // The filter is merely an optimization, carry on without it if it can't be built.
+        let bloom_filter = match self.bloom_filters {
+            true => match any_car.bloom_filter() {
+                Ok(filter) => {
+                    if let Some(filter) = &filter {
+                        debug!("Built a bloom filter of {} bytes", filter.size());
+                    }
+                    filter
+                }
+                Err(e) => {
+                    warn!("couldn't build bloom filter: {e}");
+                    None
+                }
+            },
+            false => None,
+        };
         let mut read_only = self.read_only.write();
         let key = read_only.len() as u64;
-        read_only.push(
-            any_car
+        read_only.push(ReadOnlyCar {
+            car: any_car
                 .with_cache(self.shared_cache.clone(), key)
                 .into_dyn(),
-        );
+            bloom_filter,
+        });
     }
 
     pub fn with_read_only_files(self, files: impl Iterator<Item = PathBuf>) -> io::Result<Self> {
@@ -69,19 +123,70 @@ impl<WriterT> ManyCar<WriterT> {
         Ok(self)
     }
 
+    /// Adds each file as a read-only store. A shard index adds all of its
+    /// shards.
     pub fn read_only_files(&self, files: impl Iterator<Item = PathBuf>) -> io::Result<()> {
+        self.read_only_files_at(files, ChainEpoch::MAX)
+    }
+
+    pub fn with_read_only_files_at(
+        self,
+        files: impl Iterator<Item = PathBuf>,
+        epoch: ChainEpoch,
+    ) -> io::Result<Self> {
+        self.read_only_files_at(files, epoch)?;
+        Ok(self)
+    }
+
+    /// Adds each file as a read-only store. A shard index only adds the
+    /// shards needed to read the chain and state at `epoch`.
+    pub fn read_only_files_at(
+        &self,
+        files: impl Iterator<Item = PathBuf>,
+        epoch: ChainEpoch,
+    ) -> io::Result<()> {
         for file in files {
-            self.read_only(AnyCar::new(EitherMmapOrRandomAccessFile::open(file)?)?);
+            if is_shard_index(&file) {
+                let index = ShardIndex::load(&file)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                self.read_only_shards(&index, epoch)?;
+            } else {
+                self.read_only(AnyCar::new(EitherMmapOrRandomAccessFile::open(file)?)?);
+            }
         }
 
         Ok(())
     }
 
+    /// Mounts the shards needed to read the chain and state at `epoch`,
+    /// skipping those that are already mounted. Returns the number of newly
+    /// mounted shards.
+    pub fn read_only_shards(&self, index: &ShardIndex, epoch: ChainEpoch) -> io::Result<usize> {
+        // Held throughout, so that concurrent callers don't mount a shard twice.
+        let mut mounted_shards = self.mounted_shards.lock();
+        let mut mounted = 0;
+        for shard in index.shards_for(epoch) {
+            if mounted_shards.contains(&shard.path) {
+                continue;
+            }
+            debug!("Mounting shard {}", shard.path.display());
+            self.read_only(AnyCar::new(EitherMmapOrRandomAccessFile::open(
+                &shard.path,
+            )?)?);
+            mounted_shards.insert(shard.path.clone());
+            mounted += 1;
+        }
+        Ok(mounted)
+    }
+
     pub fn heaviest_tipset(&self) -> anyhow::Result<Tipset> {
         let tipsets = self
             .read_only
             .read()
             .iter()
+            .map(|read_only| &read_only.car)
+            // Cold storage segments are not tagged with a tipset.
+            .filter(|car| !car.roots().is_empty())
             .map(AnyCar::heaviest_tipset)
             .collect::<anyhow::Result<Vec<_>>>()?;
         tipsets
@@ -110,7 +215,10 @@ impl<WriterT: Blockstore> Blockstore for ManyCar<WriterT> {
         // In practice, there is a massive performance loss when providing
         // more than a single reader.
         for reader in self.read_only.read().iter() {
-            if let Some(val) = reader.get(k)? {
+            if !reader.may_contain(k) {
+                continue;
+            }
+            if let Some(val) = reader.car.get(k)? {
                 return Ok(Some(val));
             }
         }
@@ -140,6 +248,21 @@ impl<WriterT: BitswapStoreReadWrite + Blockstore> BitswapStoreReadWrite for Many
     }
 }
 
+// Only the writable store is garbage collected, the read-only stores are immutable.
+impl<WriterT: GarbageCollectable> GarbageCollectable for ManyCar<WriterT> {
+    fn get_keys(&self) -> anyhow::Result<HashSet<u32>> {
+        self.writer.get_keys()
+    }
+
+    fn remove_keys_with(
+        &self,
+        keys: HashSet<u32>,
+        on_removed: &mut dyn FnMut(u64),
+    ) -> anyhow::Result<RemovedRecords> {
+        self.writer.remove_keys_with(keys, on_removed)
+    }
+}
+
 impl<WriterT: SettingsStore> SettingsStore for ManyCar<WriterT> {
     fn read_bin(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
         SettingsStore::read_bin(self.writer(), key)
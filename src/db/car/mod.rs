@@ -1,14 +1,18 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 mod any;
+pub mod carv2;
 pub mod forest;
 mod many;
 pub mod plain;
+pub mod shards;
 
 pub use any::AnyCar;
+pub use carv2::CarV2;
 pub use forest::ForestCar;
 pub use many::ManyCar;
 pub use plain::PlainCar;
+pub use shards::ShardIndex;
 
 use crate::utils::db::car_index::FrameOffset;
 use ahash::HashMap;
@@ -57,7 +57,6 @@
 //! - Use safe arithmetic for all operations - a malicious frame shouldn't cause a crash.
 //! - Theoretically, file-backed blockstores should be clonable (or even [`Sync`]) with very low
 //!   overhead, so that multiple threads could perform operations concurrently.
-//! - CARv2 support
 //! - A wrapper that abstracts over car formats for reading.
 
 use crate::cid_collections::{hash_map::Entry as CidHashMapEntry, CidHashMap};
@@ -186,8 +185,8 @@ impl TryFrom<&'static [u8]> for PlainCar<&'static [u8]> {
 /// you should get data that corresponds to a [`Cid`] (but NOT the [`Cid`] itself).
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 pub struct UncompressedBlockDataLocation {
-    offset: u64,
-    length: u32,
+    pub(super) offset: u64,
+    pub(super) length: u32,
 }
 
 impl<ReaderT> Blockstore for PlainCar<ReaderT>
@@ -281,7 +280,7 @@ fn handle_write_cache(
     }
 }
 
-fn get_roots_from_v1_header(reader: impl Read) -> io::Result<Vec<Cid>> {
+pub(super) fn get_roots_from_v1_header(reader: impl Read) -> io::Result<Vec<Cid>> {
     match read_header(reader)? {
         CarHeader { roots, version: 1 } if !roots.is_empty() => Ok(roots),
         _other_version => Err(io::Error::new(
@@ -329,7 +328,7 @@ fn read_header(mut reader: impl Read) -> io::Result<CarHeader> {
 ///
 /// [`Ok(None)`] on EOF
 #[tracing::instrument(level = "trace", skip_all, ret)]
-fn read_block_data_location_and_skip(
+pub(super) fn read_block_data_location_and_skip(
     mut reader: (impl Read + Seek),
 ) -> io::Result<Option<(Cid, UncompressedBlockDataLocation)>> {
     let Some(body_length) = read_varint_body_length_or_eof(&mut reader)? else {
@@ -0,0 +1,117 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! A historical archive can be split into shards, each covering a range of
+//! epochs and containing only the blocks that are first reachable from the
+//! tipsets in that range. The [`ShardIndex`] maps epochs to shards so that a
+//! [`super::ManyCar`] store can mount the shards needed for an epoch.
+//!
+//! Since a shard leaves out every block that is reachable from older tipsets,
+//! the chain and state at an epoch are only complete once all shards starting
+//! at or before that epoch are mounted.
+
+use crate::blocks::TipsetKeys;
+use crate::shim::clock::ChainEpoch;
+use anyhow::Context as _;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub const SHARD_INDEX_EXTENSION: &str = ".shards.json";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ShardIndex {
+    pub network: String,
+    /// Shards, oldest first.
+    pub shards: Vec<Shard>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct Shard {
+    /// First epoch of the shard.
+    pub from: ChainEpoch,
+    /// Last epoch of the shard, inclusive.
+    pub to: ChainEpoch,
+    /// Path to the shard, relative to the index file.
+    pub path: PathBuf,
+    /// Heaviest tipset of the shard, which is also the root of the CAR file.
+    #[serde(with = "crate::lotus_json")]
+    pub head: TipsetKeys,
+}
+
+/// Returns `true` if `path` names a shard index rather than a CAR file.
+pub fn is_shard_index(path: &Path) -> bool {
+    path.to_string_lossy().ends_with(SHARD_INDEX_EXTENSION)
+}
+
+impl ShardIndex {
+    /// Reads the index at `path`. Shard paths are resolved against the
+    /// directory of the index.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let file = std::fs::File::open(path)
+            .with_context(|| format!("couldn't open shard index {}", path.display()))?;
+        let mut index: ShardIndex = serde_json::from_reader(std::io::BufReader::new(file))
+            .with_context(|| format!("invalid shard index {}", path.display()))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for shard in index.shards.iter_mut() {
+            shard.path = dir.join(&shard.path);
+        }
+        Ok(index)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("couldn't write shard index {}", path.display()))
+    }
+
+    /// All shards needed to read the chain and state at `epoch`, that is every
+    /// shard starting at or before `epoch`.
+    pub fn shards_for(&self, epoch: ChainEpoch) -> impl Iterator<Item = &Shard> {
+        self.shards.iter().filter(move |shard| shard.from <= epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::car::AnyCar;
+    use crate::networks::calibnet;
+
+    fn shard(from: ChainEpoch, to: ChainEpoch, head: &TipsetKeys) -> Shard {
+        Shard {
+            from,
+            to,
+            path: format!("shard_{from}_{to}.forest.car.zst").into(),
+            head: head.clone(),
+        }
+    }
+
+    #[test]
+    fn shard_index_round_trip() {
+        let head = AnyCar::try_from(calibnet::DEFAULT_GENESIS)
+            .unwrap()
+            .heaviest_tipset()
+            .unwrap()
+            .key()
+            .clone();
+        let index = ShardIndex {
+            network: "calibnet".into(),
+            shards: vec![shard(0, 99, &head), shard(100, 199, &head)],
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(format!("calibnet{SHARD_INDEX_EXTENSION}"));
+        assert!(is_shard_index(&path));
+        index.save(&path).unwrap();
+
+        let loaded = ShardIndex::load(&path).unwrap();
+        assert_eq!(
+            loaded.shards[1].path,
+            dir.path().join(&index.shards[1].path)
+        );
+        assert_eq!(loaded.shards[1].head, head);
+
+        assert_eq!(loaded.shards_for(50).count(), 1);
+        assert_eq!(loaded.shards_for(150).count(), 2);
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Selection of the database backend. [`Db`] dispatches to the backend chosen
+//! in the configuration, all of them implement [`Blockstore`],
+//! [`SettingsStore`], [`GarbageCollectable`] and [`DBStatistics`].
+
+use std::path::{Path, PathBuf};
+
+use super::db_mode::choose_db;
+use super::parity_db::ParityDb;
+use super::parity_db_config::ParityDbConfig;
+use super::redb_db::{RedbConfig, RedbDb};
+use super::{DBStatistics, GarbageCollectable, RemovedRecords, SettingsStore};
+use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
+use ahash::HashSet;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use serde::{Deserialize, Serialize};
+
+/// Database backends available in Forest.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(rename_all = "snake_case")]
+pub enum DbBackend {
+    #[default]
+    ParityDb,
+    Redb,
+}
+
+/// Backend-agnostic database configuration, the `[db]` section. The options of
+/// each backend are in their own section.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct BlockstoreConfig {
+    pub backend: DbBackend,
+    /// Maximum size, in bytes, of the in-memory cache of recently read and
+    /// written blocks. `0` disables the cache.
+    #[cfg_attr(test, arbitrary(gen(|g| u32::arbitrary(g) as _)))]
+    pub block_cache_size: u64,
+}
+
+impl Default for BlockstoreConfig {
+    fn default() -> Self {
+        Self {
+            backend: DbBackend::default(),
+            block_cache_size: crate::db::BlockCache::<()>::DEFAULT_SIZE as u64,
+        }
+    }
+}
+
+/// Configuration of the selected backend.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DbConfig {
+    ParityDb(ParityDbConfig),
+    Redb(RedbConfig),
+}
+
+pub enum Db {
+    ParityDb(ParityDb),
+    Redb(RedbDb),
+}
+
+/// Returns the path to the database directory to be used by the daemon.
+pub fn db_root(chain_data_root: &Path) -> anyhow::Result<PathBuf> {
+    choose_db(chain_data_root)
+}
+
+pub fn open_db(path: PathBuf, config: DbConfig) -> anyhow::Result<Db> {
+    match config {
+        DbConfig::ParityDb(config) => Ok(Db::ParityDb(ParityDb::open(path, &config)?)),
+        DbConfig::Redb(config) => Ok(Db::Redb(RedbDb::open(&path, &config)?)),
+    }
+}
+
+macro_rules! dispatch {
+    ($db:expr, $inner:ident => $body:expr) => {
+        match $db {
+            Db::ParityDb($inner) => $body,
+            Db::Redb($inner) => $body,
+        }
+    };
+}
+
+impl Blockstore for Db {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        dispatch!(self, db => Blockstore::get(db, k))
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        dispatch!(self, db => Blockstore::has(db, k))
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        dispatch!(self, db => db.put_keyed(k, block))
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> anyhow::Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        dispatch!(self, db => db.put_many_keyed(blocks))
+    }
+}
+
+impl SettingsStore for Db {
+    fn read_bin(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        dispatch!(self, db => db.read_bin(key))
+    }
+
+    fn write_bin(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        dispatch!(self, db => db.write_bin(key, value))
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        dispatch!(self, db => db.exists(key))
+    }
+
+    fn setting_keys(&self) -> anyhow::Result<Vec<String>> {
+        dispatch!(self, db => db.setting_keys())
+    }
+}
+
+impl BitswapStoreRead for Db {
+    fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
+        dispatch!(self, db => BitswapStoreRead::contains(db, cid))
+    }
+
+    fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        dispatch!(self, db => BitswapStoreRead::get(db, cid))
+    }
+}
+
+impl BitswapStoreReadWrite for Db {
+    type Params = libipld::DefaultParams;
+
+    fn insert(&self, block: &libipld::Block<Self::Params>) -> anyhow::Result<()> {
+        dispatch!(self, db => db.insert(block))
+    }
+}
+
+impl DBStatistics for Db {
+    fn get_statistics(&self) -> Option<String> {
+        dispatch!(self, db => db.get_statistics())
+    }
+}
+
+impl GarbageCollectable for Db {
+    fn get_keys(&self) -> anyhow::Result<HashSet<u32>> {
+        dispatch!(self, db => db.get_keys())
+    }
+
+    fn remove_keys_with(
+        &self,
+        keys: HashSet<u32>,
+        on_removed: &mut dyn FnMut(u64),
+    ) -> anyhow::Result<RemovedRecords> {
+        dispatch!(self, db => db.remove_keys_with(keys, on_removed))
+    }
+}
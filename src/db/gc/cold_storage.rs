@@ -0,0 +1,99 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Cold storage for the garbage collector. Reachable blocks older than the GC depth, which can no
+//! longer change, are written into an immutable `.forest.car.zst` segment which is then served
+//! read-only, keeping the hot database small while history stays queryable.
+
+use crate::db::car::forest::{Encoder, FOREST_CAR_FILE_EXTENSION};
+use crate::utils::db::car_stream::CarBlock;
+use std::path::{Path, PathBuf};
+use tempfile::TempPath;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::info;
+
+/// Mounts a freshly written segment, e.g. in a [`crate::db::car::ManyCar`].
+pub type MountSegment = Box<dyn Fn(&Path) -> anyhow::Result<()> + Send + Sync>;
+
+/// Destination of the blocks finalized by [`super::MarkAndSweep`].
+pub struct ColdStorage {
+    dir: PathBuf,
+    mount: MountSegment,
+}
+
+/// A segment being written in the background. Dropping it discards the segment.
+pub struct Segment {
+    sender: mpsc::Sender<anyhow::Result<CarBlock>>,
+    writer: JoinHandle<anyhow::Result<TempPath>>,
+    count: usize,
+}
+
+impl Segment {
+    /// Appends `block` to the segment.
+    pub async fn push(&mut self, block: CarBlock) -> anyhow::Result<()> {
+        self.sender
+            .send(Ok(block))
+            .await
+            .map_err(|_| anyhow::anyhow!("cold storage segment writer stopped"))?;
+        self.count += 1;
+        Ok(())
+    }
+}
+
+impl ColdStorage {
+    /// Creates cold storage writing segments into `dir`. Segments are named `cold-*` and use the
+    /// regular `.forest.car.zst` extension, so that they are picked up along with the other
+    /// CAR-backed stores when the node restarts.
+    pub fn new(dir: PathBuf, mount: MountSegment) -> Self {
+        Self { dir, mount }
+    }
+
+    /// Starts writing a new segment into a temporary file, which only becomes a segment once
+    /// [`ColdStorage::finish_segment`] succeeds.
+    pub fn start_segment(&self) -> anyhow::Result<Segment> {
+        let temp_path = tempfile::NamedTempFile::new_in(&self.dir)?.into_temp_path();
+        let (sender, receiver) = mpsc::channel(1024);
+        let writer = tokio::spawn(async move {
+            let mut writer = tokio::io::BufWriter::new(tokio::fs::File::create(&temp_path).await?);
+            let frames = Encoder::compress_stream_default(ReceiverStream::new(receiver));
+            Encoder::write(&mut writer, vec![], frames).await?;
+            writer.shutdown().await?;
+            anyhow::Ok(temp_path)
+        });
+        Ok(Segment {
+            sender,
+            writer,
+            count: 0,
+        })
+    }
+
+    /// Persists and mounts `segment`. The blocks may only be removed from the hot database once
+    /// this succeeds. Empty segments are discarded.
+    pub async fn finish_segment(&self, segment: Segment) -> anyhow::Result<()> {
+        let Segment {
+            sender,
+            writer,
+            count,
+        } = segment;
+        drop(sender);
+        let temp_path = writer.await??;
+        if count == 0 {
+            return Ok(());
+        }
+
+        let segment_path = self.dir.join(format!(
+            "cold-{}{FOREST_CAR_FILE_EXTENSION}",
+            chrono::Utc::now().timestamp_millis()
+        ));
+        temp_path.persist(&segment_path)?;
+        (self.mount)(&segment_path)?;
+        info!(
+            "Moved {count} blocks into cold storage segment {}",
+            segment_path.display()
+        );
+        Ok(())
+    }
+}
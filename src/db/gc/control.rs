@@ -0,0 +1,298 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::metrics;
+use chrono::{DateTime, Utc};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::{watch, MutexGuard, OwnedMutexGuard};
+
+/// Step of the garbage collector, see the [module documentation](super).
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GcPhase {
+    /// Waiting for the next run.
+    #[default]
+    Idle,
+    /// Collecting all the database keys.
+    Mark,
+    /// Waiting for the marked records to become older than `chain finality`.
+    Wait,
+    /// Removing the reachable records from the marked set.
+    Filter,
+    /// Removing the remaining records from the database.
+    Sweep,
+}
+
+impl GcPhase {
+    // Value of the `gc_phase` metric.
+    fn as_metric(self) -> i64 {
+        self as i64
+    }
+}
+
+/// Snapshot of the garbage collector state.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct GcStatus {
+    pub phase: GcPhase,
+    pub paused: bool,
+    /// Records processed in the current phase.
+    pub progress: u64,
+    /// Records marked by the current run.
+    pub marked: u64,
+    /// Records removed by the last completed run.
+    pub swept: u64,
+    /// Bytes removed from the database by the last completed run.
+    pub reclaimed_bytes: u64,
+    /// Completion time of the last run.
+    pub last_run: Option<DateTime<Utc>>,
+}
+
+/// Shared handle exposing the status of a [`super::MarkAndSweep`] and allowing to pause, resume
+/// or abort it.
+#[derive(Debug)]
+pub struct GcControl {
+    status: Mutex<GcStatus>,
+    paused: watch::Sender<bool>,
+    abort: AtomicBool,
+    // Held while sweeping, and by a [`GcPauseGuard`] to keep the sweep from starting.
+    sweep: Arc<tokio::sync::Mutex<()>>,
+}
+
+/// Keeps the garbage collector paused, see [`GcControl::pause_guard`].
+pub struct GcPauseGuard {
+    control: Arc<GcControl>,
+    was_paused: bool,
+    _sweep: OwnedMutexGuard<()>,
+}
+
+impl Drop for GcPauseGuard {
+    fn drop(&mut self) {
+        // A pause requested by the user outlives the guard.
+        if !self.was_paused {
+            self.control.resume();
+        }
+    }
+}
+
+impl Default for GcControl {
+    fn default() -> Self {
+        Self {
+            status: Default::default(),
+            paused: watch::channel(false).0,
+            abort: AtomicBool::new(false),
+            sweep: Default::default(),
+        }
+    }
+}
+
+impl GcControl {
+    pub fn status(&self) -> GcStatus {
+        GcStatus {
+            paused: *self.paused.borrow(),
+            ..self.status.lock().clone()
+        }
+    }
+
+    /// Suspends the garbage collector at its next checkpoint.
+    pub fn pause(&self) {
+        self.paused.send_replace(true);
+        metrics::GC_PAUSED.set(1);
+    }
+
+    pub fn resume(&self) {
+        self.paused.send_replace(false);
+        metrics::GC_PAUSED.set(0);
+    }
+
+    /// Pauses the garbage collector until the returned guard is dropped, e.g. while reading blocks
+    /// it could remove. Waits for an ongoing sweep to complete, which can't be paused.
+    pub async fn pause_guard(self: &Arc<Self>) -> GcPauseGuard {
+        let was_paused = *self.paused.borrow();
+        self.pause();
+        GcPauseGuard {
+            control: Arc::clone(self),
+            was_paused,
+            _sweep: Arc::clone(&self.sweep).lock_owned().await,
+        }
+    }
+
+    /// Cancels the current run, dropping the marked set. Fails if there is no run in progress.
+    pub fn abort(&self) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            self.status.lock().phase != GcPhase::Idle,
+            "no garbage collection in progress"
+        );
+        self.abort.store(true, Ordering::Relaxed);
+        // Wake up a paused garbage collector so that it notices the abort.
+        self.paused.send_modify(|_| {});
+        Ok(())
+    }
+
+    /// Waits while the garbage collector is paused. Returns `false` if the run has been aborted.
+    pub(super) async fn checkpoint(&self) -> bool {
+        if *self.paused.borrow() {
+            let mut paused = self.paused.subscribe();
+            // The sender is owned by `self`, so this can't fail.
+            let _ = paused
+                .wait_for(|paused| !paused || self.abort.load(Ordering::Relaxed))
+                .await;
+        }
+        !self.abort.load(Ordering::Relaxed)
+    }
+
+    /// Completes once the garbage collector is paused or the run is aborted, so that waits can be
+    /// cut short.
+    pub(super) async fn interrupted(&self) {
+        let mut paused = self.paused.subscribe();
+        // The sender is owned by `self`, so this can't fail.
+        let _ = paused
+            .wait_for(|paused| *paused || self.abort.load(Ordering::Relaxed))
+            .await;
+    }
+
+    /// Waits for any [`GcPauseGuard`] to be dropped. The sweep holds the returned guard.
+    pub(super) async fn sweeping(&self) -> MutexGuard<'_, ()> {
+        self.sweep.lock().await
+    }
+
+    pub(super) fn set_phase(&self, phase: GcPhase) {
+        let mut status = self.status.lock();
+        status.phase = phase;
+        status.progress = 0;
+        if phase == GcPhase::Mark {
+            self.abort.store(false, Ordering::Relaxed);
+        }
+        metrics::GC_PHASE.set(phase.as_metric());
+        metrics::GC_PROGRESS.set(0);
+    }
+
+    pub(super) fn add_progress(&self, records: u64) {
+        self.status.lock().progress += records;
+        metrics::GC_PROGRESS.add(records as i64);
+    }
+
+    pub(super) fn set_marked(&self, marked: u64) {
+        self.status.lock().marked = marked;
+        metrics::GC_MARKED_RECORDS.set(marked as i64);
+    }
+
+    /// Records the outcome of a completed run.
+    pub(super) fn finish_run(&self, swept: u64, reclaimed_bytes: u64) {
+        let now = Utc::now();
+        {
+            let mut status = self.status.lock();
+            status.swept = swept;
+            status.reclaimed_bytes = reclaimed_bytes;
+            status.last_run = Some(now);
+        }
+        metrics::GC_SWEPT_RECORDS.inc_by(swept);
+        metrics::GC_RECLAIMED_BYTES.inc_by(reclaimed_bytes);
+        metrics::GC_LAST_RUN.set(now.timestamp());
+        self.set_marked(0);
+        self.set_phase(GcPhase::Idle);
+    }
+
+    /// Resets the state after an aborted run.
+    pub(super) fn abort_run(&self) {
+        self.abort.store(false, Ordering::Relaxed);
+        self.set_marked(0);
+        self.set_phase(GcPhase::Idle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn pause_resume_abort() {
+        let control = Arc::new(GcControl::default());
+        assert!(control.checkpoint().await);
+        assert!(control.abort().is_err());
+
+        control.set_phase(GcPhase::Filter);
+        control.pause();
+        assert!(control.status().paused);
+        let waiting = tokio::spawn({
+            let control = control.clone();
+            async move { control.checkpoint().await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!waiting.is_finished());
+        control.resume();
+        assert!(waiting.await.unwrap());
+
+        control.pause();
+        let waiting = tokio::spawn({
+            let control = control.clone();
+            async move { control.checkpoint().await }
+        });
+        control.abort().unwrap();
+        assert!(!waiting.await.unwrap());
+
+        control.abort_run();
+        assert_eq!(control.status().phase, GcPhase::Idle);
+    }
+
+    #[tokio::test]
+    async fn pause_guard() {
+        let control = Arc::new(GcControl::default());
+        let guard = control.pause_guard().await;
+        assert!(control.status().paused);
+        // A sweep can't start while the guard is held, even if the user resumes the collector.
+        control.resume();
+        let sweeping = tokio::spawn({
+            let control = control.clone();
+            async move { drop(control.sweeping().await) }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!sweeping.is_finished());
+        drop(guard);
+        sweeping.await.unwrap();
+        assert!(!control.status().paused);
+
+        // A pause requested by the user is kept.
+        control.pause();
+        drop(control.pause_guard().await);
+        assert!(control.status().paused);
+
+        // The guard waits for an ongoing sweep.
+        control.resume();
+        let sweep = control.sweeping().await;
+        let guard = tokio::spawn({
+            let control = control.clone();
+            async move { drop(control.pause_guard().await) }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!guard.is_finished());
+        drop(sweep);
+        guard.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn interrupted_by_pause_and_abort() {
+        let control = Arc::new(GcControl::default());
+        let interrupted = tokio::spawn({
+            let control = control.clone();
+            async move { control.interrupted().await }
+        });
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!interrupted.is_finished());
+        control.pause();
+        interrupted.await.unwrap();
+
+        control.resume();
+        control.set_phase(GcPhase::Wait);
+        let interrupted = tokio::spawn({
+            let control = control.clone();
+            async move { control.interrupted().await }
+        });
+        control.abort().unwrap();
+        interrupted.await.unwrap();
+    }
+}
@@ -0,0 +1,94 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use once_cell::sync::Lazy;
+use prometheus::core::{AtomicI64, AtomicU64, GenericCounter, GenericGauge};
+
+pub static GC_PHASE: Lazy<Box<GenericGauge<AtomicI64>>> = Lazy::new(|| {
+    let gc_phase = Box::new(
+        GenericGauge::<AtomicI64>::new(
+            "gc_phase",
+            "Current garbage collector phase: 0 idle, 1 mark, 2 wait, 3 filter, 4 sweep",
+        )
+        .expect("Defining the gc_phase metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(gc_phase.clone())
+        .expect("Registering the gc_phase metric with the metrics registry must succeed");
+    gc_phase
+});
+pub static GC_PAUSED: Lazy<Box<GenericGauge<AtomicI64>>> = Lazy::new(|| {
+    let gc_paused = Box::new(
+        GenericGauge::<AtomicI64>::new("gc_paused", "Whether the garbage collector is paused")
+            .expect("Defining the gc_paused metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(gc_paused.clone())
+        .expect("Registering the gc_paused metric with the metrics registry must succeed");
+    gc_paused
+});
+pub static GC_PROGRESS: Lazy<Box<GenericGauge<AtomicI64>>> = Lazy::new(|| {
+    let gc_progress = Box::new(
+        GenericGauge::<AtomicI64>::new(
+            "gc_progress",
+            "Records processed in the current garbage collector phase",
+        )
+        .expect("Defining the gc_progress metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(gc_progress.clone())
+        .expect("Registering the gc_progress metric with the metrics registry must succeed");
+    gc_progress
+});
+pub static GC_MARKED_RECORDS: Lazy<Box<GenericGauge<AtomicI64>>> = Lazy::new(|| {
+    let gc_marked_records = Box::new(
+        GenericGauge::<AtomicI64>::new(
+            "gc_marked_records",
+            "Records marked by the current garbage collector run",
+        )
+        .expect("Defining the gc_marked_records metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(gc_marked_records.clone())
+        .expect("Registering the gc_marked_records metric with the metrics registry must succeed");
+    gc_marked_records
+});
+pub static GC_SWEPT_RECORDS: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
+    let gc_swept_records = Box::new(
+        GenericCounter::<AtomicU64>::new(
+            "gc_swept_records",
+            "Total records removed by the garbage collector",
+        )
+        .expect("Defining the gc_swept_records metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(gc_swept_records.clone())
+        .expect("Registering the gc_swept_records metric with the metrics registry must succeed");
+    gc_swept_records
+});
+pub static GC_RECLAIMED_BYTES: Lazy<Box<GenericCounter<AtomicU64>>> = Lazy::new(|| {
+    let gc_reclaimed_bytes = Box::new(
+        GenericCounter::<AtomicU64>::new(
+            "gc_reclaimed_bytes",
+            "Total bytes removed from the database by the garbage collector",
+        )
+        .expect("Defining the gc_reclaimed_bytes metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(gc_reclaimed_bytes.clone())
+        .expect("Registering the gc_reclaimed_bytes metric with the metrics registry must succeed");
+    gc_reclaimed_bytes
+});
+pub static GC_LAST_RUN: Lazy<Box<GenericGauge<AtomicI64>>> = Lazy::new(|| {
+    let gc_last_run = Box::new(
+        GenericGauge::<AtomicI64>::new(
+            "gc_last_run",
+            "Unix timestamp of the last completed garbage collector run",
+        )
+        .expect("Defining the gc_last_run metric must succeed"),
+    );
+    prometheus::default_registry()
+        .register(gc_last_run.clone())
+        .expect("Registering the gc_last_run metric with the metrics registry must succeed");
+    gc_last_run
+});
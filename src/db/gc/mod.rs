@@ -17,7 +17,8 @@
 //!
 //! - No `BlockHeader` reachable from HEAD may be garbage collected.
 //! - No data younger than `chain finality` epochs may be garbage collected.
-//! - State-trees older than `depth` epochs should be garbage collected.
+//! - State-trees older than `depth` epochs should be garbage collected, unless the node runs in
+//!   archival mode, in which case all reachable state-trees are retained.
 //! - Not all unreachable data has to be garbage collected. In other words, it's
 //!   acceptable for the garbage collector to be conservative.
 //! - The garbage collector may not prevent access to the database.
@@ -34,6 +35,15 @@
 //! marked set, leaving only unreachable entries that are older than `chain finality`.
 //! 4. Sweep, removing all the remaining marked entries from the database.
 //!
+//! With [`ColdStorage`] configured, the entries which are only reachable from tipsets older than
+//! `depth` epochs are finalized: they are written into an immutable `.forest.car.zst` segment as
+//! they are visited in step 3, which is mounted in the sweep step. They are only removed from the
+//! database once the segment is mounted.
+//!
+//! The reachable graph is traversed through the same store which serves reads, i.e. including the
+//! read-only CAR-backed stores and cold storage segments. Blocks which are only reachable through
+//! blocks that already moved to cold storage are therefore kept as well.
+//!
 //! ## Correctness
 //! This algorithm considers all the blocks that are visited during the `snapshot export` task
 //! reachable, making sure they are kept in the database after the run. It makes sure to retain the
@@ -54,6 +64,7 @@
 //! During the `mark` and up to the `sweep` stage, the algorithm requires `4 bytes` of memory for
 //! each database record. Additionally, the seen cache while traversing the reachable graph
 //! executing the `filter` stage requires at least `32 bytes` of memory for each reachable block.
+//! With cold storage, the finalized set takes another `4 bytes` for each finalized record.
 //! For a typical mainnet snapshot of about 100 GiB that adds up to roughly 2.5 GiB.
 //!
 //! ## Scheduling
@@ -64,39 +75,62 @@
 //! 3. Then, the `sweep` step happens.
 //! 4. Finally, the algorithm waits for a configured amount of time to initiate the next run.
 //!
+//! The interval, the times of day during which the `mark`, `filter` and `sweep` steps may start,
+//! the rate at which blocks are read in the `filter` step and the rate at which records are removed
+//! in the `sweep` step are set by [`GcConfig`]. A run can
+//! be paused, resumed or aborted at any time through [`GcControl`], which also reports its status.
+//! A started sweep runs to completion, [`GcControl::pause_guard`] waits for it.
+//!
 //! ## Performance
 //! The time complexity of mark and sweep steps is `O(n)`. The filter step is currently utilizing a
 //! depth-first search algorithm, with `O(V+E)` complexity, where V is the number of vertices and E
 //! is the number of edges.
 
+mod cold_storage;
+mod control;
+mod metrics;
+mod schedule;
+pub use cold_storage::{ColdStorage, MountSegment};
+pub use control::{GcControl, GcPauseGuard, GcPhase, GcStatus};
+pub use schedule::{GcConfig, TimeWindow};
+
 use crate::blocks::Tipset;
 use crate::chain::ChainEpochDelta;
 
 use crate::db::{truncated_hash, GarbageCollectable};
 use crate::ipld::unordered_stream_graph;
 use crate::shim::clock::ChainEpoch;
+use crate::utils::db::car_stream::CarBlock;
 use ahash::{HashSet, HashSetExt};
-use futures::StreamExt;
+use cold_storage::Segment;
+use futures::{Stream, StreamExt};
 use fvm_ipld_blockstore::Blockstore;
+use human_repr::HumanCount as _;
 use std::mem;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::time;
+use std::time::{Duration, Instant};
+use tokio::time::{self, MissedTickBehavior};
 use tracing::info;
 
 /// [`MarkAndSweep`] is a simple garbage collector implementation that traverses all the database
 /// keys writing them to a [`HashSet`], then filters out those that need to be kept and schedules
 /// the rest for removal.
 ///
-/// Note: The GC does not know anything about the hybrid CAR-backed and ParityDB approach, only
-/// taking care of the latter.
+/// Note: The reachable graph is read through `db`, which may be a hybrid CAR-backed and ParityDB
+/// store such as [`crate::db::car::ManyCar`]. Only the writable part of it is garbage collected.
 pub struct MarkAndSweep<DB> {
     db: Arc<DB>,
     get_heaviest_tipset: Box<dyn Fn() -> Arc<Tipset> + Send>,
     marked: HashSet<u32>,
+    finalized: HashSet<u32>,
     epoch_marked: ChainEpoch,
     depth: ChainEpochDelta,
     block_time: Duration,
+    archival: bool,
+    cold_storage: Option<ColdStorage>,
+    segment: Option<Segment>,
+    config: GcConfig,
+    control: Arc<GcControl>,
 }
 
 impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<DB> {
@@ -104,7 +138,7 @@ impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<D
     ///
     /// # Arguments
     ///
-    /// * `db` - A reference to the database instance.
+    /// * `db` - A reference to the database instance, through which the reachable graph is read.
     /// * `get_heaviest_tipset` - A function that facilitates heaviest tipset retrieval.
     /// * `depth` - The number of state-roots to retain. Should be at least `2 * chain finality`.
     /// * `block_time` - An average block production time.
@@ -119,49 +153,193 @@ impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<D
             get_heaviest_tipset,
             depth,
             marked: HashSet::new(),
+            finalized: HashSet::new(),
             epoch_marked: 0,
             block_time,
+            archival: false,
+            cold_storage: None,
+            segment: None,
+            config: GcConfig::default(),
+            control: Default::default(),
         }
     }
+
+    /// Keeps all the reachable state-trees instead of only the most recent `depth` ones. Unreachable
+    /// data, e.g. from forks, is still collected.
+    pub fn with_archival(mut self, archival: bool) -> Self {
+        self.archival = archival;
+        self
+    }
+
+    /// Moves the reachable blocks older than `depth` epochs into cold storage segments.
+    pub fn with_cold_storage(mut self, cold_storage: Option<ColdStorage>) -> Self {
+        self.cold_storage = cold_storage;
+        self
+    }
+
+    /// Sets the interval, time windows and throttling of the runs.
+    pub fn with_config(mut self, config: GcConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    /// Shares `control` with the garbage collector, to observe and steer it from elsewhere.
+    pub fn with_control(mut self, control: Arc<GcControl>) -> Self {
+        self.control = control;
+        self
+    }
+
     // Populate the initial set with all the available database keys.
     fn populate(&mut self) -> anyhow::Result<()> {
         self.marked = self.db.get_keys()?;
+        self.control.set_marked(self.marked.len() as u64);
         Ok(())
     }
 
-    // Filter out the initial set, leaving only the entries that need to be removed.
+    // Filter out the initial set, leaving only the entries that need to be removed. With cold
+    // storage, the entries only reachable from tipsets older than `depth` epochs are moved to the
+    // finalized set and written into a new segment. Returns `false` if the run has been aborted.
     // NOTE: One concern here is that this is going to consume a lot of CPU.
-    async fn filter(&mut self, tipset: Arc<Tipset>, depth: ChainEpochDelta) -> anyhow::Result<()> {
+    async fn filter(
+        &mut self,
+        tipset: Arc<Tipset>,
+        depth: ChainEpochDelta,
+    ) -> anyhow::Result<bool> {
         // NOTE: We want to keep all the block headers from genesis to heaviest tipset epoch.
-        let mut stream = unordered_stream_graph(
+        // Archival nodes keep the state-trees and messages of all epochs as well.
+        let cutoff = tipset.epoch() - depth;
+        let mut throttle = Throttle::new(self.config.max_blocks_per_second);
+
+        let mut recent = unordered_stream_graph(
             self.db.clone(),
-            (*tipset).clone().chain(self.db.clone()),
-            depth,
+            (*tipset)
+                .clone()
+                .chain(self.db.clone())
+                .take_while(move |tipset| tipset.epoch() > cutoff),
+            cutoff,
         );
+        if !self.unmark(&mut recent, &mut throttle, false).await? {
+            return Ok(false);
+        }
 
+        // Blocks which are also reachable from recent tipsets have been seen already. The limit is
+        // an epoch: non-archival nodes drop the state-trees of the tipsets up to the cutoff.
+        let stateroot_limit = if self.archival { -1 } else { cutoff };
+        let mut finalized = unordered_stream_graph(
+            self.db.clone(),
+            (*tipset)
+                .clone()
+                .chain(self.db.clone())
+                .skip_while(move |tipset| tipset.epoch() > cutoff),
+            stateroot_limit,
+        )
+        .with_seen(recent.into_seen());
+        self.segment = match &self.cold_storage {
+            Some(cold_storage) => Some(cold_storage.start_segment()?),
+            None => None,
+        };
+        let finalize = self.segment.is_some();
+        self.unmark(&mut finalized, &mut throttle, finalize).await
+    }
+
+    // Removes the streamed blocks from the marked set, moving them to the finalized set and the
+    // segment if `finalize` is set. Returns `false` if the run has been aborted.
+    async fn unmark(
+        &mut self,
+        stream: &mut (impl Stream<Item = anyhow::Result<CarBlock>> + Unpin),
+        throttle: &mut Option<Throttle>,
+        finalize: bool,
+    ) -> anyhow::Result<bool> {
         while let Some(block) = stream.next().await {
+            if !self.control.checkpoint().await {
+                return Ok(false);
+            }
             let block = block?;
-            self.marked.remove(&truncated_hash(block.cid.hash()));
+            let key = truncated_hash(block.cid.hash());
+            // Blocks which are not in the database, e.g. those already in cold storage, are not
+            // marked.
+            if self.marked.remove(&key) && finalize {
+                self.finalized.insert(key);
+                if let Some(segment) = &mut self.segment {
+                    segment.push(block).await?;
+                }
+            }
+            self.control.add_progress(1);
+
+            if let Some(throttle) = throttle {
+                throttle.tick().await;
+            }
         }
 
-        anyhow::Ok(())
+        anyhow::Ok(true)
+    }
+
+    // Remove marked keys from the database. With cold storage, the finalized keys are only removed
+    // from the database once the segment holding them is mounted.
+    async fn sweep(&mut self) -> anyhow::Result<()> {
+        // Held until the blocks are removed.
+        let sweep_control = self.control.clone();
+        let _sweeping = sweep_control.sweeping().await;
+        let mut keys = mem::take(&mut self.marked);
+        let finalized = mem::take(&mut self.finalized);
+        if let (Some(cold_storage), Some(segment)) = (&self.cold_storage, self.segment.take()) {
+            cold_storage.finish_segment(segment).await?;
+            keys.extend(finalized);
+        }
+        // The removal blocks, and so does its throttling.
+        let db = self.db.clone();
+        let control = self.control.clone();
+        let mut throttle = SweepThrottle::new(self.config.max_sweep_bytes_per_second);
+        let removed = tokio::task::spawn_blocking(move || {
+            db.remove_keys_with(keys, &mut |bytes| {
+                control.add_progress(1);
+                if let Some(throttle) = &mut throttle {
+                    throttle.removed(bytes);
+                }
+            })
+        })
+        .await??;
+        info!(
+            "GC removed {} records, reclaiming {}",
+            removed.count,
+            removed.bytes.human_count_bytes()
+        );
+        self.control.finish_run(removed.count, removed.bytes);
+        Ok(())
     }
 
-    // Remove marked keys from the database.
-    fn sweep(&mut self) -> anyhow::Result<()> {
-        let marked = mem::take(&mut self.marked);
-        self.db.remove_keys(marked)
+    // Drops the marked and finalized sets and the unfinished segment after the run has been
+    // aborted.
+    fn abort(&mut self) {
+        info!("GC run aborted");
+        self.marked.clear();
+        self.finalized.clear();
+        self.segment = None;
+        self.control.abort_run();
     }
 
-    /// Starts the Garbage Collection loop.
-    ///
-    /// # Arguments
-    ///
-    /// * `interval` - GC Interval to avoid constantly consuming node's resources.
+    // Sleeps until the next allowed time window, if outside of any. Returns `true` if it slept.
+    async fn wait_for_window(config: &GcConfig) -> bool {
+        match config.delay_until_window(chrono::Utc::now().time()) {
+            Some(delay) => {
+                info!(
+                    "GC waits {}s for the next allowed time window",
+                    delay.as_secs()
+                );
+                time::sleep(delay).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Starts the Garbage Collection loop, running every [`GcConfig::interval`] to avoid constantly
+    /// consuming node's resources.
     ///
     /// NOTE: This currently does not take into account the fact that we might be starting the node
     /// using CAR-backed storage with a snapshot, for implementation simplicity.
-    pub async fn gc_loop(&mut self, interval: Duration) -> anyhow::Result<()> {
+    pub async fn gc_loop(&mut self) -> anyhow::Result<()> {
+        let interval = self.config.interval;
         loop {
             self.gc_workflow(interval).await?
         }
@@ -171,11 +349,10 @@ impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<D
     // next step.
     async fn gc_workflow(&mut self, interval: Duration) -> anyhow::Result<()> {
         let depth = self.depth;
-        let tipset = (self.get_heaviest_tipset)();
-        let current_epoch = tipset.epoch();
+        let mut tipset = (self.get_heaviest_tipset)();
         // Don't run the GC if there aren't enough state-roots yet. Sleep and yield to the main loop
         // in order to refresh the heaviest tipset value.
-        if depth > current_epoch {
+        if depth > tipset.epoch() {
             time::sleep(interval).await;
             return anyhow::Ok(());
         }
@@ -184,43 +361,146 @@ impl<DB: Blockstore + GarbageCollectable + Sync + Send + 'static> MarkAndSweep<D
         if self.marked.is_empty() {
             // Make sure we don't run the GC too often.
             time::sleep(interval).await;
+            // Pausing the GC while idle postpones the next run.
+            self.control.checkpoint().await;
+            Self::wait_for_window(&self.config).await;
+            tipset = (self.get_heaviest_tipset)();
 
             info!("populate keys for GC");
+            self.control.set_phase(GcPhase::Mark);
             self.populate()?;
-            self.epoch_marked = current_epoch;
+            self.epoch_marked = tipset.epoch();
+            self.control.set_phase(GcPhase::Wait);
+        } else if !self.control.checkpoint().await {
+            self.abort();
+            return anyhow::Ok(());
         }
 
-        let epochs_since_marked = current_epoch - self.epoch_marked;
+        let epochs_since_marked = tipset.epoch() - self.epoch_marked;
         // Don't proceed with next steps until we advance at least `depth` epochs. Sleep and yield
-        // to the main loop in order to refresh the heaviest tipset value.
+        // to the main loop in order to refresh the heaviest tipset value. Pausing or aborting the
+        // run wakes it up, for the main loop to act on it.
         if epochs_since_marked < depth {
-            time::sleep(self.block_time * (depth - epochs_since_marked) as u32).await;
+            tokio::select! {
+                _ = time::sleep(self.block_time * (depth - epochs_since_marked) as u32) => {}
+                _ = self.control.interrupted() => {}
+            }
+            return anyhow::Ok(());
+        }
+
+        // Yield to the main loop after sleeping in order to refresh the heaviest tipset value.
+        if Self::wait_for_window(&self.config).await {
             return anyhow::Ok(());
         }
 
         info!("filter keys for GC");
-        self.filter(tipset, depth).await?;
+        self.control.set_phase(GcPhase::Filter);
+        if !self.filter(tipset, depth).await? {
+            self.abort();
+            return anyhow::Ok(());
+        }
 
+        // The sweep can't be paused, honor a pause requested during the filter.
+        if !self.control.checkpoint().await {
+            self.abort();
+            return anyhow::Ok(());
+        }
         info!("GC sweep");
-        self.sweep()?;
+        self.control.set_phase(GcPhase::Sweep);
+        self.sweep().await?;
 
         anyhow::Ok(())
     }
 }
+
+// Sleeps whenever the sweep gets ahead of its byte rate, by at least 10ms to stay above the timer
+// resolution.
+struct SweepThrottle {
+    max_bytes_per_second: u64,
+    start: Instant,
+    bytes: u64,
+}
+
+impl SweepThrottle {
+    fn new(max_bytes_per_second: u64) -> Option<Self> {
+        match max_bytes_per_second {
+            0 => None,
+            rate => Some(Self {
+                max_bytes_per_second: rate,
+                start: Instant::now(),
+                bytes: 0,
+            }),
+        }
+    }
+
+    fn removed(&mut self, bytes: u64) {
+        self.bytes += bytes;
+        let due = Duration::from_secs_f64(self.bytes as f64 / self.max_bytes_per_second as f64);
+        if let Some(ahead) = due.checked_sub(self.start.elapsed()) {
+            if ahead >= Duration::from_millis(10) {
+                std::thread::sleep(ahead);
+            }
+        }
+    }
+}
+
+// Waits for a tick every `chunk` blocks, at most every 10ms to stay above the timer resolution.
+struct Throttle {
+    interval: time::Interval,
+    chunk: u64,
+    visited: u64,
+}
+
+impl Throttle {
+    fn new(max_blocks_per_second: u32) -> Option<Self> {
+        match max_blocks_per_second {
+            0 => None,
+            rate => {
+                let chunk = rate.div_ceil(100);
+                let mut interval = time::interval(Duration::from_secs(1) * chunk / rate);
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                Some(Self {
+                    interval,
+                    chunk: chunk as u64,
+                    visited: 0,
+                })
+            }
+        }
+    }
+
+    async fn tick(&mut self) {
+        self.visited += 1;
+        if self.visited % self.chunk == 0 {
+            self.interval.tick().await;
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use crate::blocks::{BlockHeader, Tipset};
     use crate::chain::{ChainEpochDelta, ChainStore};
 
-    use crate::db::{GarbageCollectable, MarkAndSweep, MemoryDB};
+    use super::SweepThrottle;
+    use crate::db::car::{AnyCar, ForestCar, ManyCar, PlainCar};
+    use crate::db::db_engine::{Db, DbBackend};
+    use crate::db::tests::db_utils::db_engine::TempDb;
+    use crate::db::{
+        truncated_hash, BlockCache, ColdStorage, GarbageCollectable, GcControl, GcPhase,
+        MarkAndSweep, MemoryDB, SettingsStore,
+    };
     use crate::message_pool::test_provider::{mock_block, mock_block_with_parents};
     use crate::networks::ChainConfig;
 
+    use crate::utils::db::car_stream::{CarBlock, CarWriter};
     use crate::utils::db::CborStoreExt;
 
     use core::time::Duration;
 
     use crate::shim::clock::ChainEpoch;
+    use ahash::HashSet;
+    use cid::Cid;
+    use futures::StreamExt as _;
     use fvm_ipld_blockstore::Blockstore;
     use std::sync::Arc;
 
@@ -233,7 +513,7 @@ mod test {
         }
     }
 
-    fn run_to_epoch(db: impl Blockstore, cs: &ChainStore<MemoryDB>, epoch: ChainEpoch) {
+    fn run_to_epoch<DB: Blockstore>(db: impl Blockstore, cs: &ChainStore<DB>, epoch: ChainEpoch) {
         let mut heaviest_tipset = cs.heaviest_tipset();
 
         for _ in heaviest_tipset.epoch()..epoch {
@@ -246,14 +526,63 @@ mod test {
         }
     }
 
-    struct GCTester {
-        db: Arc<MemoryDB>,
-        store: Arc<ChainStore<MemoryDB>>,
+    // Like `run_to_epoch`, but every block gets a state root of its own. Returns the state roots,
+    // oldest first.
+    fn run_to_epoch_with_state<DB: Blockstore>(
+        db: impl Blockstore,
+        cs: &ChainStore<DB>,
+        epoch: ChainEpoch,
+    ) -> Vec<Cid> {
+        let mut state_roots = vec![];
+        let mut heaviest_tipset = cs.heaviest_tipset();
+
+        for epoch in heaviest_tipset.epoch() + 1..=epoch {
+            let state_root = db.put_cbor_default(&epoch).unwrap();
+            let parent = heaviest_tipset.min_ticket_block();
+            let block = BlockHeader::builder()
+                .miner_address(*parent.miner_address())
+                .parents(heaviest_tipset.key().clone())
+                .state_root(state_root)
+                .weight(parent.weight() + 1)
+                .epoch(epoch)
+                .build()
+                .unwrap();
+            db.put_cbor_default(&block).unwrap();
+
+            cs.set_heaviest_tipset(Arc::new(Tipset::from(&block)))
+                .unwrap();
+            heaviest_tipset = cs.heaviest_tipset();
+            state_roots.push(state_root);
+        }
+        state_roots
     }
 
-    impl GCTester {
+    trait TestDb: Blockstore + SettingsStore + GarbageCollectable + Send + Sync + 'static {}
+
+    impl<T: Blockstore + SettingsStore + GarbageCollectable + Send + Sync + 'static> TestDb for T {}
+
+    struct GCTester<DB> {
+        db: Arc<DB>,
+        store: Arc<ChainStore<DB>>,
+        _dir: Option<tempfile::TempDir>, // kept for cleaning up during Drop
+    }
+
+    impl GCTester<MemoryDB> {
         fn new() -> Self {
-            let db = Arc::new(MemoryDB::default());
+            Self::with_db(MemoryDB::default(), None)
+        }
+    }
+
+    impl GCTester<Db> {
+        fn with_backend(backend: DbBackend) -> Self {
+            let (db, dir) = TempDb::new(backend).into_parts();
+            Self::with_db(db, Some(dir))
+        }
+    }
+
+    impl<DB: TestDb> GCTester<DB> {
+        fn with_db(db: DB, dir: Option<tempfile::TempDir>) -> Self {
+            let db = Arc::new(db);
             let config = ChainConfig::default();
             let gen_block: BlockHeader = mock_block(1, 1);
             db.put_cbor_default(&gen_block).unwrap();
@@ -261,7 +590,11 @@ mod test {
                 ChainStore::new(db.clone(), db.clone(), Arc::new(config), gen_block).unwrap(),
             );
 
-            GCTester { db, store }
+            GCTester {
+                db,
+                store,
+                _dir: dir,
+            }
         }
 
         fn run_epochs(&self, delta: ChainEpochDelta) {
@@ -270,6 +603,12 @@ mod test {
             run_to_epoch(&self.db, &self.store, epoch);
         }
 
+        fn run_epochs_with_state(&self, delta: ChainEpochDelta) -> Vec<Cid> {
+            let tipset = self.store.heaviest_tipset();
+            let epoch = tipset.epoch() + delta;
+            run_to_epoch_with_state(&self.db, &self.store, epoch)
+        }
+
         fn insert_unreachable(&self, block_number: i64) {
             insert_unreachable(&self.db, block_number as u64);
         }
@@ -434,4 +773,335 @@ mod test {
             current_epoch + 1 + depth * 2
         );
     }
+
+    async fn archival_collects_unreachable_data<DB: TestDb>(new_tester: impl Fn() -> GCTester<DB>) {
+        for archival in [false, true] {
+            let depth = 5;
+            let tester = new_tester();
+            let mut gc = MarkAndSweep::new(
+                tester.db.clone(),
+                tester.get_heaviest_tipset_fn(),
+                depth,
+                ZERO_DURATION,
+            )
+            .with_archival(archival);
+
+            // Most state roots are at epochs above `depth`, so that they are only collected if the
+            // state root limit is an epoch relative to the head.
+            let state_roots = tester.run_epochs_with_state(depth * 3);
+            tester.insert_unreachable(3);
+            let unreachable = mock_block(1, 4).cid().to_owned();
+            // Mark.
+            gc.gc_workflow(ZERO_DURATION).await.unwrap();
+            let recent_state_roots = tester.run_epochs_with_state(depth);
+            // Sweep, the head is exactly `depth` epochs above the last marked state root.
+            gc.gc_workflow(ZERO_DURATION).await.unwrap();
+
+            // Unreachable nodes are removed either way, but the state roots older than `depth`
+            // only survive on archival nodes.
+            assert!(!tester.db.has(&unreachable).unwrap());
+            for state_root in &state_roots {
+                assert_eq!(tester.db.has(state_root).unwrap(), archival);
+            }
+            for state_root in &recent_state_roots {
+                assert!(tester.db.has(state_root).unwrap());
+            }
+        }
+    }
+
+    async fn sweep_evicts_cached_blocks<DB: TestDb>(tester: GCTester<DB>) {
+        let depth = 5;
+        let cached = Arc::new(BlockCache::new(tester.db.clone(), 1 << 20));
+        let mut gc = MarkAndSweep::new(
+            cached.clone(),
+            tester.get_heaviest_tipset_fn(),
+            depth,
+            ZERO_DURATION,
+        );
+
+        tester.run_epochs(depth);
+        let unreachable: BlockHeader = mock_block(1000, 1000);
+        let cid = cached.put_cbor_default(&unreachable).unwrap();
+        assert!(cached.get(&cid).unwrap().is_some());
+        // Mark.
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+        tester.run_epochs(depth);
+        // Sweep.
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+
+        assert!(!tester.db.has(&cid).unwrap());
+        assert!(!cached.has(&cid).unwrap());
+        assert!(cached.get(&cid).unwrap().is_none());
+    }
+
+    async fn aborted_run_keeps_data<DB: TestDb>(tester: GCTester<DB>) {
+        let depth = 5;
+        let control = Arc::new(GcControl::default());
+        let mut gc = MarkAndSweep::new(
+            tester.db.clone(),
+            tester.get_heaviest_tipset_fn(),
+            depth,
+            ZERO_DURATION,
+        )
+        .with_control(control.clone());
+
+        tester.run_epochs(depth);
+        tester.insert_unreachable(3);
+        // Mark.
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+        assert_eq!(control.status().phase, GcPhase::Wait);
+        assert_eq!(control.status().marked, 1 + depth as u64 + 3);
+
+        control.abort().unwrap();
+        tester.run_epochs(depth);
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+
+        assert!(gc.marked.is_empty());
+        assert_eq!(control.status().phase, GcPhase::Idle);
+        assert_eq!(control.status().last_run, None);
+        assert_eq!(
+            tester.db.get_keys().unwrap().len() as i64,
+            // `Genesis block + twice the depth + unreachable nodes.`
+            1 + depth * 2 + 3
+        );
+    }
+
+    #[tokio::test]
+    async fn abort_interrupts_wait() {
+        let depth = 5;
+        let tester = GCTester::new();
+        let control = Arc::new(GcControl::default());
+        let mut gc = MarkAndSweep::new(
+            tester.db.clone(),
+            tester.get_heaviest_tipset_fn(),
+            depth,
+            Duration::from_secs(60 * 60),
+        )
+        .with_control(control.clone());
+
+        tester.run_epochs(depth);
+        tokio::spawn({
+            let control = control.clone();
+            async move {
+                while control.status().phase != GcPhase::Wait {
+                    tokio::time::sleep(Duration::from_millis(1)).await;
+                }
+                control.abort().unwrap();
+            }
+        });
+        // Mark, then wait for `depth` more epochs of an hour each, unless aborted.
+        tokio::time::timeout(Duration::from_secs(10), gc.gc_workflow(ZERO_DURATION))
+            .await
+            .expect("the wait wasn't interrupted")
+            .unwrap();
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+
+        assert!(gc.marked.is_empty());
+        assert_eq!(control.status().phase, GcPhase::Idle);
+    }
+
+    #[test]
+    fn sweep_throttle() {
+        assert!(SweepThrottle::new(0).is_none());
+
+        let start = std::time::Instant::now();
+        let mut throttle = SweepThrottle::new(10_000).unwrap();
+        for _ in 0..10 {
+            throttle.removed(100);
+        }
+        // 1000 bytes at 10000 bytes per second, give or take the 10ms of slack.
+        assert!(start.elapsed() >= Duration::from_millis(90));
+    }
+
+    async fn finalized_data_moved_to_cold_storage<DB: TestDb>(tester: GCTester<DB>) {
+        let depth = 5;
+        let dir = tempfile::tempdir().unwrap();
+        let mounted = Arc::new(parking_lot::Mutex::new(vec![]));
+        let cold_storage = {
+            let mounted = mounted.clone();
+            ColdStorage::new(
+                dir.path().to_owned(),
+                Box::new(move |path| {
+                    mounted.lock().push(path.to_owned());
+                    Ok(())
+                }),
+            )
+        };
+        let mut gc = MarkAndSweep::new(
+            tester.db.clone(),
+            tester.get_heaviest_tipset_fn(),
+            depth,
+            ZERO_DURATION,
+        )
+        .with_archival(true)
+        .with_cold_storage(Some(cold_storage));
+
+        let old_state_roots = tester.run_epochs_with_state(depth);
+        let old_head = *tester.store.heaviest_tipset().min_ticket_block().cid();
+        let unreachable: BlockHeader = mock_block(1000, 1000);
+        tester.db.put_cbor_default(&unreachable).unwrap();
+        // Mark.
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+        let recent_state_roots = tester.run_epochs_with_state(depth * 2);
+        // Sweep.
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+
+        let mounted = mounted.lock();
+        assert_eq!(mounted.len(), 1);
+        let segment = ForestCar::try_from(mounted[0].as_path()).unwrap();
+        // Garbage is deleted.
+        assert!(!tester.db.has(unreachable.cid()).unwrap());
+        assert!(!segment.has(unreachable.cid()).unwrap());
+        // Finalized blocks are moved into the segment.
+        for cid in old_state_roots.iter().chain([&old_head]) {
+            assert!(!tester.db.has(cid).unwrap());
+            assert!(segment.has(cid).unwrap());
+        }
+        // Recent blocks stay in the database.
+        for cid in &recent_state_roots {
+            assert!(tester.db.has(cid).unwrap());
+        }
+    }
+
+    // Moves `cids` from the database of `tester` into a read-only store of `store`, as an earlier
+    // cold storage run would have.
+    async fn move_to_cold_storage<DB: TestDb>(
+        tester: &GCTester<DB>,
+        store: &ManyCar<Arc<DB>>,
+        cids: &[Cid],
+    ) {
+        let blocks = cids
+            .iter()
+            .map(|cid| {
+                Ok(CarBlock {
+                    cid: *cid,
+                    data: tester.db.get(cid).unwrap().unwrap(),
+                })
+            })
+            .collect::<Vec<_>>();
+        let mut car_bytes = vec![];
+        futures::stream::iter(blocks)
+            .forward(CarWriter::new_carv1(vec![cids[0]], &mut car_bytes).unwrap())
+            .await
+            .unwrap();
+        store.read_only(AnyCar::<Vec<u8>>::Memory(PlainCar::new(car_bytes).unwrap()));
+        tester
+            .db
+            .remove_keys(HashSet::from_iter(
+                cids.iter().map(|cid| truncated_hash(cid.hash())),
+            ))
+            .unwrap();
+    }
+
+    async fn keep_blocks_reachable_through_cold_storage<DB: TestDb>(tester: GCTester<DB>) {
+        let depth = 5;
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(ManyCar::new(tester.db.clone()));
+        let cold_storage = {
+            let store = store.clone();
+            ColdStorage::new(
+                dir.path().to_owned(),
+                Box::new(move |path| Ok(store.read_only_files(std::iter::once(path.to_owned()))?)),
+            )
+        };
+        let mut gc = MarkAndSweep::new(
+            store.clone(),
+            tester.get_heaviest_tipset_fn(),
+            depth,
+            ZERO_DURATION,
+        )
+        .with_archival(true)
+        .with_cold_storage(Some(cold_storage));
+
+        // The first header and its state root are in cold storage already, but a block linked from
+        // the state root and the genesis header are still in the database.
+        let genesis = tester.store.heaviest_tipset();
+        let live = tester.db.put_cbor_default(&"live").unwrap();
+        let state_root = tester.db.put_cbor_default(&(1, live)).unwrap();
+        let parent = genesis.min_ticket_block();
+        let header = BlockHeader::builder()
+            .miner_address(*parent.miner_address())
+            .parents(genesis.key().clone())
+            .state_root(state_root)
+            .weight(parent.weight() + 1)
+            .epoch(1)
+            .build()
+            .unwrap();
+        let header_cid = tester.db.put_cbor_default(&header).unwrap();
+        tester
+            .store
+            .set_heaviest_tipset(Arc::new(Tipset::from(&header)))
+            .unwrap();
+        tester.run_epochs_with_state(depth);
+        move_to_cold_storage(&tester, &store, &[header_cid, state_root]).await;
+
+        // Mark.
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+        tester.run_epochs_with_state(depth * 2);
+        // Sweep.
+        gc.gc_workflow(ZERO_DURATION).await.unwrap();
+
+        for cid in [&live, genesis.min_ticket_block().cid()] {
+            assert!(!tester.db.has(cid).unwrap());
+            assert!(store.has(cid).unwrap());
+        }
+    }
+
+    // Runs each of the tests against the in-memory database and against each of the database
+    // backends. The graph walk needs its workers to make progress while the stream is polled,
+    // hence the multi-threaded runtime.
+    macro_rules! backend_tests {
+        ($($test:ident),+ $(,)?) => {
+            $(
+                mod $test {
+                    use super::*;
+
+                    #[tokio::test(flavor = "multi_thread")]
+                    async fn memory() {
+                        $test(GCTester::new()).await
+                    }
+
+                    #[tokio::test(flavor = "multi_thread")]
+                    // Iterating ParityDb columns doesn't give visibility guarantees for the
+                    // latest commits, see https://github.com/paritytech/parity-db/issues/227.
+                    #[ignore]
+                    async fn paritydb() {
+                        $test(GCTester::with_backend(DbBackend::ParityDb)).await
+                    }
+
+                    #[tokio::test(flavor = "multi_thread")]
+                    async fn redb() {
+                        $test(GCTester::with_backend(DbBackend::Redb)).await
+                    }
+                }
+            )+
+        };
+    }
+
+    backend_tests!(
+        sweep_evicts_cached_blocks,
+        aborted_run_keeps_data,
+        finalized_data_moved_to_cold_storage,
+        keep_blocks_reachable_through_cold_storage,
+    );
+
+    mod archival_collects_unreachable_data {
+        use super::*;
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn memory() {
+            archival_collects_unreachable_data(GCTester::new).await
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        #[ignore]
+        async fn paritydb() {
+            archival_collects_unreachable_data(|| GCTester::with_backend(DbBackend::ParityDb)).await
+        }
+
+        #[tokio::test(flavor = "multi_thread")]
+        async fn redb() {
+            archival_collects_unreachable_data(|| GCTester::with_backend(DbBackend::Redb)).await
+        }
+    }
 }
@@ -0,0 +1,165 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use chrono::NaiveTime;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr, DurationSeconds};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Scheduling of the garbage collector runs.
+#[serde_as]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct GcConfig {
+    /// Time to wait between two runs.
+    #[serde_as(as = "DurationSeconds<u64>")]
+    #[cfg_attr(test, arbitrary(gen(
+        |g| Duration::from_secs(u32::arbitrary(g) as u64)
+    )))]
+    pub interval: Duration,
+    /// Times of day (UTC) during which the expensive steps are allowed to run, e.g.
+    /// `"01:00-05:30"`. Windows may wrap around midnight. No windows means no restriction.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    pub windows: Vec<TimeWindow>,
+    /// Maximum number of blocks read per second while traversing the reachable graph. `0` means
+    /// unlimited.
+    pub max_blocks_per_second: u32,
+    /// Maximum number of bytes removed from the database per second in the sweep step. `0` means
+    /// unlimited.
+    #[cfg_attr(test, arbitrary(gen(|g| u32::arbitrary(g) as _)))]
+    pub max_sweep_bytes_per_second: u64,
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(60 * 60 * 10),
+            windows: vec![],
+            max_blocks_per_second: 0,
+            max_sweep_bytes_per_second: 0,
+        }
+    }
+}
+
+impl GcConfig {
+    /// Time to wait until the next allowed window opens, or `None` if the garbage collector may
+    /// run at `now`.
+    pub fn delay_until_window(&self, now: NaiveTime) -> Option<Duration> {
+        if self.windows.is_empty() || self.windows.iter().any(|w| w.contains(now)) {
+            return None;
+        }
+        self.windows
+            .iter()
+            .map(|w| {
+                let delay = w.start - now;
+                if delay < chrono::Duration::zero() {
+                    delay + chrono::Duration::days(1)
+                } else {
+                    delay
+                }
+            })
+            .min()
+            .and_then(|delay| delay.to_std().ok())
+    }
+}
+
+/// A daily time range, `start` inclusive, `end` exclusive.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct TimeWindow {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl TimeWindow {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            // The window wraps around midnight.
+            self.start <= time || time < self.end
+        }
+    }
+}
+
+const TIME_FORMAT: &str = "%H:%M";
+
+impl fmt::Display for TimeWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}",
+            self.start.format(TIME_FORMAT),
+            self.end.format(TIME_FORMAT)
+        )
+    }
+}
+
+impl FromStr for TimeWindow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s
+            .split_once('-')
+            .ok_or_else(|| anyhow::anyhow!("invalid time window {s}, expected HH:MM-HH:MM"))?;
+        Ok(Self {
+            start: NaiveTime::parse_from_str(start.trim(), TIME_FORMAT)?,
+            end: NaiveTime::parse_from_str(end.trim(), TIME_FORMAT)?,
+        })
+    }
+}
+
+#[cfg(test)]
+impl quickcheck::Arbitrary for TimeWindow {
+    fn arbitrary(g: &mut quickcheck::Gen) -> Self {
+        let mut time = || {
+            NaiveTime::from_hms_opt(u32::arbitrary(g) % 24, u32::arbitrary(g) % 60, 0)
+                .expect("valid time")
+        };
+        Self {
+            start: time(),
+            end: time(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    fn time(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, TIME_FORMAT).unwrap()
+    }
+
+    #[quickcheck]
+    fn time_window_round_trip(window: TimeWindow) {
+        assert_eq!(window.to_string().parse::<TimeWindow>().unwrap(), window);
+    }
+
+    #[test]
+    fn time_windows() {
+        let night: TimeWindow = "22:00-04:30".parse().unwrap();
+        assert!(night.contains(time("23:15")));
+        assert!(night.contains(time("01:00")));
+        assert!(!night.contains(time("04:30")));
+        assert!(!night.contains(time("12:00")));
+
+        let config = GcConfig {
+            windows: vec![night, "13:00-14:00".parse().unwrap()],
+            ..Default::default()
+        };
+        assert_eq!(config.delay_until_window(time("02:00")), None);
+        assert_eq!(
+            config.delay_until_window(time("12:30")),
+            Some(Duration::from_secs(30 * 60))
+        );
+        assert_eq!(
+            config.delay_until_window(time("20:00")),
+            Some(Duration::from_secs(2 * 60 * 60))
+        );
+        assert_eq!(GcConfig::default().delay_until_window(time("20:00")), None);
+    }
+}
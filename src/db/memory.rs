@@ -1,7 +1,7 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::db::{truncated_hash, GarbageCollectable};
+use crate::db::{truncated_hash, GarbageCollectable, RemovedRecords};
 use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
 use ahash::{HashMap, HashSet, HashSetExt};
 use cid::Cid;
@@ -27,16 +27,27 @@ impl GarbageCollectable for MemoryDB {
         Ok(set)
     }
 
-    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<()> {
-        let mut db = self.blockchain_db.write();
-        db.retain(|key, _| {
+    fn remove_keys_with(
+        &self,
+        keys: HashSet<u32>,
+        on_removed: &mut dyn FnMut(u64),
+    ) -> anyhow::Result<RemovedRecords> {
+        let mut removed = RemovedRecords::default();
+        let mut sizes = vec![];
+        self.blockchain_db.write().retain(|key, value| {
             let cid = Cid::try_from(key.as_slice());
             match cid {
-                Ok(cid) => !keys.contains(&truncated_hash(cid.hash())),
+                Ok(cid) if keys.contains(&truncated_hash(cid.hash())) => {
+                    removed.count += 1;
+                    removed.bytes += value.len() as u64;
+                    sizes.push(value.len() as u64);
+                    false
+                }
                 _ => true,
             }
         });
-        Ok(())
+        sizes.into_iter().for_each(on_removed);
+        Ok(removed)
     }
 }
 
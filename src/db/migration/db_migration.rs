@@ -1,18 +1,89 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
 
+use anyhow::Context as _;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 use tracing::info;
 
 use crate::{
     db::{
         db_mode::{get_latest_versioned_database, DbMode},
-        migration::migration_map::create_migration_chain,
+        migration::migration_map::{create_migration_chain, StepProgress},
+        redb_db::REDB_FILE,
     },
     utils::version::FOREST_VERSION,
 };
 
+/// Name of the file, in the chain data directory, recording the progress of an ongoing migration.
+const MIGRATION_JOURNAL: &str = "migration.json";
+
+/// Progress of a migration chain. It is written before the first step, whenever a step makes
+/// progress and after each completed one, and removed once the whole chain has succeeded, so that an interrupted migration can be
+/// resumed or rolled back.
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct MigrationJournal {
+    /// Version of the database the migration started from. It is kept until the migration is
+    /// complete.
+    #[serde_as(as = "DisplayFromStr")]
+    source: Version,
+    #[serde_as(as = "DisplayFromStr")]
+    target: Version,
+    /// Versions of the databases successfully migrated to, in order.
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    completed: Vec<Version>,
+    /// Progress of the step being run, if it has done any work.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    in_progress: Option<StepJournal>,
+}
+
+/// Units of work done by an interrupted step, see [`StepProgress`].
+#[serde_as]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct StepJournal {
+    /// Version the step migrates to.
+    #[serde_as(as = "DisplayFromStr")]
+    to: Version,
+    done: Vec<String>,
+}
+
+impl MigrationJournal {
+    fn load(chain_data_path: &Path) -> anyhow::Result<Option<Self>> {
+        let path = chain_data_path.join(MIGRATION_JOURNAL);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let journal = serde_json::from_slice(&std::fs::read(&path)?)
+            .with_context(|| format!("invalid migration journal {}", path.display()))?;
+        Ok(Some(journal))
+    }
+
+    fn save(&self, chain_data_path: &Path) -> anyhow::Result<()> {
+        // Write then rename, so that a crash never leaves a truncated journal behind.
+        let tmp = chain_data_path.join(format!("{MIGRATION_JOURNAL}.tmp"));
+        std::fs::write(&tmp, serde_json::to_vec_pretty(self)?)?;
+        std::fs::rename(tmp, chain_data_path.join(MIGRATION_JOURNAL))?;
+        Ok(())
+    }
+
+    fn remove(chain_data_path: &Path) -> anyhow::Result<()> {
+        std::fs::remove_file(chain_data_path.join(MIGRATION_JOURNAL))?;
+        Ok(())
+    }
+}
+
+/// A step of a migration chain, as reported by [`DbMigration::plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationStep {
+    pub from: Version,
+    pub to: Version,
+}
+
 /// Governs the database migration process. This is the entry point for the migration process.
 pub struct DbMigration {
     /// Root of the chain data directory. This is where all the databases are stored.
@@ -43,10 +114,33 @@ impl DbMigration {
         }
     }
 
+    /// Returns the migration steps that [`DbMigration::migrate`] would run, if any.
+    pub fn plan(&self) -> anyhow::Result<Vec<MigrationStep>> {
+        if !self.is_migration_required()? {
+            return Ok(vec![]);
+        }
+        let latest_db_version = get_latest_versioned_database(&self.chain_data_path)?
+            .unwrap_or_else(|| FOREST_VERSION.clone());
+        Ok(create_migration_chain(&latest_db_version, &FOREST_VERSION)?
+            .iter()
+            .map(|migration| MigrationStep {
+                from: migration.from().clone(),
+                to: migration.to().clone(),
+            })
+            .collect())
+    }
+
+    /// Returns `true` if a migration has been started but not completed.
+    pub fn is_interrupted(&self) -> anyhow::Result<bool> {
+        Ok(MigrationJournal::load(&self.chain_data_path)?.is_some())
+    }
+
     /// Performs a database migration if required. Note that this may take a long time to complete
     /// and may need a lot of disk space (at least twice the size of the current database).
-    /// On a successful migration, the current database will be removed and the new database will
-    /// be used.
+    /// The progress is recorded as each step goes, so that an interrupted migration is resumed
+    /// from where it stopped. The database the migration started from is only removed
+    /// once every step has passed its post-migration checks; until then the migration can be
+    /// abandoned with [`DbMigration::rollback`].
     /// This method is tested via integration tests.
     pub fn migrate(&self) -> anyhow::Result<()> {
         if !self.is_migration_required()? {
@@ -56,19 +150,81 @@ impl DbMigration {
 
         let latest_db_version = get_latest_versioned_database(&self.chain_data_path)?
             .unwrap_or_else(|| FOREST_VERSION.clone());
+        let target_db_version: &Version = &FOREST_VERSION;
 
-        info!(
-            "Migrating database from version {} to {}",
-            latest_db_version, *FOREST_VERSION
-        );
-
-        let target_db_version = &FOREST_VERSION;
+        let mut journal = match MigrationJournal::load(&self.chain_data_path)? {
+            Some(mut journal) => {
+                info!(
+                    "Resuming interrupted migration from version {} at version {}",
+                    journal.source, latest_db_version
+                );
+                journal.target = target_db_version.clone();
+                journal
+            }
+            None => {
+                info!(
+                    "Migrating database from version {} to {}",
+                    latest_db_version, *FOREST_VERSION
+                );
+                MigrationJournal {
+                    source: latest_db_version.clone(),
+                    target: target_db_version.clone(),
+                    completed: vec![],
+                    in_progress: None,
+                }
+            }
+        };
+        journal.save(&self.chain_data_path)?;
 
         let migrations = create_migration_chain(&latest_db_version, target_db_version)?;
+        let total = journal.completed.len() + migrations.len();
 
         for migration in migrations {
-            migration.migrate(&self.chain_data_path)?;
+            let step = journal.completed.len() + 1;
+            info!(
+                "Migration step {step}/{total}: {} to {}",
+                migration.from(),
+                migration.to()
+            );
+            let start = Instant::now();
+            let is_source = *migration.from() == journal.source;
+            // Only an interrupted step towards the same version can be resumed.
+            let done = match journal.in_progress.take() {
+                Some(step) if step.to == *migration.to() => step.done,
+                _ => vec![],
+            };
+            let mut on_done = |done: &[String]| {
+                journal.in_progress = Some(StepJournal {
+                    to: migration.to().clone(),
+                    done: done.to_vec(),
+                });
+                journal.save(&self.chain_data_path)
+            };
+            migration.migrate(
+                &self.chain_data_path,
+                !is_source,
+                &mut StepProgress::new(done, &mut on_done),
+            )?;
+            journal.in_progress = None;
+            journal.completed.push(migration.to().clone());
+            journal.save(&self.chain_data_path)?;
+            info!(
+                "Migration step {step}/{total} done in {}",
+                humantime::format_duration(std::time::Duration::from_secs(
+                    start.elapsed().as_secs()
+                ))
+            );
+        }
+
+        // Every step succeeded, the source database and any intermediate one left behind by a
+        // crash are no longer needed.
+        for version in std::iter::once(&journal.source).chain(&journal.completed) {
+            let db = self.chain_data_path.join(version.to_string());
+            if version != target_db_version && db.exists() {
+                std::fs::remove_dir_all(db)?;
+            }
         }
+        MigrationJournal::remove(&self.chain_data_path)?;
 
         info!(
             "Migration to version {} complete",
@@ -77,6 +233,64 @@ impl DbMigration {
 
         Ok(())
     }
+
+    /// Abandons an interrupted migration: removes the databases it created and leaves the one it
+    /// started from in place. Returns the version of the restored database. Fails if any of the
+    /// databases is in use, e.g. by a running node.
+    pub fn rollback(&self) -> anyhow::Result<Version> {
+        let journal = MigrationJournal::load(&self.chain_data_path)?
+            .context("no interrupted migration to roll back")?;
+
+        for version in std::iter::once(&journal.source).chain(&journal.completed) {
+            let db = self.chain_data_path.join(version.to_string());
+            if db.is_dir() && is_in_use(&db) {
+                anyhow::bail!(
+                    "database {} is in use, stop the node before rolling back the migration",
+                    db.display()
+                );
+            }
+        }
+
+        for version in &journal.completed {
+            let db = self.chain_data_path.join(version.to_string());
+            if db.exists() {
+                info!("Removing migrated database {}", db.display());
+                std::fs::remove_dir_all(db)?;
+            }
+        }
+        for entry in std::fs::read_dir(&self.chain_data_path)? {
+            let path = entry?.path();
+            let is_temporary = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("migration_"));
+            if is_temporary && path.is_dir() {
+                info!("Removing temporary database {}", path.display());
+                std::fs::remove_dir_all(path)?;
+            }
+        }
+        MigrationJournal::remove(&self.chain_data_path)?;
+
+        info!("Rolled back to database version {}", journal.source);
+        Ok(journal.source)
+    }
+}
+
+/// Returns `true` if the database in `db_path` is opened by another process. Both backends hold
+/// an exclusive lock on their database while it is open.
+fn is_in_use(db_path: &Path) -> bool {
+    if db_path.join(REDB_FILE).exists() {
+        matches!(
+            redb::Database::open(db_path.join(REDB_FILE)),
+            Err(redb::DatabaseError::DatabaseAlreadyOpen)
+        )
+    } else {
+        // The lock is taken before the columns are checked, any column layout does.
+        matches!(
+            parity_db::Db::open(&parity_db::Options::with_columns(db_path, 0)),
+            Err(parity_db::Error::Locked(_))
+        )
+    }
 }
 
 #[cfg(test)]
@@ -130,4 +344,43 @@ mod tests {
         std::env::remove_var(FOREST_DB_DEV_MODE);
         assert!(db_migration.is_migration_required().unwrap());
     }
+
+    #[test]
+    fn test_rollback_interrupted_migration() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db_migration = DbMigration::new(temp_dir.path().to_owned());
+        assert!(db_migration.rollback().is_err());
+
+        for dir in ["0.1.0", "0.2.0", "migration_0_2_0_0_3_0", "car_db"] {
+            std::fs::create_dir(temp_dir.path().join(dir)).unwrap();
+        }
+        MigrationJournal {
+            source: Version::new(0, 1, 0),
+            target: Version::new(0, 3, 0),
+            completed: vec![Version::new(0, 2, 0)],
+            in_progress: None,
+        }
+        .save(temp_dir.path())
+        .unwrap();
+        assert!(db_migration.is_interrupted().unwrap());
+
+        // The databases mustn't be swapped under a running node.
+        let db = parity_db::Db::open_or_create(&parity_db::Options::with_columns(
+            &temp_dir.path().join("0.2.0"),
+            1,
+        ))
+        .unwrap();
+        assert!(db_migration.rollback().is_err());
+        assert!(db_migration.is_interrupted().unwrap());
+        drop(db);
+
+        assert_eq!(db_migration.rollback().unwrap(), Version::new(0, 1, 0));
+        assert!(!db_migration.is_interrupted().unwrap());
+        let mut remaining = std::fs::read_dir(temp_dir.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        remaining.sort();
+        assert_eq!(remaining, ["0.1.0", "car_db"]);
+    }
 }
@@ -7,7 +7,9 @@ use std::{
     sync::Arc,
 };
 
+use crate::db::db_engine::DbBackend;
 use crate::db::migration::v0_16_0::Migration0_15_2_0_16_0;
+use crate::db::redb_db::REDB_FILE;
 use anyhow::bail;
 use anyhow::Context as _;
 use itertools::Itertools;
@@ -33,10 +35,53 @@ pub(super) trait MigrationOperation {
     /// Ideally, the migration should use as little of the Forest codebase as possible to avoid
     /// potential issues with the migration code itself and having to update it in the future.
     /// Returns the path to the migrated database (which is not yet validated)
-    fn migrate(&self, chain_data_path: &Path) -> anyhow::Result<PathBuf>;
+    /// Units of work that are done should be reported to `progress`, so that an interrupted
+    /// migration resumes after them rather than starting over.
+    fn migrate(
+        &self,
+        chain_data_path: &Path,
+        progress: &mut StepProgress,
+    ) -> anyhow::Result<PathBuf>;
     /// Performs post-migration checks. This is the place to check if the migration database is
     /// ready to be used by Forest and renamed into a versioned database.
     fn post_checks(&self, chain_data_path: &Path) -> anyhow::Result<()>;
+    /// Database backends the migration can handle. Migrations that rewrite the database only
+    /// know about the ParityDb columns.
+    fn backends(&self) -> &'static [DbBackend] {
+        &[DbBackend::ParityDb]
+    }
+}
+
+/// Progress of a migration step, made of units of work named by the [`MigrationOperation`].
+/// Units reported done are recorded in the migration journal, and skipped when an interrupted
+/// step is resumed.
+pub(super) struct StepProgress<'a> {
+    done: Vec<String>,
+    on_done: &'a mut dyn FnMut(&[String]) -> anyhow::Result<()>,
+}
+
+impl<'a> StepProgress<'a> {
+    pub fn new(
+        done: Vec<String>,
+        on_done: &'a mut dyn FnMut(&[String]) -> anyhow::Result<()>,
+    ) -> Self {
+        Self { done, on_done }
+    }
+
+    /// Returns `true` if the step is resumed after having done some work, which must then be kept.
+    pub fn is_resumed(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn is_done(&self, unit: &str) -> bool {
+        self.done.iter().any(|done| done == unit)
+    }
+
+    /// Records `unit` as done. An error interrupts the step.
+    pub fn done(&mut self, unit: impl Into<String>) -> anyhow::Result<()> {
+        self.done.push(unit.into());
+        (self.on_done)(&self.done)
+    }
 }
 
 /// Migrations map. The key is the starting version and the value is the tuple of the target version
@@ -93,21 +138,38 @@ pub struct Migration {
 }
 
 impl Migration {
-    pub fn migrate(&self, chain_data_path: &Path) -> anyhow::Result<()> {
+    pub fn from(&self) -> &Version {
+        &self.from
+    }
+
+    pub fn to(&self) -> &Version {
+        &self.to
+    }
+
+    /// Runs the migration. The source database is only removed if `remove_source` is set, and
+    /// never before the post-migration checks pass.
+    pub fn migrate(
+        &self,
+        chain_data_path: &Path,
+        remove_source: bool,
+        progress: &mut StepProgress,
+    ) -> anyhow::Result<()> {
         info!(
             "Migrating database from version {} to {}",
             self.from, self.to
         );
 
         self.pre_checks(chain_data_path)?;
-        let migrated_db = self.migrator.migrate(chain_data_path)?;
+        let migrated_db = self.migrator.migrate(chain_data_path, progress)?;
         self.post_checks(chain_data_path)?;
 
         let new_db = chain_data_path.join(format!("{}", self.to));
         std::fs::rename(migrated_db, new_db)?;
 
-        let old_db = chain_data_path.join(format!("{}", self.from));
-        std::fs::remove_dir_all(old_db)?;
+        if remove_source {
+            let old_db = chain_data_path.join(format!("{}", self.from));
+            std::fs::remove_dir_all(old_db)?;
+        }
 
         info!("Database migration complete");
         Ok(())
@@ -130,6 +192,19 @@ impl Migration {
             );
         }
 
+        let backend = if source_db.join(REDB_FILE).exists() {
+            DbBackend::Redb
+        } else {
+            DbBackend::ParityDb
+        };
+        if !self.migrator.backends().contains(&backend) {
+            bail!(
+                "migrating a {backend:?} database from version {} to {} isn't supported",
+                self.from,
+                self.to
+            );
+        }
+
         self.migrator.pre_checks(chain_data_path)
     }
 
@@ -269,7 +344,11 @@ mod tests {
             Ok(())
         }
 
-        fn migrate(&self, _chain_data_path: &Path) -> anyhow::Result<PathBuf> {
+        fn migrate(
+            &self,
+            _chain_data_path: &Path,
+            _progress: &mut StepProgress,
+        ) -> anyhow::Result<PathBuf> {
             Ok("".into())
         }
 
@@ -285,6 +364,42 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_redb_only_migrated_by_void_migrations() {
+        let chain_data_path = TempDir::new().unwrap();
+        let path = chain_data_path.path();
+        let redb = |version: &str| {
+            let dir = path.join(version);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join(REDB_FILE), b"").unwrap();
+        };
+        let migration = |from: Version, to: Version, migrator| Migration { from, to, migrator };
+
+        redb("0.15.2");
+        migration(
+            Version::new(0, 15, 2),
+            Version::new(0, 16, 0),
+            Arc::new(Migration0_15_2_0_16_0::new(
+                Version::new(0, 15, 2),
+                Version::new(0, 16, 0),
+            )),
+        )
+        .pre_checks(path)
+        .unwrap_err();
+
+        redb("0.16.0");
+        migration(
+            Version::new(0, 16, 0),
+            Version::new(0, 16, 1),
+            Arc::new(MigrationVoid::new(
+                Version::new(0, 16, 0),
+                Version::new(0, 16, 1),
+            )),
+        )
+        .pre_checks(path)
+        .unwrap();
+    }
+
     #[test]
     fn test_migration_should_use_shortest_path() {
         let migrations = MigrationsMap::from_iter(
@@ -423,7 +538,11 @@ mod tests {
             Ok(())
         }
 
-        fn migrate(&self, chain_data_path: &Path) -> anyhow::Result<PathBuf> {
+        fn migrate(
+            &self,
+            chain_data_path: &Path,
+            _progress: &mut StepProgress,
+        ) -> anyhow::Result<PathBuf> {
             let temp_db_path = chain_data_path.join(temporary_db_name(&self.from, &self.to));
             fs::create_dir(&temp_db_path).unwrap();
             Ok(temp_db_path)
@@ -461,8 +580,16 @@ mod tests {
         fs::create_dir(temp_dir.path().join("0.1.0")).unwrap();
         assert!(migration.pre_checks(temp_dir.path()).is_ok());
 
-        migration.migrate(temp_dir.path()).unwrap();
+        let mut on_done = |_: &[String]| Ok(());
+        migration
+            .migrate(
+                temp_dir.path(),
+                false,
+                &mut StepProgress::new(vec![], &mut on_done),
+            )
+            .unwrap();
         assert!(temp_dir.path().join("0.2.0").exists());
+        assert!(temp_dir.path().join("0.1.0").exists());
 
         assert!(migration.post_checks(temp_dir.path()).is_err());
         fs::create_dir(temp_dir.path().join("migration_0_1_0_0_2_0")).unwrap();
@@ -7,4 +7,4 @@ mod v0_12_1;
 mod v0_16_0;
 mod void_migration;
 
-pub use db_migration::DbMigration;
+pub use db_migration::{DbMigration, MigrationStep};
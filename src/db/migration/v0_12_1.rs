@@ -14,7 +14,7 @@ use semver::Version;
 use std::path::{Path, PathBuf};
 use tracing::info;
 
-use super::migration_map::MigrationOperation;
+use super::migration_map::{MigrationOperation, StepProgress};
 
 pub(super) struct Migration0_12_1_0_13_0 {
     from: Version,
@@ -29,33 +29,59 @@ impl MigrationOperation for Migration0_12_1_0_13_0 {
         Ok(())
     }
 
-    fn migrate(&self, chain_data_path: &Path) -> anyhow::Result<PathBuf> {
+    fn migrate(
+        &self,
+        chain_data_path: &Path,
+        progress: &mut StepProgress,
+    ) -> anyhow::Result<PathBuf> {
         let source_db = chain_data_path.join(self.from.to_string());
 
         let temp_db_path = chain_data_path.join(temporary_db_name(&self.from, &self.to));
-        if temp_db_path.exists() {
+        if !progress.is_done("copy") {
+            if temp_db_path.exists() {
+                info!(
+                    "removing old temporary database {temp_db_path}",
+                    temp_db_path = temp_db_path.display()
+                );
+                std::fs::remove_dir_all(&temp_db_path)?;
+            }
+
+            // copy the old database to a new directory
             info!(
-                "removing old temporary database {temp_db_path}",
+                "copying old database from {source_db} to {temp_db_path}",
+                source_db = source_db.display(),
                 temp_db_path = temp_db_path.display()
             );
-            std::fs::remove_dir_all(&temp_db_path)?;
+            fs_extra::copy_items(
+                &[source_db.as_path()],
+                temp_db_path.clone(),
+                &CopyOptions::default().copy_inside(true),
+            )?;
+            progress.done("copy")?;
         }
 
-        // copy the old database to a new directory
-        info!(
-            "copying old database from {source_db} to {temp_db_path}",
-            source_db = source_db.display(),
-            temp_db_path = temp_db_path.display()
-        );
-        fs_extra::copy_items(
-            &[source_db.as_path()],
-            temp_db_path.clone(),
-            &CopyOptions::default().copy_inside(true),
-        )?;
+        // because of the rolling db, we have to do the migration for each sub-database...
+        let sub_dbs: Vec<(String, PathBuf)> = temp_db_path
+            .read_dir()?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|entry| entry.is_dir())
+            .filter_map(|entry| {
+                let unit = format!("{}/settings", entry.file_name()?.to_str()?);
+                Some((unit, entry))
+            })
+            .filter(|(unit, _)| !progress.is_done(unit))
+            .collect();
 
         // there are two YAML files that are supposed to be in the `Settings` column now.
-        // We need to add them manually to the database.
+        // We need to add them manually to the database. They are only removed once every
+        // sub-database has been migrated.
         let head_file_path = chain_data_path.join("HEAD");
+        let estimated_records_path = chain_data_path.join("meta.yaml");
+        if sub_dbs.is_empty() {
+            remove_if_exists(&head_file_path)?;
+            remove_if_exists(&estimated_records_path)?;
+            return Ok(temp_db_path);
+        }
         let head = std::fs::read(&head_file_path)?;
 
         // The HEAD type was kept in binary format, so we need to convert it to JSON.
@@ -63,7 +89,6 @@ impl MigrationOperation for Migration0_12_1_0_13_0 {
         let head = serde_json::to_vec(&head)?;
 
         // Estimated records were kept in a YAML file...
-        let estimated_records_path = chain_data_path.join("meta.yaml");
         let estimated_records = std::fs::read_to_string(&estimated_records_path)?;
         let estimated_records = estimated_records
             .split_once(':')
@@ -76,12 +101,7 @@ impl MigrationOperation for Migration0_12_1_0_13_0 {
             .parse::<u64>()?;
         let estimated_records = serde_json::to_vec(&estimated_records)?;
 
-        // because of the rolling db, we have to do the migration for each sub-database...
-        for sub_db in temp_db_path
-            .read_dir()?
-            .filter_map(|entry| Some(entry.ok()?.path()))
-            .filter(|entry| entry.is_dir())
-        {
+        for (unit, sub_db) in sub_dbs {
             let db = paritydb_0_12_1::ParityDb::open(&sub_db)?;
 
             // The `Settings` column is now binary-tree indexed, there is only one entry in this version
@@ -129,6 +149,8 @@ impl MigrationOperation for Migration0_12_1_0_13_0 {
                 Some(estimated_records.clone()),
             )];
             db.db.commit(tx)?;
+            drop(db);
+            progress.done(unit)?;
         }
 
         std::fs::remove_file(head_file_path)?;
@@ -156,6 +178,13 @@ impl MigrationOperation for Migration0_12_1_0_13_0 {
     }
 }
 
+fn remove_if_exists(path: &Path) -> anyhow::Result<()> {
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
 /// Database settings from Forest `v0.12.1`
 mod paritydb_0_12_1 {
     use parity_db::{CompressionType, Db, Options};
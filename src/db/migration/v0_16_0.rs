@@ -5,9 +5,9 @@
 //! We are getting rid of rolling db in favor of mark-and-sweep GC. Therefore the two databases
 //! previously representing node state have to be merged into a new one and removed.
 
-use crate::db::db_engine::Db;
 use crate::db::migration::migration_map::temporary_db_name;
 use crate::db::migration::v0_16_0::paritydb_0_15_1::{DbColumn, ParityDb};
+use crate::db::parity_db::ParityDb as Db;
 use anyhow::Context;
 use cid::multihash::Code::Blake2b256;
 use cid::multihash::MultihashDigest;
@@ -19,7 +19,7 @@ use std::path::{Path, PathBuf};
 use strum::IntoEnumIterator;
 use tracing::info;
 
-use super::migration_map::MigrationOperation;
+use super::migration_map::{MigrationOperation, StepProgress};
 
 pub(super) struct Migration0_15_2_0_16_0 {
     from: Version,
@@ -40,7 +40,11 @@ impl MigrationOperation for Migration0_15_2_0_16_0 {
         Ok(())
     }
 
-    fn migrate(&self, chain_data_path: &Path) -> anyhow::Result<PathBuf> {
+    fn migrate(
+        &self,
+        chain_data_path: &Path,
+        progress: &mut StepProgress,
+    ) -> anyhow::Result<PathBuf> {
         let source_db = chain_data_path.join(self.from.to_string());
 
         let db_paths: Vec<PathBuf> = source_db
@@ -52,7 +56,8 @@ impl MigrationOperation for Migration0_15_2_0_16_0 {
             })
             .collect();
         let temp_db_path = chain_data_path.join(temporary_db_name(&self.from, &self.to));
-        if temp_db_path.exists() {
+        // The temporary database holds the work done before an interruption.
+        if temp_db_path.exists() && !progress.is_resumed() {
             info!(
                 "removing old temporary database {temp_db_path}",
                 temp_db_path = temp_db_path.display()
@@ -65,7 +70,10 @@ impl MigrationOperation for Migration0_15_2_0_16_0 {
 
         // Make sure `car_db` dir exists as it might not be the case when migrating
         // from older versions.
-        if old_car_db_path.is_dir() {
+        if old_car_db_path.is_dir() && !progress.is_done("car_db") {
+            if new_car_db_path.exists() {
+                std::fs::remove_dir_all(&new_car_db_path)?;
+            }
             info!(
                 "copying snapshot from {source_db} to {temp_db_path}",
                 source_db = old_car_db_path.display(),
@@ -77,15 +85,25 @@ impl MigrationOperation for Migration0_15_2_0_16_0 {
                 new_car_db_path.clone(),
                 &CopyOptions::default().copy_inside(true),
             )?;
+            progress.done("car_db")?;
         }
 
-        // open the new database to migrate data from the old one.
-        let new_db = ParityDb::open(&temp_db_path)?;
-
         // because of the rolling db, we have to do the migration for each sub-database...
         for sub_db in &db_paths {
+            let partition = sub_db
+                .file_name()
+                .and_then(|name| name.to_str())
+                .context("invalid RollingDB partition name")?
+                .to_owned();
+            if progress.is_done(&partition) {
+                info!("RollingDB partition {:?} already migrated", sub_db);
+                continue;
+            }
             info!("migrating RollingDB partition {:?}", sub_db);
             let db = ParityDb::open(sub_db)?;
+            // open the new database to migrate data from the old one. Writes are idempotent, a
+            // partition interrupted halfway is simply migrated again.
+            let new_db = ParityDb::open(&temp_db_path)?;
 
             for col in DbColumn::iter() {
                 info!("migrating column {}", col);
@@ -124,9 +142,12 @@ impl MigrationOperation for Migration0_15_2_0_16_0 {
                     }
                 }
             }
+
+            // The writes are only flushed to disk once the database is closed.
+            drop(new_db);
+            progress.done(partition)?;
         }
 
-        drop(new_db);
         Ok(temp_db_path)
     }
 
@@ -213,3 +234,77 @@ mod paritydb_0_15_1 {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use tempfile::TempDir;
+
+    fn put_block(partition: &Path, data: &[u8]) -> Cid {
+        let cid = Cid::new_v1(DAG_CBOR, Blake2b256.digest(data));
+        ParityDb::open(partition)
+            .unwrap()
+            .db
+            .commit_changes([Db::set_operation(
+                DbColumn::GraphDagCborBlake2b256 as u8,
+                cid.to_bytes(),
+                data.to_vec(),
+            )])
+            .unwrap();
+        cid
+    }
+
+    #[test]
+    fn interrupted_migration_is_resumed() {
+        let chain_data_path = TempDir::new().unwrap();
+        let path = chain_data_path.path();
+        let source_db = path.join("0.15.2");
+        std::fs::create_dir_all(source_db.join("car_db")).unwrap();
+        std::fs::write(source_db.join("car_db").join("snapshot"), b"snapshot").unwrap();
+        let first = put_block(&source_db.join("partition_1"), b"first");
+        let second = put_block(&source_db.join("partition_2"), b"second");
+
+        let migration = Migration0_15_2_0_16_0::new(Version::new(0, 15, 2), Version::new(0, 16, 0));
+
+        // Interrupt the migration once the first partition is done.
+        let done = RefCell::new(vec![]);
+        let mut on_done = |units: &[String]| {
+            *done.borrow_mut() = units.to_vec();
+            if units.len() > 1 {
+                anyhow::bail!("interrupted");
+            }
+            Ok(())
+        };
+        migration
+            .migrate(path, &mut StepProgress::new(vec![], &mut on_done))
+            .unwrap_err();
+        let done = done.into_inner();
+        assert_eq!(done.len(), 2);
+        assert_eq!(done[0], "car_db");
+
+        // The work done before the interruption isn't redone.
+        std::fs::remove_dir_all(source_db.join("car_db")).unwrap();
+        std::fs::remove_dir_all(source_db.join(&done[1])).unwrap();
+        let mut on_done = |_: &[String]| Ok(());
+        let temp_db_path = migration
+            .migrate(path, &mut StepProgress::new(done, &mut on_done))
+            .unwrap();
+        migration.post_checks(path).unwrap();
+
+        assert_eq!(
+            std::fs::read(temp_db_path.join("car_db").join("snapshot")).unwrap(),
+            b"snapshot"
+        );
+        let db = ParityDb::open(&temp_db_path).unwrap();
+        for (cid, data) in [(first, b"first".as_slice()), (second, b"second")] {
+            assert_eq!(
+                db.db
+                    .get(DbColumn::GraphDagCborBlake2b256 as u8, &cid.to_bytes())
+                    .unwrap()
+                    .as_deref(),
+                Some(data)
+            );
+        }
+    }
+}
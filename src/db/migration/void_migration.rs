@@ -3,13 +3,14 @@
 
 //! Migration logic from any version that requires no migration logic.
 
+use crate::db::db_engine::DbBackend;
 use crate::db::migration::migration_map::temporary_db_name;
 use fs_extra::dir::CopyOptions;
 use semver::Version;
 use std::path::{Path, PathBuf};
 use tracing::info;
 
-use super::migration_map::MigrationOperation;
+use super::migration_map::{MigrationOperation, StepProgress};
 
 pub(super) struct MigrationVoid {
     from: Version,
@@ -21,10 +22,17 @@ impl MigrationOperation for MigrationVoid {
         Ok(())
     }
 
-    fn migrate(&self, chain_data_path: &Path) -> anyhow::Result<PathBuf> {
+    fn migrate(
+        &self,
+        chain_data_path: &Path,
+        progress: &mut StepProgress,
+    ) -> anyhow::Result<PathBuf> {
         let source_db = chain_data_path.join(self.from.to_string());
 
         let temp_db_path = chain_data_path.join(temporary_db_name(&self.from, &self.to));
+        if progress.is_done("copy") {
+            return Ok(temp_db_path);
+        }
         if temp_db_path.exists() {
             info!(
                 "removing old temporary database {temp_db_path}",
@@ -43,6 +51,7 @@ impl MigrationOperation for MigrationVoid {
             temp_db_path.clone(),
             &CopyOptions::default().copy_inside(true),
         )?;
+        progress.done("copy")?;
 
         Ok(temp_db_path)
     }
@@ -58,6 +67,11 @@ impl MigrationOperation for MigrationVoid {
         Ok(())
     }
 
+    // Copies the database directory as is, whatever the backend.
+    fn backends(&self) -> &'static [DbBackend] {
+        &[DbBackend::ParityDb, DbBackend::Redb]
+    }
+
     fn new(from: Version, to: Version) -> Self
     where
         Self: Sized,
@@ -88,7 +102,10 @@ mod test {
 
         let path = chain_data_path.path();
         migration.pre_checks(path).unwrap();
-        let temp_db_path = migration.migrate(path).unwrap();
+        let mut on_done = |_: &[String]| Ok(());
+        let temp_db_path = migration
+            .migrate(path, &mut StepProgress::new(vec![], &mut on_done))
+            .unwrap();
         migration.post_checks(path).unwrap();
 
         // check that the temporary database directory exists and contains the file with the
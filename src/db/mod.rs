@@ -1,13 +1,18 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+pub mod backup;
+mod block_cache;
+pub use block_cache::BlockCache;
 pub mod car;
+pub mod db_engine;
 mod memory;
 pub mod parity_db;
 pub mod parity_db_config;
+pub mod redb_db;
 
 mod gc;
-pub use gc::MarkAndSweep;
+pub use gc::{ColdStorage, GcConfig, GcControl, GcPhase, GcStatus, MarkAndSweep, TimeWindow};
 pub use memory::MemoryDB;
 mod db_mode;
 pub mod migration;
@@ -121,7 +126,39 @@ pub trait GarbageCollectable {
     /// # Arguments
     ///
     /// * `keys` - A set of keys to be removed from the database.
-    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<()>;
+    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<RemovedRecords> {
+        self.remove_keys_with(keys, &mut |_| {})
+    }
+
+    /// Same as [`GarbageCollectable::remove_keys`], but calls `on_removed` with the size in bytes
+    /// of each removed record, e.g. to throttle the removal. It may block, but isn't called while
+    /// writes to the database are blocked.
+    fn remove_keys_with(
+        &self,
+        keys: HashSet<u32>,
+        on_removed: &mut dyn FnMut(u64),
+    ) -> anyhow::Result<RemovedRecords>;
+}
+
+impl<DB: GarbageCollectable> GarbageCollectable for Arc<DB> {
+    fn get_keys(&self) -> anyhow::Result<HashSet<u32>> {
+        self.as_ref().get_keys()
+    }
+
+    fn remove_keys_with(
+        &self,
+        keys: HashSet<u32>,
+        on_removed: &mut dyn FnMut(u64),
+    ) -> anyhow::Result<RemovedRecords> {
+        self.as_ref().remove_keys_with(keys, on_removed)
+    }
+}
+
+/// Number and total size of the records removed by [`GarbageCollectable::remove_keys`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct RemovedRecords {
+    pub count: u64,
+    pub bytes: u64,
 }
 
 /// A function that converts a [`multihash::MultihashGeneric`] digest into a `u32` representation.
@@ -131,26 +168,9 @@ pub(crate) fn truncated_hash<const S: usize>(hash: &multihash::MultihashGeneric<
     u32::from_le_bytes(digest[0..4].try_into().expect("shouldn't fail"))
 }
 
-pub mod db_engine {
-    use std::path::{Path, PathBuf};
-
-    use super::db_mode::choose_db;
-
-    pub type Db = crate::db::parity_db::ParityDb;
-    pub type DbConfig = crate::db::parity_db_config::ParityDbConfig;
-
-    /// Returns the path to the database directory to be used by the daemon.
-    pub fn db_root(chain_data_root: &Path) -> anyhow::Result<PathBuf> {
-        choose_db(chain_data_root)
-    }
-
-    pub fn open_db(path: PathBuf, config: DbConfig) -> anyhow::Result<Db> {
-        Db::open(path, &config).map_err(Into::into)
-    }
-}
-
 #[cfg(test)]
 mod tests {
+    mod db_engine_test;
     pub mod db_utils;
     mod mem_test;
     mod parity_test;
@@ -8,6 +8,7 @@ use super::SettingsStore;
 
 use crate::db::{
     parity_db_config::ParityDbConfig, truncated_hash, DBStatistics, GarbageCollectable,
+    RemovedRecords,
 };
 use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
 
@@ -309,15 +310,23 @@ impl GarbageCollectable for ParityDb {
         Ok(set)
     }
 
-    fn remove_keys(&self, keys: HashSet<u32>) -> anyhow::Result<()> {
+    fn remove_keys_with(
+        &self,
+        keys: HashSet<u32>,
+        on_removed: &mut dyn FnMut(u64),
+    ) -> anyhow::Result<RemovedRecords> {
+        let mut removed = RemovedRecords::default();
         let mut iter = self.db.iter(DbColumn::GraphFull as u8)?;
-        while let Some((key, _)) = iter.next()? {
+        while let Some((key, value)) = iter.next()? {
             let cid = Cid::try_from(key)?;
 
             if keys.contains(&truncated_hash(cid.hash())) {
                 self.db
                     .commit_changes([Self::dereference_operation(&cid)])
-                    .context("error remove")?
+                    .context("error remove")?;
+                removed.count += 1;
+                removed.bytes += value.len() as u64;
+                on_removed(value.len() as u64);
             }
         }
 
@@ -338,11 +347,14 @@ impl GarbageCollectable for ParityDb {
                         result = res;
                         return false;
                     }
+                    removed.count += 1;
+                    removed.bytes += val.value.len() as u64;
+                    on_removed(val.value.len() as u64);
                 }
                 true
             })?;
 
-        result
+        result.map(|()| removed)
     }
 }
 
@@ -0,0 +1,280 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! [`redb`](https://docs.rs/redb) backend. `redb` is an embedded,
+//! memory-mapped, copy-on-write B-tree store in the spirit of LMDB. All the
+//! blocks go in a single table keyed by CID, and the settings in another.
+
+use super::SettingsStore;
+use crate::db::{truncated_hash, DBStatistics, GarbageCollectable, RemovedRecords};
+use crate::libp2p_bitswap::{BitswapStoreRead, BitswapStoreReadWrite};
+use ahash::{HashSet, HashSetExt};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use redb::{
+    Database, Durability, ReadableTable, ReadableTableMetadata, TableDefinition, WriteTransaction,
+};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use tracing::warn;
+
+const BLOCKS: TableDefinition<&[u8], &[u8]> = TableDefinition::new("blocks");
+const SETTINGS: TableDefinition<&str, &[u8]> = TableDefinition::new("settings");
+
+/// Number of blocks removed per write transaction by the garbage collector.
+const REMOVAL_BATCH_SIZE: usize = 10_000;
+
+/// Name of the database file in the database directory.
+pub const REDB_FILE: &str = "blockstore.redb";
+
+/// `redb` configuration exposed in Forest.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(test, derive(derive_quickcheck_arbitrary::Arbitrary))]
+#[serde(default)]
+pub struct RedbConfig {
+    /// Size, in bytes, of the page cache of `redb`.
+    #[cfg_attr(test, arbitrary(gen(|g| u32::arbitrary(g) as _)))]
+    pub cache_size: u64,
+}
+
+impl Default for RedbConfig {
+    fn default() -> Self {
+        Self {
+            // The `redb` default.
+            cache_size: 1024 * 1024 * 1024,
+        }
+    }
+}
+
+pub struct RedbDb {
+    db: Database,
+}
+
+impl RedbDb {
+    /// Opens or creates the database in the `dir` directory.
+    pub fn open(dir: &Path, config: &RedbConfig) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let db = Database::builder()
+            .set_cache_size(config.cache_size as usize)
+            .create(dir.join(REDB_FILE))?;
+        // Create the tables up front, so that read transactions can always open them.
+        let tx = db.begin_write()?;
+        tx.open_table(BLOCKS)?;
+        tx.open_table(SETTINGS)?;
+        tx.commit()?;
+        Ok(Self { db })
+    }
+
+    // Blocks are content-addressed and can be fetched again, their writes are only made durable
+    // by the next settings write (e.g. the new head), sparing an `fsync` per block.
+    fn write_blocks(
+        &self,
+        f: impl FnOnce(&WriteTransaction) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let mut tx = self.db.begin_write()?;
+        tx.set_durability(Durability::Eventual);
+        f(&tx)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // Iterates over all the blocks, stopping at the first error.
+    fn for_each(&self, mut f: impl FnMut(Cid, &[u8]) -> anyhow::Result<()>) -> anyhow::Result<()> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(BLOCKS)?;
+        for entry in table.iter()? {
+            let (key, value) = entry?;
+            f(Cid::try_from(key.value())?, value.value())?;
+        }
+        Ok(())
+    }
+}
+
+impl SettingsStore for RedbDb {
+    fn read_bin(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(SETTINGS)?;
+        let value = table.get(key)?.map(|value| value.value().to_vec());
+        Ok(value)
+    }
+
+    fn write_bin(&self, key: &str, value: &[u8]) -> anyhow::Result<()> {
+        let tx = self.db.begin_write()?;
+        tx.open_table(SETTINGS)?.insert(key, value)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn exists(&self, key: &str) -> anyhow::Result<bool> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(SETTINGS)?;
+        let exists = table.get(key)?.is_some();
+        Ok(exists)
+    }
+
+    fn setting_keys(&self) -> anyhow::Result<Vec<String>> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(SETTINGS)?;
+        let mut keys = vec![];
+        for entry in table.iter()? {
+            keys.push(entry?.0.value().to_owned());
+        }
+        Ok(keys)
+    }
+}
+
+impl Blockstore for RedbDb {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(BLOCKS)?;
+        let value = table
+            .get(k.to_bytes().as_slice())?
+            .map(|value| value.value().to_vec());
+        Ok(value)
+    }
+
+    fn has(&self, k: &Cid) -> anyhow::Result<bool> {
+        let tx = self.db.begin_read()?;
+        let table = tx.open_table(BLOCKS)?;
+        let exists = table.get(k.to_bytes().as_slice())?.is_some();
+        Ok(exists)
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.write_blocks(|tx| {
+            tx.open_table(BLOCKS)?
+                .insert(k.to_bytes().as_slice(), block)?;
+            Ok(())
+        })
+    }
+
+    fn put_many_keyed<D, I>(&self, blocks: I) -> anyhow::Result<()>
+    where
+        Self: Sized,
+        D: AsRef<[u8]>,
+        I: IntoIterator<Item = (Cid, D)>,
+    {
+        self.write_blocks(|tx| {
+            let mut table = tx.open_table(BLOCKS)?;
+            for (k, block) in blocks {
+                table.insert(k.to_bytes().as_slice(), block.as_ref())?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl BitswapStoreRead for RedbDb {
+    fn contains(&self, cid: &Cid) -> anyhow::Result<bool> {
+        Blockstore::has(self, cid)
+    }
+
+    fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        Blockstore::get(self, cid)
+    }
+}
+
+impl BitswapStoreReadWrite for RedbDb {
+    /// `fvm_ipld_encoding::DAG_CBOR(0x71)` is covered by
+    /// [`libipld::DefaultParams`] under feature `dag-cbor`
+    type Params = libipld::DefaultParams;
+
+    fn insert(&self, block: &libipld::Block<Self::Params>) -> anyhow::Result<()> {
+        self.put_keyed(block.cid(), block.data())
+    }
+}
+
+impl DBStatistics for RedbDb {
+    fn get_statistics(&self) -> Option<String> {
+        let stats = || -> anyhow::Result<String> {
+            let tx = self.db.begin_read()?;
+            let table = tx.open_table(BLOCKS)?;
+            let stats = table.stats()?;
+            Ok(format!(
+                "blocks: {}\ntree height: {}\nleaf pages: {}\nbranch pages: {}\nstored bytes: {}\nmetadata bytes: {}\nfragmented bytes: {}\n",
+                table.len()?,
+                stats.tree_height(),
+                stats.leaf_pages(),
+                stats.branch_pages(),
+                stats.stored_bytes(),
+                stats.metadata_bytes(),
+                stats.fragmented_bytes(),
+            ))
+        };
+        match stats() {
+            Ok(stats) => Some(stats),
+            Err(e) => {
+                warn!("Unable to collect database statistics: {e}");
+                None
+            }
+        }
+    }
+}
+
+impl GarbageCollectable for RedbDb {
+    fn get_keys(&self) -> anyhow::Result<HashSet<u32>> {
+        let mut set = HashSet::new();
+        self.for_each(|cid, _| {
+            set.insert(truncated_hash(cid.hash()));
+            Ok(())
+        })?;
+        Ok(set)
+    }
+
+    fn remove_keys_with(
+        &self,
+        keys: HashSet<u32>,
+        on_removed: &mut dyn FnMut(u64),
+    ) -> anyhow::Result<RemovedRecords> {
+        let mut removed = RemovedRecords::default();
+        let mut doomed = vec![];
+        self.for_each(|cid, value| {
+            if keys.contains(&truncated_hash(cid.hash())) {
+                removed.count += 1;
+                removed.bytes += value.len() as u64;
+                doomed.push((cid.to_bytes(), value.len() as u64));
+            }
+            Ok(())
+        })?;
+
+        // Removals are committed in batches, so that `on_removed` doesn't hold up the writers.
+        for batch in doomed.chunks(REMOVAL_BATCH_SIZE) {
+            let tx = self.db.begin_write()?;
+            {
+                let mut table = tx.open_table(BLOCKS)?;
+                for (key, _) in batch {
+                    table.remove(key.as_slice())?;
+                }
+            }
+            tx.commit()?;
+            for (_, size) in batch {
+                on_removed(*size);
+            }
+        }
+        Ok(removed)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::db::tests::db_utils::redb::TempRedbDb;
+    use crate::utils::db::CborStoreExt as _;
+
+    #[test]
+    fn garbage_collect() {
+        let db = TempRedbDb::new();
+        let kept = db.put_cbor_default(&"kept").unwrap();
+        let removed = db.put_cbor_default(&"removed").unwrap();
+
+        let keys = db.get_keys().unwrap();
+        assert_eq!(keys.len(), 2);
+
+        let doomed = HashSet::from_iter([truncated_hash(removed.hash())]);
+        let stats = db.remove_keys(doomed).unwrap();
+        assert_eq!(stats.count, 1);
+        assert!(db.has(&kept).unwrap());
+        assert!(!db.has(&removed).unwrap());
+        assert!(db.get_statistics().unwrap().contains("blocks: 1"));
+    }
+}
@@ -0,0 +1,65 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use super::{db_utils::db_engine::TempDb, subtests};
+use crate::db::db_engine::DbBackend;
+
+/// Runs all the subtests against each of the given backends, through [`crate::db::db_engine::Db`].
+/// The attributes after a backend apply to its garbage collection test.
+macro_rules! backend_tests {
+    ($($name:ident => $backend:expr $(, #[$gc_attr:meta])*);+ $(;)?) => {
+        $(
+            mod $name {
+                use super::*;
+
+                #[test]
+                fn db_write() {
+                    subtests::write_bin(&*TempDb::new($backend));
+                }
+
+                #[test]
+                fn db_read() {
+                    subtests::read_bin(&*TempDb::new($backend));
+                }
+
+                #[test]
+                fn db_exists() {
+                    subtests::exists(&*TempDb::new($backend));
+                }
+
+                #[test]
+                fn db_does_not_exist() {
+                    subtests::does_not_exist(&*TempDb::new($backend));
+                }
+
+                #[test]
+                fn db_write_read_obj() {
+                    subtests::write_read_obj(&*TempDb::new($backend));
+                }
+
+                #[test]
+                fn db_blockstore() {
+                    subtests::blockstore(&*TempDb::new($backend));
+                }
+
+                #[test]
+                $(#[$gc_attr])*
+                fn db_garbage_collectable() {
+                    subtests::garbage_collectable(&*TempDb::new($backend));
+                }
+
+                #[test]
+                fn db_statistics() {
+                    subtests::statistics(&*TempDb::new($backend));
+                }
+            }
+        )+
+    };
+}
+
+backend_tests!(
+    // Iterating ParityDb columns doesn't give visibility guarantees for the latest commits, see
+    // https://github.com/paritytech/parity-db/issues/227.
+    paritydb => DbBackend::ParityDb, #[ignore];
+    redb => DbBackend::Redb;
+);
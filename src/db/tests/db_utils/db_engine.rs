@@ -0,0 +1,47 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::ops::Deref;
+
+use crate::db::{
+    db_engine::{open_db, Db, DbBackend, DbConfig},
+    parity_db_config::ParityDbConfig,
+    redb_db::RedbConfig,
+};
+
+/// Temporary, self-cleaning database of any backend
+pub struct TempDb {
+    db: Db,
+    _dir: tempfile::TempDir, // kept for cleaning up during Drop
+}
+
+impl TempDb {
+    /// Creates a new DB in a temporary path that gets wiped out when the
+    /// variable gets out of scope.
+    pub fn new(backend: DbBackend) -> TempDb {
+        let dir = tempfile::Builder::new()
+            .tempdir()
+            .expect("Failed to create temporary path for db.");
+        let config = match backend {
+            DbBackend::ParityDb => DbConfig::ParityDb(ParityDbConfig {
+                enable_statistics: true,
+            }),
+            DbBackend::Redb => DbConfig::Redb(RedbConfig::default()),
+        };
+        let db = open_db(dir.path().join("db"), config).unwrap();
+        TempDb { db, _dir: dir }
+    }
+
+    /// Splits the database from the directory that has to outlive it.
+    pub fn into_parts(self) -> (Db, tempfile::TempDir) {
+        (self.db, self._dir)
+    }
+}
+
+impl Deref for TempDb {
+    type Target = Db;
+
+    fn deref(&self) -> &Self::Target {
+        &self.db
+    }
+}
@@ -1,4 +1,6 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+pub(in crate::db) mod db_engine;
 pub(in crate::db) mod parity;
+pub(in crate::db) mod redb;
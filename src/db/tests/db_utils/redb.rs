@@ -0,0 +1,32 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::ops::Deref;
+
+use crate::db::redb_db::{RedbConfig, RedbDb};
+
+/// Temporary, self-cleaning `redb` database
+pub struct TempRedbDb {
+    db: RedbDb,
+    _dir: tempfile::TempDir, // kept for cleaning up during Drop
+}
+
+impl TempRedbDb {
+    /// Creates a new DB in a temporary path that gets wiped out when the
+    /// variable gets out of scope.
+    pub fn new() -> TempRedbDb {
+        let dir = tempfile::Builder::new()
+            .tempdir()
+            .expect("Failed to create temporary path for db.");
+        let db = RedbDb::open(dir.path(), &RedbConfig::default()).unwrap();
+        TempRedbDb { db, _dir: dir }
+    }
+}
+
+impl Deref for TempRedbDb {
+    type Target = RedbDb;
+
+    fn deref(&self) -> &Self::Target {
+        &self.db
+    }
+}
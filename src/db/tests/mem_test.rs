@@ -34,3 +34,15 @@ fn mem_write_read_obj() {
     let db = MemoryDB::default();
     subtests::write_read_obj(&db);
 }
+
+#[test]
+fn mem_db_blockstore() {
+    let db = MemoryDB::default();
+    subtests::blockstore(&db);
+}
+
+#[test]
+fn mem_db_garbage_collectable() {
+    let db = MemoryDB::default();
+    subtests::garbage_collectable(&db);
+}
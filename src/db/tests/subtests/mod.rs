@@ -1,7 +1,14 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use crate::db::{SettingsStore, SettingsStoreExt};
+use crate::db::{
+    truncated_hash, DBStatistics, GarbageCollectable, SettingsStore, SettingsStoreExt,
+};
+use crate::utils::cid::CidCborExt as _;
+use crate::utils::db::CborStoreExt as _;
+use ahash::HashSet;
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
 
 pub fn write_bin<DB>(db: &DB)
 where
@@ -64,3 +71,61 @@ where
     assert!(db.read_obj::<i32>(key).unwrap().is_none());
     assert!(db.require_obj::<i32>(key).is_err());
 }
+
+pub fn blockstore<DB>(db: &DB)
+where
+    DB: Blockstore,
+{
+    let cid = db.put_cbor_default(&"Nyarlathotep").unwrap();
+    assert!(db.has(&cid).unwrap());
+    let block = db.get(&cid).unwrap().unwrap();
+
+    // Writing the same block again is a no-op.
+    db.put_keyed(&cid, &block).unwrap();
+    assert_eq!(db.get(&cid).unwrap().unwrap(), block);
+
+    let blocks = ["Yog-Sothoth", "Shub-Niggurath"].map(|name| {
+        (
+            Cid::from_cbor_blake2b256(&name).unwrap(),
+            fvm_ipld_encoding::to_vec(&name).unwrap(),
+        )
+    });
+    db.put_many_keyed(blocks.clone()).unwrap();
+    for (cid, block) in blocks {
+        assert_eq!(db.get(&cid).unwrap().unwrap(), block);
+    }
+
+    let missing = Cid::from_cbor_blake2b256(&"Azathoth").unwrap();
+    assert!(!db.has(&missing).unwrap());
+    assert!(db.get(&missing).unwrap().is_none());
+}
+
+pub fn garbage_collectable<DB>(db: &DB)
+where
+    DB: Blockstore + GarbageCollectable,
+{
+    let kept = db.put_cbor_default(&"kept").unwrap();
+    let removed = db.put_cbor_default(&"removed").unwrap();
+
+    let keys = db.get_keys().unwrap();
+    assert_eq!(
+        keys,
+        HashSet::from_iter([truncated_hash(kept.hash()), truncated_hash(removed.hash())])
+    );
+
+    let stats = db
+        .remove_keys(HashSet::from_iter([truncated_hash(removed.hash())]))
+        .unwrap();
+    assert_eq!(stats.count, 1);
+    assert!(db.has(&kept).unwrap());
+    assert!(!db.has(&removed).unwrap());
+    assert_eq!(db.get_keys().unwrap().len(), 1);
+}
+
+pub fn statistics<DB>(db: &DB)
+where
+    DB: Blockstore + DBStatistics,
+{
+    db.put_cbor_default(&"Dagon").unwrap();
+    assert!(db.get_statistics().is_some_and(|stats| !stats.is_empty()));
+}
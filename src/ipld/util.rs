@@ -18,6 +18,7 @@ use kanal::{Receiver, Sender};
 use parking_lot::Mutex;
 use pin_project_lite::pin_project;
 use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::task::{Context, Poll};
 use tokio::task;
 use tokio::task::{JoinHandle, JoinSet};
@@ -359,6 +360,8 @@ pin_project! {
         worker_handle: JoinHandle<anyhow::Result<()>>,
         block_receiver: kanal::Receiver<anyhow::Result<CarBlock>>,
         extract_sender: kanal::Sender<Cid>,
+        // Number of CIDs sent to the workers whose graph hasn't been fully walked yet.
+        in_flight: Arc<AtomicUsize>,
         stateroot_limit: ChainEpoch,
         queue: Vec<Cid>,
         fail_on_dead_links: bool,
@@ -366,6 +369,13 @@ pin_project! {
 }
 
 impl<DB, T> UnorderedChainStream<DB, T> {
+    /// Skips the blocks in `seen`, e.g. those streamed by another traversal. Must be called
+    /// before the stream is polled.
+    pub fn with_seen(self, seen: CidHashSet) -> Self {
+        *self.seen.lock() = seen;
+        self
+    }
+
     pub fn into_seen(self) -> CidHashSet {
         match Arc::try_unwrap(self.seen) {
             Ok(v) => v.into_inner(),
@@ -401,11 +411,13 @@ pub fn unordered_stream_chain<
     let (extract_sender, extract_receiver) = kanal::unbounded();
     let fail_on_dead_links = true;
     let seen = Arc::new(Mutex::new(CidHashSet::default()));
+    let in_flight = Arc::new(AtomicUsize::new(0));
     let handle = UnorderedChainStream::<DB, T>::start_workers(
         db.clone(),
         sender.clone(),
         extract_receiver,
         seen.clone(),
+        in_flight.clone(),
         fail_on_dead_links,
     );
 
@@ -416,6 +428,7 @@ pub fn unordered_stream_chain<
         block_receiver: receiver,
         queue: Vec::new(),
         extract_sender,
+        in_flight,
         tipset_iter,
         stateroot_limit,
         fail_on_dead_links,
@@ -436,11 +449,13 @@ pub fn unordered_stream_graph<
     let (extract_sender, extract_receiver) = kanal::unbounded();
     let fail_on_dead_links = false;
     let seen = Arc::new(Mutex::new(CidHashSet::default()));
+    let in_flight = Arc::new(AtomicUsize::new(0));
     let handle = UnorderedChainStream::<DB, T>::start_workers(
         db.clone(),
         sender.clone(),
         extract_receiver,
         seen.clone(),
+        in_flight.clone(),
         fail_on_dead_links,
     );
 
@@ -452,6 +467,7 @@ pub fn unordered_stream_graph<
         queue: Vec::new(),
         tipset_iter,
         extract_sender,
+        in_flight,
         stateroot_limit,
         fail_on_dead_links,
     }
@@ -465,6 +481,7 @@ impl<DB: Blockstore + Send + Sync + 'static, T: Iterator<Item = Tipset> + Unpin>
         block_sender: Sender<anyhow::Result<CarBlock>>,
         extract_receiver: Receiver<Cid>,
         seen: Arc<Mutex<CidHashSet>>,
+        in_flight: Arc<AtomicUsize>,
         fail_on_dead_links: bool,
     ) -> JoinHandle<anyhow::Result<()>> {
         task::spawn(async move {
@@ -476,27 +493,35 @@ impl<DB: Blockstore + Send + Sync + 'static, T: Iterator<Item = Tipset> + Unpin>
                     extract_receiver.clone().to_async();
                 let db = db.clone();
                 let block_sender = block_sender.clone();
+                let in_flight = in_flight.clone();
                 handles.spawn(async move {
                     while let Ok(cid) = extract_receiver.recv().await {
-                        let mut cid_vec = vec![cid];
-                        while let Some(cid) = cid_vec.pop() {
-                            if should_save_block_to_snapshot(cid) && seen.lock().insert(cid) {
-                                if let Some(data) = db.get(&cid)? {
-                                    if cid.codec() == fvm_ipld_encoding::DAG_CBOR {
-                                        let mut new_values = extract_cids(&data)?;
-                                        cid_vec.append(&mut new_values);
+                        let walk = || {
+                            let mut cid_vec = vec![cid];
+                            while let Some(cid) = cid_vec.pop() {
+                                if should_save_block_to_snapshot(cid) && seen.lock().insert(cid) {
+                                    if let Some(data) = db.get(&cid)? {
+                                        if cid.codec() == fvm_ipld_encoding::DAG_CBOR {
+                                            let mut new_values = extract_cids(&data)?;
+                                            cid_vec.append(&mut new_values);
+                                        }
+                                        block_sender
+                                            .send(Ok(CarBlock { cid, data }))
+                                            .expect("unreachable");
+                                    } else if fail_on_dead_links {
+                                        block_sender
+                                            .send(Err(anyhow::anyhow!("missing key: {}", cid)))
+                                            .expect("unreachable");
+                                        break;
                                     }
-                                    block_sender
-                                        .send(Ok(CarBlock { cid, data }))
-                                        .expect("unreachable");
-                                } else if fail_on_dead_links {
-                                    block_sender
-                                        .send(Err(anyhow::anyhow!("missing key: {}", cid)))
-                                        .expect("unreachable");
-                                    break;
                                 }
                             }
-                        }
+                            anyhow::Ok(())
+                        };
+                        let result = walk();
+                        // All the blocks reachable from `cid` have been sent by now.
+                        in_flight.fetch_sub(1, Ordering::SeqCst);
+                        result?;
                     }
                     anyhow::Ok(())
                 });
@@ -564,6 +589,7 @@ impl<DB: Blockstore + Send + Sync + 'static, T: Iterator<Item = Tipset> + Unpin>
                             && should_save_block_to_snapshot(*block.messages())
                         {
                             if this.db.has(block.messages())? {
+                                this.in_flight.fetch_add(1, Ordering::SeqCst);
                                 this.extract_sender.send(*block.messages())?;
                                 // This will simply return an error once we reach that item in
                                 // the queue.
@@ -582,6 +608,7 @@ impl<DB: Blockstore + Send + Sync + 'static, T: Iterator<Item = Tipset> + Unpin>
                             && should_save_block_to_snapshot(*block.state_root())
                         {
                             if this.db.has(block.state_root())? {
+                                this.in_flight.fetch_add(1, Ordering::SeqCst);
                                 this.extract_sender.send(*block.state_root())?;
                                 // This will simply return an error once we reach that item in
                                 // the queue.
@@ -599,8 +626,9 @@ impl<DB: Blockstore + Send + Sync + 'static, T: Iterator<Item = Tipset> + Unpin>
                 if let Some(item) = item {
                     return Poll::Ready(Some(item));
                 }
-                // Close the sender when it's empty and exit.
-                if this.extract_sender.is_empty() && this.block_receiver.is_empty() {
+                // Close the sender when all the graphs have been walked and exit. Workers send
+                // their blocks before they're done with a graph, so none can be missed.
+                if this.in_flight.load(Ordering::SeqCst) == 0 && this.block_receiver.is_empty() {
                     this.worker_handle.abort();
                     return Poll::Ready(None);
                 }
@@ -25,6 +25,7 @@ use crate::libp2p::{
     discovery::{DiscoveryBehaviour, DiscoveryConfig},
     gossip_params::{build_peer_score_params, build_peer_score_threshold},
     hello::HelloBehaviour,
+    service::BITSWAP_PROTOCOLS,
 };
 
 use super::discovery::{DerivedDiscoveryBehaviourEvent, DiscoveryEvent};
@@ -86,15 +87,7 @@ impl ForestBehaviour {
             )
             .unwrap();
 
-        let bitswap = BitswapBehaviour::new(
-            &[
-                "/chain/ipfs/bitswap/1.2.0",
-                "/chain/ipfs/bitswap/1.1.0",
-                "/chain/ipfs/bitswap/1.0.0",
-                "/chain/ipfs/bitswap",
-            ],
-            Default::default(),
-        );
+        let bitswap = BitswapBehaviour::new(BITSWAP_PROTOCOLS, Default::default());
         if let Err(err) = crate::libp2p_bitswap::register_metrics(prometheus::default_registry()) {
             warn!("Fail to register prometheus metrics for libp2p_bitswap: {err}");
         }
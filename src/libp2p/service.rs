@@ -837,6 +837,14 @@ async fn emit_event(sender: &Sender<NetworkEvent>, event: NetworkEvent) {
 ///
 /// As a reference `lotus` uses the default `go-libp2p` transport builder which
 /// has all above protocols enabled.
+/// Protocols of the `bitswap` flavour spoken on the Filecoin network.
+pub const BITSWAP_PROTOCOLS: &[&str] = &[
+    "/chain/ipfs/bitswap/1.2.0",
+    "/chain/ipfs/bitswap/1.1.0",
+    "/chain/ipfs/bitswap/1.0.0",
+    "/chain/ipfs/bitswap",
+];
+
 pub fn build_transport(local_key: Keypair) -> anyhow::Result<Boxed<(PeerId, StreamMuxerBox)>> {
     let build_tcp = || libp2p::tcp::tokio::Transport::new(libp2p::tcp::Config::new().nodelay(true));
     let build_dns_tcp = || libp2p::dns::tokio::Transport::system(build_tcp());
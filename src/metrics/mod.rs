@@ -126,4 +126,6 @@ pub mod values {
     pub const TIPSET: &str = "tipset";
     /// tipset cache in state manager
     pub const STATE_MANAGER_TIPSET: &str = "sm_tipset";
+    /// [`crate::db::BlockCache`].
+    pub const BLOCK: &str = "block";
 }
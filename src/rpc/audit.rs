@@ -0,0 +1,322 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Optional RPC audit log and request/response capture. Both are written as
+//! JSON lines; a capture file can be replayed with `forest-tool api replay`.
+//!
+//! Lines are written by a background thread, so RPC handlers never block on
+//! file IO. Entries are dropped, with a warning, when the writer falls behind.
+//! Both files are only readable by their owner. Calls that may carry secrets
+//! (wallet methods, and methods requiring admin or sign permissions) are never
+//! captured.
+
+use std::{
+    fs::OpenOptions,
+    io::{LineWriter, Write as _},
+    net::SocketAddr,
+    os::unix::fs::OpenOptionsExt as _,
+    path::Path,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use axum::extract::connect_info::Connected;
+use http::HeaderValue;
+use hyper::server::conn::AddrStream;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use tokio::net::{TcpStream, UnixStream};
+use tokio_rustls::server::TlsStream;
+use tracing::warn;
+
+use crate::rpc_api::{Access, ACCESS_MAP};
+
+/// Address of the peer that made an RPC call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallerAddress(pub String);
+
+impl Connected<&AddrStream> for CallerAddress {
+    fn connect_info(target: &AddrStream) -> Self {
+        Self(target.remote_addr().to_string())
+    }
+}
+
+impl Connected<&TlsStream<TcpStream>> for CallerAddress {
+    fn connect_info(target: &TlsStream<TcpStream>) -> Self {
+        Self(
+            target
+                .get_ref()
+                .0
+                .peer_addr()
+                .as_ref()
+                .map_or_else(ToString::to_string, SocketAddr::to_string),
+        )
+    }
+}
+
+impl Connected<&UnixStream> for CallerAddress {
+    fn connect_info(_target: &UnixStream) -> Self {
+        Self("unix".into())
+    }
+}
+
+/// Outcome of an RPC call as recorded in the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CallStatus {
+    Ok,
+    /// The method returned a JSON-RPC error.
+    Error {
+        code: i64,
+    },
+    /// The call was rejected before reaching the method, e.g. due to
+    /// insufficient permissions.
+    Denied {
+        http_status: u16,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: String,
+    pub method: String,
+    pub token_id: Option<String>,
+    pub caller: String,
+    #[serde(flatten)]
+    pub status: CallStatus,
+    pub duration_ms: f64,
+}
+
+/// A request together with the response that was sent back to the caller.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CapturedCall {
+    pub timestamp: String,
+    pub request: serde_json::Value,
+    pub response: serde_json::Value,
+}
+
+/// Sinks for the audit log and the capture file. Either may be disabled.
+#[derive(Default)]
+pub struct RpcAudit {
+    audit_log: Option<LineSink>,
+    capture: Option<LineSink>,
+}
+
+impl RpcAudit {
+    pub fn new(audit_log: Option<&Path>, capture: Option<&Path>) -> anyhow::Result<Self> {
+        Ok(Self {
+            audit_log: audit_log.map(LineSink::open).transpose()?,
+            capture: capture.map(LineSink::open).transpose()?,
+        })
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capture.is_some()
+    }
+
+    pub fn record(
+        &self,
+        method: &str,
+        token_id: Option<&str>,
+        caller: &CallerAddress,
+        status: CallStatus,
+        elapsed: Duration,
+    ) {
+        if let Some(audit_log) = &self.audit_log {
+            let record = AuditRecord {
+                timestamp: now(),
+                method: method.to_owned(),
+                token_id: token_id.map(str::to_owned),
+                caller: caller.0.clone(),
+                status,
+                duration_ms: elapsed.as_secs_f64() * 1000.0,
+            };
+            audit_log.send(&record);
+        }
+    }
+
+    /// Captures a call, unless `method` may carry secrets such as private
+    /// keys or signing payloads.
+    pub fn capture(&self, method: &str, request: &str, response: &str) {
+        if let Some(capture) = &self.capture {
+            if may_carry_secrets(method) {
+                return;
+            }
+            let (request, response) = match (
+                serde_json::from_str(request),
+                serde_json::from_str(response),
+            ) {
+                (Ok(request), Ok(response)) => (request, response),
+                (Err(e), _) | (_, Err(e)) => {
+                    warn!("Failed to capture RPC call: {e}");
+                    return;
+                }
+            };
+            let call = CapturedCall {
+                timestamp: now(),
+                request,
+                response,
+            };
+            capture.send(&call);
+        }
+    }
+}
+
+/// Methods of the wallet namespace and methods requiring admin or sign
+/// permissions may carry secrets, so their parameters are neither captured nor
+/// logged. Unknown methods fail before reaching any handler.
+pub fn may_carry_secrets(method: &str) -> bool {
+    method.starts_with("Filecoin.Wallet")
+        || matches!(ACCESS_MAP.get(method), Some(Access::Admin | Access::Sign))
+}
+
+/// Short, non-reversible identifier of a JWT, so that calls made with the same
+/// token can be correlated without writing the token itself to disk.
+pub fn token_id(token: &HeaderValue) -> String {
+    hex::encode(&Sha256::digest(token.as_bytes())[..8])
+}
+
+/// A JSON lines file written by a background thread.
+struct LineSink {
+    sender: Option<flume::Sender<String>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl LineSink {
+    /// Number of lines that may be queued before new ones are dropped.
+    const CAPACITY: usize = 4096;
+
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .mode(0o600)
+            .open(path)
+            .with_context(|| format!("couldn't open {}", path.display()))?;
+        let (sender, receiver) = flume::bounded::<String>(Self::CAPACITY);
+        let writer = std::thread::Builder::new()
+            .name("rpc-audit-writer".into())
+            .spawn(move || {
+                let mut writer = LineWriter::new(file);
+                for line in receiver {
+                    if let Err(e) = writer.write_all(line.as_bytes()) {
+                        warn!("Failed to write RPC audit entry: {e}");
+                    }
+                }
+            })?;
+        Ok(Self {
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+
+    fn send(&self, value: &impl Serialize) {
+        let Some(sender) = &self.sender else { return };
+        match serde_json::to_string(value) {
+            Ok(mut line) => {
+                line.push('\n');
+                if sender.try_send(line).is_err() {
+                    warn!("RPC audit writer is falling behind, dropping an entry");
+                }
+            }
+            Err(e) => warn!("Failed to serialize RPC audit entry: {e}"),
+        }
+    }
+}
+
+impl Drop for LineSink {
+    /// Writes the queued lines before returning.
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+fn now() -> String {
+    chrono::Utc::now().to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt as _;
+
+    #[test]
+    fn audit_and_capture_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit_path = dir.path().join("audit.jsonl");
+        let capture_path = dir.path().join("capture.jsonl");
+        let audit = RpcAudit::new(Some(&audit_path), Some(&capture_path)).unwrap();
+
+        let caller = CallerAddress("127.0.0.1:4321".into());
+        audit.record(
+            "Filecoin.ChainHead",
+            None,
+            &caller,
+            CallStatus::Ok,
+            Duration::from_millis(3),
+        );
+        audit.record(
+            "Filecoin.Shutdown",
+            Some("0123456789abcdef"),
+            &caller,
+            CallStatus::Denied { http_status: 403 },
+            Duration::from_millis(1),
+        );
+        audit.capture(
+            "Filecoin.Version",
+            r#"{"jsonrpc":"2.0","method":"Filecoin.Version","id":0}"#,
+            r#"{"jsonrpc":"2.0","result":{"Version":"0.1"},"id":0}"#,
+        );
+        audit.capture(
+            "Filecoin.WalletExport",
+            r#"{"jsonrpc":"2.0","method":"Filecoin.WalletExport","params":["f1..."],"id":1}"#,
+            r#"{"jsonrpc":"2.0","result":{"PrivateKey":"secret"},"id":1}"#,
+        );
+        // Waits for the queued lines to be written.
+        drop(audit);
+
+        for path in [&audit_path, &capture_path] {
+            assert_eq!(
+                std::fs::metadata(path).unwrap().permissions().mode() & 0o777,
+                0o600
+            );
+        }
+
+        let records = std::fs::read_to_string(&audit_path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<AuditRecord>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].status, CallStatus::Ok);
+        assert_eq!(records[1].status, CallStatus::Denied { http_status: 403 });
+        assert_eq!(records[1].token_id.as_deref(), Some("0123456789abcdef"));
+
+        let captured = std::fs::read_to_string(&capture_path).unwrap();
+        let call = serde_json::from_str::<CapturedCall>(captured.trim()).unwrap();
+        assert_eq!(call.request["method"], "Filecoin.Version");
+        assert_eq!(call.response["result"]["Version"], "0.1");
+    }
+
+    #[test]
+    fn secrets_are_not_captured() {
+        assert!(!may_carry_secrets("Filecoin.ChainHead"));
+        assert!(may_carry_secrets("Filecoin.WalletBalance"));
+        assert!(may_carry_secrets("Filecoin.WalletImport"));
+        assert!(may_carry_secrets("Filecoin.AuthNew"));
+        assert!(may_carry_secrets("Filecoin.MpoolPushMessage"));
+    }
+
+    #[test]
+    fn token_id_does_not_leak_token() {
+        let token = HeaderValue::from_static("Bearer secret.jwt.token");
+        let id = token_id(&token);
+        assert_eq!(id.len(), 16);
+        assert!(!id.contains("secret"));
+        assert_eq!(id, token_id(&token));
+    }
+}
@@ -132,6 +132,17 @@ where
     }
 }
 
+/// Status of the exports scheduled in the `[snapshot_export]` section of the
+/// configuration.
+pub(in crate::rpc) async fn chain_export_status<DB>(
+    data: Data<RPCState<DB>>,
+) -> Result<SnapshotExportStatus, JsonRpcError>
+where
+    DB: Blockstore,
+{
+    Ok(data.snapshot_export.read().clone())
+}
+
 pub(in crate::rpc) async fn chain_read_obj<DB: Blockstore>(
     data: Data<RPCState<DB>>,
     Params(LotusJson((obj_cid,))): Params<LotusJson<(Cid,)>>,
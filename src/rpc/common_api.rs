@@ -4,7 +4,7 @@
 
 use crate::rpc_api::data_types::{APIVersion, RPCState, Version};
 use fvm_ipld_blockstore::Blockstore;
-use jsonrpc_v2::{Data, Error as JsonRpcError};
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
 use semver::Version as SemVer;
 use tokio::sync::mpsc::Sender;
 
@@ -34,3 +34,16 @@ pub(in crate::rpc) async fn start_time<DB: Blockstore>(
 ) -> Result<chrono::DateTime<chrono::Utc>, JsonRpcError> {
     Ok(data.start_time)
 }
+
+/// Lists the log filter directives currently in effect
+pub(in crate::rpc) async fn log_list() -> Result<Vec<String>, JsonRpcError> {
+    Ok(crate::cli_shared::logger::log_directives()?)
+}
+
+/// Changes the log level of a subsystem at runtime, `*` being the default level
+pub(in crate::rpc) async fn log_set_level(
+    Params((subsystem, level)): Params<(String, String)>,
+) -> Result<(), JsonRpcError> {
+    crate::cli_shared::logger::set_log_level(&subsystem, &level)?;
+    Ok(())
+}
@@ -0,0 +1,110 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::sync::Arc;
+
+use crate::cid_collections::CidHashSet;
+use crate::db::backup::{SettingsDump, BACKUP_CHAIN_FILE, BACKUP_SETTINGS_FILE};
+use crate::rpc_api::{
+    data_types::RPCState,
+    db_api::{DbBackupParams, GcStatus},
+};
+use fvm_ipld_blockstore::Blockstore;
+use jsonrpc_v2::{Data, Error as JsonRpcError, Params};
+use once_cell::sync::Lazy;
+use sha2::Sha256;
+use tokio::sync::Mutex;
+use tracing::info;
+
+/// Writes a point-in-time backup of the chain and the settings store into
+/// `output_dir`, see [`crate::db::backup`].
+pub(in crate::rpc) async fn db_backup<DB>(
+    data: Data<RPCState<DB>>,
+    Params(DbBackupParams {
+        output_dir,
+        recent_roots,
+    }): Params<DbBackupParams>,
+) -> Result<(), JsonRpcError>
+where
+    DB: Blockstore + Send + Sync + 'static,
+{
+    static LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+    let Ok(_locked) = LOCK.try_lock() else {
+        return Err(JsonRpcError::Provided {
+            code: http::StatusCode::SERVICE_UNAVAILABLE.as_u16() as _,
+            message: "Another backup is still in progress",
+        });
+    };
+
+    let chain_finality = data.state_manager.chain_config().policy.chain_finality;
+    if recent_roots < chain_finality {
+        Err(&format!("backup depth must be at least {chain_finality}"))?;
+    }
+
+    // Blocks reachable from the captured head mustn't be swept while they're exported.
+    let _gc_paused = data.gc.pause_guard().await;
+
+    // Capture the head and the settings together, the chain is then exported
+    // from that head regardless of how the node progresses meanwhile.
+    let head = data.chain_store.heaviest_tipset();
+    let settings = SettingsDump::read(data.chain_store.settings(), head.key())?;
+
+    tokio::fs::create_dir_all(&output_dir).await?;
+    let temp_path = tempfile::NamedTempFile::new_in(&output_dir)?.into_temp_path();
+    let file = tokio::fs::File::create(&temp_path).await?;
+    crate::chain::export::<Sha256>(
+        Arc::clone(&data.chain_store.db),
+        &head,
+        recent_roots,
+        file,
+        CidHashSet::default(),
+        true,
+    )
+    .await?;
+    temp_path.persist(output_dir.join(BACKUP_CHAIN_FILE))?;
+    settings.save(&output_dir.join(BACKUP_SETTINGS_FILE))?;
+
+    info!(
+        "Backed up the database at epoch {} to {}",
+        head.epoch(),
+        output_dir.display()
+    );
+    Ok(())
+}
+
+pub(in crate::rpc) async fn db_gc_status<DB>(
+    data: Data<RPCState<DB>>,
+) -> Result<GcStatus, JsonRpcError>
+where
+    DB: Blockstore,
+{
+    Ok(data.gc.status())
+}
+
+/// Suspends the garbage collector until [`db_gc_resume`] is called.
+pub(in crate::rpc) async fn db_gc_pause<DB>(data: Data<RPCState<DB>>) -> Result<(), JsonRpcError>
+where
+    DB: Blockstore,
+{
+    data.gc.pause();
+    Ok(())
+}
+
+pub(in crate::rpc) async fn db_gc_resume<DB>(data: Data<RPCState<DB>>) -> Result<(), JsonRpcError>
+where
+    DB: Blockstore,
+{
+    data.gc.resume();
+    Ok(())
+}
+
+/// Cancels the ongoing garbage collection run. The next one starts after the
+/// configured interval.
+pub(in crate::rpc) async fn db_gc_abort<DB>(data: Data<RPCState<DB>>) -> Result<(), JsonRpcError>
+where
+    DB: Blockstore,
+{
+    data.gc.abort()?;
+    Ok(())
+}
@@ -0,0 +1,184 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{
+        counter::Counter,
+        family::Family,
+        gauge::Gauge,
+        histogram::{exponential_buckets, Histogram},
+    },
+    registry::Registry,
+};
+use tracing::warn;
+
+use crate::rpc::audit::may_carry_secrets;
+
+/// Maximum length of the parameters printed in a slow call log line.
+const MAX_LOGGED_PARAMS_LEN: usize = 256;
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct MethodLabels {
+    pub method: String,
+}
+
+#[derive(Clone, Debug, Hash, PartialEq, Eq, EncodeLabelSet)]
+pub struct ErrorLabels {
+    pub method: String,
+    pub code: i64,
+}
+
+pub static RPC_METHOD_TIME: Lazy<Family<MethodLabels, Histogram>> = Lazy::new(|| {
+    // 1ms to ~65s
+    Family::new_with_constructor(|| Histogram::new(exponential_buckets(0.001, 2.0, 17)))
+});
+pub static RPC_METHOD_FAILURE: Lazy<Family<ErrorLabels, Counter>> = Lazy::new(Family::default);
+pub static RPC_ACTIVE_WS_CONNECTIONS: Lazy<Gauge> = Lazy::new(Gauge::default);
+
+/// Counts an open websocket connection in [`RPC_ACTIVE_WS_CONNECTIONS`] until
+/// dropped.
+pub(in crate::rpc) struct ActiveWsConnection(());
+
+impl ActiveWsConnection {
+    pub(in crate::rpc) fn new() -> Self {
+        RPC_ACTIVE_WS_CONNECTIONS.inc();
+        Self(())
+    }
+}
+
+impl Drop for ActiveWsConnection {
+    fn drop(&mut self) {
+        RPC_ACTIVE_WS_CONNECTIONS.dec();
+    }
+}
+
+/// Calls taking longer than this are logged together with their parameters.
+/// A zero threshold disables the logging.
+#[derive(Debug, Clone, Copy)]
+pub struct SlowCallThreshold(pub Duration);
+
+pub async fn register_metrics() {
+    let mut registry = Registry::default();
+    registry.register(
+        "rpc_method_time",
+        "Duration of JSON-RPC method calls in seconds",
+        RPC_METHOD_TIME.clone(),
+    );
+    registry.register(
+        "rpc_method_failure",
+        "Number of failed JSON-RPC method calls by error code",
+        RPC_METHOD_FAILURE.clone(),
+    );
+    registry.register(
+        "rpc_active_ws_connections",
+        "Number of open JSON-RPC websocket connections",
+        RPC_ACTIVE_WS_CONNECTIONS.clone(),
+    );
+    crate::metrics::add_metrics_registry("rpc".into(), registry).await;
+}
+
+/// Records the outcome of a single RPC call. `params` is only evaluated when
+/// the call is slow enough to be logged, and never for methods which may carry
+/// secrets.
+pub fn observe_call(
+    method: &str,
+    elapsed: Duration,
+    error_code: Option<i64>,
+    SlowCallThreshold(threshold): SlowCallThreshold,
+    params: impl FnOnce() -> String,
+) {
+    RPC_METHOD_TIME
+        .get_or_create(&MethodLabels {
+            method: method.to_owned(),
+        })
+        .observe(elapsed.as_secs_f64());
+    if let Some(code) = error_code {
+        RPC_METHOD_FAILURE
+            .get_or_create(&ErrorLabels {
+                method: method.to_owned(),
+                code,
+            })
+            .inc();
+    }
+    if !threshold.is_zero() && elapsed >= threshold {
+        let params = match may_carry_secrets(method) {
+            true => "<redacted>".into(),
+            false => truncate(params(), MAX_LOGGED_PARAMS_LEN),
+        };
+        warn!(
+            "Slow RPC call: {method} took {}, params: {params}",
+            humantime::format_duration(elapsed),
+        );
+    }
+}
+
+fn truncate(mut s: String, max_len: usize) -> String {
+    if s.len() > max_len {
+        let mut end = max_len;
+        while !s.is_char_boundary(end) {
+            end -= 1;
+        }
+        s.truncate(end);
+        s.push_str("...");
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn truncate_params() {
+        assert_eq!(truncate("[1,2]".into(), 10), "[1,2]");
+        assert_eq!(truncate("[1,2,3,4]".into(), 4), "[1,2...");
+        // never split a multi-byte character
+        assert_eq!(truncate("ąę".into(), 3), "ą...");
+    }
+
+    #[test]
+    fn active_ws_connection_counted_until_dropped() {
+        let before = RPC_ACTIVE_WS_CONNECTIONS.get();
+        let result = std::panic::catch_unwind(|| {
+            let _active = ActiveWsConnection::new();
+            assert_eq!(RPC_ACTIVE_WS_CONNECTIONS.get(), before + 1);
+            panic!("connection handler panicked");
+        });
+        assert!(result.is_err());
+        assert_eq!(RPC_ACTIVE_WS_CONNECTIONS.get(), before);
+    }
+
+    #[test]
+    fn observe_failed_call() {
+        let method = "Filecoin.TestObserveFailedCall";
+        observe_call(
+            method,
+            Duration::from_millis(5),
+            Some(-32601),
+            SlowCallThreshold(Duration::ZERO),
+            || unreachable!("params must not be rendered when slow call logging is disabled"),
+        );
+        let failures = RPC_METHOD_FAILURE
+            .get_or_create(&ErrorLabels {
+                method: method.into(),
+                code: -32601,
+            })
+            .get();
+        assert_eq!(failures, 1);
+    }
+
+    #[test]
+    fn secrets_are_not_logged() {
+        observe_call(
+            "Filecoin.WalletImport",
+            Duration::from_secs(2),
+            None,
+            SlowCallThreshold(Duration::from_secs(1)),
+            || unreachable!("params of wallet methods must not be rendered"),
+        );
+    }
+}
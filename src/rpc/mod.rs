@@ -1,44 +1,67 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+pub mod audit;
 mod auth_api;
 mod beacon_api;
 mod chain_api;
 mod common_api;
+mod db_api;
 mod gas_api;
+pub mod metrics;
 mod mpool_api;
 mod net_api;
 mod node_api;
 mod rpc_http_handler;
+pub mod rpc_listener;
 mod rpc_util;
 mod rpc_ws_handler;
 mod state_api;
 mod sync_api;
 mod wallet_api;
 
-use std::{net::TcpListener, sync::Arc};
+use std::{net::TcpListener, os::unix::net::UnixListener, sync::Arc, time::Duration};
 
 use crate::rpc_api::{
-    auth_api::*, beacon_api::*, chain_api::*, common_api::*, data_types::RPCState, gas_api::*,
-    mpool_api::*, net_api::*, node_api::NODE_STATUS, state_api::*, sync_api::*, wallet_api::*,
+    auth_api::*, beacon_api::*, chain_api::*, common_api::*, data_types::RPCState, db_api::*,
+    gas_api::*, mpool_api::*, net_api::*, node_api::NODE_STATUS, state_api::*, sync_api::*,
+    wallet_api::*,
 };
 use axum::routing::{get, post};
 use fvm_ipld_blockstore::Blockstore;
 use jsonrpc_v2::{Data, Error as JSONRPCError, Server};
 use tokio::sync::mpsc::Sender;
+use tokio_rustls::rustls::ServerConfig;
 use tracing::info;
 
 use crate::rpc::{
+    audit::{CallerAddress, RpcAudit},
     beacon_api::beacon_get_entry,
-    common_api::{shutdown, start_time, version},
+    common_api::{log_list, log_set_level, shutdown, start_time, version},
+    metrics::SlowCallThreshold,
     rpc_http_handler::rpc_http_handler,
+    rpc_listener::{LocalConnection, TlsIncoming, UnixIncoming},
     rpc_ws_handler::rpc_ws_handler,
     state_api::*,
 };
 
+/// Sockets the RPC server accepts connections on.
+pub struct RpcListeners {
+    pub tcp: TcpListener,
+    /// If set, connections on the TCP listener are served over TLS.
+    pub tls_config: Option<Arc<ServerConfig>>,
+    pub unix_socket: Option<UnixListener>,
+}
+
 pub async fn start_rpc<DB>(
     state: Arc<RPCState<DB>>,
-    rpc_endpoint: TcpListener,
+    RpcListeners {
+        tcp: rpc_endpoint,
+        tls_config,
+        unix_socket,
+    }: RpcListeners,
+    slow_call_threshold: Duration,
+    audit: RpcAudit,
     forest_version: &'static str,
     shutdown_send: Sender<()>,
 ) -> Result<(), JSONRPCError>
@@ -64,6 +87,7 @@ where
             // Chain API
             .with_method(CHAIN_GET_MESSAGE, chain_api::chain_get_message::<DB>)
             .with_method(CHAIN_EXPORT, chain_api::chain_export::<DB>)
+            .with_method(CHAIN_EXPORT_STATUS, chain_api::chain_export_status::<DB>)
             .with_method(CHAIN_READ_OBJ, chain_read_obj::<DB>)
             .with_method(CHAIN_HAS_OBJ, chain_has_obj::<DB>)
             .with_method(CHAIN_GET_BLOCK_MESSAGES, chain_get_block_messages::<DB>)
@@ -121,6 +145,7 @@ where
                 STATE_GET_RANDOMNESS_FROM_BEACON,
                 state_get_randomness_from_beacon::<DB>,
             )
+            .with_method(STATE_ACTOR_PROOF, state_actor_proof::<DB>)
             // Gas API
             .with_method(GAS_ESTIMATE_FEE_CAP, gas_estimate_fee_cap::<DB>)
             .with_method(GAS_ESTIMATE_GAS_LIMIT, gas_estimate_gas_limit::<DB>)
@@ -130,6 +155,14 @@ where
             .with_method(VERSION, move || version(block_delay, forest_version))
             .with_method(SHUTDOWN, move || shutdown(shutdown_send.clone()))
             .with_method(START_TIME, start_time::<DB>)
+            .with_method(LOG_LIST, log_list)
+            .with_method(LOG_SET_LEVEL, log_set_level)
+            // DB API
+            .with_method(DB_BACKUP, db_api::db_backup::<DB>)
+            .with_method(DB_GC_STATUS, db_api::db_gc_status::<DB>)
+            .with_method(DB_GC_PAUSE, db_api::db_gc_pause::<DB>)
+            .with_method(DB_GC_RESUME, db_api::db_gc_resume::<DB>)
+            .with_method(DB_GC_ABORT, db_api::db_gc_abort::<DB>)
             // Net API
             .with_method(NET_ADDRS_LISTEN, net_api::net_addrs_listen::<DB>)
             .with_method(NET_PEERS, net_api::net_peers::<DB>)
@@ -144,11 +177,38 @@ where
     let app = axum::Router::new()
         .route("/rpc/v0", get(rpc_ws_handler))
         .route("/rpc/v0", post(rpc_http_handler))
+        .layer(axum::Extension(SlowCallThreshold(slow_call_threshold)))
+        .layer(axum::Extension(Arc::new(audit)))
         .with_state(rpc_server);
+    metrics::register_metrics().await;
 
     info!("Ready for RPC connections");
-    let server = axum::Server::from_tcp(rpc_endpoint)?.serve(app.into_make_service());
-    server.await?;
+    // Connections over the Unix socket are trusted, see `LocalConnection`.
+    let local_app = app.clone().layer(axum::Extension(LocalConnection));
+    let tcp_server = async move {
+        match tls_config {
+            Some(tls_config) => {
+                axum::Server::builder(TlsIncoming::new(rpc_endpoint, tls_config)?)
+                    .serve(app.into_make_service_with_connect_info::<CallerAddress>())
+                    .await?
+            }
+            None => {
+                axum::Server::from_tcp(rpc_endpoint)?
+                    .serve(app.into_make_service_with_connect_info::<CallerAddress>())
+                    .await?
+            }
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+    let unix_server = async move {
+        if let Some(unix_socket) = unix_socket {
+            axum::Server::builder(UnixIncoming::new(unix_socket)?)
+                .serve(local_app.into_make_service_with_connect_info::<CallerAddress>())
+                .await?
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+    tokio::try_join!(tcp_server, unix_server)?;
 
     info!("Stopped accepting RPC connections");
 
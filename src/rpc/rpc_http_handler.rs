@@ -1,26 +1,53 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::{sync::Arc, time::Instant};
+
 use crate::rpc_api::data_types::JsonRpcServerState;
-use axum::response::IntoResponse;
+use axum::{extract::ConnectInfo, response::IntoResponse};
 use http::{HeaderMap, StatusCode};
 use jsonrpc_v2::RequestObject as JsonRpcRequestObject;
 
-use crate::rpc::rpc_util::{call_rpc_str, check_permissions, get_auth_header, is_streaming_method};
+use crate::rpc::audit::{CallStatus, CallerAddress, RpcAudit};
+use crate::rpc::metrics::SlowCallThreshold;
+use crate::rpc::rpc_listener::LocalConnection;
+use crate::rpc::rpc_util::{
+    call_rpc_str, check_permissions, get_auth_header, is_streaming_method, CallContext,
+};
 
 pub async fn rpc_http_handler(
     headers: HeaderMap,
+    local: Option<axum::Extension<LocalConnection>>,
+    axum::Extension(slow_call_threshold): axum::Extension<SlowCallThreshold>,
+    axum::Extension(audit): axum::Extension<Arc<RpcAudit>>,
+    ConnectInfo(caller): ConnectInfo<CallerAddress>,
     axum::extract::State(rpc_server): axum::extract::State<JsonRpcServerState>,
     axum::Json(rpc_call): axum::Json<JsonRpcRequestObject>,
 ) -> impl IntoResponse {
+    let start = Instant::now();
+    let ctx = CallContext {
+        authorization_header: get_auth_header(headers),
+        local: local.map(|axum::Extension(local)| local),
+        caller,
+        slow_call_threshold,
+        audit,
+    };
     let response_headers = [("content-type", "application/json-rpc;charset=utf-8")];
     if let Err((code, msg)) = check_permissions(
         rpc_server.clone(),
         rpc_call.method_ref(),
-        get_auth_header(headers),
+        ctx.authorization_header.clone(),
+        ctx.local,
     )
     .await
     {
+        ctx.audit(
+            rpc_call.method_ref(),
+            CallStatus::Denied {
+                http_status: code.as_u16(),
+            },
+            start.elapsed(),
+        );
         return (code, response_headers, msg);
     }
 
@@ -32,7 +59,7 @@ pub async fn rpc_http_handler(
         );
     }
 
-    match call_rpc_str(rpc_server.clone(), rpc_call).await {
+    match call_rpc_str(rpc_server.clone(), rpc_call, None, &ctx).await {
         Ok(result) => (StatusCode::OK, response_headers, result),
         Err(err) => (
             StatusCode::INTERNAL_SERVER_ERROR,
@@ -0,0 +1,369 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Connection acceptors for the RPC server. Besides the plain TCP listener, the
+//! RPC API can be served over TLS and over a local Unix domain socket.
+
+use std::{
+    fs::File,
+    io::BufReader,
+    os::unix::fs::{FileTypeExt as _, PermissionsExt as _},
+    path::Path,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use anyhow::Context as _;
+use futures::{
+    future::BoxFuture, ready, stream::FuturesUnordered, Future as _, FutureExt as _, StreamExt as _,
+};
+use hyper::server::accept::Accept;
+use tokio::net::{TcpListener, TcpStream, UnixListener, UnixStream};
+use tokio_rustls::{
+    rustls::{Certificate, PrivateKey, ServerConfig},
+    server::TlsStream,
+    TlsAcceptor,
+};
+use tracing::{debug, error, warn};
+
+/// Marker inserted into requests that arrived over the Unix domain socket.
+/// Access to the socket is governed by its file permissions, so such requests
+/// are not required to carry a JWT and are granted admin permissions.
+#[derive(Debug, Clone, Copy)]
+pub struct LocalConnection;
+
+/// Loads a PEM-encoded certificate chain and private key into a TLS server
+/// configuration.
+pub fn load_tls_config(cert_path: &Path, key_path: &Path) -> anyhow::Result<Arc<ServerConfig>> {
+    let certs = rustls_pemfile::certs(&mut BufReader::new(
+        File::open(cert_path).with_context(|| format!("couldn't open {}", cert_path.display()))?,
+    ))
+    .with_context(|| format!("couldn't parse certificates in {}", cert_path.display()))?
+    .into_iter()
+    .map(Certificate)
+    .collect::<Vec<_>>();
+    anyhow::ensure!(
+        !certs.is_empty(),
+        "no certificates found in {}",
+        cert_path.display()
+    );
+
+    let key = rustls_pemfile::read_all(&mut BufReader::new(
+        File::open(key_path).with_context(|| format!("couldn't open {}", key_path.display()))?,
+    ))
+    .with_context(|| format!("couldn't parse private key in {}", key_path.display()))?
+    .into_iter()
+    .find_map(|item| match item {
+        rustls_pemfile::Item::RSAKey(key)
+        | rustls_pemfile::Item::PKCS8Key(key)
+        | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+        _ => None,
+    })
+    .with_context(|| format!("no private key found in {}", key_path.display()))?;
+
+    let mut config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(Arc::new(config))
+}
+
+/// Binds a Unix domain socket at `path` and restricts it to `mode`. A stale
+/// socket left behind by a previous run is removed first, but no other kind of
+/// file is. The socket is bound in a private directory and only moved to
+/// `path` once its permissions are set, so it is never reachable with the
+/// default permissions.
+pub fn bind_unix_socket(
+    path: &Path,
+    mode: u32,
+) -> anyhow::Result<std::os::unix::net::UnixListener> {
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => std::fs::remove_file(path)
+            .with_context(|| format!("couldn't remove stale socket {}", path.display()))?,
+        Ok(_) => anyhow::bail!("{} exists and is not a socket", path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e).with_context(|| format!("couldn't stat {}", path.display())),
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    // Created with mode 0700.
+    let private_dir = tempfile::Builder::new()
+        .prefix(".forest-rpc")
+        .tempdir_in(parent)
+        .with_context(|| format!("couldn't create a directory in {}", parent.display()))?;
+    let private_path = private_dir.path().join("rpc.sock");
+    let listener = std::os::unix::net::UnixListener::bind(&private_path)
+        .with_context(|| format!("couldn't bind to {}", path.display()))?;
+    std::fs::set_permissions(&private_path, std::fs::Permissions::from_mode(mode))?;
+    std::fs::rename(&private_path, path)
+        .with_context(|| format!("couldn't bind to {}", path.display()))?;
+    listener.set_nonblocking(true)?;
+    Ok(listener)
+}
+
+/// Handles accept errors the way hyper's `AddrIncoming` does, so that they don't
+/// shut the server down. Errors concerning the connection being accepted are
+/// skipped, others (e.g. running out of file descriptors) pause accepting for
+/// [`AcceptBackoff::DELAY`].
+#[derive(Default)]
+struct AcceptBackoff {
+    timeout: Option<Pin<Box<tokio::time::Sleep>>>,
+}
+
+impl AcceptBackoff {
+    const DELAY: Duration = Duration::from_secs(1);
+
+    /// Returns `Pending` while accepting is paused.
+    fn poll(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(timeout) = &mut self.timeout {
+            ready!(timeout.as_mut().poll(cx));
+            self.timeout = None;
+        }
+        Poll::Ready(())
+    }
+
+    fn on_error(&mut self, e: std::io::Error) {
+        use std::io::ErrorKind::*;
+        if matches!(
+            e.kind(),
+            ConnectionRefused | ConnectionAborted | ConnectionReset
+        ) {
+            debug!("RPC connection error while accepting: {e}");
+        } else {
+            error!(
+                "RPC accept error, pausing for {}s: {e}",
+                Self::DELAY.as_secs()
+            );
+            self.timeout = Some(Box::pin(tokio::time::sleep(Self::DELAY)));
+        }
+    }
+}
+
+/// Accepts TCP connections and performs the TLS handshakes concurrently, so
+/// that a slow client cannot stall the accept loop. Handshakes time out after
+/// [`TlsIncoming::HANDSHAKE_TIMEOUT`], and no new connections are accepted
+/// while [`TlsIncoming::MAX_HANDSHAKES`] are in flight.
+pub struct TlsIncoming {
+    listener: TcpListener,
+    acceptor: TlsAcceptor,
+    handshakes: FuturesUnordered<BoxFuture<'static, std::io::Result<TlsStream<TcpStream>>>>,
+    backoff: AcceptBackoff,
+}
+
+impl TlsIncoming {
+    pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+    pub const MAX_HANDSHAKES: usize = 256;
+
+    pub fn new(listener: std::net::TcpListener, config: Arc<ServerConfig>) -> anyhow::Result<Self> {
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            listener: TcpListener::from_std(listener)?,
+            acceptor: TlsAcceptor::from(config),
+            handshakes: FuturesUnordered::new(),
+            backoff: AcceptBackoff::default(),
+        })
+    }
+}
+
+impl Accept for TlsIncoming {
+    type Conn = TlsStream<TcpStream>;
+    type Error = std::io::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let this = self.get_mut();
+        loop {
+            // Whether a wake-up is registered with the listener, or the backoff timer.
+            let mut accept_pending = false;
+            // Connections beyond the limit wait in the listen backlog.
+            while this.handshakes.len() < Self::MAX_HANDSHAKES {
+                if this.backoff.poll(cx).is_pending() {
+                    accept_pending = true;
+                    break;
+                }
+                match this.listener.poll_accept(cx) {
+                    Poll::Ready(Ok((stream, peer))) => {
+                        debug!("Accepted TLS RPC connection from {peer}");
+                        let handshake = tokio::time::timeout(
+                            Self::HANDSHAKE_TIMEOUT,
+                            this.acceptor.accept(stream),
+                        )
+                        .map(|res| {
+                            res.unwrap_or_else(|_| {
+                                Err(std::io::Error::new(
+                                    std::io::ErrorKind::TimedOut,
+                                    "handshake timed out",
+                                ))
+                            })
+                        });
+                        this.handshakes.push(handshake.boxed());
+                    }
+                    Poll::Ready(Err(e)) => this.backoff.on_error(e),
+                    Poll::Pending => {
+                        accept_pending = true;
+                        break;
+                    }
+                }
+            }
+            let mut failed = false;
+            loop {
+                match this.handshakes.poll_next_unpin(cx) {
+                    Poll::Ready(Some(Ok(stream))) => return Poll::Ready(Some(Ok(stream))),
+                    Poll::Ready(Some(Err(e))) => {
+                        warn!("TLS handshake failed: {e}");
+                        failed = true;
+                    }
+                    Poll::Ready(None) | Poll::Pending => break,
+                }
+            }
+            // Otherwise every slot is taken by a handshake still in progress, which wakes us up.
+            if accept_pending || !failed {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+/// Accepts connections on a Unix domain socket.
+pub struct UnixIncoming {
+    listener: UnixListener,
+    backoff: AcceptBackoff,
+}
+
+impl UnixIncoming {
+    pub fn new(listener: std::os::unix::net::UnixListener) -> anyhow::Result<Self> {
+        Ok(Self {
+            listener: UnixListener::from_std(listener)?,
+            backoff: AcceptBackoff::default(),
+        })
+    }
+}
+
+impl Accept for UnixIncoming {
+    type Conn = UnixStream;
+    type Error = std::io::Error;
+
+    fn poll_accept(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Conn, Self::Error>>> {
+        let this = self.get_mut();
+        loop {
+            ready!(this.backoff.poll(cx));
+            match ready!(this.listener.poll_accept(cx)) {
+                Ok((stream, _)) => return Poll::Ready(Some(Ok(stream))),
+                Err(e) => this.backoff.on_error(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use hyper::{Body, Request};
+
+    #[tokio::test]
+    async fn serve_over_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forest.sock");
+        let listener = bind_unix_socket(&path, 0o600).unwrap();
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        // Only the socket is left in the directory.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let app = axum::Router::new().route("/", get(|| async { "hello" }));
+        let server = axum::Server::builder(UnixIncoming::new(listener).unwrap())
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+
+        let stream = UnixStream::connect(&path).await.unwrap();
+        let (mut sender, conn) = hyper::client::conn::handshake(stream).await.unwrap();
+        tokio::spawn(conn);
+        let response = sender
+            .send_request(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"hello");
+    }
+
+    #[tokio::test]
+    async fn tls_failed_handshake_is_skipped() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+        let cert_der = Certificate(cert.serialize_der().unwrap());
+        let config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![cert_der.clone()],
+                PrivateKey(cert.serialize_private_key_der()),
+            )
+            .unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = axum::Router::new().route("/", get(|| async { "hello" }));
+        let server = axum::Server::builder(TlsIncoming::new(listener, Arc::new(config)).unwrap())
+            .serve(app.into_make_service());
+        tokio::spawn(server);
+
+        // A client which doesn't speak TLS fails its handshake.
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        tokio::io::AsyncWriteExt::write_all(&mut stream, b"GET / HTTP/1.1\r\n\r\n")
+            .await
+            .unwrap();
+        let mut buf = vec![];
+        tokio::io::AsyncReadExt::read_to_end(&mut stream, &mut buf)
+            .await
+            .ok();
+
+        let mut roots = tokio_rustls::rustls::RootCertStore::empty();
+        roots.add(&cert_der).unwrap();
+        let connector = tokio_rustls::TlsConnector::from(Arc::new(
+            tokio_rustls::rustls::ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(roots)
+                .with_no_client_auth(),
+        ));
+        let stream = connector
+            .connect(
+                "localhost".try_into().unwrap(),
+                TcpStream::connect(addr).await.unwrap(),
+            )
+            .await
+            .unwrap();
+        let (mut sender, conn) = hyper::client::conn::handshake(stream).await.unwrap();
+        tokio::spawn(conn);
+        let response = sender
+            .send_request(Request::get("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        assert_eq!(&body[..], b"hello");
+    }
+
+    #[test]
+    fn bind_unix_socket_replaces_only_sockets() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("forest.sock");
+        drop(bind_unix_socket(&path, 0o600).unwrap());
+        // A stale socket is replaced.
+        bind_unix_socket(&path, 0o600).unwrap();
+
+        let file = dir.path().join("forest.conf");
+        std::fs::write(&file, "keep").unwrap();
+        assert!(bind_unix_socket(&file, 0o600).is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "keep");
+    }
+}
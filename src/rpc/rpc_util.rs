@@ -1,9 +1,17 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use crate::auth::ADMIN;
+use crate::rpc::audit::{may_carry_secrets, token_id, CallStatus, CallerAddress, RpcAudit};
+use crate::rpc::metrics::{observe_call, SlowCallThreshold};
+use crate::rpc::rpc_listener::LocalConnection;
 use crate::rpc_api::{auth_api::*, check_access, data_types::JsonRpcServerState, ACCESS_MAP};
 use http::{HeaderMap, HeaderValue, StatusCode};
 use serde::de::DeserializeOwned;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tracing::{debug, error};
 
 pub fn get_error_obj(code: i64, message: String) -> jsonrpc_v2::Error {
@@ -43,8 +51,11 @@ pub async fn check_permissions(
     rpc_server: JsonRpcServerState,
     method: &str,
     authorization_header: Option<HeaderValue>,
+    local: Option<LocalConnection>,
 ) -> Result<(), (StatusCode, String)> {
     let claims = match authorization_header {
+        // Requests over the Unix socket are authorized by its file permissions
+        _ if local.is_some() => ADMIN.iter().map(ToString::to_string).collect(),
         Some(token) => {
             let token = token
                 .to_str()
@@ -84,13 +95,87 @@ pub fn get_auth_header(headers: HeaderMap) -> Option<HeaderValue> {
     headers.get("Authorization").cloned()
 }
 
-// Calls an RPC method and returns the full response as a string.
+/// Connection-level details shared by the calls made over one HTTP request or
+/// websocket connection.
+pub struct CallContext {
+    pub authorization_header: Option<HeaderValue>,
+    pub local: Option<LocalConnection>,
+    pub caller: CallerAddress,
+    pub slow_call_threshold: SlowCallThreshold,
+    pub audit: Arc<RpcAudit>,
+}
+
+impl CallContext {
+    /// Adds a call to the audit log, if enabled.
+    pub fn audit(&self, method: &str, status: CallStatus, elapsed: Duration) {
+        self.audit.record(
+            method,
+            self.authorization_header.as_ref().map(token_id).as_deref(),
+            &self.caller,
+            status,
+            elapsed,
+        );
+    }
+}
+
+// Calls an RPC method and returns the full response as a string. The call is
+// recorded in the RPC metrics and, if enabled, the audit log and capture file.
+// `raw_request` is the request as received, if available. Otherwise the request
+// is serialized again, but only if it may be logged as a slow call or captured.
 pub async fn call_rpc_str(
     rpc_server: JsonRpcServerState,
     rpc_request: jsonrpc_v2::RequestObject,
+    raw_request: Option<&str>,
+    ctx: &CallContext,
 ) -> anyhow::Result<String> {
+    let method = rpc_request.method_ref().to_owned();
+    let serialized;
+    let raw_request = match raw_request {
+        Some(raw_request) => raw_request,
+        None if !may_carry_secrets(&method)
+            && (!ctx.slow_call_threshold.0.is_zero() || ctx.audit.is_capturing()) =>
+        {
+            serialized = serde_json::to_string(&rpc_request)?;
+            &serialized
+        }
+        None => "",
+    };
+    let start = Instant::now();
     let rpc_subscription_response = rpc_server.handle(rpc_request).await;
-    Ok(serde_json::to_string(&rpc_subscription_response)?)
+    let elapsed = start.elapsed();
+    let error_code = get_error_code(&rpc_subscription_response);
+    observe_call(
+        &method,
+        elapsed,
+        error_code,
+        ctx.slow_call_threshold,
+        || {
+            serde_json::from_str::<serde_json::Value>(raw_request)
+                .ok()
+                .and_then(|request| request.get("params").map(ToString::to_string))
+                .unwrap_or_default()
+        },
+    );
+    ctx.audit(
+        &method,
+        error_code.map_or(CallStatus::Ok, |code| CallStatus::Error { code }),
+        elapsed,
+    );
+    let response = serde_json::to_string(&rpc_subscription_response)?;
+    if ctx.audit.is_capturing() {
+        ctx.audit.capture(&method, raw_request, &response);
+    }
+    Ok(response)
+}
+
+fn get_error_code(response: &jsonrpc_v2::ResponseObjects) -> Option<i64> {
+    match response {
+        jsonrpc_v2::ResponseObjects::One(jsonrpc_v2::ResponseObject::Error {
+            error: jsonrpc_v2::Error::Full { code, .. } | jsonrpc_v2::Error::Provided { code, .. },
+            ..
+        }) => Some(*code),
+        _ => None,
+    }
 }
 
 // Returns both the RPC response string and the result value in a tuple.
@@ -1,27 +1,33 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use std::sync::Arc;
+use std::{sync::Arc, time::Instant};
 
 use crate::rpc_api::data_types::JsonRpcServerState;
 use axum::{
     extract::{
         ws::{Message, WebSocket},
-        WebSocketUpgrade,
+        ConnectInfo, WebSocketUpgrade,
     },
     response::IntoResponse,
 };
 use crossbeam::atomic::AtomicCell;
 use futures::{stream::SplitSink, SinkExt, StreamExt};
-use http::{HeaderMap, HeaderValue};
+use http::HeaderMap;
 use tokio::sync::RwLock;
 use tracing::{debug, error, info, warn};
 
-use crate::rpc::rpc_util::{call_rpc_str, check_permissions, get_auth_header, get_error_str};
+use crate::rpc::audit::{CallStatus, CallerAddress, RpcAudit};
+use crate::rpc::metrics::{ActiveWsConnection, SlowCallThreshold};
+use crate::rpc::rpc_listener::LocalConnection;
+use crate::rpc::rpc_util::{
+    call_rpc_str, check_permissions, get_auth_header, get_error_str, CallContext,
+};
 
 async fn rpc_ws_task(
-    authorization_header: Option<HeaderValue>,
+    ctx: Arc<CallContext>,
     rpc_call: jsonrpc_v2::RequestObject,
+    request_text: String,
     rpc_server: JsonRpcServerState,
     _is_socket_active: Arc<AtomicCell<bool>>,
     ws_sender: Arc<RwLock<SplitSink<WebSocket, Message>>>,
@@ -29,12 +35,27 @@ async fn rpc_ws_task(
     let call_method = rpc_call.method_ref();
     let _call_id = rpc_call.id_ref();
 
-    check_permissions(rpc_server.clone(), call_method, authorization_header)
-        .await
-        .map_err(|(_, e)| anyhow::Error::msg(e))?;
+    let start = Instant::now();
+    check_permissions(
+        rpc_server.clone(),
+        call_method,
+        ctx.authorization_header.clone(),
+        ctx.local,
+    )
+    .await
+    .map_err(|(code, e)| {
+        ctx.audit(
+            call_method,
+            CallStatus::Denied {
+                http_status: code.as_u16(),
+            },
+            start.elapsed(),
+        );
+        anyhow::Error::msg(e)
+    })?;
 
     info!("RPC WS called method: {}", call_method);
-    let response = call_rpc_str(rpc_server.clone(), rpc_call).await?;
+    let response = call_rpc_str(rpc_server.clone(), rpc_call, Some(&request_text), &ctx).await?;
     ws_sender
         .write()
         .await
@@ -46,21 +67,30 @@ async fn rpc_ws_task(
 
 pub async fn rpc_ws_handler(
     headers: HeaderMap,
+    local: Option<axum::Extension<LocalConnection>>,
+    axum::Extension(slow_call_threshold): axum::Extension<SlowCallThreshold>,
+    axum::Extension(audit): axum::Extension<Arc<RpcAudit>>,
+    ConnectInfo(caller): ConnectInfo<CallerAddress>,
     axum::extract::State(rpc_server): axum::extract::State<JsonRpcServerState>,
     ws: WebSocketUpgrade,
 ) -> impl IntoResponse {
-    let authorization_header = get_auth_header(headers);
-    ws.on_upgrade(move |socket| async {
-        rpc_ws_handler_inner(socket, authorization_header, rpc_server).await
-    })
+    let ctx = Arc::new(CallContext {
+        authorization_header: get_auth_header(headers),
+        local: local.map(|axum::Extension(local)| local),
+        caller,
+        slow_call_threshold,
+        audit,
+    });
+    ws.on_upgrade(move |socket| async { rpc_ws_handler_inner(socket, ctx, rpc_server).await })
 }
 
 async fn rpc_ws_handler_inner(
     socket: WebSocket,
-    authorization_header: Option<HeaderValue>,
+    ctx: Arc<CallContext>,
     rpc_server: JsonRpcServerState,
 ) {
     info!("Accepted WS connection!");
+    let _active = ActiveWsConnection::new();
     let (sender, mut receiver) = socket.split();
     let ws_sender = Arc::new(RwLock::new(sender));
     let socket_active = Arc::new(AtomicCell::new(true));
@@ -70,7 +100,7 @@ async fn rpc_ws_handler_inner(
             debug!("WS RPC Request: {}", request_text);
             if !request_text.is_empty() {
                 info!("RPC Request Received: {:?}", &request_text);
-                let authorization_header = authorization_header.clone();
+                let task_ctx = ctx.clone();
                 let task_rpc_server = rpc_server.clone();
                 let task_socket_active = socket_active.clone();
                 let task_ws_sender = ws_sender.clone();
@@ -80,8 +110,9 @@ async fn rpc_ws_handler_inner(
                     Ok(rpc_call) => {
                         tokio::task::spawn(async move {
                             match rpc_ws_task(
-                                authorization_header,
+                                task_ctx,
                                 rpc_call,
+                                request_text,
                                 task_rpc_server,
                                 task_socket_active,
                                 task_ws_sender.clone(),
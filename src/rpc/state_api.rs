@@ -7,12 +7,13 @@ use crate::cid_collections::CidHashSet;
 use crate::ipld::json::IpldJson;
 use crate::libp2p::NetworkMessage;
 use crate::lotus_json::LotusJson;
-use crate::rpc_api::data_types::{MarketDeal, MessageLookup, RPCState};
+use crate::rpc_api::data_types::{ActorProof, MarketDeal, MessageLookup, RPCState};
 use crate::shim::{
     address::Address, clock::ChainEpoch, executor::Receipt, message::Message,
     state_tree::ActorState, version::NetworkVersion,
 };
 use crate::state_manager::chain_rand::ChainRand;
+use crate::state_manager::proof::{proof_to_car, prove_actor};
 use crate::state_manager::{InvocResult, MarketBalance};
 use crate::utils::db::car_stream::{CarBlock, CarWriter};
 use ahash::{HashMap, HashMapExt};
@@ -94,6 +95,23 @@ pub(crate) async fn state_get_actor<DB: Blockstore>(
     state.map(Into::into).map_err(|e| e.into())
 }
 
+/// Proves the state of an actor in the parent state of the given tipset, and
+/// optionally the value at a path in the state of the actor.
+pub(crate) async fn state_actor_proof<DB: Blockstore>(
+    data: Data<RPCState<DB>>,
+    Params(LotusJson((addr, path, tsk))): Params<LotusJson<(Address, Vec<String>, TipsetKeys)>>,
+) -> Result<LotusJson<ActorProof>, JsonRpcError> {
+    let ts = data.chain_store.load_required_tipset(&tsk)?;
+    let state_root = *ts.parent_state();
+    let (proven, blocks) = prove_actor(data.state_manager.blockstore(), &state_root, &addr, &path)?;
+    Ok(LotusJson(ActorProof {
+        state_root,
+        actor: proven.actor,
+        value: proven.value,
+        proof: proof_to_car(&state_root, &blocks)?,
+    }))
+}
+
 /// looks up the Escrow and Locked balances of the given address in the Storage
 /// Market
 pub(in crate::rpc) async fn state_market_balance<DB: Blockstore + Send + Sync + 'static>(
@@ -132,6 +132,8 @@ mod tests {
             start_time,
             chain_store: cs_for_chain.clone(),
             beacon,
+            gc: Default::default(),
+            snapshot_export: Default::default(),
         });
         (state, network_rx)
     }
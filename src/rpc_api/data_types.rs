@@ -7,6 +7,8 @@ use crate::beacon::BeaconSchedule;
 use crate::blocks::TipsetKeys;
 use crate::chain::ChainStore;
 use crate::chain_sync::{BadBlockCache, SyncState};
+use crate::daemon::snapshot_export::SnapshotExportStatus;
+use crate::db::GcControl;
 use crate::ipld::json::IpldJson;
 use crate::key_management::KeyStore;
 pub use crate::libp2p::{Multiaddr, Protocol};
@@ -58,6 +60,8 @@ where
     pub network_name: String,
     pub start_time: chrono::DateTime<Utc>,
     pub beacon: Arc<BeaconSchedule>,
+    pub gc: Arc<GcControl>,
+    pub snapshot_export: Arc<SyncRwLock<SnapshotExportStatus>>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -371,3 +375,21 @@ pub struct SectorOnChainInfo {
 }
 
 lotus_json_with_self!(SectorOnChainInfo);
+
+/// Inclusion proof of an actor in the parent state of a tipset, see
+/// [`crate::state_manager::proof`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ActorProof {
+    #[serde(with = "crate::lotus_json")]
+    pub state_root: Cid,
+    #[serde(with = "crate::lotus_json")]
+    pub actor: Option<ActorState>,
+    #[serde(with = "crate::lotus_json")]
+    pub value: Option<Ipld>,
+    /// CARv1 archive holding the blocks of the proof.
+    #[serde(with = "crate::lotus_json")]
+    pub proof: Vec<u8>,
+}
+
+lotus_json_with_self!(ActorProof);
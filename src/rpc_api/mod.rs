@@ -37,6 +37,7 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     // Chain API
     access.insert(chain_api::CHAIN_GET_MESSAGE, Access::Read);
     access.insert(chain_api::CHAIN_EXPORT, Access::Read);
+    access.insert(chain_api::CHAIN_EXPORT_STATUS, Access::Read);
     access.insert(chain_api::CHAIN_READ_OBJ, Access::Read);
     access.insert(chain_api::CHAIN_HAS_OBJ, Access::Read);
     access.insert(chain_api::CHAIN_GET_BLOCK_MESSAGES, Access::Read);
@@ -87,6 +88,7 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(state_api::STATE_NETWORK_VERSION, Access::Read);
     access.insert(state_api::STATE_FETCH_ROOT, Access::Read);
     access.insert(state_api::STATE_GET_RANDOMNESS_FROM_BEACON, Access::Read);
+    access.insert(state_api::STATE_ACTOR_PROOF, Access::Read);
 
     // Gas API
     access.insert(gas_api::GAS_ESTIMATE_GAS_LIMIT, Access::Read);
@@ -94,10 +96,19 @@ pub static ACCESS_MAP: Lazy<HashMap<&str, Access>> = Lazy::new(|| {
     access.insert(gas_api::GAS_ESTIMATE_FEE_CAP, Access::Read);
     access.insert(gas_api::GAS_ESTIMATE_MESSAGE_GAS, Access::Read);
 
+    // DB API
+    access.insert(db_api::DB_BACKUP, Access::Admin);
+    access.insert(db_api::DB_GC_STATUS, Access::Read);
+    access.insert(db_api::DB_GC_PAUSE, Access::Admin);
+    access.insert(db_api::DB_GC_RESUME, Access::Admin);
+    access.insert(db_api::DB_GC_ABORT, Access::Admin);
+
     // Common API
     access.insert(common_api::VERSION, Access::Read);
     access.insert(common_api::SHUTDOWN, Access::Admin);
     access.insert(common_api::START_TIME, Access::Read);
+    access.insert(common_api::LOG_LIST, Access::Admin);
+    access.insert(common_api::LOG_SET_LEVEL, Access::Admin);
 
     // Net API
     access.insert(net_api::NET_ADDRS_LISTEN, Access::Read);
@@ -178,6 +189,12 @@ pub mod chain_api {
 
     pub type ChainExportResult = Option<String>;
 
+    pub const CHAIN_EXPORT_STATUS: &str = "Filecoin.ChainExportStatus";
+
+    pub use crate::daemon::snapshot_export::{ExportRecord, SnapshotExportStatus};
+
+    lotus_json_with_self!(SnapshotExportStatus);
+
     pub const CHAIN_READ_OBJ: &str = "Filecoin.ChainReadObj";
     pub const CHAIN_HAS_OBJ: &str = "Filecoin.ChainHasObj";
     pub const CHAIN_GET_BLOCK_MESSAGES: &str = "Filecoin.ChainGetBlockMessages";
@@ -192,6 +209,31 @@ pub mod chain_api {
     pub const CHAIN_GET_PARENT_MESSAGES: &str = "Filecoin.ChainGetParentMessages";
 }
 
+/// Database API
+pub mod db_api {
+    use std::path::PathBuf;
+
+    use crate::lotus_json::lotus_json_with_self;
+    use serde::{Deserialize, Serialize};
+
+    pub use crate::db::{GcPhase, GcStatus};
+
+    pub const DB_BACKUP: &str = "Filecoin.DbBackup";
+    pub const DB_GC_STATUS: &str = "Filecoin.DbGcStatus";
+    pub const DB_GC_PAUSE: &str = "Filecoin.DbGcPause";
+    pub const DB_GC_RESUME: &str = "Filecoin.DbGcResume";
+    pub const DB_GC_ABORT: &str = "Filecoin.DbGcAbort";
+
+    #[derive(Debug, Clone, Serialize, Deserialize)]
+    pub struct DbBackupParams {
+        /// Directory on the node's file system to write the backup to
+        pub output_dir: PathBuf,
+        pub recent_roots: i64,
+    }
+
+    lotus_json_with_self!(DbBackupParams, GcStatus);
+}
+
 /// Message Pool API
 pub mod mpool_api {
     pub const MPOOL_PENDING: &str = "Filecoin.MpoolPending";
@@ -240,6 +282,7 @@ pub mod state_api {
     pub const STATE_ACCOUNT_KEY: &str = "Filecoin.StateAccountKey";
     pub const STATE_CIRCULATING_SUPPLY: &str = "Filecoin.StateCirculatingSupply";
     pub const STATE_DECODE_PARAMS: &str = "Filecoin.StateDecodeParams";
+    pub const STATE_ACTOR_PROOF: &str = "Filecoin.StateActorProof";
 }
 
 /// Gas API
@@ -257,6 +300,8 @@ pub mod common_api {
     pub const START_TIME: &str = "Filecoin.StartTime";
     pub const DISCOVER: &str = "Filecoin.Discover";
     pub const SESSION: &str = "Filecoin.Session";
+    pub const LOG_LIST: &str = "Filecoin.LogList";
+    pub const LOG_SET_LEVEL: &str = "Filecoin.LogSetLevel";
 }
 
 /// Net API
@@ -84,6 +84,14 @@ impl ApiInfo {
         RpcRequest::new(CHAIN_EXPORT, params)
     }
 
+    pub async fn chain_export_status(&self) -> Result<SnapshotExportStatus, JsonRpcError> {
+        self.call(Self::chain_export_status_req()).await
+    }
+
+    pub fn chain_export_status_req() -> RpcRequest<SnapshotExportStatus> {
+        RpcRequest::new(CHAIN_EXPORT_STATUS, ())
+    }
+
     #[allow(dead_code)]
     pub async fn chain_get_message(&self, cid: Cid) -> Result<Message, JsonRpcError> {
         self.call(Self::chain_get_message_req(cid)).await
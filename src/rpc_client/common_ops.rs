@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0, MIT
 
 use crate::rpc_api::{
-    common_api::{DISCOVER, SESSION, SHUTDOWN, START_TIME, VERSION},
+    common_api::{DISCOVER, LOG_LIST, LOG_SET_LEVEL, SESSION, SHUTDOWN, START_TIME, VERSION},
     data_types::{APIVersion, DiscoverResult},
 };
 use chrono::{DateTime, Utc};
@@ -34,6 +34,26 @@ impl ApiInfo {
         RpcRequest::new(DISCOVER, ())
     }
 
+    pub async fn log_list(&self) -> Result<Vec<String>, JsonRpcError> {
+        self.call(Self::log_list_req()).await
+    }
+
+    pub fn log_list_req() -> RpcRequest<Vec<String>> {
+        RpcRequest::new(LOG_LIST, ())
+    }
+
+    pub async fn log_set_level(
+        &self,
+        subsystem: String,
+        level: String,
+    ) -> Result<(), JsonRpcError> {
+        self.call(Self::log_set_level_req(subsystem, level)).await
+    }
+
+    pub fn log_set_level_req(subsystem: String, level: String) -> RpcRequest<()> {
+        RpcRequest::new(LOG_SET_LEVEL, (subsystem, level))
+    }
+
     pub fn session_req() -> RpcRequest<String> {
         RpcRequest::new(SESSION, ())
     }
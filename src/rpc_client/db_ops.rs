@@ -0,0 +1,50 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+use crate::rpc_api::db_api::{
+    DbBackupParams, GcStatus, DB_BACKUP, DB_GC_ABORT, DB_GC_PAUSE, DB_GC_RESUME, DB_GC_STATUS,
+};
+
+use super::{ApiInfo, JsonRpcError, RpcRequest};
+
+impl ApiInfo {
+    pub async fn db_backup(&self, params: DbBackupParams) -> Result<(), JsonRpcError> {
+        self.call(Self::db_backup_req(params)).await
+    }
+
+    pub fn db_backup_req(params: DbBackupParams) -> RpcRequest<()> {
+        RpcRequest::new(DB_BACKUP, params)
+    }
+
+    pub async fn db_gc_status(&self) -> Result<GcStatus, JsonRpcError> {
+        self.call(Self::db_gc_status_req()).await
+    }
+
+    pub fn db_gc_status_req() -> RpcRequest<GcStatus> {
+        RpcRequest::new(DB_GC_STATUS, ())
+    }
+
+    pub async fn db_gc_pause(&self) -> Result<(), JsonRpcError> {
+        self.call(Self::db_gc_pause_req()).await
+    }
+
+    pub fn db_gc_pause_req() -> RpcRequest<()> {
+        RpcRequest::new(DB_GC_PAUSE, ())
+    }
+
+    pub async fn db_gc_resume(&self) -> Result<(), JsonRpcError> {
+        self.call(Self::db_gc_resume_req()).await
+    }
+
+    pub fn db_gc_resume_req() -> RpcRequest<()> {
+        RpcRequest::new(DB_GC_RESUME, ())
+    }
+
+    pub async fn db_gc_abort(&self) -> Result<(), JsonRpcError> {
+        self.call(Self::db_gc_abort_req()).await
+    }
+
+    pub fn db_gc_abort_req() -> RpcRequest<()> {
+        RpcRequest::new(DB_GC_ABORT, ())
+    }
+}
@@ -4,6 +4,7 @@
 pub mod auth_ops;
 pub mod chain_ops;
 pub mod common_ops;
+pub mod db_ops;
 pub mod mpool_ops;
 pub mod net_ops;
 pub mod node_ops;
@@ -22,7 +23,7 @@ use crate::lotus_json::HasLotusJson;
 use crate::utils::net::global_http_client;
 use http::StatusCode;
 use jsonrpc_v2::{Id, RequestObject, V2};
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize};
 use tracing::debug;
 
 pub const API_INFO_KEY: &str = "FULLNODE_API_INFO";
@@ -96,24 +97,41 @@ impl ApiInfo {
             .with_id(0)
             .finish();
 
-        let api_url = multiaddress_to_url(&self.multiaddr);
+        self.send(&rpc_req).await.map(HasLotusJson::from_lotus_json)
+    }
 
-        debug!("Using JSON-RPC v2 HTTP URL: {}", api_url);
+    /// Sends a raw JSON-RPC request object, e.g. one read back from an RPC
+    /// capture file, and returns the result.
+    pub async fn send<R: DeserializeOwned>(
+        &self,
+        rpc_req: &RequestObject,
+    ) -> Result<R, JsonRpcError> {
+        let rpc_res: JsonRpcResponse<R> = match unix_socket_path(&self.multiaddr) {
+            Some(path) => {
+                debug!("Using JSON-RPC v2 over Unix socket: {}", path);
+                call_unix_socket(&path, rpc_req).await?
+            }
+            None => {
+                let api_url = multiaddress_to_url(&self.multiaddr);
 
-        let request = global_http_client().post(api_url).json(&rpc_req);
-        let request = match self.token.as_ref() {
-            Some(token) => request.header(http::header::AUTHORIZATION, token),
-            _ => request,
-        };
+                debug!("Using JSON-RPC v2 HTTP URL: {}", api_url);
 
-        let response = request.send().await?;
-        if response.status() == StatusCode::NOT_FOUND {
-            return Err(JsonRpcError::METHOD_NOT_FOUND);
-        }
-        let rpc_res: JsonRpcResponse<T::LotusJson> = response.json().await?;
+                let request = global_http_client().post(api_url).json(rpc_req);
+                let request = match self.token.as_ref() {
+                    Some(token) => request.header(http::header::AUTHORIZATION, token),
+                    _ => request,
+                };
+
+                let response = request.send().await?;
+                if response.status() == StatusCode::NOT_FOUND {
+                    return Err(JsonRpcError::METHOD_NOT_FOUND);
+                }
+                response.json().await?
+            }
+        };
 
         match rpc_res {
-            JsonRpcResponse::Result { result, .. } => Ok(HasLotusJson::from_lotus_json(result)),
+            JsonRpcResponse::Result { result, .. } => Ok(result),
             JsonRpcResponse::Error { error, .. } => Err(error),
         }
     }
@@ -201,6 +219,52 @@ struct Url {
     host: String,
 }
 
+/// Returns the socket path if the multi-address points to a Unix domain
+/// socket, e.g. `/unix/%2Fvar%2Frun%2Fforest.sock`.
+fn unix_socket_path(multiaddr: &Multiaddr) -> Option<String> {
+    multiaddr.iter().find_map(|protocol| match protocol {
+        Protocol::Unix(path) => Some(path.into_owned()),
+        _ => None,
+    })
+}
+
+/// Sends a single JSON-RPC request over a Unix domain socket. The daemon
+/// authorizes such connections by the socket file permissions, so no token is
+/// sent.
+async fn call_unix_socket<R: DeserializeOwned>(
+    path: &str,
+    rpc_req: &RequestObject,
+) -> Result<JsonRpcResponse<R>, JsonRpcError> {
+    fn to_rpc_error(e: impl fmt::Display) -> JsonRpcError {
+        JsonRpcError {
+            code: 0,
+            message: Cow::Owned(e.to_string()),
+        }
+    }
+
+    let stream = tokio::net::UnixStream::connect(path)
+        .await
+        .map_err(to_rpc_error)?;
+    let (mut sender, connection) = hyper::client::conn::handshake(stream)
+        .await
+        .map_err(to_rpc_error)?;
+    tokio::spawn(connection);
+    let request = http::Request::post(format!("/{RPC_ENDPOINT}"))
+        .header(http::header::CONTENT_TYPE, "application/json")
+        .body(hyper::Body::from(
+            serde_json::to_vec(rpc_req).map_err(to_rpc_error)?,
+        ))
+        .map_err(to_rpc_error)?;
+    let response = sender.send_request(request).await.map_err(to_rpc_error)?;
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(JsonRpcError::METHOD_NOT_FOUND);
+    }
+    let body = hyper::body::to_bytes(response.into_body())
+        .await
+        .map_err(to_rpc_error)?;
+    serde_json::from_slice(&body).map_err(to_rpc_error)
+}
+
 /// Parses a multi-address into a URL
 fn multiaddress_to_url(multiaddr: &Multiaddr) -> String {
     // Fold Multiaddress into a Url struct
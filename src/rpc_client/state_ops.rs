@@ -6,7 +6,7 @@ use std::path::PathBuf;
 use crate::{
     blocks::TipsetKeys,
     rpc_api::{
-        data_types::{ApiActorState, SectorOnChainInfo},
+        data_types::{ActorProof, ApiActorState, SectorOnChainInfo},
         state_api::*,
     },
     shim::{
@@ -37,6 +37,24 @@ impl ApiInfo {
         RpcRequest::new(STATE_GET_ACTOR, (address, head))
     }
 
+    pub async fn state_actor_proof(
+        &self,
+        address: Address,
+        path: Vec<String>,
+        head: TipsetKeys,
+    ) -> Result<ActorProof, JsonRpcError> {
+        self.call(Self::state_actor_proof_req(address, path, head))
+            .await
+    }
+
+    pub fn state_actor_proof_req(
+        address: Address,
+        path: Vec<String>,
+        head: TipsetKeys,
+    ) -> RpcRequest<ActorProof> {
+        RpcRequest::new(STATE_ACTOR_PROOF, (address, path, head))
+    }
+
     pub async fn state_fetch_root(
         &self,
         root: Cid,
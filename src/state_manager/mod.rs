@@ -4,6 +4,7 @@
 pub mod chain_rand;
 mod errors;
 mod metrics;
+pub mod proof;
 mod utils;
 use crate::chain_sync::SyncConfig;
 use crate::interpreter::{MessageCallbackCtx, VMTrace};
@@ -359,6 +360,10 @@ where
     #[instrument(skip(self))]
     pub async fn tipset_state(self: &Arc<Self>, tipset: &Arc<Tipset>) -> anyhow::Result<CidPair> {
         let key = tipset.key();
+        // Cached states are available, only look for missing ones on a cache miss.
+        if self.sync_config.archival && self.cache.get(key).is_none() {
+            self.recompute_missing_states(tipset).await?;
+        }
         self.cache
             .get_or_else(key, || async move {
                 let ts_state = self
@@ -370,6 +375,65 @@ where
             .await
     }
 
+    /// Archival nodes may lack the state of historical tipsets, e.g. when the
+    /// chain was bootstrapped from a snapshot and no archive snapshot covers the
+    /// requested epoch. Walks back to the closest ancestor whose parent state is
+    /// available, either cached or in the store, and recomputes the states from
+    /// there up to `tipset`.
+    ///
+    /// The walk is bounded by [`SyncConfig::max_recomputed_epochs`], and fails
+    /// before recomputing anything if the messages of a tipset are missing.
+    async fn recompute_missing_states(
+        self: &Arc<Self>,
+        tipset: &Arc<Tipset>,
+    ) -> anyhow::Result<()> {
+        let max_epochs = self.sync_config.max_recomputed_epochs;
+        let mut missing = vec![];
+        let mut current = Arc::clone(tipset);
+        while current.epoch() > 0
+            && self.cache.get(current.parents()).is_none()
+            && !self.blockstore().has(current.parent_state())?
+        {
+            anyhow::ensure!(
+                (missing.len() as i64) < max_epochs,
+                "state of epoch {} is more than {max_epochs} epochs away from any available state, \
+                 mount an archive snapshot covering it",
+                tipset.epoch(),
+            );
+            current = self
+                .chain_store()
+                .chain_index
+                .load_required_tipset(current.parents())?;
+            for block in current.blocks() {
+                anyhow::ensure!(
+                    self.blockstore().has(block.messages())?,
+                    "messages of epoch {} are missing, can't recompute the state of epoch {}",
+                    current.epoch(),
+                    tipset.epoch(),
+                );
+            }
+            missing.push(Arc::clone(&current));
+        }
+        if let Some(oldest) = missing.last() {
+            info!(
+                "Recomputing {} missing state roots from epoch {}",
+                missing.len(),
+                oldest.epoch()
+            );
+        }
+        for tipset in missing.into_iter().rev() {
+            let tipset = &tipset;
+            self.cache
+                .get_or_else(tipset.key(), || async move {
+                    Ok(self
+                        .compute_tipset_state(Arc::clone(tipset), NO_CALLBACK, VMTrace::NotTraced)
+                        .await?)
+                })
+                .await?;
+        }
+        Ok(())
+    }
+
     #[instrument(skip(self, rand))]
     fn call_raw(
         self: &Arc<Self>,
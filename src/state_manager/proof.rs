@@ -0,0 +1,639 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! State inclusion proofs.
+//!
+//! A proof is the set of blocks loaded while looking an actor up in a state
+//! tree, from the state root down to the actor (including the resolution of
+//! non-ID addresses through the init actor), and optionally down a path into
+//! the state of the actor. Anyone holding a trusted state root can check the
+//! proof by running the same lookup against the proof blocks alone, without
+//! trusting the node that produced it.
+//!
+//! Segments of the path are raw IPLD map keys and list indices, or keys of
+//! the HAMTs, KAMTs and AMTs found along the way, which are looked up the way
+//! the actors do, e.g. `hamt:f01234` or `kamt:0x2a` for a storage slot of an
+//! EVM contract. The verifier runs the same keyed lookups.
+//!
+//! Proofs are exchanged as CARv1 archives rooted at the state root.
+
+use crate::cid_collections::CidHashSet;
+use crate::db::MemoryDB;
+use crate::ipld::Ipld;
+use crate::shim::address::Address;
+use crate::shim::state_tree::{ActorState, StateTree};
+use crate::utils::db::car_stream::{CarBlock, CarHeader};
+use crate::utils::encoding::{extract_cids, from_slice_with_fallback};
+use anyhow::{bail, ensure, Context as _};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use integer_encoding::VarInt as _;
+use parking_lot::Mutex;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// Result of a lookup in a state tree.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProvenActor {
+    /// The actor, or `None` if the proof shows that there is no such actor.
+    #[serde(with = "crate::lotus_json")]
+    pub actor: Option<ActorState>,
+    /// The value at the requested path in the state of the actor.
+    #[serde(with = "crate::lotus_json")]
+    pub value: Option<Ipld>,
+}
+
+/// Proves the state of the actor at `address` in the state tree at
+/// `state_root`, and optionally the value at `path` in the state of the actor.
+/// Returns the result of the lookup and the blocks of the proof.
+pub fn prove_actor<DB: Blockstore>(
+    db: &DB,
+    state_root: &Cid,
+    address: &Address,
+    path: &[String],
+) -> anyhow::Result<(ProvenActor, Vec<CarBlock>)> {
+    let store = Arc::new(RecordingStore {
+        inner: db,
+        seen: Mutex::default(),
+        blocks: Mutex::default(),
+    });
+    let proven = lookup(store.clone(), state_root, address, path)?;
+    let blocks = std::mem::take(&mut *store.blocks.lock());
+    Ok((proven, blocks))
+}
+
+/// Checks a proof against a trusted `state_root`, and returns the proven
+/// actor. Fails if a block doesn't match its CID, if a block isn't reachable
+/// from the state root, or if the blocks don't hold the whole lookup.
+pub fn verify_actor_proof(
+    state_root: &Cid,
+    address: &Address,
+    path: &[String],
+    blocks: &[CarBlock],
+) -> anyhow::Result<ProvenActor> {
+    let db = Arc::new(MemoryDB::default());
+    for block in blocks {
+        ensure!(
+            block.valid(),
+            "proof block {} doesn't match its CID",
+            block.cid
+        );
+        db.put_keyed(&block.cid, &block.data)?;
+    }
+
+    let reachable = reachable_blocks(&db, *state_root)?;
+    if let Some(block) = blocks.iter().find(|it| !reachable.contains(&it.cid)) {
+        bail!(
+            "proof block {} isn't reachable from the state root",
+            block.cid
+        );
+    }
+
+    lookup(db, state_root, address, path).context("incomplete proof")
+}
+
+/// Encodes a proof as a CARv1 archive rooted at `state_root`.
+pub fn proof_to_car(state_root: &Cid, blocks: &[CarBlock]) -> anyhow::Result<Vec<u8>> {
+    let header = fvm_ipld_encoding::to_vec(&CarHeader {
+        roots: vec![*state_root],
+        version: 1,
+    })?;
+    let mut car = header.len().encode_var_vec();
+    car.extend(header);
+    for block in blocks {
+        block.write(&mut car)?;
+    }
+    Ok(car)
+}
+
+/// Decodes a proof encoded with [`proof_to_car`], returning the state root
+/// and the blocks.
+pub fn proof_from_car(mut car: &[u8]) -> anyhow::Result<(Cid, Vec<CarBlock>)> {
+    let header: CarHeader =
+        from_slice_with_fallback(next_frame(&mut car)?.context("missing CAR header")?)?;
+    ensure!(
+        header.version == 1,
+        "unsupported CAR version {}",
+        header.version
+    );
+    let [state_root] = header.roots.as_slice() else {
+        bail!("a proof must have a single root");
+    };
+    let mut blocks = vec![];
+    while let Some(frame) = next_frame(&mut car)? {
+        blocks.push(CarBlock::from_bytes(frame.to_vec())?);
+    }
+    Ok((*state_root, blocks))
+}
+
+/// Parses a path into the state of an actor, given as `/`-separated
+/// segments, e.g. `2/0` or `2/kamt:0x2a`. See [`PathSegment`].
+pub fn parse_path(path: &str) -> Vec<String> {
+    path.split('/')
+        .filter(|segment| !segment.is_empty())
+        .map(String::from)
+        .collect()
+}
+
+/// Bit width of the HAMTs of the builtin actors.
+const HAMT_BIT_WIDTH: u32 = 5;
+/// Bit width of the KAMT holding the storage of EVM contracts.
+const KAMT_BIT_WIDTH: u32 = 5;
+
+/// A segment of a path into the state of an actor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PathSegment {
+    /// A key of an IPLD map or an index of an IPLD list, e.g. `2`.
+    Field(String),
+    /// A key of a HAMT, hashed with SHA-256, given as an address or as
+    /// `0x`-prefixed hex bytes, e.g. `hamt:f01234`.
+    Hamt(Vec<u8>),
+    /// A 256-bit key of a KAMT, which is its own hash, given in decimal or as
+    /// `0x`-prefixed hex, e.g. `kamt:0x2a` for a storage slot of an EVM
+    /// contract.
+    Kamt([u8; 32]),
+    /// An index of an AMT, e.g. `amt:42`.
+    Amt(u64),
+}
+
+impl std::str::FromStr for PathSegment {
+    type Err = anyhow::Error;
+
+    fn from_str(segment: &str) -> anyhow::Result<Self> {
+        let parse_hex = |hex: &str| hex::decode(hex).context("invalid hex key");
+        Ok(match segment.split_once(':') {
+            Some(("hamt", key)) => match key.strip_prefix("0x") {
+                Some(hex) => PathSegment::Hamt(parse_hex(hex)?),
+                None => PathSegment::Hamt(
+                    Address::from_str(key)
+                        .with_context(|| format!("invalid HAMT key {key}"))?
+                        .to_bytes(),
+                ),
+            },
+            Some(("kamt", key)) => {
+                let slot = match key.strip_prefix("0x") {
+                    Some(hex) => num::BigUint::parse_bytes(hex.as_bytes(), 16),
+                    None => num::BigUint::parse_bytes(key.as_bytes(), 10),
+                }
+                .with_context(|| format!("invalid KAMT key {key}"))?
+                .to_bytes_be();
+                ensure!(slot.len() <= 32, "KAMT key {key} is longer than 256 bits");
+                let mut padded = [0; 32];
+                padded[32 - slot.len()..].copy_from_slice(&slot);
+                PathSegment::Kamt(padded)
+            }
+            Some(("amt", index)) => PathSegment::Amt(index.parse().context("invalid AMT index")?),
+            _ => PathSegment::Field(segment.to_owned()),
+        })
+    }
+}
+
+/// Splits the next varint-prefixed frame off `car`.
+fn next_frame<'a>(car: &mut &'a [u8]) -> anyhow::Result<Option<&'a [u8]>> {
+    if car.is_empty() {
+        return Ok(None);
+    }
+    let (len, offset) = usize::decode_var(car).context("invalid CAR frame length")?;
+    ensure!(car.len() - offset >= len, "truncated CAR frame");
+    let (frame, rest) = car[offset..].split_at(len);
+    *car = rest;
+    Ok(Some(frame))
+}
+
+/// Looks the actor up, and follows `path` into its state. Each segment of the
+/// path is a map key, a list index or a key of a HAMT, KAMT or AMT. Links are
+/// followed along the way.
+fn lookup<DB: Blockstore>(
+    db: Arc<DB>,
+    state_root: &Cid,
+    address: &Address,
+    path: &[String],
+) -> anyhow::Result<ProvenActor> {
+    let state_tree = StateTree::new_from_root(db.clone(), state_root)?;
+    let Some(actor) = state_tree.get_actor(address)? else {
+        ensure!(path.is_empty(), "actor {address} not found");
+        return Ok(ProvenActor {
+            actor: None,
+            value: None,
+        });
+    };
+    if path.is_empty() {
+        return Ok(ProvenActor {
+            actor: Some(actor),
+            value: None,
+        });
+    }
+
+    let mut value = Ipld::Link(actor.state);
+    for segment in path {
+        value = match segment.parse()? {
+            PathSegment::Field(key) => match resolve_link(&db, value)? {
+                Ipld::Map(mut map) => map.remove(&key),
+                Ipld::List(mut list) => key
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|index| *index < list.len())
+                    .map(|index| list.swap_remove(index)),
+                _ => None,
+            },
+            PathSegment::Hamt(key) => {
+                use sha2::Digest as _;
+                let hash = sha2::Sha256::digest(&key);
+                hamt_get(&db, value, &hash, &key, HAMT_BIT_WIDTH)?
+            }
+            PathSegment::Kamt(key) => {
+                // Keys are stored as big-endian integers without leading zeros.
+                let stored_key = &key[key.iter().take_while(|byte| **byte == 0).count()..];
+                hamt_get(&db, value, &key, stored_key, KAMT_BIT_WIDTH)?
+            }
+            PathSegment::Amt(index) => amt_get(&db, value, index)?,
+        }
+        .with_context(|| format!("path segment {segment} not found"))?;
+    }
+    Ok(ProvenActor {
+        actor: Some(actor),
+        value: Some(resolve_link(&db, value)?),
+    })
+}
+
+fn resolve_link(db: &impl Blockstore, value: Ipld) -> anyhow::Result<Ipld> {
+    match value {
+        Ipld::Link(cid) if cid.codec() == fvm_ipld_encoding::DAG_CBOR => {
+            let data = db
+                .get(&cid)?
+                .with_context(|| format!("missing block {cid}"))?;
+            from_slice_with_fallback(&data)
+        }
+        value => Ok(value),
+    }
+}
+
+/// Looks `key` up in the HAMT or KAMT rooted at `root`, following `hash` bit
+/// by bit. A node is a bitfield of its populated slots and a pointer per slot,
+/// either a link to a child node or a bucket of key-value pairs. In a KAMT, a
+/// link may carry an extension, the bits shared by every key below it. These
+/// bits are skipped rather than compared: a key which doesn't share them isn't
+/// stored below the link, so the lookup ends up in the same result.
+fn hamt_get(
+    db: &impl Blockstore,
+    root: Ipld,
+    hash: &[u8],
+    key: &[u8],
+    bit_width: u32,
+) -> anyhow::Result<Option<Ipld>> {
+    let mut node = resolve_link(db, root)?;
+    let mut consumed = 0;
+    loop {
+        let Ipld::List(fields) = node else {
+            bail!("invalid HAMT node");
+        };
+        let [Ipld::Bytes(bitfield), Ipld::List(mut pointers)] =
+            <[Ipld; 2]>::try_from(fields).map_err(|_| anyhow::anyhow!("invalid HAMT node"))?
+        else {
+            bail!("invalid HAMT node");
+        };
+        let slot = next_hash_bits(hash, &mut consumed, bit_width)?;
+        // The bitfield is a big-endian integer.
+        let is_set = |bit: u32| {
+            let byte = (bit / 8) as usize;
+            byte < bitfield.len() && bitfield[bitfield.len() - 1 - byte] & (1 << (bit % 8)) != 0
+        };
+        if !is_set(slot) {
+            return Ok(None);
+        }
+        let position = (0..slot).filter(|bit| is_set(*bit)).count();
+        ensure!(position < pointers.len(), "invalid HAMT node");
+        node = match pointers.swap_remove(position) {
+            link @ Ipld::Link(_) => resolve_link(db, link)?,
+            Ipld::List(items) if items.iter().any(|item| matches!(item, Ipld::Link(_))) => {
+                let mut link = None;
+                for item in items {
+                    match item {
+                        Ipld::Link(cid) => link = Some(cid),
+                        extension => consumed += extension_length(&extension)?,
+                    }
+                }
+                resolve_link(db, Ipld::Link(link.context("invalid KAMT link")?))?
+            }
+            Ipld::List(bucket) => {
+                for pair in bucket {
+                    if let Ipld::List(pair) = pair {
+                        if let [Ipld::Bytes(stored_key), value] = pair.as_slice() {
+                            if stored_key == key {
+                                return Ok(Some(value.clone()));
+                            }
+                        }
+                    }
+                }
+                return Ok(None);
+            }
+            _ => bail!("invalid HAMT pointer"),
+        };
+    }
+}
+
+/// Returns the next `bit_width` bits of `hash`, most significant first, or the
+/// remaining ones at the end of the hash.
+fn next_hash_bits(hash: &[u8], consumed: &mut u32, bit_width: u32) -> anyhow::Result<u32> {
+    let remaining = (hash.len() as u32 * 8).saturating_sub(*consumed);
+    ensure!(remaining > 0, "HAMT deeper than its hash");
+    let mut bits = 0;
+    for _ in 0..bit_width.min(remaining) {
+        let bit = (hash[(*consumed / 8) as usize] >> (7 - *consumed % 8)) & 1;
+        bits = (bits << 1) | u32::from(bit);
+        *consumed += 1;
+    }
+    Ok(bits)
+}
+
+/// Number of hash bits covered by a KAMT extension, which holds the number of
+/// bits along with the bits themselves.
+fn extension_length(extension: &Ipld) -> anyhow::Result<u32> {
+    match extension {
+        Ipld::Integer(length) => Ok(u32::try_from(*length)?),
+        Ipld::List(fields) => fields
+            .iter()
+            .find_map(|field| match field {
+                Ipld::Integer(length) => Some(u32::try_from(*length)),
+                _ => None,
+            })
+            .context("invalid KAMT extension")?
+            .map_err(Into::into),
+        _ => Ok(0),
+    }
+}
+
+/// Looks `index` up in the AMT rooted at `root`. The root holds the bit width
+/// and height of the tree, and each node a little-endian bitmap of its
+/// populated slots, the links to its children and the values of a leaf.
+fn amt_get(db: &impl Blockstore, root: Ipld, index: u64) -> anyhow::Result<Option<Ipld>> {
+    let Ipld::List(root) = resolve_link(db, root)? else {
+        bail!("invalid AMT root");
+    };
+    let [Ipld::Integer(bit_width), Ipld::Integer(height), _, node] =
+        <[Ipld; 4]>::try_from(root).map_err(|_| anyhow::anyhow!("invalid AMT root"))?
+    else {
+        bail!("invalid AMT root");
+    };
+    let bit_width = u32::try_from(bit_width)?;
+    let mut height = u32::try_from(height)?;
+    // Number of indices below a slot of a node at `height`.
+    let slot_span = |height: u32| 1_u64.checked_shl(bit_width * height).unwrap_or(u64::MAX);
+    if index / slot_span(height) >= slot_span(1) {
+        return Ok(None);
+    }
+
+    let mut node = node;
+    let mut index = index;
+    loop {
+        let Ipld::List(fields) = node else {
+            bail!("invalid AMT node");
+        };
+        let [Ipld::Bytes(bitmap), Ipld::List(mut links), Ipld::List(mut values)] =
+            <[Ipld; 3]>::try_from(fields).map_err(|_| anyhow::anyhow!("invalid AMT node"))?
+        else {
+            bail!("invalid AMT node");
+        };
+        let slot = index / slot_span(height);
+        index %= slot_span(height);
+        let is_set = |bit: u64| {
+            let byte = (bit / 8) as usize;
+            byte < bitmap.len() && bitmap[byte] & (1 << (bit % 8)) != 0
+        };
+        if !is_set(slot) {
+            return Ok(None);
+        }
+        let position = (0..slot).filter(|bit| is_set(*bit)).count();
+        if height == 0 {
+            ensure!(position < values.len(), "invalid AMT node");
+            return Ok(Some(values.swap_remove(position)));
+        }
+        ensure!(position < links.len(), "invalid AMT node");
+        node = resolve_link(db, links.swap_remove(position))?;
+        height -= 1;
+    }
+}
+
+/// CIDs of the blocks of `db` reachable from `root` through blocks of `db`.
+fn reachable_blocks(db: &impl Blockstore, root: Cid) -> anyhow::Result<CidHashSet> {
+    let mut reachable = CidHashSet::default();
+    let mut stack = vec![root];
+    while let Some(cid) = stack.pop() {
+        if let Some(data) = db.get(&cid)? {
+            if reachable.insert(cid) && cid.codec() == fvm_ipld_encoding::DAG_CBOR {
+                stack.extend(extract_cids(&data)?);
+            }
+        }
+    }
+    Ok(reachable)
+}
+
+/// Read-only block store recording the blocks it returns.
+struct RecordingStore<'a, DB> {
+    inner: &'a DB,
+    seen: Mutex<CidHashSet>,
+    blocks: Mutex<Vec<CarBlock>>,
+}
+
+impl<DB: Blockstore> Blockstore for RecordingStore<'_, DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        let data = self.inner.get(k)?;
+        if let Some(data) = &data {
+            if self.seen.lock().insert(*k) {
+                self.blocks.lock().push(CarBlock {
+                    cid: *k,
+                    data: data.clone(),
+                });
+            }
+        }
+        Ok(data)
+    }
+
+    fn put_keyed(&self, _k: &Cid, _block: &[u8]) -> anyhow::Result<()> {
+        bail!("state proofs are read-only")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::shim::econ::TokenAmount;
+    use crate::shim::state_tree::StateTreeVersion;
+    use crate::utils::db::CborStoreExt as _;
+    use fil_actor_account_state::v10::State as AccountState;
+
+    // Mainnet v10 account actor
+    const ACCOUNT_CODE: &str = "bafk2bzaceampw4romta75hyz5p4cqriypmpbgnkxncgxgqn6zptv5lsp2w2bo";
+
+    fn state_root(db: &Arc<MemoryDB>, actors: u64) -> Cid {
+        let mut tree = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+        for id in 100..100 + actors {
+            let state = db
+                .put_cbor_default(&AccountState {
+                    address: Address::new_id(id).into(),
+                })
+                .unwrap();
+            let actor = ActorState::new(
+                Cid::try_from(ACCOUNT_CODE).unwrap(),
+                state,
+                TokenAmount::from_atto(id),
+                0,
+                None,
+            );
+            tree.set_actor(&Address::new_id(id), actor).unwrap();
+        }
+        tree.flush().unwrap()
+    }
+
+    #[test]
+    fn prove_and_verify_actor() {
+        let db = Arc::new(MemoryDB::default());
+        let root = state_root(&db, 1000);
+        let address = Address::new_id(500);
+        let path = vec!["0".to_string()];
+
+        let (proven, blocks) = prove_actor(&db, &root, &address, &path).unwrap();
+        let actor = proven.actor.clone().unwrap();
+        assert_eq!(actor.balance, TokenAmount::from_atto(500).into());
+        // The account state is a tuple holding the address.
+        assert_eq!(proven.value, Some(Ipld::Bytes(address.to_bytes())));
+        // A proof only holds the path to the actor.
+        assert!(blocks.len() < 10);
+
+        let car = proof_to_car(&root, &blocks).unwrap();
+        let (car_root, car_blocks) = proof_from_car(&car).unwrap();
+        assert_eq!((car_root, &car_blocks), (root, &blocks));
+        assert_eq!(
+            verify_actor_proof(&root, &address, &path, &blocks).unwrap(),
+            proven
+        );
+
+        // Another actor isn't covered by the proof.
+        verify_actor_proof(&root, &Address::new_id(900), &[], &blocks).unwrap_err();
+        // Neither is another state root.
+        let other_root = state_root(&db, 999);
+        verify_actor_proof(&other_root, &address, &[], &blocks).unwrap_err();
+        // Tampered blocks are rejected.
+        let mut tampered = blocks.clone();
+        tampered[0].data.push(0);
+        verify_actor_proof(&root, &address, &[], &tampered).unwrap_err();
+        // Unrelated blocks are rejected.
+        let (_, unrelated) = prove_actor(&db, &other_root, &address, &[]).unwrap();
+        let mut padded = blocks.clone();
+        padded.extend(unrelated);
+        verify_actor_proof(&root, &address, &[], &padded).unwrap_err();
+    }
+
+    #[test]
+    fn parse_paths() {
+        assert!(parse_path("").is_empty());
+        assert_eq!(parse_path("/2/0/"), ["2", "0"]);
+
+        let parse = |segment: &str| segment.parse::<PathSegment>().unwrap();
+        assert_eq!(parse("2"), PathSegment::Field("2".into()));
+        assert_eq!(
+            parse("hamt:f0100"),
+            PathSegment::Hamt(Address::new_id(100).to_bytes())
+        );
+        assert_eq!(parse("hamt:0x0164"), PathSegment::Hamt(vec![1, 100]));
+        assert_eq!(parse("amt:42"), PathSegment::Amt(42));
+        let mut slot = [0; 32];
+        slot[31] = 42;
+        assert_eq!(parse("kamt:42"), PathSegment::Kamt(slot));
+        assert_eq!(parse("kamt:0x2a"), PathSegment::Kamt(slot));
+        assert!(format!("kamt:0x{}", "ff".repeat(33))
+            .parse::<PathSegment>()
+            .is_err());
+    }
+
+    // An actor whose state holds a HAMT keyed by addresses, an AMT and a
+    // single-node KAMT, like the storage of an EVM contract.
+    fn collections_root(db: &Arc<MemoryDB>, slot: [u8; 32]) -> Cid {
+        use fil_actors_shared::fvm_ipld_hamt::BytesKey;
+        use fil_actors_shared::v12::{make_empty_map, Array};
+
+        let mut map = make_empty_map::<_, u64>(db.as_ref(), HAMT_BIT_WIDTH);
+        for id in 0..500 {
+            map.set(BytesKey(Address::new_id(id).to_bytes()), id)
+                .unwrap();
+        }
+        let hamt = map.flush().unwrap();
+        let mut array = Array::<u64, _>::new(db.as_ref());
+        for index in (0..1000).step_by(3) {
+            array.set(index, index * 2).unwrap();
+        }
+        let amt = array.flush().unwrap();
+        let mut consumed = 0;
+        let kamt_slot = next_hash_bits(&slot, &mut consumed, KAMT_BIT_WIDTH).unwrap();
+        let bitfield = (1_u32 << kamt_slot).to_be_bytes();
+        let kamt = db
+            .put_cbor_default(&Ipld::List(vec![
+                Ipld::Bytes(bitfield.to_vec()),
+                Ipld::List(vec![Ipld::List(vec![Ipld::List(vec![
+                    Ipld::Bytes(vec![42]),
+                    Ipld::Bytes(vec![7]),
+                ])])]),
+            ]))
+            .unwrap();
+        let state = db.put_cbor_default(&(hamt, amt, kamt)).unwrap();
+
+        let mut tree = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+        let actor = ActorState::new(
+            Cid::try_from(ACCOUNT_CODE).unwrap(),
+            state,
+            TokenAmount::from_atto(1),
+            0,
+            None,
+        );
+        tree.set_actor(&Address::new_id(100), actor).unwrap();
+        tree.flush().unwrap()
+    }
+
+    #[test]
+    fn prove_keyed_collection_entries() {
+        let db = Arc::new(MemoryDB::default());
+        let mut slot = [0; 32];
+        slot[31] = 42;
+        let root = collections_root(&db, slot);
+        let address = Address::new_id(100);
+
+        for (path, expected) in [
+            ("0/hamt:f0321", Ipld::Integer(321)),
+            ("1/amt:999", Ipld::Integer(1998)),
+            ("2/kamt:42", Ipld::Bytes(vec![7])),
+        ] {
+            let path = parse_path(path);
+            let (proven, blocks) = prove_actor(&db, &root, &address, &path).unwrap();
+            assert_eq!(proven.value, Some(expected));
+            assert_eq!(
+                verify_actor_proof(&root, &address, &path, &blocks).unwrap(),
+                proven
+            );
+        }
+
+        // Missing entries.
+        for path in ["0/hamt:f0500", "1/amt:998", "1/amt:100000", "2/kamt:43"] {
+            prove_actor(&db, &root, &address, &parse_path(path)).unwrap_err();
+        }
+
+        // A proof for one key doesn't cover another one.
+        let (_, blocks) = prove_actor(&db, &root, &address, &parse_path("0/hamt:f0321")).unwrap();
+        verify_actor_proof(&root, &address, &parse_path("0/hamt:f0123"), &blocks).unwrap_err();
+    }
+
+    #[test]
+    fn prove_missing_actor() {
+        let db = Arc::new(MemoryDB::default());
+        let root = state_root(&db, 10);
+        let address = Address::new_id(5000);
+        let (proven, blocks) = prove_actor(&db, &root, &address, &[]).unwrap();
+        assert_eq!(proven.actor, None);
+        assert_eq!(
+            verify_actor_proof(&root, &address, &[], &blocks)
+                .unwrap()
+                .actor,
+            None
+        );
+    }
+}
@@ -1,6 +1,7 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+mod report;
 mod resolve;
 
 use std::{
@@ -25,6 +26,7 @@ use fil_actor_interface::{
 };
 use fvm_ipld_blockstore::Blockstore;
 use libipld_core::ipld::Ipld;
+pub use report::{state_diff, ActorChange, ActorSummary, StateDiff};
 use resolve::resolve_cids_recursive;
 use serde::{Deserialize, Serialize};
 use similar::{ChangeTag, TextDiff};
@@ -129,54 +131,65 @@ fn pp_actor_state(
 ) -> Result<String, anyhow::Error> {
     let mut buffer = String::new();
     writeln!(&mut buffer, "{actor_state:?}")?;
-    if let Ok(miner_state) = MinerState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{miner_state:?}")?;
+    if let Some(state) = builtin_actor_state(bs, actor_state, false) {
+        buffer.push_str(&state);
         return Ok(buffer);
     }
-    if let Ok(cron_state) = CronState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{cron_state:?}")?;
-        return Ok(buffer);
+
+    let resolved = actor_to_resolved(bs, actor_state, depth);
+    buffer = serde_json::to_string_pretty(&resolved)?;
+    Ok(buffer)
+}
+
+/// Decodes the state of a builtin actor and formats it with [`std::fmt::Debug`],
+/// pretty-printed if `pretty` is set. Returns `None` for other actors.
+fn builtin_actor_state(
+    bs: &impl Blockstore,
+    actor_state: &ActorState,
+    pretty: bool,
+) -> Option<String> {
+    fn debug(state: &impl std::fmt::Debug, pretty: bool) -> Option<String> {
+        Some(match pretty {
+            true => format!("{state:#?}"),
+            false => format!("{state:?}"),
+        })
     }
-    if let Ok(account_state) = AccountState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{account_state:?}")?;
-        return Ok(buffer);
+
+    let (code, state) = (actor_state.code, actor_state.state);
+    if let Ok(miner_state) = MinerState::load(bs, code, state) {
+        return debug(&miner_state, pretty);
     }
-    if let Ok(power_state) = PowerState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{power_state:?}")?;
-        return Ok(buffer);
+    if let Ok(cron_state) = CronState::load(bs, code, state) {
+        return debug(&cron_state, pretty);
     }
-    if let Ok(init_state) = InitState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{init_state:?}")?;
-        return Ok(buffer);
+    if let Ok(account_state) = AccountState::load(bs, code, state) {
+        return debug(&account_state, pretty);
     }
-    if let Ok(reward_state) = RewardState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{reward_state:?}")?;
-        return Ok(buffer);
+    if let Ok(power_state) = PowerState::load(bs, code, state) {
+        return debug(&power_state, pretty);
     }
-    if let Ok(system_state) = SystemState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{system_state:?}")?;
-        return Ok(buffer);
+    if let Ok(init_state) = InitState::load(bs, code, state) {
+        return debug(&init_state, pretty);
     }
-    if let Ok(multi_sig_state) = MultiSigState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{multi_sig_state:?}")?;
-        return Ok(buffer);
+    if let Ok(reward_state) = RewardState::load(bs, code, state) {
+        return debug(&reward_state, pretty);
     }
-    if let Ok(market_state) = MarketState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{market_state:?}")?;
-        return Ok(buffer);
+    if let Ok(system_state) = SystemState::load(bs, code, state) {
+        return debug(&system_state, pretty);
     }
-    if let Ok(datacap_state) = DatacapState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{datacap_state:?}")?;
-        return Ok(buffer);
+    if let Ok(multi_sig_state) = MultiSigState::load(bs, code, state) {
+        return debug(&multi_sig_state, pretty);
     }
-    if let Ok(evm_state) = EvmState::load(bs, actor_state.code, actor_state.state) {
-        write!(&mut buffer, "{evm_state:?}")?;
-        return Ok(buffer);
+    if let Ok(market_state) = MarketState::load(bs, code, state) {
+        return debug(&market_state, pretty);
     }
-
-    let resolved = actor_to_resolved(bs, actor_state, depth);
-    buffer = serde_json::to_string_pretty(&resolved)?;
-    Ok(buffer)
+    if let Ok(datacap_state) = DatacapState::load(bs, code, state) {
+        return debug(&datacap_state, pretty);
+    }
+    if let Ok(evm_state) = EvmState::load(bs, code, state) {
+        return debug(&evm_state, pretty);
+    }
+    None
 }
 
 fn print_diffs(handle: &mut impl Write, diffs: TextDiff<str>) -> std::io::Result<()> {
@@ -0,0 +1,250 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Machine-readable report of the differences between two state trees.
+
+use super::{builtin_actor_state, root_to_state_map};
+use crate::shim::{
+    address::Address,
+    econ::TokenAmount,
+    state_tree::{ActorState, StateTree},
+};
+use cid::Cid;
+use fvm_ipld_blockstore::Blockstore;
+use serde::Serialize;
+use similar::{ChangeTag, TextDiff};
+use std::sync::Arc;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct StateDiff {
+    #[serde(with = "crate::lotus_json")]
+    pub from: Cid,
+    #[serde(with = "crate::lotus_json")]
+    pub to: Cid,
+    pub created: Vec<ActorSummary>,
+    pub deleted: Vec<ActorSummary>,
+    pub modified: Vec<ActorChange>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ActorSummary {
+    #[serde(with = "crate::lotus_json")]
+    pub address: Address,
+    #[serde(with = "crate::lotus_json")]
+    pub code: Cid,
+    #[serde(with = "crate::lotus_json")]
+    pub balance: TokenAmount,
+    pub nonce: u64,
+}
+
+impl ActorSummary {
+    fn new(address: Address, actor: &ActorState) -> Self {
+        Self {
+            address,
+            code: actor.code,
+            balance: actor.balance.clone().into(),
+            nonce: actor.sequence,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ActorChange {
+    #[serde(with = "crate::lotus_json")]
+    pub address: Address,
+    #[serde(with = "crate::lotus_json")]
+    pub from_code: Cid,
+    #[serde(with = "crate::lotus_json")]
+    pub to_code: Cid,
+    #[serde(with = "crate::lotus_json")]
+    pub from_balance: TokenAmount,
+    #[serde(with = "crate::lotus_json")]
+    pub to_balance: TokenAmount,
+    #[serde(with = "crate::lotus_json")]
+    pub balance_delta: TokenAmount,
+    pub from_nonce: u64,
+    pub to_nonce: u64,
+    pub state_changed: bool,
+    /// Changed lines of the decoded states, prefixed with `-` for the old
+    /// state and `+` for the new one. Only set for builtin actors, when
+    /// requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fields: Option<Vec<String>>,
+}
+
+/// Lists the actors created, deleted and modified between the `from` and `to`
+/// state roots. With `fields`, the changes of the decoded states of builtin
+/// actors are included.
+pub fn state_diff<BS: Blockstore>(
+    bs: &Arc<BS>,
+    from: &Cid,
+    to: &Cid,
+    fields: bool,
+) -> anyhow::Result<StateDiff> {
+    let mut from_actors = root_to_state_map(bs, from)?;
+    let mut created = vec![];
+    let mut modified = vec![];
+
+    if from != to {
+        StateTree::new_from_root(bs.clone(), to)?.for_each(|address, actor| {
+            match from_actors.remove(&address) {
+                None => created.push(ActorSummary::new(address, actor)),
+                Some(old) if &old != actor => {
+                    let (from_balance, to_balance): (TokenAmount, TokenAmount) =
+                        (old.balance.clone().into(), actor.balance.clone().into());
+                    let state_changed = old.state != actor.state;
+                    modified.push(ActorChange {
+                        address,
+                        from_code: old.code,
+                        to_code: actor.code,
+                        balance_delta: to_balance.clone() - &from_balance,
+                        from_balance,
+                        to_balance,
+                        from_nonce: old.sequence,
+                        to_nonce: actor.sequence,
+                        state_changed,
+                        fields: match fields && state_changed {
+                            true => field_diff(bs, &old, actor),
+                            false => None,
+                        },
+                    });
+                }
+                Some(_) => {}
+            }
+            Ok(())
+        })?;
+    } else {
+        from_actors.clear();
+    }
+
+    let mut deleted = from_actors
+        .iter()
+        .map(|(address, actor)| ActorSummary::new(*address, actor))
+        .collect::<Vec<_>>();
+    created.sort_by_key(|it| it.address);
+    deleted.sort_by_key(|it| it.address);
+    modified.sort_by_key(|it| it.address);
+    Ok(StateDiff {
+        from: *from,
+        to: *to,
+        created,
+        deleted,
+        modified,
+    })
+}
+
+fn field_diff(bs: &impl Blockstore, from: &ActorState, to: &ActorState) -> Option<Vec<String>> {
+    let from = builtin_actor_state(bs, from, true)?;
+    let to = builtin_actor_state(bs, to, true)?;
+    let diff = TextDiff::from_lines(&from, &to);
+    Some(
+        diff.iter_all_changes()
+            .filter_map(|change| {
+                let sign = match change.tag() {
+                    ChangeTag::Delete => '-',
+                    ChangeTag::Insert => '+',
+                    ChangeTag::Equal => return None,
+                };
+                Some(format!("{sign}{}", change.value().trim()))
+            })
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::shim::state_tree::StateTreeVersion;
+    use crate::utils::db::CborStoreExt as _;
+    use fil_actor_account_state::v10::State as AccountState;
+
+    // Mainnet v10 account actor
+    const ACCOUNT_CODE: &str = "bafk2bzaceampw4romta75hyz5p4cqriypmpbgnkxncgxgqn6zptv5lsp2w2bo";
+
+    fn account(db: &MemoryDB, id: u64, balance: u64, nonce: u64) -> ActorState {
+        let state = db
+            .put_cbor_default(&AccountState {
+                address: Address::new_id(id + 100).into(),
+            })
+            .unwrap();
+        ActorState::new(
+            Cid::try_from(ACCOUNT_CODE).unwrap(),
+            state,
+            TokenAmount::from_atto(balance),
+            nonce,
+            None,
+        )
+    }
+
+    fn state_root(db: &Arc<MemoryDB>, actors: &[(u64, ActorState)]) -> Cid {
+        let mut tree = StateTree::new(db.clone(), StateTreeVersion::V5).unwrap();
+        for (id, actor) in actors {
+            tree.set_actor(&Address::new_id(*id), actor.clone())
+                .unwrap();
+        }
+        tree.flush().unwrap()
+    }
+
+    #[test]
+    fn created_deleted_and_modified_actors() {
+        let db = Arc::new(MemoryDB::default());
+        let from = state_root(
+            &db,
+            &[
+                (1, account(&db, 1, 10, 0)),
+                (2, account(&db, 2, 20, 0)),
+                (3, account(&db, 3, 30, 0)),
+            ],
+        );
+        let to = state_root(
+            &db,
+            &[
+                (1, account(&db, 5, 10, 0)),
+                (2, account(&db, 2, 15, 1)),
+                (4, account(&db, 4, 40, 0)),
+            ],
+        );
+
+        let diff = state_diff(&db, &from, &to, true).unwrap();
+        assert_eq!(
+            diff.created,
+            vec![ActorSummary::new(
+                Address::new_id(4),
+                &account(&db, 4, 40, 0)
+            )]
+        );
+        assert_eq!(
+            diff.deleted,
+            vec![ActorSummary::new(
+                Address::new_id(3),
+                &account(&db, 3, 30, 0)
+            )]
+        );
+        let [state_change, balance_change] = diff.modified.as_slice() else {
+            panic!("expected two modified actors");
+        };
+        assert_eq!(state_change.address, Address::new_id(1));
+        assert!(state_change.state_changed);
+        assert_eq!(state_change.balance_delta, TokenAmount::default());
+        let fields = state_change.fields.as_ref().unwrap();
+        assert!(fields
+            .iter()
+            .any(|it| it.starts_with('-') && it.contains("101")));
+        assert!(fields
+            .iter()
+            .any(|it| it.starts_with('+') && it.contains("105")));
+
+        assert_eq!(balance_change.address, Address::new_id(2));
+        assert_eq!(balance_change.balance_delta, TokenAmount::from_atto(-5));
+        assert_eq!((balance_change.from_nonce, balance_change.to_nonce), (0, 1));
+        assert!(!balance_change.state_changed);
+        assert_eq!(balance_change.fields, None);
+
+        let same = state_diff(&db, &from, &from, true).unwrap();
+        assert!(same.created.is_empty() && same.deleted.is_empty() && same.modified.is_empty());
+    }
+}
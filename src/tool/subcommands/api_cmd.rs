@@ -1,13 +1,21 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
-use ahash::HashMap;
+use ahash::{HashMap, HashSet};
+use anyhow::{bail, Context as _};
+use cid::Cid;
 use clap::Subcommand;
 use fil_actors_shared::v10::runtime::DomainSeparationTag;
-use serde::de::DeserializeOwned;
+use rand::{rngs::StdRng, seq::IteratorRandom as _, SeedableRng as _};
+use serde::{de::DeserializeOwned, Deserialize};
+use similar::TextDiff;
+use std::fmt::Display;
+use std::fs::File;
+use std::io::{BufRead as _, BufReader};
 use std::path::PathBuf;
 use std::str::FromStr;
 use tabled::{builder::Builder, settings::Style};
+use tracing::info;
 
 use crate::blocks::Tipset;
 use crate::blocks::TipsetKeys;
@@ -15,6 +23,11 @@ use crate::cid_collections::CidHashSet;
 use crate::db::car::ManyCar;
 use crate::lotus_json::HasLotusJson;
 use crate::message::Message as _;
+use crate::rpc::audit::CapturedCall;
+use crate::rpc_api::{
+    auth_api, beacon_api, chain_api, common_api, db_api, gas_api, mpool_api, net_api, node_api,
+    state_api, sync_api, wallet_api, Access, ACCESS_MAP,
+};
 use crate::rpc_client::{ApiInfo, JsonRpcError, RpcRequest};
 use crate::shim::address::Address;
 
@@ -37,6 +50,48 @@ pub enum ApiCommands {
         /// Cancel test run on the first failure
         #[arg(long)]
         fail_fast: bool,
+        /// Number of tipsets sampled from the snapshot to generate tests
+        #[arg(long, default_value_t = 20)]
+        n_tipsets: usize,
+        /// Seed for sampling tipsets. Random if not set, and reported so that
+        /// the run can be reproduced.
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Report format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        report_format: ReportFormat,
+        /// Write the report to this file instead of stdout
+        #[arg(long)]
+        report_file: Option<PathBuf>,
+        /// JSON file with per-method tolerances, e.g.
+        /// `{"Filecoin.ChainGetBlock": {"ignore_fields": ["Timestamp"], "ignore_order": true}}`
+        #[arg(long)]
+        policy: Option<PathBuf>,
+    },
+    /// Replay an RPC capture file (see `rpc_capture_file` in the daemon
+    /// configuration) against a node and compare the results with the captured
+    /// responses
+    Replay {
+        /// Capture file, one JSON request/response pair per line
+        capture_file: PathBuf,
+        /// Address of the node to replay the calls against
+        #[clap(long, default_value_t = ApiInfo::from_str("/ip4/127.0.0.1/tcp/1234/http").expect("infallible"))]
+        target: ApiInfo,
+        /// Filter which calls to replay according to method name. Case sensitive.
+        #[arg(long, default_value = "")]
+        filter: String,
+        /// Print a diff of every response that differs from the capture
+        #[arg(long)]
+        show_diff: bool,
+        /// Cancel replay on the first difference
+        #[arg(long)]
+        fail_fast: bool,
+        /// Report format
+        #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+        report_format: ReportFormat,
+        /// Write the report to this file instead of stdout
+        #[arg(long)]
+        report_file: Option<PathBuf>,
     },
 }
 
@@ -49,7 +104,49 @@ impl ApiCommands {
                 snapshot_files,
                 filter,
                 fail_fast,
-            } => compare_apis(forest, lotus, snapshot_files, filter, fail_fast).await?,
+                n_tipsets,
+                seed,
+                report_format,
+                report_file,
+                policy,
+            } => {
+                let policy = match policy {
+                    Some(path) => serde_json::from_reader(BufReader::new(
+                        File::open(&path)
+                            .with_context(|| format!("couldn't open {}", path.display()))?,
+                    ))
+                    .with_context(|| format!("invalid policy file {}", path.display()))?,
+                    None => HashMap::default(),
+                };
+                let options = CompareOptions {
+                    filter,
+                    fail_fast,
+                    n_tipsets,
+                    seed,
+                    report_format,
+                    report_file,
+                    policy,
+                };
+                compare_apis(forest, lotus, snapshot_files, options).await?
+            }
+            Self::Replay {
+                capture_file,
+                target,
+                filter,
+                show_diff,
+                fail_fast,
+                report_format,
+                report_file,
+            } => {
+                let options = ReplayOptions {
+                    filter,
+                    show_diff,
+                    fail_fast,
+                    report_format,
+                    report_file,
+                };
+                replay_capture(capture_file, target, options).await?
+            }
         }
         Ok(())
     }
@@ -84,10 +181,93 @@ impl EndpointStatus {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ReportFormat {
+    Markdown,
+    Json,
+    Junit,
+}
+
+/// Differences between Forest and Lotus responses which are acceptable for a
+/// given method. Only applies to tests which require identical responses.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+struct Tolerance {
+    /// Object fields (at any depth) which are not compared, e.g. timestamps.
+    ignore_fields: Vec<String>,
+    /// Compare arrays regardless of the order of their elements.
+    ignore_order: bool,
+}
+
+impl Tolerance {
+    fn normalize(&self, value: serde_json::Value) -> serde_json::Value {
+        use serde_json::Value;
+        match value {
+            Value::Object(map) => Value::Object(
+                map.into_iter()
+                    .filter(|(key, _)| !self.ignore_fields.contains(key))
+                    .map(|(key, value)| (key, self.normalize(value)))
+                    .collect(),
+            ),
+            Value::Array(values) => {
+                let mut values = values
+                    .into_iter()
+                    .map(|value| self.normalize(value))
+                    .collect::<Vec<_>>();
+                if self.ignore_order {
+                    values.sort_by_cached_key(ToString::to_string);
+                }
+                Value::Array(values)
+            }
+            value => value,
+        }
+    }
+}
+
+struct CompareOptions {
+    filter: String,
+    fail_fast: bool,
+    n_tipsets: usize,
+    seed: Option<u64>,
+    report_format: ReportFormat,
+    report_file: Option<PathBuf>,
+    policy: HashMap<String, Tolerance>,
+}
+
+struct ReplayOptions {
+    filter: String,
+    show_diff: bool,
+    fail_fast: bool,
+    report_format: ReportFormat,
+    report_file: Option<PathBuf>,
+}
+
+/// Column names, JUnit suite name and pass criterion of a report.
+struct ReportKind {
+    header: [&'static str; 3],
+    suite: &'static str,
+    passed: fn(EndpointStatus, EndpointStatus) -> bool,
+}
+
+const COMPARE_REPORT: ReportKind = ReportKind {
+    header: ["RPC Method", "Forest", "Lotus"],
+    suite: "forest-api-compare",
+    passed: is_passing,
+};
+
+// A replayed call passes if the target behaves exactly as captured.
+const REPLAY_REPORT: ReportKind = ReportKind {
+    header: ["RPC Method", "Target", "Capture"],
+    suite: "forest-api-replay",
+    passed: |target_status, capture_status| target_status == capture_status,
+};
+
 struct RpcTest {
     request: RpcRequest,
     check_syntax: Box<dyn Fn(serde_json::Value) -> bool>,
     check_semantics: Box<dyn Fn(serde_json::Value, serde_json::Value) -> bool>,
+    // Forest has to return exactly the same JSON as Lotus
+    identity: bool,
 }
 
 impl RpcTest {
@@ -101,6 +281,7 @@ impl RpcTest {
             request: request.lower(),
             check_syntax: Box::new(|value| serde_json::from_value::<T::LotusJson>(value).is_ok()),
             check_semantics: Box::new(|_, _| true),
+            identity: false,
         }
     }
 
@@ -124,6 +305,7 @@ impl RpcTest {
                     })
                 })
             }),
+            identity: false,
         }
     }
 
@@ -134,13 +316,38 @@ impl RpcTest {
         T: HasLotusJson,
         T::LotusJson: DeserializeOwned,
     {
-        RpcTest::validate(request, |forest, lotus| forest == lotus)
+        RpcTest {
+            identity: true,
+            ..RpcTest::validate(request, |forest, lotus| forest == lotus)
+        }
+    }
+
+    // Check that an endpoint exists and that both responses are valid JSON,
+    // for requests built without a typed response.
+    fn raw(request: RpcRequest) -> RpcTest {
+        RpcTest {
+            request,
+            check_syntax: Box::new(|_| true),
+            check_semantics: Box::new(|_, _| true),
+            identity: false,
+        }
+    }
+
+    // Check that an endpoint exists and that Forest returns exactly the same
+    // JSON as Lotus, for requests built without a typed response.
+    fn raw_identity(request: RpcRequest) -> RpcTest {
+        RpcTest {
+            check_semantics: Box::new(|forest, lotus| forest == lotus),
+            identity: true,
+            ..RpcTest::raw(request)
+        }
     }
 
     async fn run(
         &self,
         forest_api: &ApiInfo,
         lotus_api: &ApiInfo,
+        tolerance: Option<&Tolerance>,
     ) -> (EndpointStatus, EndpointStatus) {
         let forest_resp = forest_api.call(self.request.clone()).await;
         let lotus_resp = lotus_api.call(self.request.clone()).await;
@@ -149,7 +356,13 @@ impl RpcTest {
             (Ok(forest), Ok(lotus))
                 if (self.check_syntax)(forest.clone()) && (self.check_syntax)(lotus.clone()) =>
             {
-                let forest_status = if (self.check_semantics)(forest, lotus) {
+                let semantics_ok = match tolerance {
+                    Some(tolerance) if self.identity => {
+                        tolerance.normalize(forest) == tolerance.normalize(lotus)
+                    }
+                    _ => (self.check_semantics)(forest, lotus),
+                };
+                let forest_status = if semantics_ok {
                     EndpointStatus::Valid
                 } else {
                     EndpointStatus::InvalidResponse
@@ -265,20 +478,366 @@ fn state_tests(shared_tipset: &Tipset) -> Vec<RpcTest> {
     ]
 }
 
+// Shape of the parameters of a read-only method. The calls generated for a
+// method are built from its kind and the values sampled from a snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ParamKind {
+    // No parameters. The result depends on the node, so only the endpoints are
+    // compared.
+    NoParams,
+    // `(TipsetKeys,)` of every sampled tipset.
+    Tipset,
+    // `(TipsetKeys,)` of the snapshot head only, for methods which are too
+    // expensive to call at every sampled tipset.
+    Head,
+    // `(ChainEpoch,)` of every sampled tipset.
+    Epoch,
+    // `(ChainEpoch, TipsetKeys)` of every sampled tipset, looked up from the
+    // snapshot head.
+    Height,
+    // `(Cid,)` of every block of the sampled tipsets.
+    Block,
+    // `(Cid,)` of every sampled message.
+    Message,
+    // `(Cid, TipsetKeys)` of every sampled message, at the snapshot head.
+    MessageAtHead,
+    // `(TipsetKeys, Cid)` of every sampled message and its tipset, in Lotus
+    // order.
+    Replay,
+    // `(Cid, i64)` of every sampled message, with a confidence of 0.
+    WaitMessage,
+    // `(Message, TipsetKeys)` of every sampled message and its tipset.
+    Call,
+    // `(Message, i64, TipsetKeys)`, like `Call` with a max queue of 20 blocks.
+    FeeCap,
+    // `(u64, Address, i64, TipsetKeys)`, the sender and gas limit of every
+    // sampled message.
+    GasPremium,
+    // `(Message, Option<MessageSendSpec>, TipsetKeys)`, like `Call`.
+    MessageGas,
+    // `(Address, Vec<u8>, Signature)` of every sampled secp message.
+    Signature,
+    // `(Address,)` of every sampled actor. The result is taken at the head of
+    // each node, so only the endpoints are compared.
+    Actor,
+    // `(Address, TipsetKeys)` of every sampled actor, at the snapshot head.
+    ActorAtHead,
+    // `(Address, TipsetKeys)` of every sampled miner, at the snapshot head.
+    MinerAtHead,
+    // `(Address, Vec<String>, TipsetKeys)` of every sampled actor, at the
+    // snapshot head.
+    ActorProof,
+    // `(i64, ChainEpoch, Vec<u8>, TipsetKeys)` of every sampled tipset.
+    Randomness,
+    // Never called, for the given reason.
+    Skip(&'static str),
+}
+
+// Every read-only method of `ACCESS_MAP` has a kind, which is checked by
+// `every_read_only_method_has_a_param_kind`.
+fn param_kind(method: &str) -> Option<ParamKind> {
+    use ParamKind::*;
+    let kind = match method {
+        auth_api::AUTH_VERIFY => Skip("needs a token of the node"),
+        beacon_api::BEACON_GET_ENTRY => Epoch,
+        chain_api::CHAIN_EXPORT => Skip("writes a snapshot on the node"),
+        chain_api::CHAIN_GET_MESSAGE => Message,
+        chain_api::CHAIN_READ_OBJ
+        | chain_api::CHAIN_HAS_OBJ
+        | chain_api::CHAIN_GET_BLOCK
+        | chain_api::CHAIN_GET_BLOCK_MESSAGES
+        | chain_api::CHAIN_GET_PARENT_MESSAGES
+        | sync_api::SYNC_CHECK_BAD => Block,
+        chain_api::CHAIN_GET_TIPSET_BY_HEIGHT => Height,
+        chain_api::CHAIN_GET_TIPSET
+        | chain_api::CHAIN_GET_MESSAGES_IN_TIPSET
+        | state_api::STATE_NETWORK_VERSION => Tipset,
+        chain_api::CHAIN_EXPORT_STATUS
+        | chain_api::CHAIN_GET_GENESIS
+        | chain_api::CHAIN_HEAD
+        | common_api::VERSION
+        | common_api::START_TIME
+        | db_api::DB_GC_STATUS
+        | net_api::NET_ADDRS_LISTEN
+        | net_api::NET_PEERS
+        | net_api::NET_INFO
+        | node_api::NODE_STATUS
+        | state_api::STATE_NETWORK_NAME
+        | sync_api::SYNC_STATE
+        | wallet_api::WALLET_DEFAULT_ADDRESS => NoParams,
+        mpool_api::MPOOL_PENDING | state_api::STATE_MARKET_DEALS => Head,
+        wallet_api::WALLET_BALANCE => Actor,
+        wallet_api::WALLET_VERIFY => Signature,
+        state_api::STATE_CALL | gas_api::GAS_ESTIMATE_GAS_LIMIT => Call,
+        state_api::STATE_REPLAY => Replay,
+        state_api::STATE_GET_ACTOR | state_api::STATE_MARKET_BALANCE => ActorAtHead,
+        state_api::STATE_MINER_POWER => MinerAtHead,
+        state_api::STATE_GET_RECEIPT => MessageAtHead,
+        state_api::STATE_WAIT_MSG => WaitMessage,
+        state_api::STATE_FETCH_ROOT => Skip("fetches a whole state tree into the node"),
+        state_api::STATE_GET_RANDOMNESS_FROM_BEACON => Randomness,
+        state_api::STATE_ACTOR_PROOF => ActorProof,
+        gas_api::GAS_ESTIMATE_FEE_CAP => FeeCap,
+        gas_api::GAS_ESTIMATE_GAS_PREMIUM => GasPremium,
+        gas_api::GAS_ESTIMATE_MESSAGE_GAS => MessageGas,
+        _ => return None,
+    };
+    Some(kind)
+}
+
+// Methods are only called if they are read-only, so that comparing nodes never
+// changes their state.
+fn is_read_only(method: &str) -> bool {
+    matches!(ACCESS_MAP.get(method), Some(Access::Read))
+}
+
+// A message sampled from the snapshot, along with the tipset including it.
+struct SampledMessage {
+    cid: Cid,
+    message: crate::shim::message::Message,
+    signature: Option<crate::shim::crypto::Signature>,
+    tipset: TipsetKeys,
+}
+
+// Values sampled from a snapshot, from which the calls to every read-only
+// method are generated. The tipsets are sampled from the last `SAMPLE_WINDOW`
+// epochs, for which snapshots contain both messages and state. At most
+// `n_tipsets` messages, actors and miners are sampled from these tipsets.
+struct Samples {
+    head: Tipset,
+    tipsets: Vec<Tipset>,
+    messages: Vec<SampledMessage>,
+    actors: Vec<Address>,
+    miners: Vec<Address>,
+}
+
+impl Samples {
+    fn new(store: &ManyCar, n_tipsets: usize, seed: Option<u64>) -> anyhow::Result<Self> {
+        const SAMPLE_WINDOW: usize = 900;
+
+        let head = store.heaviest_tipset()?;
+        let seed = seed.unwrap_or_else(rand::random);
+        info!(
+            "Sampling {n_tipsets} tipsets with seed {seed}, rerun with `--seed {seed}` to reproduce"
+        );
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut tipsets = head
+            .clone()
+            .chain(store)
+            .take(SAMPLE_WINDOW)
+            .choose_multiple(&mut rng, n_tipsets);
+        tipsets.sort_by_key(|tipset| std::cmp::Reverse(tipset.epoch()));
+
+        let mut seen = CidHashSet::default();
+        let mut messages = vec![];
+        let mut actors = HashSet::default();
+        let mut miners = HashSet::default();
+        for tipset in &tipsets {
+            for block in tipset.blocks() {
+                miners.insert(*block.miner_address());
+                let (bls_messages, secp_messages) =
+                    crate::chain::store::block_messages(store, block)?;
+                let signed = bls_messages
+                    .into_iter()
+                    .map(|message| (message, None))
+                    .chain(
+                        secp_messages
+                            .into_iter()
+                            .map(|msg| (msg.message, Some(msg.signature))),
+                    );
+                for (message, signature) in signed {
+                    let cid = message.cid()?;
+                    if seen.insert(cid) {
+                        actors.insert(message.from());
+                        actors.insert(message.to());
+                        messages.push(SampledMessage {
+                            cid,
+                            message,
+                            signature,
+                            tipset: tipset.key().clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut sample = |addresses: HashSet<Address>| {
+            let mut addresses = addresses.into_iter().collect::<Vec<_>>();
+            addresses.sort_by_key(Address::to_string);
+            addresses.into_iter().choose_multiple(&mut rng, n_tipsets)
+        };
+        let actors = sample(actors);
+        let miners = sample(miners);
+        let messages = messages.into_iter().choose_multiple(&mut rng, n_tipsets);
+        Ok(Samples {
+            head,
+            tipsets,
+            messages,
+            actors,
+            miners,
+        })
+    }
+
+    // Calls to `method` with parameters of the given kind.
+    fn requests(&self, method: &'static str, kind: ParamKind) -> Vec<RpcRequest> {
+        use ParamKind::*;
+        let head = self.head.key();
+        let tipsets = self.tipsets.iter();
+        let messages = self.messages.iter();
+        match kind {
+            NoParams => vec![RpcRequest::new(method, ())],
+            Tipset => tipsets
+                .map(|ts| RpcRequest::new(method, (ts.key().clone(),)))
+                .collect(),
+            Head => vec![RpcRequest::new(method, (head.clone(),))],
+            Epoch => tipsets
+                .map(|ts| RpcRequest::new(method, (ts.epoch(),)))
+                .collect(),
+            Height => tipsets
+                .map(|ts| RpcRequest::new(method, (ts.epoch(), head.clone())))
+                .collect(),
+            Block => tipsets
+                .flat_map(|ts| ts.blocks())
+                .map(|block| RpcRequest::new(method, (*block.cid(),)))
+                .collect(),
+            Message => messages
+                .map(|msg| RpcRequest::new(method, (msg.cid,)))
+                .collect(),
+            MessageAtHead => messages
+                .map(|msg| RpcRequest::new(method, (msg.cid, head.clone())))
+                .collect(),
+            Replay => messages
+                .map(|msg| RpcRequest::new(method, (msg.tipset.clone(), msg.cid)))
+                .collect(),
+            WaitMessage => messages
+                .map(|msg| RpcRequest::new(method, (msg.cid, 0_i64)))
+                .collect(),
+            Call => messages
+                .map(|msg| RpcRequest::new(method, (msg.message.clone(), msg.tipset.clone())))
+                .collect(),
+            FeeCap => messages
+                .map(|msg| {
+                    RpcRequest::new(method, (msg.message.clone(), 20_i64, msg.tipset.clone()))
+                })
+                .collect(),
+            GasPremium => messages
+                .map(|msg| {
+                    let params = (
+                        10_u64,
+                        msg.message.from(),
+                        msg.message.gas_limit() as i64,
+                        msg.tipset.clone(),
+                    );
+                    RpcRequest::new(method, params)
+                })
+                .collect(),
+            MessageGas => messages
+                .map(|msg| {
+                    let params = (
+                        msg.message.clone(),
+                        serde_json::Value::Null,
+                        msg.tipset.clone(),
+                    );
+                    RpcRequest::new(method, params)
+                })
+                .collect(),
+            Signature => messages
+                .filter_map(|msg| {
+                    let signature = msg.signature.clone()?;
+                    let data = msg.cid.to_bytes();
+                    Some(RpcRequest::new(
+                        method,
+                        (msg.message.from(), data, signature),
+                    ))
+                })
+                .collect(),
+            Actor => self
+                .actors
+                .iter()
+                .map(|actor| RpcRequest::new(method, (actor.to_string(),)))
+                .collect(),
+            ActorAtHead => self
+                .actors
+                .iter()
+                .map(|actor| RpcRequest::new(method, (*actor, head.clone())))
+                .collect(),
+            MinerAtHead => self
+                .miners
+                .iter()
+                .map(|miner| RpcRequest::new(method, (*miner, head.clone())))
+                .collect(),
+            ActorProof => self
+                .actors
+                .iter()
+                .map(|actor| RpcRequest::new(method, (*actor, Vec::<String>::new(), head.clone())))
+                .collect(),
+            Randomness => tipsets
+                .map(|ts| {
+                    let params = (
+                        DomainSeparationTag::ElectionProofProduction as i64,
+                        ts.epoch(),
+                        b"dead beef".to_vec(),
+                        ts.key().clone(),
+                    );
+                    RpcRequest::new(method, params)
+                })
+                .collect(),
+            Skip(_) => vec![],
+        }
+    }
+}
+
+// Generate tests for every read-only method of `ACCESS_MAP` which `tests`
+// don't call yet. Without samples, only methods without parameters are
+// called. Results at a fixed tipset have to be identical.
+fn generated_tests(tests: &[RpcTest], samples: Option<&Samples>) -> Vec<RpcTest> {
+    let mut methods = ACCESS_MAP
+        .keys()
+        .copied()
+        .filter(|method| is_read_only(method))
+        .filter(|method| !tests.iter().any(|test| test.request.method_name == *method))
+        .collect::<Vec<_>>();
+    methods.sort();
+
+    let mut generated = vec![];
+    for method in methods {
+        let Some(kind) = param_kind(method) else {
+            continue;
+        };
+        if let ParamKind::Skip(reason) = kind {
+            info!("Not calling {method}: {reason}");
+            continue;
+        }
+        let requests = match (kind, samples) {
+            (ParamKind::NoParams, _) => vec![RpcRequest::new(method, ())],
+            (_, Some(samples)) => samples.requests(method, kind),
+            (_, None) => vec![],
+        };
+        let node_dependent = matches!(kind, ParamKind::NoParams | ParamKind::Actor);
+        generated.extend(requests.into_iter().map(|request| match node_dependent {
+            true => RpcTest::raw(request),
+            false => RpcTest::raw_identity(request),
+        }));
+    }
+    generated
+}
+
 // Extract tests that use chain-specific data such as block CIDs or message
-// CIDs. Right now, only the last 20 tipsets are used. It would be nice to
-// sample a greater range.
-fn snapshot_tests(store: &ManyCar) -> anyhow::Result<Vec<RpcTest>> {
+// CIDs, at the sampled tipsets.
+fn snapshot_tests(store: &ManyCar, samples: &Samples) -> anyhow::Result<Vec<RpcTest>> {
     let mut tests = vec![];
-    let shared_tipset = store.heaviest_tipset()?;
+    let shared_tipset = &samples.head;
     let root_tsk = shared_tipset.key().clone();
-    tests.extend(chain_tests_with_tipset(&shared_tipset));
-    tests.extend(state_tests(&shared_tipset));
+    tests.extend(chain_tests_with_tipset(shared_tipset));
+    tests.extend(state_tests(shared_tipset));
 
     let mut seen = CidHashSet::default();
-    for tipset in shared_tipset.chain(&store).take(20) {
+    let mut seen_actors = HashSet::default();
+    for tipset in &samples.tipsets {
+        let tsk = tipset.key();
+        tests.extend(chain_tests_with_tipset(tipset));
         tests.push(RpcTest::identity(
-            ApiInfo::chain_get_messages_in_tipset_req(tipset.key().clone()),
+            ApiInfo::chain_get_messages_in_tipset_req(tsk.clone()),
         ));
         for block in tipset.blocks() {
             tests.push(RpcTest::identity(ApiInfo::chain_get_block_messages_req(
@@ -288,7 +847,7 @@ fn snapshot_tests(store: &ManyCar) -> anyhow::Result<Vec<RpcTest>> {
                 *block.cid(),
             )));
 
-            let (bls_messages, secp_messages) = crate::chain::store::block_messages(&store, block)?;
+            let (bls_messages, secp_messages) = crate::chain::store::block_messages(store, block)?;
             for msg in bls_messages {
                 if seen.insert(msg.cid()?) {
                     tests.push(RpcTest::identity(ApiInfo::chain_get_message_req(
@@ -298,6 +857,7 @@ fn snapshot_tests(store: &ManyCar) -> anyhow::Result<Vec<RpcTest>> {
                         msg.from(),
                         root_tsk.clone(),
                     )));
+                    tests.extend(actor_tests(&mut seen_actors, &msg.to(), tsk));
                 }
             }
             for msg in secp_messages {
@@ -317,27 +877,49 @@ fn snapshot_tests(store: &ManyCar) -> anyhow::Result<Vec<RpcTest>> {
                             root_tsk.clone(),
                         )));
                     }
+                    tests.extend(actor_tests(&mut seen_actors, &msg.to(), tsk));
                 }
             }
             tests.push(RpcTest::identity(ApiInfo::state_miner_power_req(
                 *block.miner_address(),
-                tipset.key().clone(),
-            )))
+                tsk.clone(),
+            )));
+            tests.extend(actor_tests(&mut seen_actors, block.miner_address(), tsk));
         }
         tests.push(RpcTest::basic(ApiInfo::state_circulating_supply_req(
-            tipset.key().clone(),
+            tsk.clone(),
         )))
     }
     Ok(tests)
 }
 
+// Tests for an actor found in the snapshot, e.g. a message recipient or a
+// block miner, at the tipset it was found in. Every actor is only tested once.
+// The balance is queried at the head of each node, which moves, so only its
+// schema is checked.
+fn actor_tests(
+    seen: &mut HashSet<Address>,
+    address: &Address,
+    tipset_keys: &TipsetKeys,
+) -> Vec<RpcTest> {
+    if !seen.insert(*address) {
+        return vec![];
+    }
+    vec![
+        RpcTest::identity(ApiInfo::state_get_actor_req(*address, tipset_keys.clone())),
+        RpcTest::basic(ApiInfo::wallet_balance_req(address.to_string())),
+    ]
+}
+
 /// Compare two RPC providers. The providers are labeled `forest` and `lotus`,
 /// but other nodes may be used (such as `venus`). The `lotus` node is assumed
 /// to be correct and the `forest` node will be marked as incorrect if it
 /// deviates.
 ///
 /// If snapshot files are provided, these files will be used to generate
-/// additional tests.
+/// additional tests. Every other read-only method in the [`ACCESS_MAP`] is
+/// called with parameters sampled from the snapshots. Methods without any test
+/// are reported as untested.
 ///
 /// Example output:
 /// ```markdown
@@ -352,8 +934,7 @@ async fn compare_apis(
     forest: ApiInfo,
     lotus: ApiInfo,
     snapshot_files: Vec<PathBuf>,
-    filter: String,
-    fail_fast: bool,
+    options: CompareOptions,
 ) -> anyhow::Result<()> {
     let mut tests = vec![];
 
@@ -364,42 +945,301 @@ async fn compare_apis(
     tests.extend(net_tests());
     tests.extend(node_tests());
 
-    if !snapshot_files.is_empty() {
-        let store = ManyCar::try_from(snapshot_files)?;
-        tests.extend(snapshot_tests(&store)?);
-    }
+    let samples = match snapshot_files.is_empty() {
+        true => None,
+        false => {
+            let store = ManyCar::try_from(snapshot_files)?;
+            let samples = Samples::new(&store, options.n_tipsets, options.seed)?;
+            tests.extend(snapshot_tests(&store, &samples)?);
+            Some(samples)
+        }
+    };
+    tests.extend(generated_tests(&tests, samples.as_ref()));
 
     tests.sort_by_key(|test| test.request.method_name);
 
     let mut results = HashMap::default();
 
     for test in tests.into_iter() {
-        if !test.request.method_name.contains(&filter) {
+        if !test.request.method_name.contains(&options.filter) {
             continue;
         }
-        let (forest_status, lotus_status) = test.run(&forest, &lotus).await;
+        let tolerance = options.policy.get(test.request.method_name);
+        let (forest_status, lotus_status) = test.run(&forest, &lotus, tolerance).await;
         results
             .entry((test.request.method_name, forest_status, lotus_status))
             .and_modify(|v| *v += 1)
             .or_insert(1u32);
         if (forest_status != EndpointStatus::Valid || lotus_status != EndpointStatus::Valid)
-            && fail_fast
+            && options.fail_fast
         {
             break;
         }
     }
 
+    // Methods served by Forest for which no test could be generated
+    let mut untested = ACCESS_MAP
+        .keys()
+        .copied()
+        .filter(|method| method.contains(&options.filter))
+        .filter(|method| !results.keys().any(|(tested, _, _)| tested == method))
+        .collect::<Vec<_>>();
+    untested.sort();
+
     let mut results = results.into_iter().collect::<Vec<_>>();
     results.sort();
-    println!("{}", format_as_markdown(&results));
+    write_report(
+        &results,
+        &untested,
+        &COMPARE_REPORT,
+        options.report_format,
+        options.report_file,
+    )
+}
+
+fn write_report<M: Display>(
+    results: &[((M, EndpointStatus, EndpointStatus), u32)],
+    untested: &[&str],
+    kind: &ReportKind,
+    format: ReportFormat,
+    file: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let report = match format {
+        ReportFormat::Markdown => {
+            let mut report = format_as_markdown(results, kind.header);
+            if !untested.is_empty() {
+                report.push_str(&format!("\n\nUntested methods: {}", untested.join(", ")));
+            }
+            report
+        }
+        ReportFormat::Json => format_as_json(results, untested, kind)?,
+        ReportFormat::Junit => format_as_junit(results, untested, kind),
+    };
+    match file {
+        Some(path) => std::fs::write(&path, report)
+            .with_context(|| format!("couldn't write {}", path.display()))?,
+        None => println!("{report}"),
+    }
 
     Ok(())
 }
 
-fn format_as_markdown(results: &[((&'static str, EndpointStatus, EndpointStatus), u32)]) -> String {
+// Forest passes if it returns a valid response, or fails the same way Lotus
+// does.
+fn is_passing(forest_status: EndpointStatus, lotus_status: EndpointStatus) -> bool {
+    forest_status == EndpointStatus::Valid || forest_status == lotus_status
+}
+
+fn format_as_json<M: Display>(
+    results: &[((M, EndpointStatus, EndpointStatus), u32)],
+    untested: &[&str],
+    kind: &ReportKind,
+) -> anyhow::Result<String> {
+    let [_, left, right] = kind.header.map(str::to_lowercase);
+    let results = results
+        .iter()
+        .map(|((method, left_status, right_status), n)| {
+            serde_json::json!({
+                "method": method.to_string(),
+                left.as_str(): format!("{left_status:?}"),
+                right.as_str(): format!("{right_status:?}"),
+                "count": n,
+                "passed": (kind.passed)(*left_status, *right_status),
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(serde_json::to_string_pretty(&serde_json::json!({
+        "results": results,
+        "untested": untested,
+    }))?)
+}
+
+/// Formats the results as a JUnit XML report, with one test case per method
+/// and outcome. Untested methods are reported as skipped.
+fn format_as_junit<M: Display>(
+    results: &[((M, EndpointStatus, EndpointStatus), u32)],
+    untested: &[&str],
+    kind: &ReportKind,
+) -> String {
+    let [_, left, right] = kind.header;
+    let failures = results
+        .iter()
+        .filter(|((_, left_status, right_status), _)| !(kind.passed)(*left_status, *right_status))
+        .count();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{failures}\" skipped=\"{}\">\n",
+        kind.suite,
+        results.len() + untested.len(),
+        untested.len(),
+    ));
+    for ((method, left_status, right_status), n) in results {
+        let name = xml_escape(&format!("{method} ({n})"));
+        let classname = xml_escape(&method.to_string());
+        if (kind.passed)(*left_status, *right_status) {
+            xml.push_str(&format!(
+                "  <testcase classname=\"{classname}\" name=\"{name}\"/>\n"
+            ));
+        } else {
+            xml.push_str(&format!(
+                "  <testcase classname=\"{classname}\" name=\"{name}\">\n    \
+                 <failure message=\"{left}: {left_status:?}, {right}: {right_status:?}\"/>\n  \
+                 </testcase>\n"
+            ));
+        }
+    }
+    for method in untested {
+        let method = xml_escape(method);
+        xml.push_str(&format!(
+            "  <testcase classname=\"{method}\" name=\"{method}\">\n    <skipped/>\n  </testcase>\n"
+        ));
+    }
+    xml.push_str("</testsuite>\n");
+    xml
+}
+
+fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Replay the calls recorded in an RPC capture file against `target`. A call is
+/// `Valid` if the target returns the same result (or the same kind of error) as
+/// the captured response. Fails if any call doesn't match the capture.
+///
+/// Example output:
+/// ```markdown
+/// | RPC Method                  | Target            | Capture |
+/// |-----------------------------|-------------------|---------|
+/// | Filecoin.ChainGetBlock (12) | Valid             | Valid   |
+/// | Filecoin.StateCall          | InvalidResponse   | Valid   |
+/// ```
+async fn replay_capture(
+    capture_file: PathBuf,
+    target: ApiInfo,
+    options: ReplayOptions,
+) -> anyhow::Result<()> {
+    let reader = BufReader::new(
+        File::open(&capture_file)
+            .with_context(|| format!("couldn't open {}", capture_file.display()))?,
+    );
+
+    let mut results = HashMap::default();
+    for (line_no, line) in reader.lines().enumerate() {
+        let call: CapturedCall = serde_json::from_str(&line?)
+            .with_context(|| format!("invalid capture at line {}", line_no + 1))?;
+        let request: jsonrpc_v2::RequestObject = serde_json::from_value(call.request)?;
+        let method = request.method_ref().to_owned();
+        if !method.contains(&options.filter) {
+            continue;
+        }
+
+        let captured = captured_result(call.response);
+        let replayed = target.send::<serde_json::Value>(&request).await;
+        if let (true, Ok(replayed), Ok(captured)) = (options.show_diff, &replayed, &captured) {
+            if replayed != captured {
+                print_json_diff(&method, captured, replayed)?;
+            }
+        }
+        let (target_status, capture_status) = replay_status(replayed, captured);
+        results
+            .entry((method, target_status, capture_status))
+            .and_modify(|v| *v += 1)
+            .or_insert(1u32);
+        if target_status != capture_status && options.fail_fast {
+            break;
+        }
+    }
+
+    let mut results = results.into_iter().collect::<Vec<_>>();
+    results.sort();
+    write_report(
+        &results,
+        &[],
+        &REPLAY_REPORT,
+        options.report_format,
+        options.report_file,
+    )?;
+
+    let mismatches: u32 = results
+        .iter()
+        .filter(|((_, target_status, capture_status), _)| target_status != capture_status)
+        .map(|(_, n)| n)
+        .sum();
+    if mismatches > 0 {
+        bail!("{mismatches} replayed calls didn't match the capture");
+    }
+    Ok(())
+}
+
+/// Statuses of the target and of the capture for a replayed call. The target
+/// is `InvalidResponse` if both succeeded with different results.
+fn replay_status(
+    replayed: Result<serde_json::Value, JsonRpcError>,
+    captured: Result<serde_json::Value, JsonRpcError>,
+) -> (EndpointStatus, EndpointStatus) {
+    match (replayed, captured) {
+        (Ok(replayed), Ok(captured)) => {
+            let status = match replayed == captured {
+                true => EndpointStatus::Valid,
+                false => EndpointStatus::InvalidResponse,
+            };
+            (status, EndpointStatus::Valid)
+        }
+        (Err(replayed), Err(captured)) => (
+            EndpointStatus::from_json_error(replayed),
+            EndpointStatus::from_json_error(captured),
+        ),
+        (Ok(_), Err(captured)) => (
+            EndpointStatus::Valid,
+            EndpointStatus::from_json_error(captured),
+        ),
+        (Err(replayed), Ok(_)) => (
+            EndpointStatus::from_json_error(replayed),
+            EndpointStatus::Valid,
+        ),
+    }
+}
+
+/// Extracts the result (or the error) from a captured JSON-RPC response.
+// The fields are taken out directly, as `JsonRpcResponse` can only be
+// deserialized from borrowed JSON text.
+fn captured_result(mut response: serde_json::Value) -> Result<serde_json::Value, JsonRpcError> {
+    if let Some(error) = response.get_mut("error") {
+        return Err(serde_json::from_value(error.take()).unwrap_or(JsonRpcError::PARSE_ERROR));
+    }
+    match response.get_mut("result") {
+        Some(result) => Ok(result.take()),
+        None => Err(JsonRpcError::PARSE_ERROR),
+    }
+}
+
+fn print_json_diff(
+    method: &str,
+    expected: &serde_json::Value,
+    actual: &serde_json::Value,
+) -> anyhow::Result<()> {
+    let expected = serde_json::to_string_pretty(expected)?;
+    let actual = serde_json::to_string_pretty(actual)?;
+    println!("{method}:");
+    println!(
+        "{}",
+        TextDiff::from_lines(&expected, &actual)
+            .unified_diff()
+            .header("capture", "target")
+    );
+    Ok(())
+}
+
+fn format_as_markdown<M: Display>(
+    results: &[((M, EndpointStatus, EndpointStatus), u32)],
+    header: [&str; 3],
+) -> String {
     let mut builder = Builder::default();
 
-    builder.set_header(["RPC Method", "Forest", "Lotus"]);
+    builder.set_header(header);
 
     for ((method, forest_status, lotus_status), n) in results {
         builder.push_record([
@@ -415,3 +1255,150 @@ fn format_as_markdown(results: &[((&'static str, EndpointStatus, EndpointStatus)
 
     builder.build().with(Style::markdown()).to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::car::AnyCar;
+    use serde_json::json;
+
+    #[test]
+    fn tolerance_ignores_fields_and_order() {
+        let tolerance = Tolerance {
+            ignore_fields: vec!["Timestamp".into()],
+            ignore_order: true,
+        };
+        let forest = json!({"Cids": [2, 1], "Inner": {"Timestamp": 1, "Height": 5}});
+        let lotus = json!({"Cids": [1, 2], "Inner": {"Timestamp": 2, "Height": 5}});
+        assert_eq!(
+            tolerance.normalize(forest),
+            tolerance.normalize(lotus.clone())
+        );
+
+        let strict = Tolerance::default();
+        assert_ne!(
+            strict.normalize(json!({"Cids": [2, 1]})),
+            strict.normalize(json!({"Cids": [1, 2]}))
+        );
+    }
+
+    #[test]
+    fn every_read_only_method_has_a_param_kind() {
+        for method in ACCESS_MAP.keys().filter(|method| is_read_only(method)) {
+            assert!(param_kind(method).is_some(), "{method} has no param kind");
+        }
+    }
+
+    #[test]
+    fn generated_tests_are_read_only_and_not_duplicated() {
+        let store = ManyCar::from(AnyCar::try_from(crate::genesis::EXPORT_SR_40).unwrap());
+        let samples = Samples::new(&store, 5, Some(0)).unwrap();
+        assert_eq!(samples.tipsets.len(), 5);
+
+        let generated = generated_tests(&common_tests(), Some(&samples));
+        let methods = generated
+            .iter()
+            .map(|test| test.request.method_name)
+            .collect::<Vec<_>>();
+        assert!(methods.contains(&sync_api::SYNC_STATE));
+        assert!(!methods.contains(&common_api::VERSION));
+        assert!(!methods.contains(&chain_api::CHAIN_EXPORT));
+        assert!(methods.iter().all(|method| is_read_only(method)));
+        // Expensive methods are only called at the head.
+        assert_eq!(
+            methods
+                .iter()
+                .filter(|method| **method == state_api::STATE_MARKET_DEALS)
+                .count(),
+            1
+        );
+        assert_eq!(
+            methods
+                .iter()
+                .filter(|method| **method == chain_api::CHAIN_GET_TIPSET)
+                .count(),
+            5
+        );
+
+        // Without a snapshot, only the methods without parameters are called.
+        let generated = generated_tests(&[], None);
+        assert!(generated
+            .iter()
+            .all(|test| param_kind(test.request.method_name) == Some(ParamKind::NoParams)));
+    }
+
+    #[test]
+    fn junit_report() {
+        let results = [
+            (
+                (
+                    "Filecoin.ChainHead",
+                    EndpointStatus::Valid,
+                    EndpointStatus::Valid,
+                ),
+                1,
+            ),
+            (
+                (
+                    "Filecoin.StateGetActor",
+                    EndpointStatus::InvalidResponse,
+                    EndpointStatus::Valid,
+                ),
+                3,
+            ),
+        ];
+        let xml = format_as_junit(&results, &["Filecoin.Shutdown"], &COMPARE_REPORT);
+        assert!(xml.contains(r#"tests="3" failures="1" skipped="1""#));
+        assert!(xml.contains("Forest: InvalidResponse, Lotus: Valid"));
+        assert!(xml.contains("<skipped/>"));
+    }
+
+    #[test]
+    fn replay_status_of_captured_calls() {
+        // A small capture: a successful call, a failed call and a corrupt
+        // response.
+        let capture = [
+            r#"{"timestamp":"","request":{"jsonrpc":"2.0","method":"Filecoin.ChainHead","id":0},"response":{"jsonrpc":"2.0","result":{"Height":10},"id":0}}"#,
+            r#"{"timestamp":"","request":{"jsonrpc":"2.0","method":"Filecoin.Foo","id":1},"response":{"jsonrpc":"2.0","error":{"code":-32601,"message":"not found"},"id":1}}"#,
+            r#"{"timestamp":"","request":{"jsonrpc":"2.0","method":"Filecoin.ChainHead","id":2},"response":{"jsonrpc":"2.0","id":2}}"#,
+        ]
+        .map(|line| {
+            let call: CapturedCall = serde_json::from_str(line).unwrap();
+            captured_result(call.response)
+        });
+        let [ok, not_found, corrupt] = capture;
+        assert_eq!(ok.as_ref().unwrap(), &json!({"Height": 10}));
+        assert_eq!(
+            not_found.as_ref().unwrap_err().code,
+            JsonRpcError::METHOD_NOT_FOUND.code
+        );
+        assert_eq!(
+            corrupt.as_ref().unwrap_err().code,
+            JsonRpcError::PARSE_ERROR.code
+        );
+
+        use EndpointStatus::*;
+        let result = || Ok(json!({"Height": 10}));
+        assert_eq!(replay_status(result(), ok), (Valid, Valid));
+        assert_eq!(
+            replay_status(
+                result(),
+                captured_result(json!({"jsonrpc": "2.0", "result": {"Height": 11}, "id": 0}))
+            ),
+            (InvalidResponse, Valid)
+        );
+        assert_eq!(
+            replay_status(Err(JsonRpcError::METHOD_NOT_FOUND), not_found),
+            (MissingMethod, MissingMethod)
+        );
+        assert_eq!(replay_status(result(), corrupt), (Valid, InvalidResponse));
+        assert_eq!(
+            replay_status(Err(JsonRpcError::INVALID_REQUEST), result()),
+            (InvalidRequest, Valid)
+        );
+
+        // Only calls that behave as captured pass.
+        assert!((REPLAY_REPORT.passed)(MissingMethod, MissingMethod));
+        assert!(!(REPLAY_REPORT.passed)(Valid, InvalidResponse));
+    }
+}
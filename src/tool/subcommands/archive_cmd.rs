@@ -26,6 +26,8 @@
 //!
 //! Additional reading: [`crate::db::car::plain`]
 
+mod stats;
+
 use crate::blocks::Tipset;
 use crate::chain::{
     index::{ChainIndex, ResolveNullTipset},
@@ -33,16 +35,22 @@ use crate::chain::{
 };
 use crate::cid_collections::CidHashSet;
 use crate::cli_shared::{snapshot, snapshot::TrustedVendor};
+use crate::daemon::db_util::ensure_diff_extends;
+use crate::db::car::shards::{Shard, ShardIndex, SHARD_INDEX_EXTENSION};
 use crate::db::car::ManyCar;
-use crate::db::car::{AnyCar, RandomAccessFileReader};
+use crate::db::car::{AnyCar, ForestCar, RandomAccessFileReader};
 use crate::interpreter::VMTrace;
 use crate::ipld::{stream_graph, unordered_stream_graph};
 use crate::networks::{calibnet, mainnet, ChainConfig, NetworkChain};
-use crate::shim::address::CurrentNetwork;
+use crate::shim::address::{Address, CurrentNetwork};
 use crate::shim::clock::{ChainEpoch, EPOCHS_IN_DAY, EPOCH_DURATION_SECONDS};
 use crate::shim::fvm_shared_latest::address::Network;
 use crate::shim::machine::MultiEngine;
+use crate::state_manager::proof::{
+    parse_path, proof_from_car, proof_to_car, prove_actor, verify_actor_proof, ProvenActor,
+};
 use crate::state_manager::{apply_block_messages, NO_CALLBACK};
+use crate::utils::io::HttpRangeReader;
 use anyhow::{bail, Context as _};
 use chrono::NaiveDateTime;
 use cid::Cid;
@@ -53,18 +61,37 @@ use fvm_ipld_blockstore::Blockstore;
 use indicatif::ProgressIterator;
 use itertools::Itertools;
 use sha2::Sha256;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::io::{AsyncWriteExt, BufWriter};
 use tracing::info;
+use url::Url;
+
+use stats::ArchiveStats;
 
 #[derive(Debug, Subcommand)]
 pub enum ArchiveCommands {
     /// Show basic information about an archive.
     Info {
-        /// Path to an uncompressed archive (CAR)
+        /// Path to an archive (CAR), or `http(s)` URL of a remote
+        /// `.forest.car.zst` archive, which is queried with range requests
+        /// rather than downloaded
         snapshot: PathBuf,
     },
+    /// Show how the blocks and bytes of an archive break down into block
+    /// headers, messages, receipts, state trees and the state of each actor
+    /// type, along with the largest actors and an epoch histogram.
+    Stats {
+        /// Snapshot input paths. Supports `.car`, `.car.zst`, and `.forest.car.zst`.
+        #[arg(required = true)]
+        snapshot_files: Vec<PathBuf>,
+        /// Number of largest actors to show.
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        /// Number of epochs per bucket of the epoch histogram.
+        #[arg(long, default_value_t = EPOCHS_IN_DAY)]
+        bucket_size: ChainEpoch,
+    },
     /// Trim a snapshot of the chain and write it to `<output_path>`
     Export {
         /// Snapshot input path. Currently supports only `.car` file format.
@@ -92,6 +119,34 @@ pub enum ArchiveCommands {
         #[arg(long, default_value_t = false)]
         force: bool,
     },
+    /// Export a range of epochs as shards of `--shard-size` epochs each. A
+    /// shard only contains the blocks that are first reachable from the
+    /// tipsets in its range, so shards don't overlap. An index file mapping
+    /// epochs to shards is written next to the shards, and extended when the
+    /// next range is exported to the same directory. Blocks missing from the
+    /// snapshots are skipped.
+    ExportRange {
+        /// Snapshot input paths. Supports `.car`, `.car.zst`, and `.forest.car.zst`.
+        #[arg(required = true)]
+        snapshot_files: Vec<PathBuf>,
+        /// First epoch of the range.
+        #[arg(long)]
+        from: ChainEpoch,
+        /// Last epoch of the range. Defaults to the heaviest tipset of the
+        /// snapshots.
+        #[arg(long)]
+        to: Option<ChainEpoch>,
+        /// Number of epochs per shard.
+        #[arg(long, default_value_t = 30 * EPOCHS_IN_DAY)]
+        shard_size: ChainEpochDelta,
+        /// Directory of the shards and the index.
+        #[arg(short, long, default_value = ".")]
+        output_dir: PathBuf,
+        /// Overwrite existing shards, and start a new index rather than
+        /// extending the existing one.
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
     /// Print block headers at 30 day interval for a snapshot file
     Checkpoints {
         /// Path to snapshot file.
@@ -112,6 +167,21 @@ pub enum ArchiveCommands {
         #[arg(long, default_value_t = false)]
         force: bool,
     },
+    /// Check that diff snapshots, given in chain order, extend a base
+    /// snapshot, and optionally merge them into a single snapshot.
+    Chain {
+        /// Base snapshot. Supports `.car`, `.car.zst`, and `.forest.car.zst`.
+        base: PathBuf,
+        /// Diff snapshots created with `archive export --diff`, oldest first.
+        #[arg(required = true)]
+        diffs: Vec<PathBuf>,
+        /// Merge the base and the diffs into this snapshot file.
+        #[arg(short, long)]
+        output_path: Option<PathBuf>,
+        /// Overwrite output file without prompting.
+        #[arg(long, default_value_t = false)]
+        force: bool,
+    },
     /// Show the difference between the canonical and computed state of a
     /// tipset.
     Diff {
@@ -126,16 +196,103 @@ pub enum ArchiveCommands {
         #[arg(long)]
         depth: Option<u64>,
     },
+    /// Report the actors created, deleted and modified between two state
+    /// trees, as JSON.
+    StateDiff {
+        /// Snapshot input paths, in which the `--from` and `--to` epochs are
+        /// looked up. Supports `.car`, `.car.zst`, and `.forest.car.zst`.
+        snapshot_files: Vec<PathBuf>,
+        /// State to compare from: an epoch, or a snapshot file whose head
+        /// state is used.
+        #[arg(long)]
+        from: StateRef,
+        /// State to compare to: an epoch, or a snapshot file whose head state
+        /// is used.
+        #[arg(long)]
+        to: StateRef,
+        /// Include the changes of the decoded states of builtin actors.
+        #[arg(long)]
+        fields: bool,
+        /// Write the report to this file rather than to stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Prove the state of an actor, and optionally of a value in its state,
+    /// with the minimal set of state tree blocks. The proof is written as a
+    /// CAR file rooted at the state root.
+    ActorProof {
+        /// Snapshot input paths. Supports `.car`, `.car.zst`, and `.forest.car.zst`.
+        #[arg(required = true)]
+        snapshot_files: Vec<PathBuf>,
+        /// Address of the actor.
+        #[arg(long)]
+        address: Address,
+        /// Prove the parent state of the tipset at this epoch. Defaults to the
+        /// heaviest tipset of the snapshots.
+        #[arg(long)]
+        epoch: Option<ChainEpoch>,
+        /// Path to a value in the state of the actor, as `/`-separated map
+        /// keys, list indices and `hamt:`, `amt:` or `kamt:` keys, e.g. `2/0`
+        /// or `2/kamt:0`. Links are followed.
+        #[arg(long)]
+        path: Option<String>,
+        /// Path to the proof.
+        #[arg(short, long, default_value = "proof.car")]
+        output: PathBuf,
+    },
+    /// Check a proof produced by `actor-proof` against a trusted state root,
+    /// and print the proven actor.
+    VerifyProof {
+        /// Path to the proof.
+        proof: PathBuf,
+        /// Trusted state root.
+        #[arg(long)]
+        state_root: Cid,
+        /// Address of the actor.
+        #[arg(long)]
+        address: Address,
+        /// Path to a value in the state of the actor, as given to
+        /// `actor-proof`.
+        #[arg(long)]
+        path: Option<String>,
+    },
+}
+
+/// A state tree, given as the epoch of a tipset or as a snapshot file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateRef {
+    Epoch(ChainEpoch),
+    Snapshot(PathBuf),
+}
+
+impl std::str::FromStr for StateRef {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s.parse() {
+            Ok(epoch) => StateRef::Epoch(epoch),
+            Err(_) => StateRef::Snapshot(s.into()),
+        })
+    }
 }
 
 impl ArchiveCommands {
     pub async fn run(self) -> anyhow::Result<()> {
         match self {
             Self::Info { snapshot } => {
-                println!(
-                    "{}",
-                    ArchiveInfo::from_store(AnyCar::try_from(snapshot.as_path())?)?
-                );
+                println!("{}", ArchiveInfo::from_store(open_archive(&snapshot)?)?);
+                Ok(())
+            }
+            Self::Stats {
+                snapshot_files,
+                top,
+                bucket_size,
+            } => {
+                let store = ManyCar::try_from(snapshot_files)?;
+                let heaviest_tipset = store.heaviest_tipset()?;
+                let stats =
+                    ArchiveStats::from_store(store, heaviest_tipset, top, bucket_size, true)?;
+                println!("{stats}");
                 Ok(())
             }
             Self::Export {
@@ -147,7 +304,11 @@ impl ArchiveCommands {
                 diff_depth,
                 force,
             } => {
-                let store = ManyCar::try_from(snapshot_files)?;
+                // Only the shards up to `epoch` are needed from a shard index.
+                let store = ManyCar::<crate::db::MemoryDB>::default().with_read_only_files_at(
+                    snapshot_files.into_iter(),
+                    epoch.unwrap_or(ChainEpoch::MAX),
+                )?;
                 let heaviest_tipset = store.heaviest_tipset()?;
                 do_export(
                     store,
@@ -161,6 +322,36 @@ impl ArchiveCommands {
                 )
                 .await
             }
+            Self::ExportRange {
+                snapshot_files,
+                from,
+                to,
+                shard_size,
+                output_dir,
+                force,
+            } => {
+                let store = ManyCar::try_from(snapshot_files)?;
+                let heaviest_tipset = store.heaviest_tipset()?;
+                let index = export_range(
+                    store,
+                    heaviest_tipset,
+                    from,
+                    to,
+                    shard_size,
+                    &output_dir,
+                    force,
+                )
+                .await?;
+                for shard in index.shards.iter().filter(|shard| shard.from >= from) {
+                    println!(
+                        "{}: epochs {}..={}",
+                        shard.path.display(),
+                        shard.from,
+                        shard.to
+                    );
+                }
+                Ok(())
+            }
             Self::Checkpoints {
                 snapshot_files: snapshot,
             } => print_checkpoints(snapshot),
@@ -169,15 +360,99 @@ impl ArchiveCommands {
                 output_path,
                 force,
             } => merge_snapshots(snapshot_files, output_path, force).await,
+            Self::Chain {
+                base,
+                diffs,
+                output_path,
+                force,
+            } => {
+                check_snapshot_chain(&base, &diffs)?;
+                match output_path {
+                    Some(output_path) => {
+                        merge_snapshots(
+                            std::iter::once(base).chain(diffs).collect(),
+                            output_path,
+                            force,
+                        )
+                        .await
+                    }
+                    None => Ok(()),
+                }
+            }
             Self::Diff {
                 snapshot_files,
                 epoch,
                 depth,
             } => show_tipset_diff(snapshot_files, epoch, depth).await,
+            Self::StateDiff {
+                snapshot_files,
+                from,
+                to,
+                fields,
+                output,
+            } => {
+                let diff = state_diff(snapshot_files, &from, &to, fields)?;
+                match output {
+                    Some(output) => {
+                        serde_json::to_writer_pretty(std::fs::File::create(output)?, &diff)?
+                    }
+                    None => println!("{}", serde_json::to_string_pretty(&diff)?),
+                }
+                Ok(())
+            }
+            Self::ActorProof {
+                snapshot_files,
+                address,
+                epoch,
+                path,
+                output,
+            } => {
+                let (proven, proof) = actor_proof(
+                    snapshot_files,
+                    &address,
+                    epoch,
+                    &parse_path(&path.unwrap_or_default()),
+                )?;
+                std::fs::write(output, proof)?;
+                println!("{}", serde_json::to_string_pretty(&proven)?);
+                Ok(())
+            }
+            Self::VerifyProof {
+                proof,
+                state_root,
+                address,
+                path,
+            } => {
+                let (root, blocks) = proof_from_car(&std::fs::read(proof)?)?;
+                if root != state_root {
+                    bail!("the proof is rooted at {root}, not at {state_root}");
+                }
+                let proven = verify_actor_proof(
+                    &state_root,
+                    &address,
+                    &parse_path(&path.unwrap_or_default()),
+                    &blocks,
+                )?;
+                println!("{}", serde_json::to_string_pretty(&proven)?);
+                Ok(())
+            }
         }
     }
 }
 
+/// Opens a local archive, or a remote `.forest.car.zst` archive if `snapshot`
+/// is an `http(s)` URL.
+fn open_archive(snapshot: &Path) -> anyhow::Result<AnyCar<Box<dyn RandomAccessFileReader>>> {
+    match snapshot
+        .to_str()
+        .and_then(|s| Url::parse(s).ok())
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+    {
+        Some(url) => Ok(AnyCar::from(ForestCar::new(HttpRangeReader::open(url)?)?).into_dyn()),
+        None => Ok(AnyCar::try_from(snapshot)?.into_dyn()),
+    }
+}
+
 #[derive(Debug)]
 pub struct ArchiveInfo {
     variant: String,
@@ -447,6 +722,176 @@ async fn do_export(
     Ok(())
 }
 
+/// Exports the epochs `from..=to` as shards of `shard_size` epochs, oldest
+/// first. A new index has to start at genesis, an existing index is extended
+/// from its last shard. Blocks held by earlier shards, as told by their
+/// indexes, are left out. Blocks missing from `store` are skipped.
+/// Returns the updated index.
+async fn export_range(
+    store: impl Blockstore + Send + Sync + 'static,
+    root: Tipset,
+    from: ChainEpoch,
+    to: Option<ChainEpoch>,
+    shard_size: ChainEpochDelta,
+    output_dir: &Path,
+    force: bool,
+) -> anyhow::Result<ShardIndex> {
+    use crate::db::car::forest;
+
+    let store = Arc::new(store);
+    let heaviest_tipset = Arc::new(root);
+    let genesis = heaviest_tipset.genesis(&store)?;
+    let network = NetworkChain::from_genesis_or_devnet_placeholder(genesis.cid()).to_string();
+
+    let to = to.unwrap_or(heaviest_tipset.epoch());
+    if shard_size <= 0 {
+        bail!("shard size must be positive");
+    }
+    if from < 0 || from > to {
+        bail!("invalid epoch range {from}..={to}");
+    }
+    if to > heaviest_tipset.epoch() {
+        bail!(
+            "epoch {to} is past the heaviest tipset of the snapshots at epoch {}",
+            heaviest_tipset.epoch()
+        );
+    }
+
+    let index_path = output_dir.join(format!("{network}{SHARD_INDEX_EXTENSION}"));
+    let mut index = match index_path.exists() && !force {
+        true => {
+            let mut index = ShardIndex::load(&index_path)?;
+            if index.network != network {
+                bail!(
+                    "{} indexes {} shards, not {network} shards",
+                    index_path.display(),
+                    index.network
+                );
+            }
+            let next = index.shards.last().map_or(0, |shard| shard.to + 1);
+            if from != next {
+                bail!(
+                    "{} ends at epoch {}, the range has to start at epoch {next}",
+                    index_path.display(),
+                    next - 1
+                );
+            }
+            // Shards are written next to the index.
+            for shard in index.shards.iter_mut() {
+                shard.path = shard.path.file_name().unwrap_or_default().into();
+            }
+            index
+        }
+        false => {
+            // Without the older shards, the index couldn't serve any epoch.
+            if from != 0 {
+                bail!("a new shard index has to start at epoch 0, not at epoch {from}");
+            }
+            ShardIndex {
+                network: network.clone(),
+                shards: vec![],
+            }
+        }
+    };
+
+    let chain_index = ChainIndex::new(&store);
+    let tipset_at = |epoch| {
+        chain_index
+            .tipset_by_height(epoch, heaviest_tipset.clone(), ResolveNullTipset::TakeOlder)
+            .with_context(|| format!("no tipset at epoch {epoch}"))
+    };
+
+    // Blocks of the older shards may not be reachable from the tipset before
+    // the range anymore, so they are looked up in the shards themselves. The
+    // bloom filters keep the lookups of new blocks cheap.
+    let exported = Arc::new(ManyCar::<crate::db::MemoryDB>::default().with_bloom_filters(true));
+    exported.read_only_files(
+        index
+            .shards
+            .iter()
+            .map(|shard| output_dir.join(&shard.path)),
+    )?;
+    let unexported = Arc::new(Unexported {
+        store: store.clone(),
+        exported: exported.clone(),
+    });
+
+    for shard_from in (from..=to).step_by(shard_size as usize) {
+        let shard_to = (shard_from + shard_size - 1).min(to);
+        // A range of null rounds yields an empty shard rooted at an older
+        // tipset, which keeps the index free of gaps.
+        let head = tipset_at(shard_to)?;
+
+        let path = PathBuf::from(format!(
+            "{network}_shard_{shard_from}_{shard_to}.forest.car.zst"
+        ));
+        let output_path = output_dir.join(&path);
+        if !force && output_path.exists() {
+            bail!(
+                "{} already exists, use --force to overwrite it",
+                output_path.display()
+            );
+        }
+        info!(
+            "exporting epochs {shard_from}..={shard_to} to {}",
+            output_path.display()
+        );
+
+        let mut writer = BufWriter::new(
+            tokio::fs::File::create(&output_path)
+                .await
+                .with_context(|| format!("unable to create {}", output_path.display()))?,
+        );
+        let tipsets = (*head)
+            .clone()
+            .chain(store.clone())
+            .take_while(move |ts| ts.epoch() >= shard_from);
+        let blocks = stream_graph(unexported.clone(), tipsets, shard_from - 1);
+        let frames = forest::Encoder::compress_stream_default(blocks);
+        forest::Encoder::write(
+            &mut writer,
+            head.key().cids.clone().into_iter().collect(),
+            Box::pin(frames.into_stream()),
+        )
+        .await?;
+        writer.flush().await.context("failed to flush")?;
+        exported.read_only_files(std::iter::once(output_path))?;
+
+        index.shards.push(Shard {
+            from: shard_from,
+            to: shard_to,
+            path,
+            head: head.key().clone(),
+        });
+        // Keep the index usable if a later shard fails.
+        index.save(&index_path)?;
+    }
+    index.save(&index_path)?;
+
+    ShardIndex::load(&index_path)
+}
+
+/// A view of `store` without the blocks already held by `exported`. Graph
+/// traversals treat those as dead links, so they neither yield nor descend
+/// into them.
+struct Unexported<DB> {
+    store: DB,
+    exported: Arc<ManyCar>,
+}
+
+impl<DB: Blockstore> Blockstore for Unexported<DB> {
+    fn get(&self, k: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        match self.exported.has(k)? {
+            true => Ok(None),
+            false => self.store.get(k),
+        }
+    }
+
+    fn put_keyed(&self, k: &Cid, block: &[u8]) -> anyhow::Result<()> {
+        self.store.put_keyed(k, block)
+    }
+}
+
 // TODO(lemmih): https://github.com/ChainSafe/forest/issues/3347
 //               Testing with diff snapshots can be significantly improved
 /// Merge a set of snapshots (diff snapshots or lite snapshots). The output
@@ -499,6 +944,27 @@ async fn merge_snapshots(
     Ok(())
 }
 
+/// Checks that each diff snapshot extends the chain of the base snapshot and
+/// the diffs before it. Returns the heaviest tipset of the resulting chain.
+fn check_snapshot_chain(base: &Path, diffs: &[PathBuf]) -> anyhow::Result<Tipset> {
+    let mut head = AnyCar::try_from(base)?.heaviest_tipset()?;
+    println!("{}: epoch {}", base.display(), head.epoch());
+    for diff in diffs {
+        let car = AnyCar::try_from(diff.as_path())?;
+        let diff_head = car.heaviest_tipset()?;
+        ensure_diff_extends(&head, &car, &diff_head)
+            .with_context(|| format!("invalid diff snapshot {}", diff.display()))?;
+        println!(
+            "{}: epochs {}..={}",
+            diff.display(),
+            head.epoch() + 1,
+            diff_head.epoch()
+        );
+        head = diff_head;
+    }
+    Ok(head)
+}
+
 /// Compute the tree of actor states for a given epoch and compare it to the
 /// expected result (as encoded in the blockchain). Differences are printed
 /// using the diff format (red for the blockchain state, green for the computed
@@ -578,10 +1044,89 @@ async fn show_tipset_diff(
     Ok(())
 }
 
+/// Compares the `from` and `to` state trees. Epochs are looked up in the chain
+/// of the heaviest tipset of all the input snapshots.
+fn state_diff(
+    mut snapshot_files: Vec<PathBuf>,
+    from: &StateRef,
+    to: &StateRef,
+    fields: bool,
+) -> anyhow::Result<crate::statediff::StateDiff> {
+    for state in [from, to] {
+        if let StateRef::Snapshot(path) = state {
+            if !snapshot_files.contains(path) {
+                snapshot_files.push(path.clone());
+            }
+        }
+    }
+    if snapshot_files.is_empty() {
+        bail!("no snapshot to look up the `--from` and `--to` epochs in");
+    }
+    let store = Arc::new(ManyCar::try_from(snapshot_files)?);
+    let heaviest_tipset = Arc::new(store.heaviest_tipset()?);
+    set_network(&store, &heaviest_tipset);
+
+    let chain_index = ChainIndex::new(store.clone());
+    let state_root = |state: &StateRef| -> anyhow::Result<Cid> {
+        Ok(match state {
+            StateRef::Epoch(epoch) => *chain_index
+                .tipset_by_height(
+                    *epoch,
+                    heaviest_tipset.clone(),
+                    ResolveNullTipset::TakeOlder,
+                )
+                .with_context(|| format!("no tipset at epoch {epoch}"))?
+                .parent_state(),
+            StateRef::Snapshot(path) => *AnyCar::try_from(path.as_path())?
+                .heaviest_tipset()?
+                .parent_state(),
+        })
+    };
+    crate::statediff::state_diff(&store, &state_root(from)?, &state_root(to)?, fields)
+}
+
+/// Uses testnet addresses if the snapshot isn't a mainnet one.
+fn set_network(store: &impl Blockstore, heaviest_tipset: &Tipset) {
+    if let Ok(genesis) = heaviest_tipset.genesis(store) {
+        let network = NetworkChain::from_genesis_or_devnet_placeholder(genesis.cid());
+        if ChainConfig::from_chain(&network).is_testnet() {
+            CurrentNetwork::set_global(Network::Testnet);
+        }
+    }
+}
+
+/// Proves the actor at `address` in the parent state of the tipset at `epoch`,
+/// and returns the proven actor and the proof as a CAR file.
+fn actor_proof(
+    snapshot_files: Vec<PathBuf>,
+    address: &Address,
+    epoch: Option<ChainEpoch>,
+    path: &[String],
+) -> anyhow::Result<(ProvenActor, Vec<u8>)> {
+    let store = Arc::new(ManyCar::try_from(snapshot_files)?);
+    let heaviest_tipset = Arc::new(store.heaviest_tipset()?);
+    set_network(&store, &heaviest_tipset);
+    let tipset = match epoch {
+        Some(epoch) => ChainIndex::new(store.clone())
+            .tipset_by_height(epoch, heaviest_tipset, ResolveNullTipset::TakeOlder)
+            .with_context(|| format!("no tipset at epoch {epoch}"))?,
+        None => heaviest_tipset,
+    };
+    let state_root = tipset.parent_state();
+    let (proven, blocks) = prove_actor(&store, state_root, address, path)?;
+    info!(
+        "proved {address} at epoch {} with {} blocks",
+        tipset.epoch(),
+        blocks.len()
+    );
+    Ok((proven, proof_to_car(state_root, &blocks)?))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::db::car::AnyCar;
+    use crate::ipld::stream_chain;
     use crate::utils::db::car_stream::CarStream;
     use tempfile::TempDir;
     use tokio::io::BufReader;
@@ -620,6 +1165,15 @@ mod tests {
         CarStream::new(BufReader::new(file)).await.unwrap();
     }
 
+    #[test]
+    fn parse_state_ref() {
+        assert_eq!("42".parse(), Ok(StateRef::Epoch(42)));
+        assert_eq!(
+            "snapshot.car".parse(),
+            Ok(StateRef::Snapshot("snapshot.car".into()))
+        );
+    }
+
     #[test]
     fn archive_info_calibnet() {
         let info = ArchiveInfo::from_store_with(
@@ -641,4 +1195,128 @@ mod tests {
         assert_eq!(info.network, "mainnet");
         assert_eq!(info.epoch, 0);
     }
+
+    #[tokio::test]
+    async fn export_range_genesis() {
+        let output_dir = TempDir::new().unwrap();
+        let store = AnyCar::try_from(calibnet::DEFAULT_GENESIS).unwrap();
+        let heaviest_tipset = store.heaviest_tipset().unwrap();
+        let index = export_range(
+            store,
+            heaviest_tipset.clone(),
+            0,
+            None,
+            10,
+            output_dir.path(),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(index.network, "calibnet");
+        assert_eq!(index.shards.len(), 1);
+        assert_eq!(index.shards[0].head, *heaviest_tipset.key());
+
+        let many = ManyCar::try_from(vec![output_dir
+            .path()
+            .join(format!("calibnet{SHARD_INDEX_EXTENSION}"))])
+        .unwrap();
+        assert_eq!(many.heaviest_tipset().unwrap(), heaviest_tipset);
+        let mut blocks = stream_chain(&many, heaviest_tipset.chain(&many), 0);
+        while blocks.try_next().await.unwrap().is_some() {}
+    }
+
+    #[tokio::test]
+    async fn export_range_shards() {
+        let output_dir = TempDir::new().unwrap();
+        let store = Arc::new(AnyCar::try_from(crate::genesis::EXPORT_SR_40).unwrap());
+        let heaviest_tipset = store.heaviest_tipset().unwrap();
+        let to = heaviest_tipset.epoch();
+
+        // A new index has to start at genesis.
+        assert!(export_range(
+            store.clone(),
+            heaviest_tipset.clone(),
+            10,
+            None,
+            10,
+            output_dir.path(),
+            false,
+        )
+        .await
+        .is_err());
+
+        // Export the range in two runs, the second extending the index.
+        export_range(
+            store.clone(),
+            heaviest_tipset.clone(),
+            0,
+            Some(19),
+            10,
+            output_dir.path(),
+            false,
+        )
+        .await
+        .unwrap();
+        let index = export_range(
+            store.clone(),
+            heaviest_tipset.clone(),
+            20,
+            None,
+            10,
+            output_dir.path(),
+            false,
+        )
+        .await
+        .unwrap();
+        assert_eq!(index.shards.len(), 4);
+        assert_eq!(index.shards[0].from, 0);
+        assert_eq!(index.shards[3].to, to);
+        assert!(index.shards.windows(2).all(|w| w[0].to + 1 == w[1].from));
+
+        // Shards don't overlap, and together hold the whole snapshot.
+        let mut seen = CidHashSet::default();
+        for shard in &index.shards {
+            let file = tokio::fs::File::open(&shard.path).await.unwrap();
+            let cids: Vec<Cid> = CarStream::new(BufReader::new(file))
+                .await
+                .unwrap()
+                .map_ok(|block| block.cid)
+                .try_collect()
+                .await
+                .unwrap();
+            assert!(cids.into_iter().all(|cid| seen.insert(cid)));
+        }
+        let mut blocks = stream_graph(&store, heaviest_tipset.clone().chain(&store), 0);
+        while let Some(block) = blocks.try_next().await.unwrap() {
+            assert!(seen.contains(&block.cid));
+        }
+
+        // The next range has to continue the index, unless it's overwritten.
+        assert!(export_range(
+            store.clone(),
+            heaviest_tipset.clone(),
+            30,
+            None,
+            10,
+            output_dir.path(),
+            false,
+        )
+        .await
+        .is_err());
+
+        // Mounting on demand only adds the missing shards.
+        let many = ManyCar::new(crate::db::MemoryDB::default());
+        assert_eq!(many.read_only_shards(&index, 15).unwrap(), 2);
+        assert_eq!(many.read_only_shards(&index, to).unwrap(), 2);
+        assert_eq!(many.read_only_shards(&index, to).unwrap(), 0);
+        assert_eq!(many.heaviest_tipset().unwrap(), heaviest_tipset);
+
+        let index_path = output_dir
+            .path()
+            .join(format!("{}{SHARD_INDEX_EXTENSION}", index.network));
+        let many = ManyCar::<crate::db::MemoryDB>::default()
+            .with_read_only_files_at(std::iter::once(index_path), 15)
+            .unwrap();
+        assert_eq!(many.heaviest_tipset().unwrap().epoch(), 19);
+    }
 }
@@ -0,0 +1,441 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Storage breakdown of an archive.
+//!
+//! The graph is walked from the heaviest tipset towards genesis, and each
+//! block is counted once, under the kind of data it was first reached as:
+//! block headers, messages, receipts, state trees (the actors HAMT and the
+//! state-root info), actor code, or the state of an actor. Actor states are
+//! grouped by actor type, resolved from the actor code CID.
+//!
+//! The epoch histogram attributes each block to the newest tipset that
+//! references it, which shows how much data every range of epochs adds to the
+//! archive.
+
+use crate::blocks::Tipset;
+use crate::cid_collections::CidHashSet;
+use crate::ipld::Ipld;
+use crate::shim::address::Address;
+use crate::shim::clock::ChainEpoch;
+use crate::shim::machine::BuiltinActorManifest;
+use crate::shim::state_tree::StateTree;
+use crate::utils::encoding::{extract_cids, from_slice_with_fallback};
+use ahash::HashMap;
+use cid::multihash::Code::Identity;
+use cid::Cid;
+use fil_actor_interface::system;
+use fvm_ipld_blockstore::Blockstore;
+use human_repr::HumanCount as _;
+use indicatif::ProgressIterator as _;
+use itertools::Itertools as _;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Number of blocks and bytes of some kind of data.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Usage {
+    pub blocks: u64,
+    pub bytes: u64,
+}
+
+impl Usage {
+    fn add(&mut self, other: Usage) {
+        self.blocks += other.blocks;
+        self.bytes += other.bytes;
+    }
+
+    fn of_block(len: usize) -> Self {
+        Usage {
+            blocks: 1,
+            bytes: len as u64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActorUsage {
+    pub address: Address,
+    pub actor: String,
+    pub usage: Usage,
+}
+
+#[derive(Debug, Default)]
+pub struct ArchiveStats {
+    pub headers: Usage,
+    pub messages: Usage,
+    pub receipts: Usage,
+    pub state_trees: Usage,
+    pub code: Usage,
+    /// Actor states, by actor type.
+    pub actors: BTreeMap<String, Usage>,
+    /// Largest actor states, largest first.
+    pub largest_actors: Vec<ActorUsage>,
+    /// Usage by range of epochs, keyed by the first epoch of each range.
+    pub epochs: BTreeMap<ChainEpoch, Usage>,
+    pub bucket_size: ChainEpoch,
+}
+
+impl ArchiveStats {
+    pub fn total(&self) -> Usage {
+        let mut total = Usage::default();
+        for usage in [
+            self.headers,
+            self.messages,
+            self.receipts,
+            self.state_trees,
+            self.code,
+        ]
+        .into_iter()
+        .chain(self.actors.values().copied())
+        {
+            total.add(usage);
+        }
+        total
+    }
+
+    /// Walks the archive from `root`. `top` is the number of largest actors to
+    /// keep and `bucket_size` the number of epochs per histogram bucket.
+    pub fn from_store(
+        store: impl Blockstore,
+        root: Tipset,
+        top: usize,
+        bucket_size: ChainEpoch,
+        progress: bool,
+    ) -> anyhow::Result<Self> {
+        let store = Arc::new(store);
+        let bucket_size = bucket_size.max(1);
+        let mut walker = Walker {
+            store: store.clone(),
+            seen: CidHashSet::default(),
+            code_names: HashMap::default(),
+            manifests_loaded: CidHashSet::default(),
+            stats: ArchiveStats {
+                bucket_size,
+                ..Default::default()
+            },
+            actors: HashMap::default(),
+        };
+
+        let root_epoch = root.epoch();
+        let tipsets = root.chain(&store);
+        let iter = if progress {
+            itertools::Either::Left(tipsets.progress_count(root_epoch as u64))
+        } else {
+            itertools::Either::Right(tipsets)
+        };
+        for tipset in iter {
+            let usage = walker.walk_tipset(&tipset)?;
+            let bucket = tipset.epoch() - tipset.epoch().rem_euclid(bucket_size);
+            walker.stats.epochs.entry(bucket).or_default().add(usage);
+        }
+
+        let Walker {
+            mut stats, actors, ..
+        } = walker;
+        stats.largest_actors = actors
+            .into_iter()
+            .map(|(address, (actor, usage))| ActorUsage {
+                address,
+                actor,
+                usage,
+            })
+            .sorted_by(|a, b| {
+                b.usage
+                    .bytes
+                    .cmp(&a.usage.bytes)
+                    .then_with(|| a.address.cmp(&b.address))
+            })
+            .take(top)
+            .collect();
+        Ok(stats)
+    }
+}
+
+impl std::fmt::Display for ArchiveStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        fn row(f: &mut std::fmt::Formatter, name: &str, usage: &Usage) -> std::fmt::Result {
+            writeln!(
+                f,
+                "  {name:<24} {:>12} {:>12}",
+                usage.blocks,
+                usage.bytes.human_count_bytes().to_string()
+            )
+        }
+
+        writeln!(f, "  {:<24} {:>12} {:>12}", "Kind", "Blocks", "Size")?;
+        row(f, "Block headers", &self.headers)?;
+        row(f, "Messages", &self.messages)?;
+        row(f, "Receipts", &self.receipts)?;
+        row(f, "State trees", &self.state_trees)?;
+        row(f, "Actor code", &self.code)?;
+        for (actor, usage) in &self.actors {
+            row(f, &format!("State: {actor}"), usage)?;
+        }
+        row(f, "Total", &self.total())?;
+
+        writeln!(f)?;
+        writeln!(f, "Largest actors:")?;
+        for actor in &self.largest_actors {
+            writeln!(
+                f,
+                "  {:<24} {:<16} {:>12}",
+                actor.address.to_string(),
+                actor.actor,
+                actor.usage.bytes.human_count_bytes().to_string()
+            )?;
+        }
+
+        writeln!(f)?;
+        write!(f, "Epochs:")?;
+        for (bucket, usage) in self.epochs.iter().rev() {
+            write!(
+                f,
+                "\n  {:<24} {:>12} {:>12}",
+                format!("{bucket}..{}", bucket + self.bucket_size),
+                usage.blocks,
+                usage.bytes.human_count_bytes().to_string()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct Walker<DB> {
+    store: Arc<DB>,
+    seen: CidHashSet,
+    /// Actor type of the code CIDs that aren't identity CIDs.
+    code_names: HashMap<Cid, String>,
+    /// State roots whose manifest has been added to `code_names`, so that it
+    /// is loaded at most once even for codes it doesn't know.
+    manifests_loaded: CidHashSet,
+    stats: ArchiveStats,
+    /// Actor type and state usage, by actor address.
+    actors: HashMap<Address, (String, Usage)>,
+}
+
+impl<DB: Blockstore> Walker<DB> {
+    /// Walks the data first reached from `tipset`, and returns its usage.
+    fn walk_tipset(&mut self, tipset: &Tipset) -> anyhow::Result<Usage> {
+        let mut total = Usage::default();
+        for block in tipset.blocks() {
+            let headers = self.walk_block(block.cid())?;
+            self.stats.headers.add(headers);
+            let messages = self.walk_dag(*block.messages())?;
+            self.stats.messages.add(messages);
+            let receipts = self.walk_dag(*block.message_receipts())?;
+            self.stats.receipts.add(receipts);
+            for usage in [headers, messages, receipts] {
+                total.add(usage);
+            }
+        }
+        total.add(self.walk_state(*tipset.parent_state())?);
+        Ok(total)
+    }
+
+    /// Marks `cid` as seen and loads it, if it hasn't been seen before and is
+    /// in the archive.
+    fn load(&mut self, cid: Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        if cid.hash().code() == u64::from(Identity) || !self.seen.insert(cid) {
+            return Ok(None);
+        }
+        self.store.get(&cid)
+    }
+
+    fn walk_block(&mut self, cid: &Cid) -> anyhow::Result<Usage> {
+        Ok(self
+            .load(*cid)?
+            .map(|data| Usage::of_block(data.len()))
+            .unwrap_or_default())
+    }
+
+    /// Walks all the blocks reachable from `root`.
+    fn walk_dag(&mut self, root: Cid) -> anyhow::Result<Usage> {
+        let mut usage = Usage::default();
+        let mut stack = vec![root];
+        while let Some(cid) = stack.pop() {
+            if let Some(data) = self.load(cid)? {
+                usage.add(Usage::of_block(data.len()));
+                if cid.codec() == fvm_ipld_encoding::DAG_CBOR {
+                    stack.extend(extract_cids(&data)?);
+                }
+            }
+        }
+        Ok(usage)
+    }
+
+    /// Walks a state tree. The root is either a `[version, actors, info]`
+    /// tuple or, for version 0, the root of the actors HAMT.
+    fn walk_state(&mut self, root: Cid) -> anyhow::Result<Usage> {
+        let Some(data) = self.store.get(&root)? else {
+            return Ok(Usage::default());
+        };
+        let actors = match from_slice_with_fallback::<Ipld>(&data)? {
+            Ipld::List(fields) => match fields.as_slice() {
+                [Ipld::Integer(_), Ipld::Link(actors), Ipld::Link(info)] => {
+                    if !self.seen.insert(root) {
+                        return Ok(Usage::default());
+                    }
+                    let mut usage = Usage::of_block(data.len());
+                    usage.add(self.walk_dag(*info)?);
+                    self.stats.state_trees.add(usage);
+                    let mut total = usage;
+                    total.add(self.walk_hamt(root, *actors)?);
+                    return Ok(total);
+                }
+                _ => root,
+            },
+            _ => root,
+        };
+        self.walk_hamt(root, actors)
+    }
+
+    /// Walks the actors HAMT of the state tree at `state_root`, and the states
+    /// of its actors.
+    fn walk_hamt(&mut self, state_root: Cid, hamt_root: Cid) -> anyhow::Result<Usage> {
+        let mut total = Usage::default();
+        let mut stack = vec![hamt_root];
+        while let Some(cid) = stack.pop() {
+            let Some(data) = self.load(cid)? else {
+                continue;
+            };
+            let usage = Usage::of_block(data.len());
+            self.stats.state_trees.add(usage);
+            total.add(usage);
+
+            // HAMT nodes are `[bitfield, pointers]`. A pointer is either a
+            // link to a child node or a bucket of key-value pairs. Version 0
+            // nodes wrap pointers in a map, with `0` for links and `1` for
+            // buckets.
+            let Ipld::List(mut node) = from_slice_with_fallback::<Ipld>(&data)? else {
+                continue;
+            };
+            let Some(Ipld::List(pointers)) = node.pop() else {
+                continue;
+            };
+            for pointer in pointers {
+                let pointer = match pointer {
+                    Ipld::Map(mut map) => match map.remove("0").or_else(|| map.remove("1")) {
+                        Some(pointer) => pointer,
+                        None => continue,
+                    },
+                    pointer => pointer,
+                };
+                match pointer {
+                    Ipld::Link(child) => stack.push(child),
+                    Ipld::List(bucket) => {
+                        for entry in bucket {
+                            total.add(self.walk_actor(state_root, entry)?);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(total)
+    }
+
+    /// Walks an `[address, [code, head, ...]]` entry of the actors HAMT.
+    fn walk_actor(&mut self, state_root: Cid, entry: Ipld) -> anyhow::Result<Usage> {
+        let Ipld::List(entry) = entry else {
+            return Ok(Usage::default());
+        };
+        let [Ipld::Bytes(key), Ipld::List(actor)] = entry.as_slice() else {
+            return Ok(Usage::default());
+        };
+        let [Ipld::Link(code), Ipld::Link(head), ..] = actor.as_slice() else {
+            return Ok(Usage::default());
+        };
+
+        let code_usage = self.walk_dag(*code)?;
+        self.stats.code.add(code_usage);
+
+        let usage = self.walk_dag(*head)?;
+        if usage != Usage::default() {
+            let name = self.actor_name(state_root, code);
+            self.stats
+                .actors
+                .entry(name.clone())
+                .or_default()
+                .add(usage);
+            if let Ok(address) = Address::from_bytes(key) {
+                self.actors
+                    .entry(address)
+                    .or_insert_with(|| (name, Usage::default()))
+                    .1
+                    .add(usage);
+            }
+        }
+
+        let mut total = code_usage;
+        total.add(usage);
+        Ok(total)
+    }
+
+    /// Resolves the actor type of `code`. Before actors version 8, code CIDs
+    /// are identity CIDs embedding names such as `fil/7/storageminer`. Later
+    /// code CIDs are resolved with the manifest of the system actor.
+    fn actor_name(&mut self, state_root: Cid, code: &Cid) -> String {
+        if code.hash().code() == u64::from(Identity) {
+            if let Ok(name) = std::str::from_utf8(code.hash().digest()) {
+                return name.rsplit('/').next().unwrap_or(name).to_string();
+            }
+        }
+        if !self.code_names.contains_key(code) && self.manifests_loaded.insert(state_root) {
+            if let Ok(manifest) = self.load_manifest(state_root) {
+                self.code_names.extend(
+                    manifest
+                        .builtin_actors()
+                        .map(|(actor, cid)| (cid, actor.name().to_string())),
+                );
+            }
+        }
+        self.code_names
+            .get(code)
+            .cloned()
+            .unwrap_or_else(|| "unknown".into())
+    }
+
+    fn load_manifest(&self, state_root: Cid) -> anyhow::Result<BuiltinActorManifest> {
+        let state_tree = StateTree::new_from_root(self.store.clone(), &state_root)?;
+        let system_actor = state_tree
+            .get_actor(&Address::SYSTEM_ACTOR)?
+            .ok_or_else(|| anyhow::anyhow!("missing system actor"))?;
+        let builtin_actors =
+            match system::State::load(&self.store, system_actor.code, system_actor.state)? {
+                system::State::V8(state) => state.builtin_actors,
+                system::State::V9(state) => state.builtin_actors,
+                system::State::V10(state) => state.builtin_actors,
+                system::State::V11(state) => state.builtin_actors,
+                system::State::V12(state) => state.builtin_actors,
+            };
+        BuiltinActorManifest::load_manifest(&self.store, &builtin_actors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::car::AnyCar;
+    use crate::networks::calibnet;
+
+    #[test]
+    fn archive_stats_calibnet() {
+        let store = AnyCar::try_from(calibnet::DEFAULT_GENESIS).unwrap();
+        let root = store.heaviest_tipset().unwrap();
+        let stats = ArchiveStats::from_store(&store, root, 5, 100, false).unwrap();
+
+        assert_eq!(stats.headers.blocks, 1);
+        assert!(stats.state_trees.blocks > 0);
+        assert!(stats.actors.contains_key("storageminer"));
+        assert!(!stats.actors.contains_key("unknown"));
+        assert_eq!(stats.largest_actors.len(), 5);
+        assert!(stats
+            .largest_actors
+            .windows(2)
+            .all(|w| w[0].usage.bytes >= w[1].usage.bytes));
+        // Every block of the genesis archive is reached from epoch 0.
+        assert_eq!(stats.epochs.len(), 1);
+        assert_eq!(stats.epochs[&0], stats.total());
+    }
+}
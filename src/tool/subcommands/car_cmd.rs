@@ -1,23 +1,29 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::io::Write as _;
 use std::path::{Path, PathBuf};
 
+use anyhow::Context as _;
+use cid::Cid;
 use clap::Subcommand;
-use futures::{StreamExt, TryStreamExt};
+use futures::{Stream, StreamExt, TryStreamExt};
 use fvm_ipld_blockstore::Blockstore;
 use indicatif::{ProgressBar, ProgressStyle};
 use itertools::Itertools;
 use tokio::{
     fs::File,
-    io::{AsyncWriteExt, BufReader},
+    io::{AsyncBufRead, AsyncReadExt as _, AsyncSeekExt as _, AsyncWriteExt, BufReader},
 };
 
-use crate::db::car::ForestCar;
+use crate::cid_collections::CidHashSet;
+use crate::db::car::{carv2, AnyCar, ForestCar};
+use crate::ipld::{json::IpldJsonRef, Ipld};
 use crate::utils::db::{
-    car_stream::CarStream,
+    car_stream::{CarBlock, CarStream},
     car_util::{dedup_block_stream, merge_car_streams},
 };
+use crate::utils::encoding::{extract_cids, from_slice_with_fallback};
 
 #[derive(Debug, Subcommand)]
 pub enum CarCommands {
@@ -30,6 +36,18 @@ pub enum CarCommands {
         #[arg(short, long)]
         output: PathBuf,
     },
+    /// Convert a CAR archive (CARv1, CARv2, `.car.zst` or `.forest.car.zst`)
+    /// to `.forest.car.zst` or to CARv2 with an index
+    Convert {
+        /// Input CAR archive
+        input: PathBuf,
+        /// Output file path
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = CarFormat::Forest)]
+        format: CarFormat,
+    },
     /// Check the validity of a CAR archive. For Filecoin-specific checks, see
     /// `forest-tool snapshot validate`.
     Validate {
@@ -42,8 +60,113 @@ pub enum CarCommands {
         #[arg(long)]
         ignore_forest_index: bool,
     },
+    /// List the blocks of a CAR archive, with their codec and size
+    Ls {
+        /// CAR archive. Supported extensions: `.car`, `.car.zst`, `.forest.car.zst`
+        car_file: PathBuf,
+    },
+    /// Print a single block of a CAR archive
+    Get {
+        /// CAR archive. Supported extensions: `.car`, `.car.zst`, `.forest.car.zst`
+        car_file: PathBuf,
+        /// CID of the block
+        cid: Cid,
+        /// Output encoding
+        #[arg(long, value_enum, default_value_t = BlockFormat::DagJson)]
+        format: BlockFormat,
+        /// Write the block to this file rather than to stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Extract the DAG rooted at a CID into a new CAR archive. Links to blocks
+    /// missing from the input archive are skipped.
+    Extract {
+        /// CAR archive. Supported extensions: `.car`, `.car.zst`, `.forest.car.zst`
+        car_file: PathBuf,
+        /// Root of the DAG
+        cid: Cid,
+        /// Output file path
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = CarFormat::Forest)]
+        format: CarFormat,
+    },
+    /// Copy the blocks matching the given codecs and hash functions into a
+    /// new CAR archive
+    Filter {
+        /// CAR archive. Supported extensions: `.car`, `.car.zst`, `.forest.car.zst`
+        car_file: PathBuf,
+        /// Output file path
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Keep blocks with these codecs, by name (`raw`, `dag-cbor`, ...) or
+        /// code. All codecs are kept if unset.
+        #[arg(long, value_parser = parse_codec)]
+        codec: Vec<u64>,
+        /// Keep blocks with these hash functions, by name (`blake2b-256`,
+        /// `sha2-256`, ...) or code. All hash functions are kept if unset.
+        #[arg(long, value_parser = parse_hash)]
+        hash: Vec<u64>,
+        /// Drop the matching blocks instead of keeping them
+        #[arg(long)]
+        exclude: bool,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = CarFormat::Forest)]
+        format: CarFormat,
+    },
+    /// Print the roots of a CAR archive, or copy the archive with new roots
+    Roots {
+        /// CAR archive. Supported extensions: `.car`, `.car.zst`, `.forest.car.zst`
+        car_file: PathBuf,
+        /// New roots of the archive
+        #[arg(long, requires = "output")]
+        set: Vec<Cid>,
+        /// Output file path, required with `--set`
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Output format
+        #[arg(long, value_enum, default_value_t = CarFormat::Forest)]
+        format: CarFormat,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CarFormat {
+    /// `.forest.car.zst`
+    Forest,
+    /// CARv2 with a `MultihashIndexSorted` index
+    Carv2,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum BlockFormat {
+    /// The block data, as stored in the archive
+    Raw,
+    /// The block decoded and encoded again as DAG-CBOR. Raw blocks are
+    /// encoded as a byte string.
+    DagCbor,
+    /// The block decoded and encoded as DAG-JSON
+    DagJson,
 }
 
+/// Names of common multicodec codecs.
+const CODECS: &[(&str, u64)] = &[
+    ("cbor", 0x51),
+    ("raw", 0x55),
+    ("dag-pb", 0x70),
+    ("dag-cbor", 0x71),
+    ("dag-json", 0x0129),
+];
+
+/// Names of common multihash functions.
+const HASHES: &[(&str, u64)] = &[
+    ("identity", 0x00),
+    ("sha2-256", 0x12),
+    ("keccak-256", 0x1b),
+    ("blake2b-256", 0xb220),
+];
+
 impl CarCommands {
     pub async fn run(self) -> anyhow::Result<()> {
         match self {
@@ -69,16 +192,218 @@ impl CarCommands {
                 crate::db::car::forest::Encoder::write(&mut writer, all_roots, frames).await?;
                 writer.flush().await?;
             }
+            Self::Convert {
+                input,
+                output,
+                format,
+            } => {
+                let stream = open_car_stream(&input).await?;
+                let roots = stream.header.roots.clone();
+                write_car(&output, roots, stream, format).await?;
+            }
             Self::Validate {
                 car_file,
                 ignore_block_validity,
                 ignore_forest_index,
             } => validate(&car_file, ignore_block_validity, ignore_forest_index).await?,
+            Self::Ls { car_file } => {
+                let mut stream = open_car_stream(&car_file).await?;
+                let mut stdout = std::io::stdout().lock();
+                while let Some(block) = stream.try_next().await? {
+                    writeln!(
+                        stdout,
+                        "{}\t{}\t{}",
+                        block.cid,
+                        multicodec_name(CODECS, block.cid.codec()),
+                        block.data.len()
+                    )?;
+                }
+            }
+            Self::Get {
+                car_file,
+                cid,
+                format,
+                output,
+            } => {
+                let car = AnyCar::try_from(car_file.as_path())?;
+                let data = car
+                    .get(&cid)?
+                    .with_context(|| format!("block {cid} is not in the archive"))?;
+                let encoded = encode_block(&cid, data, format)?;
+                match output {
+                    Some(output) => std::fs::write(output, encoded)?,
+                    None => std::io::stdout().lock().write_all(&encoded)?,
+                }
+            }
+            Self::Extract {
+                car_file,
+                cid,
+                output,
+                format,
+            } => {
+                let car = AnyCar::try_from(car_file.as_path())?;
+                anyhow::ensure!(car.has(&cid)?, "block {cid} is not in the archive");
+                let blocks = futures::stream::iter(dag_blocks(&car, cid));
+                write_car(&output, vec![cid], blocks, format).await?;
+            }
+            Self::Filter {
+                car_file,
+                output,
+                codec,
+                hash,
+                exclude,
+                format,
+            } => {
+                let stream = open_car_stream(&car_file).await?;
+                let roots = stream.header.roots.clone();
+                let blocks = stream.try_filter(|block| {
+                    let matches = (codec.is_empty() || codec.contains(&block.cid.codec()))
+                        && (hash.is_empty() || hash.contains(&block.cid.hash().code()));
+                    futures::future::ready(matches != exclude)
+                });
+                write_car(&output, roots, blocks, format).await?;
+            }
+            Self::Roots {
+                car_file,
+                set,
+                output,
+                format,
+            } => match output {
+                Some(output) => {
+                    anyhow::ensure!(!set.is_empty(), "`--set` is required with `--output`");
+                    let stream = open_car_stream(&car_file).await?;
+                    write_car(&output, set, stream, format).await?;
+                }
+                None => {
+                    for root in AnyCar::try_from(car_file.as_path())?.roots() {
+                        println!("{root}");
+                    }
+                }
+            },
         }
         Ok(())
     }
 }
 
+/// Writes `blocks` to a new CAR archive at `output`.
+async fn write_car(
+    output: &Path,
+    roots: Vec<Cid>,
+    blocks: impl Stream<Item = std::io::Result<CarBlock>> + Unpin,
+    format: CarFormat,
+) -> anyhow::Result<()> {
+    let mut writer = tokio::io::BufWriter::new(File::create(output).await?);
+    match format {
+        CarFormat::Forest => {
+            let frames = crate::db::car::forest::Encoder::compress_stream_default(
+                blocks.map_err(anyhow::Error::from),
+            );
+            crate::db::car::forest::Encoder::write(&mut writer, roots, frames).await?;
+        }
+        CarFormat::Carv2 => carv2::Encoder::write(&mut writer, roots, blocks).await?,
+    }
+    writer.flush().await?;
+    Ok(())
+}
+
+/// Blocks of the DAG rooted at `root`, in depth-first order. Identity CIDs and
+/// links to blocks missing from `store` are skipped.
+fn dag_blocks(
+    store: &impl Blockstore,
+    root: Cid,
+) -> impl Iterator<Item = std::io::Result<CarBlock>> + '_ {
+    let mut seen = CidHashSet::default();
+    let mut stack = vec![root];
+    std::iter::from_fn(move || {
+        while let Some(cid) = stack.pop() {
+            if cid.hash().code() == u64::from(cid::multihash::Code::Identity) || !seen.insert(cid) {
+                continue;
+            }
+            let data = match store.get(&cid) {
+                Ok(Some(data)) => data,
+                Ok(None) => continue,
+                Err(e) => return Some(Err(std::io::Error::other(e))),
+            };
+            if cid.codec() == fvm_ipld_encoding::DAG_CBOR {
+                match extract_cids(&data) {
+                    Ok(links) => stack.extend(links.into_iter().rev()),
+                    Err(e) => return Some(Err(std::io::Error::other(e))),
+                }
+            }
+            return Some(Ok(CarBlock { cid, data }));
+        }
+        None
+    })
+}
+
+fn encode_block(cid: &Cid, data: Vec<u8>, format: BlockFormat) -> anyhow::Result<Vec<u8>> {
+    let decode = |data: Vec<u8>| -> anyhow::Result<Ipld> {
+        match cid.codec() {
+            fvm_ipld_encoding::DAG_CBOR => from_slice_with_fallback(&data),
+            _ => Ok(Ipld::Bytes(data)),
+        }
+    };
+    Ok(match format {
+        BlockFormat::Raw => data,
+        BlockFormat::DagCbor => fvm_ipld_encoding::to_vec(&decode(data)?)?,
+        BlockFormat::DagJson => {
+            let mut json = serde_json::to_vec_pretty(&IpldJsonRef(&decode(data)?))?;
+            json.push(b'\n');
+            json
+        }
+    })
+}
+
+fn multicodec_name(names: &[(&str, u64)], code: u64) -> String {
+    names
+        .iter()
+        .find(|(_, it)| *it == code)
+        .map(|(name, _)| name.to_string())
+        .unwrap_or_else(|| format!("{code:#x}"))
+}
+
+fn parse_multicodec(names: &[(&str, u64)], s: &str) -> anyhow::Result<u64> {
+    if let Some((_, code)) = names.iter().find(|(name, _)| name.eq_ignore_ascii_case(s)) {
+        return Ok(*code);
+    }
+    match s.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    }
+    .with_context(|| {
+        format!(
+            "unknown code {s}, expected a number or one of: {}",
+            names.iter().map(|(name, _)| name).join(", ")
+        )
+    })
+}
+
+fn parse_codec(s: &str) -> anyhow::Result<u64> {
+    parse_multicodec(CODECS, s)
+}
+
+fn parse_hash(s: &str) -> anyhow::Result<u64> {
+    parse_multicodec(HASHES, s)
+}
+
+/// Streams the blocks of any CAR archive. For CARv2 archives, only the data
+/// payload is read.
+async fn open_car_stream(
+    path: &Path,
+) -> anyhow::Result<CarStream<Box<dyn AsyncBufRead + Unpin + Send>>> {
+    let mut file = File::open(path).await?;
+    let reader: Box<dyn AsyncBufRead + Unpin + Send> =
+        match carv2::CarV2Header::read(std::fs::File::open(path)?) {
+            Ok(header) => {
+                file.seek(std::io::SeekFrom::Start(header.data_offset))
+                    .await?;
+                Box::new(BufReader::new(file.take(header.data_size)))
+            }
+            Err(_) => Box::new(BufReader::new(file)),
+        };
+    Ok(CarStream::new(reader).await?)
+}
+
 /// At present, three properties are checked:
 /// - The CAR file is syntactically valid and all blocks can be streamed.
 /// - Each block CID is checked against the hash of the block.
@@ -120,17 +445,156 @@ async fn validate(
 
 #[cfg(test)]
 mod tests {
-    use super::validate;
-    use crate::db::car::forest;
+    use super::{
+        encode_block, open_car_stream, parse_codec, parse_hash, validate, BlockFormat, CarCommands,
+        CarFormat,
+    };
+    use crate::db::car::{forest, AnyCar};
+    use crate::ipld::{json::IpldJson, Ipld};
     use crate::networks::{calibnet, mainnet};
     use crate::utils::db::car_stream::CarBlock;
     use cid::multihash::{Code, MultihashDigest};
     use cid::Cid;
     use futures::{stream::iter, StreamExt, TryStreamExt};
+    use fvm_ipld_blockstore::Blockstore as _;
     use std::io::Write;
     use tempfile::{Builder, TempPath};
     use tokio::io::AsyncWriteExt;
 
+    #[tokio::test]
+    async fn convert_carv2_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let genesis = dir.path().join("genesis.car");
+        std::fs::write(&genesis, calibnet::DEFAULT_GENESIS).unwrap();
+        let carv2 = dir.path().join("genesis.carv2.car");
+        let forest = dir.path().join("genesis.forest.car.zst");
+        CarCommands::Convert {
+            input: genesis,
+            output: carv2.clone(),
+            format: CarFormat::Carv2,
+        }
+        .run()
+        .await
+        .unwrap();
+        CarCommands::Convert {
+            input: carv2.clone(),
+            output: forest.clone(),
+            format: CarFormat::Forest,
+        }
+        .run()
+        .await
+        .unwrap();
+
+        for path in [&carv2, &forest] {
+            let car = AnyCar::try_from(path.as_path()).unwrap();
+            assert!(car.has(&calibnet::GENESIS_CID).unwrap());
+        }
+        assert_eq!(
+            AnyCar::try_from(carv2.as_path()).unwrap().variant(),
+            "CARv2"
+        );
+        assert!(validate(&forest, false, false).await.is_ok());
+    }
+
+    async fn car_cids(path: &std::path::Path) -> Vec<Cid> {
+        open_car_stream(path)
+            .await
+            .unwrap()
+            .map_ok(|block| block.cid)
+            .try_collect()
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn extract_filter_and_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let genesis = dir.path().join("genesis.car");
+        std::fs::write(&genesis, calibnet::DEFAULT_GENESIS).unwrap();
+        let all = car_cids(&genesis).await;
+
+        // The genesis block links to the whole archive, but the genesis state
+        // tree doesn't.
+        let extracted = dir.path().join("extracted.forest.car.zst");
+        let car = AnyCar::try_from(genesis.as_path()).unwrap();
+        let state_root = *car.heaviest_tipset().unwrap().parent_state();
+        CarCommands::Extract {
+            car_file: genesis.clone(),
+            cid: state_root,
+            output: extracted.clone(),
+            format: CarFormat::Forest,
+        }
+        .run()
+        .await
+        .unwrap();
+        let extracted_car = AnyCar::try_from(extracted.as_path()).unwrap();
+        assert_eq!(extracted_car.roots(), vec![state_root]);
+        assert!(extracted_car.has(&state_root).unwrap());
+        assert!(!extracted_car.has(&calibnet::GENESIS_CID).unwrap());
+
+        let raw = dir.path().join("raw.car");
+        let not_raw = dir.path().join("not-raw.car");
+        for (output, exclude) in [(&raw, false), (&not_raw, true)] {
+            CarCommands::Filter {
+                car_file: genesis.clone(),
+                output: output.clone(),
+                codec: vec![parse_codec("raw").unwrap()],
+                hash: vec![],
+                exclude,
+                format: CarFormat::Carv2,
+            }
+            .run()
+            .await
+            .unwrap();
+        }
+        let (raw, not_raw) = (car_cids(&raw).await, car_cids(&not_raw).await);
+        assert!(raw.iter().all(|cid| cid.codec() == 0x55));
+        assert!(not_raw.iter().all(|cid| cid.codec() != 0x55));
+        assert_eq!(raw.len() + not_raw.len(), all.len());
+
+        let rerooted = dir.path().join("rerooted.forest.car.zst");
+        CarCommands::Roots {
+            car_file: genesis.clone(),
+            set: vec![state_root],
+            output: Some(rerooted.clone()),
+            format: CarFormat::Forest,
+        }
+        .run()
+        .await
+        .unwrap();
+        let rerooted_car = AnyCar::try_from(rerooted.as_path()).unwrap();
+        assert_eq!(rerooted_car.roots(), vec![state_root]);
+        assert_eq!(car_cids(&rerooted).await, all);
+    }
+
+    #[test]
+    fn encode_genesis_block() {
+        let car = AnyCar::try_from(calibnet::DEFAULT_GENESIS).unwrap();
+        let cid = *calibnet::GENESIS_CID;
+        let data = car.get(&cid).unwrap().unwrap();
+        assert_eq!(
+            encode_block(&cid, data.clone(), BlockFormat::Raw).unwrap(),
+            data
+        );
+        // Canonical DAG-CBOR round-trips.
+        assert_eq!(
+            encode_block(&cid, data.clone(), BlockFormat::DagCbor).unwrap(),
+            data
+        );
+        let json = encode_block(&cid, data.clone(), BlockFormat::DagJson).unwrap();
+        let IpldJson(ipld) = serde_json::from_slice(&json).unwrap();
+        assert_eq!(ipld, fvm_ipld_encoding::from_slice::<Ipld>(&data).unwrap());
+    }
+
+    #[test]
+    fn parse_multicodecs() {
+        assert_eq!(parse_codec("dag-cbor").unwrap(), 0x71);
+        assert_eq!(parse_codec("0x55").unwrap(), 0x55);
+        assert_eq!(parse_codec("113").unwrap(), 0x71);
+        assert_eq!(parse_hash("Blake2b-256").unwrap(), 0xb220);
+        parse_hash("md5").unwrap_err();
+    }
+
     #[tokio::test]
     async fn validate_junk_car() {
         let mut temp_path = Builder::new().tempfile().unwrap();
@@ -1,12 +1,38 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
 
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::blocks::{BlockHeader, Tipset, TipsetKeys};
+use crate::chain::ChainEpochDelta;
+use crate::chain_sync::SyncConfig;
+use crate::cid_collections::CidHashSet;
 use crate::cli::subcommands::prompt_confirm;
 use crate::cli_shared::{chain_path, read_config};
-use crate::db::db_engine::db_root;
+use crate::daemon::db_util::{import_chain_as_forest_car, load_all_forest_cars};
+use crate::db::backup::{SettingsDump, BACKUP_CHAIN_FILE, BACKUP_SETTINGS_FILE};
+use crate::db::car::{AnyCar, ManyCar};
+use crate::db::db_engine::{db_root, open_db, Db};
+use crate::db::migration::{DbMigration, MigrationStep};
+use crate::db::{
+    setting_keys::HEAD_KEY, truncated_hash, GarbageCollectable as _, MemoryDB, SettingsStore,
+    SettingsStoreExt as _,
+};
+use crate::libp2p::{build_transport, Keypair, Multiaddr, BITSWAP_PROTOCOLS};
+use crate::libp2p_bitswap::{request_manager::BitswapRequestManager, BitswapBehaviour};
 use crate::networks::NetworkChain;
+use crate::utils::encoding::{extract_cids, from_slice_with_fallback};
+use ahash::HashSet;
+use anyhow::Context as _;
+use cid::Cid;
 use clap::Subcommand;
-use tracing::error;
+use futures::StreamExt as _;
+use fvm_ipld_blockstore::Blockstore;
+use fvm_ipld_encoding::DAG_CBOR;
+use libp2p::swarm::{Swarm, SwarmEvent};
+use tracing::{error, info, warn};
 
 #[derive(Debug, Subcommand)]
 pub enum DBCommands {
@@ -31,6 +57,139 @@ pub enum DBCommands {
         #[arg(long)]
         chain: Option<NetworkChain>,
     },
+    /// Restore a backup made with `forest-cli db backup`. The node must not be
+    /// running.
+    Restore {
+        /// Backup directory
+        backup_dir: PathBuf,
+        /// Overwrite the head of an existing database
+        #[arg(long)]
+        force: bool,
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+    },
+    /// Walk the graph reachable from the stored head and report missing or
+    /// corrupt blocks. The node must not be running.
+    Verify {
+        /// How many state-roots to verify, starting at the head
+        #[arg(short, long)]
+        depth: Option<ChainEpochDelta>,
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+    },
+    /// Like `verify`, but fetch missing or corrupt blocks and write them to the
+    /// database. The node must not be running.
+    Repair {
+        /// Fetch blocks from this CAR file
+        #[arg(
+            long,
+            required_unless_present = "from_peers",
+            conflicts_with = "from_peers"
+        )]
+        from_car: Option<PathBuf>,
+        /// Fetch blocks over bitswap from these peers, given as multiaddresses
+        /// including the peer ID, e.g. the output of `forest-cli net listen`
+        #[arg(long, num_args = 1..)]
+        from_peers: Vec<Multiaddr>,
+        /// How many state-roots to repair, starting at the head
+        #[arg(short, long)]
+        depth: Option<ChainEpochDelta>,
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+    },
+    /// Migrate the database to the version of this binary, resuming an
+    /// interrupted migration if any. The node must not be running.
+    Migrate {
+        /// Print the migration steps without running them
+        #[arg(long, conflicts_with = "rollback")]
+        dry_run: bool,
+        /// Abandon an interrupted migration and go back to the database it
+        /// started from
+        #[arg(long)]
+        rollback: bool,
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+    },
+    /// Inspect and edit the settings store
+    #[command(subcommand)]
+    Settings(SettingsCommands),
+    /// Inspect and move the stored head
+    #[command(subcommand)]
+    Head(HeadCommands),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum SettingsCommands {
+    /// List the keys of the settings store
+    List {
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+    },
+    /// Print the value of a key, as text if it's valid UTF-8 and hex-encoded
+    /// otherwise
+    Get {
+        key: String,
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+    },
+    /// Set the value of a key. The node must not be running.
+    Set {
+        key: String,
+        value: String,
+        /// The value is hex-encoded binary data
+        #[arg(long)]
+        hex: bool,
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum HeadCommands {
+    /// Move the stored head, e.g. after a bad shutdown left it pointing at a
+    /// tipset with missing state. The node must not be running.
+    Set {
+        /// CIDs of the blocks of the new head tipset
+        #[arg(required_unless_present = "epoch", conflicts_with = "epoch")]
+        cids: Vec<Cid>,
+        /// Move the head back to this epoch of the current chain instead
+        #[arg(long)]
+        epoch: Option<i64>,
+        /// Optional TOML file containing forest daemon configuration
+        #[arg(short, long)]
+        config: Option<String>,
+        /// Optional chain, will override the chain section of configuration file if used
+        #[arg(long)]
+        chain: Option<NetworkChain>,
+    },
 }
 
 impl DBCommands {
@@ -78,6 +237,574 @@ impl DBCommands {
                     }
                 }
             }
+            Self::Restore {
+                backup_dir,
+                force,
+                config,
+                chain,
+            } => {
+                let (_, config) = read_config(config, chain)?;
+
+                let settings = SettingsDump::load(&backup_dir.join(BACKUP_SETTINGS_FILE))?;
+                let head = settings.head()?;
+
+                let dir = db_root(&chain_path(&config))?;
+                let db = open_db(dir.clone(), config.db_config())?;
+                if db.exists(HEAD_KEY)? && !force {
+                    anyhow::bail!(
+                        "database at {} already has a head, use --force to overwrite it",
+                        dir.display()
+                    );
+                }
+
+                let (car_path, ts) = import_chain_as_forest_car(
+                    &backup_dir.join(BACKUP_CHAIN_FILE),
+                    &dir.join("car_db"),
+                    false,
+                    None,
+                )
+                .await?;
+                if ts.key() != &head {
+                    std::fs::remove_file(car_path)?;
+                    anyhow::bail!("backup is inconsistent: chain export doesn't match the head");
+                }
+                settings.write_to(&db)?;
+
+                println!(
+                    "Restored backup at epoch {} into {}",
+                    ts.epoch(),
+                    dir.display()
+                );
+                Ok(())
+            }
+            Self::Verify {
+                depth,
+                config,
+                chain,
+            } => {
+                let db = open_node_db(config, chain)?;
+                let head = Tipset::load_required(&db, &db.require_obj(HEAD_KEY)?)?;
+                let depth = depth.unwrap_or(SyncConfig::default().recent_state_roots);
+                let report = verify_graph(&db, &head, depth, None).await?;
+                report.print();
+                if report.missing.is_empty() && report.corrupt.is_empty() {
+                    Ok(())
+                } else {
+                    anyhow::bail!("database verification failed")
+                }
+            }
+            Self::Repair {
+                from_car,
+                from_peers,
+                depth,
+                config,
+                chain,
+            } => {
+                let source = match from_car {
+                    Some(path) => BlockSource::Car(AnyCar::try_from(path.as_path())?.into_dyn()),
+                    None => BlockSource::Bitswap(BitswapSource::connect(from_peers).await?),
+                };
+                let db = open_node_db(config, chain)?;
+                let head_keys: TipsetKeys = db.require_obj(HEAD_KEY)?;
+                // The head itself may be missing, fetch it first.
+                for cid in head_keys.cids.clone() {
+                    if !db.has(&cid)? {
+                        if let Some(data) = source.get(&cid).await? {
+                            db.put_keyed(&cid, &data)?;
+                        }
+                    }
+                }
+                let head = Tipset::load_required(&db, &head_keys)?;
+                let depth = depth.unwrap_or(SyncConfig::default().recent_state_roots);
+                let report = verify_graph(&db, &head, depth, Some(&source)).await?;
+                report.print();
+                if report.missing.is_empty() && report.corrupt.is_empty() {
+                    Ok(())
+                } else {
+                    anyhow::bail!("some blocks couldn't be repaired")
+                }
+            }
+            Self::Migrate {
+                dry_run,
+                rollback,
+                config,
+                chain,
+            } => {
+                let (_, config) = read_config(config, chain)?;
+                let migration = DbMigration::new(chain_path(&config));
+                if *rollback {
+                    let version = migration.rollback()?;
+                    println!("Rolled back to database version {version}");
+                } else if *dry_run {
+                    if migration.is_interrupted()? {
+                        println!("An interrupted migration will be resumed");
+                    }
+                    let steps = migration.plan()?;
+                    if steps.is_empty() {
+                        println!("No database migration required");
+                    }
+                    for MigrationStep { from, to } in steps {
+                        println!("{from} -> {to}");
+                    }
+                } else {
+                    migration.migrate()?;
+                }
+                Ok(())
+            }
+            Self::Settings(cmd) => cmd.run(),
+            Self::Head(cmd) => cmd.run(),
         }
     }
 }
+
+impl SettingsCommands {
+    fn run(&self) -> anyhow::Result<()> {
+        match self {
+            Self::List { config, chain } => {
+                let db = open_settings_db(config, chain)?;
+                let mut keys = db.setting_keys()?;
+                keys.sort();
+                for key in keys {
+                    println!("{key}");
+                }
+                Ok(())
+            }
+            Self::Get { key, config, chain } => {
+                let db = open_settings_db(config, chain)?;
+                let value = db
+                    .read_bin(key)?
+                    .with_context(|| format!("key {key} not found"))?;
+                match String::from_utf8(value) {
+                    Ok(text) => println!("{text}"),
+                    Err(e) => println!("{}", hex::encode(e.as_bytes())),
+                }
+                Ok(())
+            }
+            Self::Set {
+                key,
+                value,
+                hex,
+                config,
+                chain,
+            } => {
+                let db = open_settings_db(config, chain)?;
+                let value = match hex {
+                    true => hex::decode(value)?,
+                    false => value.as_bytes().to_vec(),
+                };
+                db.write_bin(key, &value)?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl HeadCommands {
+    fn run(&self) -> anyhow::Result<()> {
+        match self {
+            Self::Set {
+                cids,
+                epoch,
+                config,
+                chain,
+            } => {
+                let db = open_node_db(config, chain)?;
+                let head = match epoch {
+                    Some(epoch) => {
+                        let current = Tipset::load_required(&db, &db.require_obj(HEAD_KEY)?)?;
+                        anyhow::ensure!(
+                            *epoch <= current.epoch(),
+                            "epoch {epoch} is above the current head at {}",
+                            current.epoch()
+                        );
+                        current
+                            .chain(&db)
+                            .find(|ts| ts.epoch() <= *epoch)
+                            .with_context(|| format!("no tipset at epoch {epoch}"))?
+                    }
+                    None => Tipset::load_required(&db, &TipsetKeys::from_iter(cids.clone()))?,
+                };
+                db.write_obj(HEAD_KEY, head.key())?;
+                println!("Head set to {} at epoch {}", head.key(), head.epoch());
+                Ok(())
+            }
+        }
+    }
+}
+
+fn open_settings_db(config: &Option<String>, chain: &Option<NetworkChain>) -> anyhow::Result<Db> {
+    let (_, config) = read_config(config, chain)?;
+    let dir = db_root(&chain_path(&config))?;
+    open_db(dir, config.db_config())
+}
+
+/// Opens the node database together with the CAR-backed stores, which hold most
+/// of the chain.
+fn open_node_db(
+    config: &Option<String>,
+    chain: &Option<NetworkChain>,
+) -> anyhow::Result<ManyCar<Db>> {
+    let (_, config) = read_config(config, chain)?;
+    let dir = db_root(&chain_path(&config))?;
+    let db = ManyCar::new(open_db(dir.clone(), config.db_config())?);
+    load_all_forest_cars(&db, &dir.join("car_db"))?;
+    Ok(db)
+}
+
+/// Where `forest-tool db repair` fetches blocks from.
+enum BlockSource {
+    Car(AnyCar<Box<dyn crate::db::car::RandomAccessFileReader>>),
+    Bitswap(BitswapSource),
+}
+
+impl BlockSource {
+    /// Returns the block `cid`, if the source has it and its content matches
+    /// the CID. Blocks which don't match are discarded, so that they never
+    /// make it into the database.
+    async fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        let data = match self {
+            BlockSource::Car(car) => car.get(cid)?,
+            BlockSource::Bitswap(bitswap) => bitswap.get(cid).await?,
+        };
+        Ok(data.filter(|data| {
+            let valid = has_valid_hash(cid, data);
+            if !valid {
+                warn!("Discarding block {cid} from the source, its content doesn't match");
+            }
+            valid
+        }))
+    }
+}
+
+/// Fetches blocks from a fixed set of peers over bitswap. Received blocks are
+/// kept in memory until they are handed out, the database is never written to
+/// directly.
+struct BitswapSource {
+    request_manager: Arc<BitswapRequestManager>,
+    received: Arc<MemoryDB>,
+    swarm: tokio::task::JoinHandle<()>,
+    block_timeout: Duration,
+}
+
+impl BitswapSource {
+    /// How long to wait for the connection to the first peer.
+    const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+    /// How long to wait for each block by default.
+    const BLOCK_TIMEOUT: Duration = Duration::from_secs(30);
+
+    async fn connect(peers: &[Multiaddr]) -> anyhow::Result<Self> {
+        let keypair = Keypair::generate_ed25519();
+        let peer_id = keypair.public().to_peer_id();
+        let swarm = Swarm::new(
+            build_transport(keypair)?,
+            BitswapBehaviour::new(BITSWAP_PROTOCOLS, Default::default()),
+            peer_id,
+            libp2p::swarm::Config::with_tokio_executor(),
+        );
+        Self::with_swarm(swarm, peers, Self::BLOCK_TIMEOUT).await
+    }
+
+    async fn with_swarm(
+        mut swarm: Swarm<BitswapBehaviour>,
+        peers: &[Multiaddr],
+        block_timeout: Duration,
+    ) -> anyhow::Result<Self> {
+        for peer in peers {
+            swarm.dial(peer.clone())?;
+        }
+        let mut pending = peers.len();
+        let connected = tokio::time::timeout(Self::CONNECT_TIMEOUT, async {
+            while pending > 0 {
+                match swarm.next().await {
+                    Some(SwarmEvent::ConnectionEstablished { peer_id, .. }) => {
+                        info!("Connected to {peer_id}");
+                        return true;
+                    }
+                    Some(SwarmEvent::OutgoingConnectionError { error, .. }) => {
+                        warn!("{error}");
+                        pending -= 1;
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            false
+        })
+        .await;
+        anyhow::ensure!(
+            connected == Ok(true),
+            "couldn't connect to any of the peers"
+        );
+
+        let request_manager = swarm.behaviour().request_manager();
+        let received = Arc::new(MemoryDB::default());
+        let swarm = tokio::spawn(run_bitswap(swarm, received.clone()));
+        Ok(Self {
+            request_manager,
+            received,
+            swarm,
+            block_timeout,
+        })
+    }
+
+    async fn get(&self, cid: &Cid) -> anyhow::Result<Option<Vec<u8>>> {
+        let (sender, receiver) = flume::bounded(1);
+        self.request_manager.clone().get_block(
+            self.received.clone(),
+            *cid,
+            self.block_timeout,
+            Some(sender),
+        );
+        if !receiver.recv_async().await.unwrap_or_default() {
+            return Ok(None);
+        }
+        let data = self.received.get(cid)?;
+        self.received
+            .remove_keys(HashSet::from_iter([truncated_hash(cid.hash())]))?;
+        Ok(data)
+    }
+}
+
+impl Drop for BitswapSource {
+    fn drop(&mut self) {
+        self.swarm.abort();
+    }
+}
+
+/// Drives `swarm`, sending the requests of its request manager and storing the
+/// received blocks in `store`.
+async fn run_bitswap(swarm: Swarm<BitswapBehaviour>, store: Arc<MemoryDB>) {
+    let request_manager = swarm.behaviour().request_manager();
+    let mut outbound_requests = request_manager.outbound_request_rx().stream().fuse();
+    let mut swarm = swarm.fuse();
+    loop {
+        tokio::select! {
+            event = swarm.next() => match event {
+                Some(SwarmEvent::Behaviour(event)) => {
+                    if let Err(e) = request_manager.handle_event(
+                        swarm.get_mut().behaviour_mut(),
+                        store.as_ref(),
+                        event,
+                    ) {
+                        warn!("{e}");
+                    }
+                }
+                Some(_) => {}
+                None => break,
+            },
+            request = outbound_requests.next() => match request {
+                Some((peer, request)) => {
+                    swarm.get_mut().behaviour_mut().send_request(&peer, request);
+                }
+                None => break,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct VerifyReport {
+    visited: usize,
+    repaired: usize,
+    missing: Vec<Cid>,
+    corrupt: Vec<Cid>,
+}
+
+impl VerifyReport {
+    fn print(&self) {
+        println!("Visited blocks: {}", self.visited);
+        if self.repaired > 0 {
+            println!("Repaired blocks: {}", self.repaired);
+        }
+        println!("Missing blocks: {}", self.missing.len());
+        for cid in &self.missing {
+            println!("  {cid}");
+        }
+        println!("Corrupt blocks: {}", self.corrupt.len());
+        for cid in &self.corrupt {
+            println!("  {cid}");
+        }
+    }
+}
+
+/// Walks the block headers from `head` down to genesis, and the messages and
+/// state-trees of the most recent `depth` epochs, re-hashing every block. With a
+/// `source`, missing and corrupt blocks are replaced and the walk continues
+/// through them.
+async fn verify_graph(
+    db: &impl Blockstore,
+    head: &Tipset,
+    depth: ChainEpochDelta,
+    source: Option<&BlockSource>,
+) -> anyhow::Result<VerifyReport> {
+    let stateroot_limit = head.epoch() - depth;
+    let mut report = VerifyReport::default();
+    let mut seen = CidHashSet::default();
+    let mut headers = head.cids();
+    let mut graph = vec![];
+
+    loop {
+        let (cid, is_header) = match (graph.pop(), headers.pop()) {
+            (Some(cid), header) => {
+                headers.extend(header);
+                (cid, false)
+            }
+            (None, Some(cid)) => (cid, true),
+            (None, None) => break,
+        };
+        if !seen.insert(cid) || !should_verify(&cid) {
+            continue;
+        }
+        report.visited += 1;
+
+        let data = match db.get(&cid)? {
+            Some(data) if has_valid_hash(&cid, &data) => data,
+            stored => {
+                let fetched = match source {
+                    Some(source) => source.get(&cid).await?,
+                    None => None,
+                };
+                match (fetched, stored) {
+                    (Some(data), _) => {
+                        db.put_keyed(&cid, &data)?;
+                        report.repaired += 1;
+                        data
+                    }
+                    (None, Some(_)) => {
+                        report.corrupt.push(cid);
+                        continue;
+                    }
+                    (None, None) => {
+                        report.missing.push(cid);
+                        continue;
+                    }
+                }
+            }
+        };
+
+        if is_header {
+            let header: BlockHeader = from_slice_with_fallback(&data)?;
+            if header.epoch() == 0 {
+                // The genesis block has a dummy parent which isn't a block header.
+                graph.extend(header.parents().cids.clone());
+            } else {
+                headers.extend(header.parents().cids.clone());
+            }
+            if header.epoch() == 0 || header.epoch() > stateroot_limit {
+                graph.push(*header.state_root());
+            }
+            if header.epoch() > stateroot_limit {
+                graph.push(*header.messages());
+            }
+        } else if cid.codec() == DAG_CBOR {
+            graph.extend(extract_cids(&data)?);
+        }
+    }
+    Ok(report)
+}
+
+/// Identity CIDs carry their data inline, only raw and `DAG_CBOR` blocks are
+/// stored in the database.
+fn should_verify(cid: &Cid) -> bool {
+    cid.hash().code() != u64::from(cid::multihash::Code::Identity)
+        && matches!(cid.codec(), crate::shim::crypto::IPLD_RAW | DAG_CBOR)
+}
+
+fn has_valid_hash(cid: &Cid, data: &[u8]) -> bool {
+    use cid::multihash::MultihashDigest as _;
+    match cid::multihash::Code::try_from(cid.hash().code()) {
+        Ok(code) => code.digest(data) == *cid.hash(),
+        // Can't verify what we can't hash.
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::MemoryDB;
+    use crate::libp2p::Protocol;
+    use crate::message_pool::test_provider::{mock_block, mock_block_with_parents};
+    use crate::utils::db::car_stream::{CarBlock, CarWriter};
+    use crate::utils::db::CborStoreExt as _;
+    use libp2p_swarm_test::SwarmExt as _;
+
+    fn bitswap_swarm() -> Swarm<BitswapBehaviour> {
+        Swarm::new_ephemeral(|_| BitswapBehaviour::new(BITSWAP_PROTOCOLS, Default::default()))
+    }
+
+    async fn car_source(cid: Cid, data: Vec<u8>) -> BlockSource {
+        let mut car_bytes = vec![];
+        futures::stream::iter([Ok(CarBlock { cid, data })])
+            .forward(CarWriter::new_carv1(vec![cid], &mut car_bytes).unwrap())
+            .await
+            .unwrap();
+        let car = AnyCar::<Vec<u8>>::Memory(crate::db::car::PlainCar::new(car_bytes).unwrap());
+        BlockSource::Car(car.into_dyn())
+    }
+
+    #[tokio::test]
+    async fn verify_and_repair() {
+        let db = MemoryDB::default();
+        let genesis = mock_block(1, 1);
+        let genesis_cid = db.put_cbor_default(&genesis).unwrap();
+        let block = mock_block_with_parents(&Tipset::from(&genesis), 1, 1);
+        db.put_cbor_default(&block).unwrap();
+        let head = Tipset::from(&block);
+
+        // The state root and messages of the mock blocks are never stored.
+        let report = verify_graph(&db, &head, 0, None).await.unwrap();
+        assert_eq!(report.visited, 3);
+        assert_eq!(report.missing, vec![*genesis.state_root()]);
+
+        // Corrupt the genesis block and repair it from a CAR file.
+        let car = car_source(genesis_cid, fvm_ipld_encoding::to_vec(&genesis).unwrap()).await;
+        db.put_keyed(&genesis_cid, b"garbage").unwrap();
+
+        let report = verify_graph(&db, &head, 0, None).await.unwrap();
+        assert_eq!(report.corrupt, vec![genesis_cid]);
+
+        // A source with a block that doesn't match its CID can't repair it.
+        let bad_car = car_source(genesis_cid, b"other garbage".to_vec()).await;
+        let report = verify_graph(&db, &head, 0, Some(&bad_car)).await.unwrap();
+        assert_eq!(report.corrupt, vec![genesis_cid]);
+        assert_eq!(db.get(&genesis_cid).unwrap().unwrap(), b"garbage");
+
+        let report = verify_graph(&db, &head, 0, Some(&car)).await.unwrap();
+        assert!(report.corrupt.is_empty());
+        assert_eq!(report.repaired, 1);
+        assert!(has_valid_hash(
+            &genesis_cid,
+            &db.get(&genesis_cid).unwrap().unwrap()
+        ));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn repair_over_bitswap() {
+        let db = MemoryDB::default();
+        let genesis = mock_block(1, 1);
+        let genesis_cid = db.put_cbor_default(&genesis).unwrap();
+        let head = Tipset::from(&genesis);
+        db.put_keyed(&genesis_cid, b"garbage").unwrap();
+
+        let peer_store = Arc::new(MemoryDB::default());
+        peer_store.put_cbor_default(&genesis).unwrap();
+        let mut peer = bitswap_swarm();
+        let (peer_addr, _) = peer.listen().with_memory_addr_external().await;
+        let peer_addr = peer_addr.with(Protocol::P2p(*peer.local_peer_id()));
+        tokio::spawn(run_bitswap(peer, peer_store));
+
+        let source = BlockSource::Bitswap(
+            BitswapSource::with_swarm(bitswap_swarm(), &[peer_addr], Duration::from_secs(2))
+                .await
+                .unwrap(),
+        );
+        let report = verify_graph(&db, &head, 0, Some(&source)).await.unwrap();
+        assert!(report.corrupt.is_empty());
+        assert_eq!(report.repaired, 1);
+        assert!(has_valid_hash(
+            &genesis_cid,
+            &db.get(&genesis_cid).unwrap().unwrap()
+        ));
+    }
+}
@@ -5,7 +5,10 @@ use super::*;
 use crate::blocks::Tipset;
 use crate::chain::index::{ChainIndex, ResolveNullTipset};
 use crate::cid_collections::CidHashSet;
-use crate::cli_shared::snapshot;
+use crate::cli_shared::snapshot::{
+    self,
+    manifest::{self, SignedSnapshotManifest, SnapshotManifest},
+};
 use crate::daemon::bundle::load_actor_bundles;
 use crate::db::car::forest::DEFAULT_FOREST_CAR_FRAME_SIZE;
 use crate::db::car::{AnyCar, ManyCar};
@@ -26,7 +29,7 @@ use dialoguer::{theme::ColorfulTheme, Confirm};
 use futures::TryStreamExt;
 use fvm_ipld_blockstore::Blockstore;
 use indicatif::{ProgressBar, ProgressStyle};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
@@ -81,6 +84,34 @@ pub enum SnapshotCommands {
         #[arg(long, default_value_t = false)]
         force: bool,
     },
+    /// Write a manifest of a snapshot, signed with a wallet key, to
+    /// `<snapshot>.manifest.json`.
+    Manifest {
+        /// Path to a snapshot CAR, which may be zstd compressed
+        snapshot: PathBuf,
+        /// File holding a private key exported with `forest-wallet export`
+        #[arg(long)]
+        key: PathBuf,
+        /// Manifest output path. Defaults to `<snapshot>.manifest.json`.
+        #[arg(short, long)]
+        output_path: Option<PathBuf>,
+    },
+    /// Check a snapshot against its signed manifest.
+    VerifyManifest {
+        /// Path to a snapshot CAR, which may be zstd compressed
+        snapshot: PathBuf,
+        /// Path to the manifest. Defaults to `<snapshot>.manifest.json`.
+        #[arg(long)]
+        manifest: Option<PathBuf>,
+        /// Address of a trusted signer. May be repeated. If unset, any valid
+        /// signature is accepted.
+        #[arg(long)]
+        trusted_signer: Vec<String>,
+        /// CID of a block header that has to be part of the snapshot chain.
+        /// May be repeated.
+        #[arg(long)]
+        checkpoint: Vec<Cid>,
+    },
     /// Filecoin keeps track of "the state of the world", including:
     /// wallets and their balances;
     /// storage providers and their deals;
@@ -202,6 +233,29 @@ impl SnapshotCommands {
                 dest.flush().await?;
                 Ok(())
             }
+            Self::Manifest {
+                snapshot,
+                key,
+                output_path,
+            } => {
+                let key = manifest::parse_exported_key(&std::fs::read_to_string(key)?)?;
+                let signed = SnapshotManifest::from_snapshot(&snapshot)
+                    .await?
+                    .sign(&key)?;
+                let output_path = output_path.unwrap_or_else(|| manifest::manifest_path(&snapshot));
+                signed.save(&output_path).await?;
+                println!("{}", output_path.display());
+                Ok(())
+            }
+            Self::VerifyManifest {
+                snapshot,
+                manifest,
+                trusted_signer,
+                checkpoint,
+            } => {
+                let manifest = manifest.unwrap_or_else(|| manifest::manifest_path(&snapshot));
+                verify_manifest(&snapshot, &manifest, &trusted_signer, &checkpoint).await
+            }
             SnapshotCommands::ComputeState {
                 snapshot,
                 epoch,
@@ -211,6 +265,43 @@ impl SnapshotCommands {
     }
 }
 
+async fn verify_manifest(
+    snapshot: &Path,
+    manifest: &Path,
+    trusted_signers: &[String],
+    checkpoints: &[Cid],
+) -> anyhow::Result<()> {
+    let signed = SignedSnapshotManifest::load(manifest).await?;
+    let trusted_signers = match trusted_signers.is_empty() {
+        true => {
+            println!("No trusted signer given, accepting any valid signature");
+            vec![signed.signer]
+        }
+        false => manifest::parse_signers(trusted_signers)?,
+    };
+    let manifest = signed.verify(&trusted_signers)?;
+    manifest.check_file(snapshot).await?;
+
+    let car = AnyCar::try_from(snapshot)?;
+    let head = car.heaviest_tipset()?;
+    manifest.check_head(&head)?;
+    let network = NetworkChain::from_genesis_or_devnet_placeholder(head.genesis(&car)?.cid());
+    if network.to_string() != manifest.network {
+        bail!(
+            "snapshot network {network} doesn't match the manifest network {}",
+            manifest.network
+        );
+    }
+    manifest::ensure_checkpoints(&car, &head, checkpoints)?;
+
+    println!(
+        "{} matches the manifest signed by {}",
+        snapshot.display(),
+        signed.signer
+    );
+    Ok(())
+}
+
 // Check the validity of a snapshot by looking at IPLD links, the genesis block,
 // and message output. More checks may be added in the future.
 //
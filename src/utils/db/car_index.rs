@@ -89,12 +89,14 @@
 //! function.
 //!
 
+mod bloom_filter;
 mod car_index_builder;
 mod hash;
 mod index_header;
 mod key_value_pair;
 mod slot;
 
+pub use bloom_filter::BloomFilter;
 pub use car_index_builder::CarIndexBuilder;
 pub use hash::Hash;
 use index_header::IndexHeader;
@@ -176,6 +178,22 @@ impl<ReaderT: ReadAt> CarIndex<ReaderT> {
         Ok(smallvec![])
     }
 
+    /// `O(n)` Build a [`BloomFilter`] of all the keys in the index, to rule out
+    /// absent keys without touching the reader.
+    pub fn bloom_filter(&self) -> Result<BloomFilter> {
+        let buckets = self.header.buckets.get();
+        // The table is filled to the load factor of the builder, sizing the
+        // filter for the number of buckets is good enough.
+        let mut filter = BloomFilter::new(buckets);
+        let mut reader = std::io::BufReader::new(Cursor::new_pos(&self.reader, self.offset));
+        for _ in 0..buckets {
+            if let Slot::Full(entry) = Slot::read(&mut reader)? {
+                filter.insert(entry.hash);
+            }
+        }
+        Ok(filter)
+    }
+
     /// Gets a mutable reference to the underlying reader.
     pub fn reader(&self) -> &ReaderT {
         &self.reader
@@ -0,0 +1,80 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+use super::Hash;
+
+/// Probabilistic set of [`struct@Hash`]es. A negative answer is definitive, a
+/// positive one is wrong for about 1% of the absent keys with the default
+/// sizing.
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    const BITS_PER_KEY: u64 = 10;
+    // Optimal for 10 bits per key: `ln(2) * 10`.
+    const HASH_FUNCTIONS: u64 = 7;
+
+    /// Creates a filter sized for `capacity` keys.
+    pub fn new(capacity: u64) -> Self {
+        let words = (capacity * Self::BITS_PER_KEY)
+            .div_ceil(u64::BITS as u64)
+            .max(1);
+        BloomFilter {
+            bits: vec![0; words as usize],
+        }
+    }
+
+    pub fn insert(&mut self, hash: Hash) {
+        for bit in self.positions(hash) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn contains(&self, hash: Hash) -> bool {
+        self.positions(hash)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Size of the filter in bytes.
+    pub fn size(&self) -> usize {
+        self.bits.len() * std::mem::size_of::<u64>()
+    }
+
+    // Double hashing, see "Less Hashing, Same Performance: Building a Better
+    // Bloom Filter" by Kirsch and Mitzenmacher. The second hash is derived by
+    // mixing the bits of the first one.
+    fn positions(&self, hash: Hash) -> impl Iterator<Item = u64> {
+        let len = self.bits.len() as u64 * u64::BITS as u64;
+        let h1 = u64::from(hash);
+        let h2 = h1.wrapping_mul(0x9e37_79b9_7f4a_7c15).rotate_left(31) | 1;
+        (0..Self::HASH_FUNCTIONS).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use quickcheck_macros::quickcheck;
+
+    #[quickcheck]
+    fn no_false_negatives(hashes: Vec<Hash>) {
+        let mut filter = BloomFilter::new(hashes.len() as u64);
+        for hash in &hashes {
+            filter.insert(*hash);
+        }
+        assert!(hashes.iter().all(|hash| filter.contains(*hash)));
+    }
+
+    #[test]
+    fn false_positive_rate() {
+        let mut filter = BloomFilter::new(10_000);
+        for n in 0..10_000_u64 {
+            filter.insert(Hash::from(n.wrapping_mul(0x2545_f491_4f6c_dd1d)));
+        }
+        let false_positives = (10_000..110_000_u64)
+            .filter(|n| filter.contains(Hash::from(n.wrapping_mul(0x2545_f491_4f6c_dd1d))))
+            .count();
+        assert!(false_positives < 2_000, "{false_positives}");
+    }
+}
@@ -90,3 +90,12 @@ fn lookup_clash_many(mut entries: Vec<(Hash, FrameOffset)>) {
         assert_eq!(&AHashSet::from_iter(query(&table, hash)), &map[&hash]);
     }
 }
+
+#[quickcheck]
+fn bloom_filter_has_all_keys(entries: Vec<(Hash, FrameOffset)>) {
+    let table = mk_table(&entries);
+    let filter = table.bloom_filter().unwrap();
+    for (hash, _) in entries {
+        assert!(filter.contains(hash));
+    }
+}
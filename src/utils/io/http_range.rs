@@ -0,0 +1,353 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! [`ReadAt`] over a remote file, using HTTP range requests. This allows
+//! opening a remote `.forest.car.zst` archive with
+//! [`ForestCar`](crate::db::car::ForestCar), which only ever reads its index
+//! and the few z-frames holding the requested blocks.
+//!
+//! The file is read in fixed-size chunks, the most recently used of which are
+//! kept in memory. The decoded z-frames are cached separately by the
+//! [`ZstdFrameCache`](crate::db::car::ZstdFrameCache) of the archive.
+//!
+//! [`ReadAt`] is blocking, the requests are sent from a dedicated thread with
+//! its own runtime, so that readers can be used from both synchronous and
+//! asynchronous contexts.
+
+use backoff::{backoff::Backoff as _, ExponentialBackoffBuilder};
+use bytes::Bytes;
+use lru::LruCache;
+use parking_lot::Mutex;
+use positioned_io::{ReadAt, Size};
+use reqwest::{header, StatusCode};
+use std::io;
+use std::num::NonZeroUsize;
+use std::ops::Range;
+use std::time::Duration;
+use tracing::{debug, warn};
+use url::Url;
+
+type RangeRequest = (Range<u64>, flume::Sender<io::Result<(Bytes, u64)>>);
+
+pub struct HttpRangeReader {
+    url: Url,
+    size: u64,
+    chunk_size: u64,
+    chunks: Mutex<LruCache<u64, Bytes>>,
+    requests: flume::Sender<RangeRequest>,
+}
+
+impl HttpRangeReader {
+    /// Size of the chunks the file is read in (64 KiB).
+    pub const DEFAULT_CHUNK_SIZE: u64 = 64 * 1024;
+    /// Maximum size of the cached chunks (64 MiB).
+    pub const DEFAULT_CACHE_SIZE: u64 = 64 * 1024 * 1024;
+
+    /// Opens a remote file. Fails if the server doesn't support range requests.
+    pub fn open(url: Url) -> io::Result<Self> {
+        Self::with_cache(url, Self::DEFAULT_CHUNK_SIZE, Self::DEFAULT_CACHE_SIZE)
+    }
+
+    pub fn with_cache(url: Url, chunk_size: u64, cache_size: u64) -> io::Result<Self> {
+        let requests = spawn_worker(url.clone())?;
+        let (_, size) = fetch(&requests, 0..1)?;
+        debug!(%url, size, "opened remote file");
+        let max_chunks = NonZeroUsize::new((cache_size / chunk_size.max(1)) as usize)
+            .unwrap_or(NonZeroUsize::MIN);
+        Ok(Self {
+            url,
+            size,
+            chunk_size: chunk_size.max(1),
+            chunks: Mutex::new(LruCache::new(max_chunks)),
+            requests,
+        })
+    }
+
+    /// Returns the chunks `first..=last`, fetching the missing ones in a single request.
+    fn chunks(&self, first: u64, last: u64) -> io::Result<Vec<Bytes>> {
+        let cached = {
+            let mut chunks = self.chunks.lock();
+            (first..=last)
+                .map(|n| chunks.get(&n).cloned())
+                .collect::<Option<Vec<_>>>()
+        };
+        if let Some(cached) = cached {
+            return Ok(cached);
+        }
+
+        let start = first * self.chunk_size;
+        let end = ((last + 1) * self.chunk_size).min(self.size);
+        let (data, _) = fetch(&self.requests, start..end)?;
+        if data.len() as u64 != end - start {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("short read from {}", self.url),
+            ));
+        }
+        let mut chunks = self.chunks.lock();
+        Ok((first..=last)
+            .map(|n| {
+                let offset = ((n - first) * self.chunk_size) as usize;
+                let chunk = data.slice(offset..(offset + self.chunk_size as usize).min(data.len()));
+                chunks.put(n, chunk.clone());
+                chunk
+            })
+            .collect())
+    }
+}
+
+impl ReadAt for HttpRangeReader {
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+        if pos >= self.size || buf.is_empty() {
+            // This matches the behaviour for seeking past the end of a file
+            return Ok(0);
+        }
+        let end = (pos + buf.len() as u64).min(self.size);
+        let first = pos / self.chunk_size;
+        let last = (end - 1) / self.chunk_size;
+        let mut read = 0;
+        for (n, chunk) in (first..=last).zip(self.chunks(first, last)?) {
+            let chunk_start = n * self.chunk_size;
+            let from = (pos.max(chunk_start) - chunk_start) as usize;
+            let to = (end.min(chunk_start + chunk.len() as u64) - chunk_start) as usize;
+            buf[read..read + to - from].copy_from_slice(&chunk[from..to]);
+            read += to - from;
+        }
+        Ok(read)
+    }
+}
+
+impl Size for HttpRangeReader {
+    fn size(&self) -> io::Result<Option<u64>> {
+        Ok(Some(self.size))
+    }
+}
+
+/// Sends a range request to the worker and waits for the response data and
+/// the total size of the file.
+fn fetch(requests: &flume::Sender<RangeRequest>, range: Range<u64>) -> io::Result<(Bytes, u64)> {
+    let (tx, rx) = flume::bounded(1);
+    requests
+        .send((range, tx))
+        .map_err(|_| io::Error::other("HTTP range reader worker stopped"))?;
+    rx.recv()
+        .map_err(|_| io::Error::other("HTTP range reader worker stopped"))?
+}
+
+// The worker stops once the reader, and thus the sender, is dropped.
+fn spawn_worker(url: Url) -> io::Result<flume::Sender<RangeRequest>> {
+    let (tx, rx) = flume::unbounded::<RangeRequest>();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    std::thread::Builder::new()
+        .name("http-range-reader".into())
+        .spawn(move || {
+            runtime.block_on(async move {
+                let client = reqwest::Client::new();
+                while let Ok((range, reply)) = rx.recv_async().await {
+                    let client = client.clone();
+                    let url = url.clone();
+                    tokio::spawn(async move {
+                        let _ = reply.send(fetch_with_retries(&client, &url, range).await);
+                    });
+                }
+            })
+        })?;
+    Ok(tx)
+}
+
+/// Sends a range request, retrying failures with exponential backoff and
+/// jitter. A `Retry-After` header of the server takes precedence over the
+/// backoff delay.
+async fn fetch_with_retries(
+    client: &reqwest::Client,
+    url: &Url,
+    range: Range<u64>,
+) -> io::Result<(Bytes, u64)> {
+    const ATTEMPTS: usize = 5;
+    const MAX_RETRY_AFTER: Duration = Duration::from_secs(60);
+    let mut backoff = ExponentialBackoffBuilder::new()
+        .with_initial_interval(Duration::from_millis(500))
+        .with_max_elapsed_time(None)
+        .build();
+    let mut attempt = 1;
+    loop {
+        match fetch_range(client, url, range.clone()).await {
+            Ok(response) => return Ok(response),
+            Err(backoff::Error::Transient { err, retry_after }) if attempt < ATTEMPTS => {
+                let delay = match retry_after {
+                    Some(retry_after) => retry_after.min(MAX_RETRY_AFTER),
+                    None => backoff.next_backoff().unwrap_or(backoff.max_interval),
+                };
+                warn!(%url, "range request failed, retrying in {delay:?}: {err}");
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(backoff::Error::Transient { err, .. } | backoff::Error::Permanent(err)) => {
+                return Err(err)
+            }
+        }
+    }
+}
+
+/// Requests `range` of `url`. Failures that may be temporary are transient,
+/// with the delay asked for by the server on `429 Too Many Requests` and
+/// `503 Service Unavailable`.
+async fn fetch_range(
+    client: &reqwest::Client,
+    url: &Url,
+    range: Range<u64>,
+) -> Result<(Bytes, u64), backoff::Error<io::Error>> {
+    let response = client
+        .get(url.clone())
+        .header(
+            header::RANGE,
+            format!("bytes={}-{}", range.start, range.end.saturating_sub(1)),
+        )
+        .send()
+        .await
+        .map_err(io::Error::other)?;
+    if let Err(e) = response.error_for_status_ref() {
+        let retry_after = match response.status() {
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::SERVICE_UNAVAILABLE => response
+                .headers()
+                .get(header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.trim().parse().ok())
+                .map(Duration::from_secs),
+            _ => None,
+        };
+        return Err(backoff::Error::Transient {
+            err: io::Error::other(e),
+            retry_after,
+        });
+    }
+    if response.status() != StatusCode::PARTIAL_CONTENT {
+        return Err(backoff::Error::permanent(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("{url} doesn't support range requests"),
+        )));
+    }
+    let size = response
+        .headers()
+        .get(header::CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit_once('/'))
+        .and_then(|(_, size)| size.parse().ok())
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid Content-Range header from {url}"),
+            )
+        })?;
+    let data = response.bytes().await.map_err(io::Error::other)?;
+    Ok((data, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::car::{forest, ForestCar};
+    use crate::networks::calibnet;
+    use crate::utils::db::car_stream::CarStream;
+    use futures::TryStreamExt as _;
+    use fvm_ipld_blockstore::Blockstore as _;
+    use http_range_header::parse_range_header;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+    use std::convert::Infallible;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Serves `data` with range requests support, counting the requests. The
+    /// first `throttled` requests are turned away with a `Retry-After` of one
+    /// second.
+    fn serve(data: Bytes, requests: Arc<AtomicUsize>, throttled: usize) -> SocketAddr {
+        let make_svc = make_service_fn(move |_conn| {
+            let (data, requests) = (data.clone(), requests.clone());
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let n = requests.fetch_add(1, Ordering::Relaxed);
+                    let range = req
+                        .headers()
+                        .get(header::RANGE)
+                        .and_then(|value| parse_range_header(value.to_str().ok()?).ok())
+                        .and_then(|ranges| ranges.validate(data.len() as u64).ok())
+                        .map(|ranges| ranges[0].clone());
+                    let response = match range {
+                        _ if n < throttled => Response::builder()
+                            .status(StatusCode::SERVICE_UNAVAILABLE)
+                            .header(header::RETRY_AFTER, "1")
+                            .body(Body::empty()),
+                        Some(range) => Response::builder()
+                            .status(StatusCode::PARTIAL_CONTENT)
+                            .header(
+                                header::CONTENT_RANGE,
+                                format!("bytes {}-{}/{}", range.start(), range.end(), data.len()),
+                            )
+                            .body(Body::from(
+                                data.slice(*range.start() as usize..=*range.end() as usize),
+                            )),
+                        None => Response::builder().body(Body::from(data.clone())),
+                    };
+                    async move { Ok::<_, Infallible>(response.unwrap()) }
+                }))
+            }
+        });
+        let server = Server::bind(&SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)).serve(make_svc);
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    async fn calibnet_genesis_forest_car() -> Bytes {
+        let stream = CarStream::new(calibnet::DEFAULT_GENESIS).await.unwrap();
+        let roots = stream.header.roots.clone();
+        let frames = forest::Encoder::compress_stream_default(stream.map_err(anyhow::Error::from));
+        let mut data = vec![];
+        forest::Encoder::write(&mut data, roots, frames)
+            .await
+            .unwrap();
+        data.into()
+    }
+
+    // The server runs on the runtime, which must not be blocked by the reads.
+    #[tokio::test(flavor = "multi_thread")]
+    async fn remote_forest_car() {
+        let data = calibnet_genesis_forest_car().await;
+        let requests = Arc::new(AtomicUsize::new(0));
+        let addr = serve(data.clone(), requests.clone(), 0);
+        let url = Url::parse(&format!("http://{addr}/genesis.forest.car.zst")).unwrap();
+
+        let reader = HttpRangeReader::with_cache(url, 1024, 1024 * 1024).unwrap();
+        assert_eq!(reader.size().unwrap(), Some(data.len() as u64));
+        // Reads spanning several chunks.
+        let mut buf = vec![0; 3000];
+        assert_eq!(reader.read_at(500, &mut buf).unwrap(), 3000);
+        assert_eq!(buf, data[500..3500]);
+
+        let car = ForestCar::new(reader).unwrap();
+        assert!(car.has(&calibnet::GENESIS_CID).unwrap());
+
+        // Cached chunks are not fetched again.
+        let before = requests.load(Ordering::Relaxed);
+        assert!(car.has(&calibnet::GENESIS_CID).unwrap());
+        assert_eq!(requests.load(Ordering::Relaxed), before);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn retry_after_throttling() {
+        let data = Bytes::from_static(b"forest");
+        let requests = Arc::new(AtomicUsize::new(0));
+        let addr = serve(data.clone(), requests.clone(), 1);
+        let url = Url::parse(&format!("http://{addr}/data")).unwrap();
+
+        let start = std::time::Instant::now();
+        let reader = HttpRangeReader::open(url).unwrap();
+        assert_eq!(reader.size().unwrap(), Some(data.len() as u64));
+        assert_eq!(requests.load(Ordering::Relaxed), 2);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+}
@@ -18,6 +18,9 @@ use url::Url;
 
 use once_cell::sync::Lazy;
 
+mod download;
+pub use download::{download_file, Download, DownloadOptions};
+
 pub fn global_http_client() -> reqwest::Client {
     static CLIENT: Lazy<reqwest::Client> = Lazy::new(reqwest::Client::new);
     CLIENT.clone()
@@ -0,0 +1,520 @@
+// Copyright 2019-2023 ChainSafe Systems
+// SPDX-License-Identifier: Apache-2.0, MIT
+
+//! Parallel, resumable downloads of large files.
+//!
+//! The file is split in segments, which are downloaded concurrently with range
+//! requests, spread over the mirrors serving the same file. A segment failing
+//! on one mirror is retried on the next one. The data goes to `<file>.part`,
+//! and the completed segments are recorded in `<file>.part.json` so that an
+//! interrupted download picks up where it stopped.
+//!
+//! The SHA-256 checksum of the file is computed while downloading: segments are
+//! hashed in order as soon as all the segments before them are complete, so the
+//! checksum is ready when the last segment lands.
+
+use super::global_http_client;
+use crate::utils::io::{AsyncWriterWithChecksum, Checksum as _};
+use crate::utils::reqwest_resume::ClientExt as _;
+use anyhow::{bail, ensure, Context as _};
+use futures::{StreamExt as _, TryStreamExt as _};
+use human_repr::HumanCount as _;
+use positioned_io::ReadAt as _;
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest as _, Sha256};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncSeekExt as _, AsyncWriteExt as _};
+use tracing::{debug, info, warn};
+use url::Url;
+
+/// How often the download progress is logged.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy)]
+pub struct DownloadOptions {
+    /// Number of segments downloaded concurrently.
+    pub connections: usize,
+    /// Size of the segments, in bytes.
+    pub segment_size: u64,
+    /// Number of attempts at each segment, over all the mirrors.
+    pub segment_attempts: usize,
+}
+
+impl Default for DownloadOptions {
+    fn default() -> Self {
+        Self {
+            connections: 8,
+            segment_size: 64 * 1024 * 1024,
+            segment_attempts: 5,
+        }
+    }
+}
+
+/// A completed download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Download {
+    pub path: PathBuf,
+    /// The URL the file was downloaded from, after redirects.
+    pub url: Url,
+    pub size: u64,
+    /// Hex-encoded SHA-256 checksum of the file.
+    pub sha256: String,
+}
+
+/// Downloads the file served by `mirrors` to `destination`, resuming a
+/// previous attempt if there is one. Mirrors that don't serve a file of the
+/// same size as the first available one are ignored.
+pub async fn download_file(
+    mirrors: &[Url],
+    destination: &Path,
+    options: &DownloadOptions,
+) -> anyhow::Result<Download> {
+    let mut sources = vec![];
+    for url in mirrors {
+        match Source::probe(url).await {
+            Ok(source) => match sources.first() {
+                Some(Source { size, .. }) if *size != source.size => warn!(
+                    %url,
+                    "ignoring mirror serving {} bytes rather than {size}",
+                    source.size
+                ),
+                _ => sources.push(source),
+            },
+            Err(e) => warn!(%url, "ignoring mirror: {e:#}"),
+        }
+    }
+    let Some(primary) = sources.first() else {
+        bail!("no mirror available for {}", destination.display());
+    };
+    info!(url = %primary.url, size = primary.size, "downloading file");
+
+    let part = with_suffix(destination, ".part");
+    let sha256 = match sources.iter().all(|it| it.ranges) && primary.size > 0 {
+        true => download_segments(&sources, &part, options).await?,
+        false => download_stream(primary, &part).await?,
+    };
+    tokio::fs::rename(&part, destination).await?;
+    Ok(Download {
+        path: destination.to_owned(),
+        url: primary.url.clone(),
+        size: primary.size,
+        sha256,
+    })
+}
+
+/// A mirror of the file.
+#[derive(Debug, Clone)]
+struct Source {
+    /// The URL of the file, after redirects. The file behind a stable URL
+    /// changes over time, so segments are always fetched from this one.
+    url: Url,
+    size: u64,
+    /// Whether the mirror supports range requests.
+    ranges: bool,
+    /// `ETag` or `Last-Modified` header of the file, if any.
+    validator: Option<String>,
+}
+
+impl Source {
+    async fn probe(url: &Url) -> anyhow::Result<Self> {
+        let response = global_http_client()
+            .get(url.clone())
+            .header(header::RANGE, "bytes=0-0")
+            .send()
+            .await?
+            .error_for_status()?;
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|it: &header::HeaderValue| it.to_str().ok())
+        };
+        let validator = header(header::ETAG)
+            .or_else(|| header(header::LAST_MODIFIED))
+            .map(String::from);
+        let (size, ranges) = match response.status() {
+            StatusCode::PARTIAL_CONTENT => {
+                let content_range =
+                    header(header::CONTENT_RANGE).context("no content-range header")?;
+                (parse_content_range_size(content_range)?, true)
+            }
+            _ => (
+                response
+                    .content_length()
+                    .context("no content-length header")?,
+                false,
+            ),
+        };
+        Ok(Self {
+            url: response.url().clone(),
+            size,
+            ranges,
+            validator,
+        })
+    }
+}
+
+/// Parses the size of the file from a `Content-Range` header, e.g. `bytes 0-0/1234`.
+fn parse_content_range_size(value: &str) -> anyhow::Result<u64> {
+    value
+        .rsplit_once('/')
+        .and_then(|(_, size)| size.parse().ok())
+        .with_context(|| format!("invalid content-range header {value}"))
+}
+
+/// Progress of a segmented download, saved next to the partial file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ResumeState {
+    size: u64,
+    segment_size: u64,
+    validator: Option<String>,
+    done: Vec<bool>,
+}
+
+impl ResumeState {
+    fn new(source: &Source, segment_size: u64) -> Self {
+        Self {
+            size: source.size,
+            segment_size,
+            validator: source.validator.clone(),
+            done: vec![false; source.size.div_ceil(segment_size) as usize],
+        }
+    }
+
+    /// Loads the state saved at `path`, if it's about the same file.
+    async fn load(path: &Path, source: &Source, segment_size: u64) -> Option<Self> {
+        let state: Self = serde_json::from_slice(&tokio::fs::read(path).await.ok()?).ok()?;
+        let fresh = Self::new(source, segment_size);
+        (state.size == fresh.size
+            && state.segment_size == fresh.segment_size
+            && state.validator == fresh.validator
+            && state.done.len() == fresh.done.len())
+        .then_some(state)
+    }
+
+    async fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let tmp = with_suffix(path, ".tmp");
+        tokio::fs::write(&tmp, serde_json::to_vec(self)?).await?;
+        tokio::fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+
+    fn segment(&self, index: usize) -> Range<u64> {
+        let start = index as u64 * self.segment_size;
+        start..(start + self.segment_size).min(self.size)
+    }
+}
+
+async fn download_segments(
+    sources: &[Source],
+    part: &Path,
+    options: &DownloadOptions,
+) -> anyhow::Result<String> {
+    let primary = &sources[0];
+    let segment_size = options.segment_size.max(1);
+    let state_path = with_suffix(part, ".json");
+    let mut state = match ResumeState::load(&state_path, primary, segment_size).await {
+        Some(state) if part.exists() => {
+            let done = state.done.iter().filter(|done| **done).count();
+            info!(
+                "resuming download with {done} of {} segments done",
+                state.done.len()
+            );
+            state
+        }
+        _ => ResumeState::new(primary, segment_size),
+    };
+    let file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .open(part)
+        .await?;
+    file.set_len(state.size).await?;
+    drop(file);
+    state.save(&state_path).await?;
+
+    // Segments are hashed in order on a blocking thread, as they complete.
+    let (hash_tx, hash_rx) = flume::unbounded::<Range<u64>>();
+    let hasher = {
+        let file = std::fs::File::open(part)?;
+        tokio::task::spawn_blocking(move || -> anyhow::Result<String> {
+            let mut hasher = Sha256::new();
+            let mut buffer = vec![];
+            for range in hash_rx {
+                buffer.resize((range.end - range.start) as usize, 0);
+                file.read_exact_at(range.start, &mut buffer)?;
+                hasher.update(&buffer);
+            }
+            Ok(hex::encode(hasher.finalize()))
+        })
+    };
+    let mut hashed = 0;
+    let mut hash_done = move |state: &ResumeState| -> anyhow::Result<()> {
+        while hashed < state.done.len() && state.done[hashed] {
+            hash_tx.send(state.segment(hashed))?;
+            hashed += 1;
+        }
+        Ok(())
+    };
+    hash_done(&state)?;
+
+    let todo = (0..state.done.len())
+        .filter(|index| !state.done[*index])
+        .map(|index| (index, state.segment(index)))
+        .collect::<Vec<_>>();
+    let mut segments = futures::stream::iter(todo)
+        .map(|(index, range)| async move {
+            download_segment(sources, index, range, part, options.segment_attempts)
+                .await
+                .map(|()| index)
+        })
+        .buffer_unordered(options.connections.max(1));
+    let (start, mut last_report) = (Instant::now(), Instant::now());
+    while let Some(index) = segments.try_next().await? {
+        state.done[index] = true;
+        state.save(&state_path).await?;
+        hash_done(&state)?;
+        if last_report.elapsed() > PROGRESS_INTERVAL {
+            last_report = Instant::now();
+            let done = state.done.iter().filter(|done| **done).count() as u64 * segment_size;
+            info!(
+                "downloaded {} of {} in {}s",
+                done.min(state.size).human_count_bytes(),
+                state.size.human_count_bytes(),
+                start.elapsed().as_secs()
+            );
+        }
+    }
+    drop(segments);
+    drop(hash_done);
+
+    let sha256 = hasher.await??;
+    tokio::fs::remove_file(&state_path).await?;
+    Ok(sha256)
+}
+
+/// Downloads the bytes of `range` into `part`, trying the mirrors in turn,
+/// starting with the one assigned to segment `index`.
+async fn download_segment(
+    sources: &[Source],
+    index: usize,
+    range: Range<u64>,
+    part: &Path,
+    attempts: usize,
+) -> anyhow::Result<()> {
+    let mut error = None;
+    for attempt in 0..attempts.max(1) {
+        let source = &sources[(index + attempt) % sources.len()];
+        match fetch_range(&source.url, range.clone(), part).await {
+            Ok(()) => return Ok(()),
+            Err(e) => {
+                debug!(url = %source.url, "segment {index} failed: {e:#}");
+                error = Some(e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        }
+    }
+    Err(error
+        .unwrap_or_else(|| anyhow::anyhow!("no attempt"))
+        .context(format!("couldn't download segment {index}")))
+}
+
+async fn fetch_range(url: &Url, range: Range<u64>, part: &Path) -> anyhow::Result<()> {
+    let response = global_http_client()
+        .resumable()
+        .get(url.clone())
+        .range(range.clone())
+        .send()
+        .await?;
+    ensure!(
+        response.response().status() == StatusCode::PARTIAL_CONTENT,
+        "expected a partial content response, got {}",
+        response.response().status()
+    );
+
+    let mut file = tokio::fs::OpenOptions::new().write(true).open(part).await?;
+    file.seek(std::io::SeekFrom::Start(range.start)).await?;
+    let mut file = tokio::io::BufWriter::new(file);
+    let mut remaining = range.end - range.start;
+    let mut stream = response.bytes_stream();
+    while let Some(bytes) = stream.try_next().await? {
+        ensure!(
+            bytes.len() as u64 <= remaining,
+            "received more than the requested range"
+        );
+        file.write_all(&bytes).await?;
+        remaining -= bytes.len() as u64;
+    }
+    ensure!(remaining == 0, "missing {remaining} bytes of the range");
+    file.flush().await?;
+    file.get_ref().sync_data().await?;
+    Ok(())
+}
+
+/// Downloads the file in a single stream, for mirrors without range requests.
+async fn download_stream(source: &Source, part: &Path) -> anyhow::Result<String> {
+    let response = global_http_client()
+        .resumable()
+        .get(source.url.clone())
+        .send()
+        .await?;
+    response.response().error_for_status_ref()?;
+    let mut reader =
+        tokio_util::io::StreamReader::new(response.bytes_stream().map_err(std::io::Error::other));
+    let mut writer = AsyncWriterWithChecksum::<Sha256, _>::new(
+        tokio::io::BufWriter::new(tokio::fs::File::create(part).await?),
+        true,
+    );
+    let size = tokio::io::copy(&mut reader, &mut writer).await?;
+    writer.flush().await?;
+    ensure!(
+        size == source.size,
+        "downloaded {size} bytes rather than {}",
+        source.size
+    );
+    Ok(hex::encode(writer.finalize()?.context("missing checksum")?))
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::service::{make_service_fn, service_fn};
+    use hyper::{Body, Request, Response, Server};
+    use std::convert::Infallible;
+    use std::net::{Ipv4Addr, SocketAddr};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Serves `data` with range requests. Every third ranged request fails.
+    async fn serve(data: Arc<Vec<u8>>, flaky: bool) -> Url {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let make_svc = make_service_fn(move |_conn| {
+            let (data, requests) = (data.clone(), requests.clone());
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let (data, requests) = (data.clone(), requests.clone());
+                    async move {
+                        let range = req.headers().get(header::RANGE).map(|value| {
+                            let value = value.to_str().unwrap().trim_start_matches("bytes=");
+                            let (start, end) = value.split_once('-').unwrap();
+                            let start: usize = start.parse().unwrap();
+                            let end = end.parse::<usize>().map_or(data.len(), |end| end + 1);
+                            start..end.min(data.len())
+                        });
+                        let response = match range {
+                            Some(_)
+                                if flaky && requests.fetch_add(1, Ordering::Relaxed) % 3 == 2 =>
+                            {
+                                Response::builder()
+                                    .status(StatusCode::SERVICE_UNAVAILABLE)
+                                    .body(Body::empty())
+                            }
+                            Some(range) => Response::builder()
+                                .status(StatusCode::PARTIAL_CONTENT)
+                                .header(
+                                    header::CONTENT_RANGE,
+                                    format!(
+                                        "bytes {}-{}/{}",
+                                        range.start,
+                                        range.end - 1,
+                                        data.len()
+                                    ),
+                                )
+                                .header(header::ETAG, "\"v1\"")
+                                .body(Body::from(data[range].to_vec())),
+                            None => Response::builder().body(Body::from(data.to_vec())),
+                        };
+                        Ok::<_, Infallible>(response.unwrap())
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&SocketAddr::new(Ipv4Addr::LOCALHOST.into(), 0)).serve(make_svc);
+        let url = Url::parse(&format!("http://{}/file", server.local_addr())).unwrap();
+        tokio::task::spawn(server);
+        url
+    }
+
+    fn test_data() -> Arc<Vec<u8>> {
+        Arc::new((0..100_000u32).map(|i| (i * 7 % 251) as u8).collect())
+    }
+
+    fn test_options() -> DownloadOptions {
+        DownloadOptions {
+            connections: 4,
+            segment_size: 7_000,
+            segment_attempts: 5,
+        }
+    }
+
+    #[tokio::test]
+    async fn download_segments_from_mirrors() {
+        let data = test_data();
+        let mirrors = [
+            serve(data.clone(), true).await,
+            serve(data.clone(), false).await,
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("file");
+
+        let download = download_file(&mirrors, &destination, &test_options())
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read(&destination).unwrap(), *data);
+        assert_eq!(download.size, data.len() as u64);
+        assert_eq!(
+            download.sha256,
+            hex::encode(Sha256::digest(data.as_slice()))
+        );
+        assert!(!with_suffix(&destination, ".part").exists());
+        assert!(!with_suffix(&destination, ".part.json").exists());
+    }
+
+    #[tokio::test]
+    async fn resume_download() {
+        let data = test_data();
+        let url = serve(data.clone(), false).await;
+        let source = Source::probe(&url).await.unwrap();
+        assert!(source.ranges);
+        assert_eq!(source.size, data.len() as u64);
+
+        // A previous attempt downloaded the even segments.
+        let dir = tempfile::tempdir().unwrap();
+        let destination = dir.path().join("file");
+        let part = with_suffix(&destination, ".part");
+        let options = test_options();
+        let mut state = ResumeState::new(&source, options.segment_size);
+        let mut partial = vec![0; data.len()];
+        for index in (0..state.done.len()).step_by(2) {
+            let range = state.segment(index);
+            let range = range.start as usize..range.end as usize;
+            partial[range.clone()].copy_from_slice(&data[range]);
+            state.done[index] = true;
+        }
+        std::fs::write(&part, &partial).unwrap();
+        state.save(&with_suffix(&part, ".json")).await.unwrap();
+
+        let download = download_file(&[url], &destination, &options).await.unwrap();
+        assert_eq!(std::fs::read(&destination).unwrap(), *data);
+        assert_eq!(
+            download.sha256,
+            hex::encode(Sha256::digest(data.as_slice()))
+        );
+    }
+
+    #[test]
+    fn content_range_size() {
+        assert_eq!(parse_content_range_size("bytes 0-0/1234").unwrap(), 1234);
+        parse_content_range_size("bytes 0-0/*").unwrap_err();
+    }
+}
@@ -14,6 +14,7 @@ use bytes::Bytes;
 use futures::{ready, FutureExt as _, Stream, TryFutureExt as _};
 use hyper::header::{self, HeaderMap, HeaderValue};
 use std::{
+    ops::Range,
     pin::Pin,
     task::{Context, Poll},
     time::Duration,
@@ -47,7 +48,7 @@ impl Client {
     ///
     /// See [`reqwest::Client::get()`].
     pub fn get(&self, url: reqwest::Url) -> RequestBuilder {
-        RequestBuilder(self.0.clone(), reqwest::Method::GET, url)
+        RequestBuilder(self.0.clone(), reqwest::Method::GET, url, None)
     }
 }
 
@@ -55,16 +56,30 @@ impl Client {
 ///
 /// See [`reqwest::RequestBuilder`].
 #[derive(Debug)]
-pub struct RequestBuilder(reqwest::Client, reqwest::Method, reqwest::Url);
+pub struct RequestBuilder(
+    reqwest::Client,
+    reqwest::Method,
+    reqwest::Url,
+    Option<Range<u64>>,
+);
 impl RequestBuilder {
+    /// Only requests the bytes of `range`, which is also used when resuming.
+    pub fn range(mut self, range: Range<u64>) -> Self {
+        self.3 = Some(range);
+        self
+    }
+
     /// Constructs the Request and sends it the target URL, returning a Response.
     ///
     /// See [`reqwest::RequestBuilder::send()`].
     pub async fn send(self) -> reqwest::Result<Response> {
-        let RequestBuilder(client, method, url) = self;
+        let RequestBuilder(client, method, url, range) = self;
 
         let response = loop {
-            let builder = client.request(method.clone(), url.clone());
+            let mut builder = client.request(method.clone(), url.clone());
+            if let Some(range) = &range {
+                builder = builder.header(header::RANGE, range_header(range.start, range));
+            }
             match builder.send().await {
                 Err(err) if !err.is_builder() && !err.is_redirect() && !err.is_status() => {
                     sleep(Duration::from_secs(1)).await
@@ -76,13 +91,15 @@ impl RequestBuilder {
             .headers()
             .get(header::ACCEPT_RANGES)
             .map(HeaderValue::as_bytes)
-            == Some(b"bytes");
+            == Some(b"bytes")
+            || response.status() == reqwest::StatusCode::PARTIAL_CONTENT;
         let resp = Response {
             client,
             method,
             url,
             response,
             accept_byte_ranges,
+            range,
             pos: 0,
         };
         Ok(resp)
@@ -99,6 +116,7 @@ pub struct Response {
     url: reqwest::Url,
     response: reqwest::Response,
     accept_byte_ranges: bool,
+    range: Option<Range<u64>>,
     pos: u64,
 }
 impl Response {
@@ -112,6 +130,7 @@ impl Response {
             url: self.url,
             decoder: Box::pin(self.response.bytes_stream()),
             accept_byte_ranges: self.accept_byte_ranges,
+            range: self.range,
             pos: self.pos,
         }
     }
@@ -127,6 +146,7 @@ struct Decoder {
     url: reqwest::Url,
     decoder: Pin<Box<dyn Stream<Item = reqwest::Result<Bytes>> + Send>>,
     accept_byte_ranges: bool,
+    range: Option<Range<u64>>,
     pos: u64,
 }
 impl Stream for Decoder {
@@ -139,10 +159,17 @@ impl Stream for Decoder {
                     if !self.accept_byte_ranges {
                         break Poll::Ready(Some(Err(err)));
                     }
+                    if matches!(&self.range, Some(range) if range.start + self.pos >= range.end) {
+                        // The whole range was received.
+                        break Poll::Ready(None);
+                    }
                     let builder = self.client.request(self.method.clone(), self.url.clone());
                     let mut headers = HeaderMap::new();
-                    let value = HeaderValue::from_str(&std::format!("bytes={}-", self.pos))
-                        .expect("unreachable");
+                    let value = match &self.range {
+                        Some(range) => range_header(range.start + self.pos, range),
+                        None => HeaderValue::from_str(&std::format!("bytes={}-", self.pos))
+                            .expect("unreachable"),
+                    };
                     headers.insert(header::RANGE, value);
                     let builder = builder.headers(headers);
                     // https://developer.mozilla.org/en-US/docs/Web/HTTP/Range_requests
@@ -163,6 +190,16 @@ impl Stream for Decoder {
     }
 }
 
+/// `Range` header for the bytes of `range` from `start` on.
+fn range_header(start: u64, range: &Range<u64>) -> HeaderValue {
+    // The end of a byte range is inclusive.
+    HeaderValue::from_str(&std::format!(
+        "bytes={start}-{}",
+        range.end.saturating_sub(1)
+    ))
+    .expect("unreachable")
+}
+
 /// Shortcut method to quickly make a GET request.
 ///
 /// See [`reqwest::get`].
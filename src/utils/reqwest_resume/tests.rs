@@ -1,6 +1,6 @@
 // Copyright 2019-2023 ChainSafe Systems
 // SPDX-License-Identifier: Apache-2.0, MIT
-use crate::utils::reqwest_resume::get;
+use crate::utils::reqwest_resume::{get, Client};
 use bytes::Bytes;
 use const_random::const_random;
 use futures::stream::StreamExt;
@@ -93,6 +93,26 @@ async fn test_resumable_get() {
     assert_eq!(Bytes::from_static(&RANDOM_BYTES), data);
 }
 
+#[tokio::test]
+async fn test_resumable_range_get() {
+    let addr = create_flaky_server().await;
+
+    let resp = Client::new()
+        .get(reqwest::Url::parse(&format!("http://{addr}")).unwrap())
+        .range(1000..5000)
+        .send()
+        .await
+        .unwrap();
+
+    let data = resp
+        .bytes_stream()
+        .map(|item| item.unwrap())
+        .collect::<Vec<Bytes>>()
+        .await
+        .concat();
+    assert_eq!(Bytes::from_static(&RANDOM_BYTES[1000..5000]), data);
+}
+
 #[tokio::test]
 async fn test_non_resumable_get() {
     let addr = create_flaky_server().await;
@@ -21,7 +21,7 @@ fn export_empty_archive() {
         .assert()
         .failure()
         .stderr(predicate::eq(
-            "Error: input not recognized as any kind of CAR data (.car, .car.zst, .forest.car)\n",
+            "Error: input not recognized as any kind of CAR data (.car, .car.zst, .forest.car, CARv2)\n",
         ));
 }
 